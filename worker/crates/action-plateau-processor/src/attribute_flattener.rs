@@ -5,10 +5,9 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, Feature};
-use serde_json::Value;
 use std::collections::{HashMap, HashSet};
 
 const DELIM: &str = "_";
@@ -469,40 +468,22 @@ impl CommonAttributeProcessor {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.AttributeFlattener",
+    description = "AttributeFlattener",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+)]
 pub struct AttributeFlattenerFactory;
 
-impl ProcessorFactory for AttributeFlattenerFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.AttributeFlattener"
-    }
-
-    fn description(&self) -> &str {
-        "AttributeFlattener"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl AttributeFlattenerFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         let flattener = Flattener::new();
         let common_processor = CommonAttributeProcessor {
@@ -717,4 +698,8 @@ impl Processor for AttributeFlattener {
     fn name(&self) -> &str {
         "AttributeFlattener"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
@@ -8,47 +8,28 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue};
-use serde_json::Value;
 
 use super::errors::PlateauProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.BuildingInstallationGeometryTypeExtractor",
+    description = "Extracts BuildingInstallationGeometryType",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+)]
 pub struct BuildingInstallationGeometryTypeExtractorFactory;
 
-impl ProcessorFactory for BuildingInstallationGeometryTypeExtractorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.BuildingInstallationGeometryTypeExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts BuildingInstallationGeometryType"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl BuildingInstallationGeometryTypeExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         let process = BuildingInstallationGeometryTypeExtractor {};
         Ok(Box::new(process))
@@ -12,7 +12,6 @@ use reearth_flow_runtime::{
 };
 use reearth_flow_types::{Attribute, AttributeValue, Feature};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::PlateauProcessorError;
 
@@ -38,66 +37,27 @@ static MAJOR_CITY_CODES: Lazy<Vec<&'static str>> = Lazy::new(|| {
     ]
 });
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.BuildingUsageAttributeValidator",
+    description = "This processor validates building usage attributes by checking for the presence of required attributes and ensuring the correctness of city codes. It outputs errors through the lBldgError and codeError ports if any issues are found.",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [L_BLDG_ERROR_PORT, CODE_ERROR_PORT, DEFAULT_PORT],
+    param = BuildingUsageAttributeValidatorParam,
+    schema = false,
+    error = PlateauProcessorError::BuildingUsageAttributeValidatorFactory,
+)]
 pub struct BuildingUsageAttributeValidatorFactory;
 
-impl ProcessorFactory for BuildingUsageAttributeValidatorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.BuildingUsageAttributeValidator"
-    }
-
-    fn description(&self) -> &str {
-        "This processor validates building usage attributes by checking for the presence of required attributes and ensuring the correctness of city codes. It outputs errors through the lBldgError and codeError ports if any issues are found."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            L_BLDG_ERROR_PORT.clone(),
-            CODE_ERROR_PORT.clone(),
-            DEFAULT_PORT.clone(),
-        ]
-    }
-
-    fn build(
+impl BuildingUsageAttributeValidatorFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        param: BuildingUsageAttributeValidatorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let param: BuildingUsageAttributeValidatorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                PlateauProcessorError::BuildingUsageAttributeValidatorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                PlateauProcessorError::BuildingUsageAttributeValidatorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(
-                PlateauProcessorError::BuildingUsageAttributeValidatorFactory(
-                    "Missing required parameter `with`".to_string(),
-                )
-                .into(),
-            );
-        };
         let mut city_name_to_code = HashMap::new();
         println!("{:?}", param.codelists_path);
         if let Some(codelists_path) = param.codelists_path {
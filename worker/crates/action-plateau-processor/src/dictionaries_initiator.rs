@@ -6,11 +6,10 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::{
     errors::PlateauProcessorError,
@@ -27,61 +26,27 @@ struct Schema {
     complex_types: HashMap<String, Vec<SchemaFeature>>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.DictionariesInitiator",
+    description = "Initializes dictionaries for PLATEAU",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REJECTED_PORT],
+    param = DictionariesInitiatorParam,
+    schema = false,
+    error = PlateauProcessorError::DictionariesInitiatorFactory,
+)]
 pub struct DictionariesInitiatorFactory;
 
-impl ProcessorFactory for DictionariesInitiatorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.DictionariesInitiator"
-    }
-
-    fn description(&self) -> &str {
-        "Initializes dictionaries for PLATEAU"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl DictionariesInitiatorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: DictionariesInitiatorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: DictionariesInitiatorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                PlateauProcessorError::DictionariesInitiatorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                PlateauProcessorError::DictionariesInitiatorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(PlateauProcessorError::DictionariesInitiatorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let xpath_to_properties = {
             let schema_json = params.schema_json.clone().ok_or(
                 PlateauProcessorError::DictionariesInitiatorFactory(
@@ -13,7 +13,7 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
 };
 use reearth_flow_storage::resolve::StorageResolver;
 use reearth_flow_storage::storage::Storage;
@@ -21,7 +21,7 @@ use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use reearth_flow_types::{Attribute, AttributeValue, Expr, Feature};
-use serde_json::{Number, Value};
+use serde_json::Number;
 
 use super::errors::PlateauProcessorError;
 
@@ -274,40 +274,22 @@ struct Envelope {
     upper_z: f64,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.DomainOfDefinitionValidator",
+    description = "Validates domain of definition of CityGML features",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REJECTED_PORT],
+)]
 pub struct DomainOfDefinitionValidatorFactory;
 
-impl ProcessorFactory for DomainOfDefinitionValidatorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.DomainOfDefinitionValidator"
-    }
-
-    fn description(&self) -> &str {
-        "Validates domain of definition of CityGML features"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl DomainOfDefinitionValidatorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         let process = DomainOfDefinitionValidator {
             feature_buffer: vec![],
@@ -408,6 +390,10 @@ impl Processor for DomainOfDefinitionValidator {
     fn name(&self) -> &str {
         "DomainOfDefinitionValidator"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
 
 #[allow(clippy::type_complexity)]
@@ -6,50 +6,30 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, Feature};
 use regex::Regex;
-use serde_json::Value;
-use std::collections::HashMap;
 use std::str::FromStr;
 
 static DIGITS_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d+").unwrap());
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.MaxLodExtractor",
+    description = "Extracts maxLod",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+)]
 pub struct MaxLodExtractorFactory;
 
-impl ProcessorFactory for MaxLodExtractorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.MaxLodExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts maxLod"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl MaxLodExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         let process = MaxLodExtractor {};
         Ok(Box::new(process))
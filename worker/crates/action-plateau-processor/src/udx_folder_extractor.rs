@@ -6,14 +6,13 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
 };
 use reearth_flow_storage::resolve::StorageResolver;
 
 use reearth_flow_eval_expr::engine::Engine;
 use reearth_flow_types::{Attribute, AttributeValue, Expr, Feature};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::PlateauProcessorError;
 
@@ -48,61 +47,27 @@ impl From<Response> for HashMap<Attribute, AttributeValue> {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.UDXFolderExtractor",
+    description = "Extracts UDX folders from cityGML path",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REJECTED_PORT],
+    param = UdxFolderExtractorParam,
+    schema = false,
+    error = PlateauProcessorError::UdxFolderExtractorFactory,
+)]
 pub struct UdxFolderExtractorFactory;
 
-impl ProcessorFactory for UdxFolderExtractorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.UDXFolderExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts UDX folders from cityGML path"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl UdxFolderExtractorFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: UdxFolderExtractorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: UdxFolderExtractorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                PlateauProcessorError::UdxFolderExtractorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                PlateauProcessorError::UdxFolderExtractorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(PlateauProcessorError::UdxFolderExtractorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let city_gml_path = expr_engine
             .compile(params.city_gml_path.as_ref())
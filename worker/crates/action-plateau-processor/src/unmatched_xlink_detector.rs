@@ -17,7 +17,6 @@ use reearth_flow_runtime::{
 };
 use reearth_flow_types::{Attribute, AttributeValue};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::{PlateauProcessorError, Result};
 
@@ -103,64 +102,27 @@ struct XlinkGmlElement {
     to: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.UnmatchedXlinkDetector",
+    description = "Detect unmatched xlink for PLATEAU",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [SUMMARY_PORT, UNMATCHED_XLINK_FROM, UNMATCHED_XLINK_TO],
+    param = UnmatchedXlinkDetectorParam,
+    schema = false,
+    error = PlateauProcessorError::UnmatchedXlinkDetectorFactory,
+)]
 pub struct UnmatchedXlinkDetectorFactory;
 
-impl ProcessorFactory for UnmatchedXlinkDetectorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.UnmatchedXlinkDetector"
-    }
-
-    fn description(&self) -> &str {
-        "Detect unmatched xlink for PLATEAU"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            SUMMARY_PORT.clone(),
-            UNMATCHED_XLINK_FROM.clone(),
-            UNMATCHED_XLINK_TO.clone(),
-        ]
-    }
-
-    fn build(
+impl UnmatchedXlinkDetectorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: UnmatchedXlinkDetectorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: UnmatchedXlinkDetectorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                PlateauProcessorError::UnmatchedXlinkDetectorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                PlateauProcessorError::UnmatchedXlinkDetectorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(PlateauProcessorError::UnmatchedXlinkDetectorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         let process = UnmatchedXlinkDetector { params };
         Ok(Box::new(process))
     }
@@ -400,65 +400,27 @@ impl SummaryResponse {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PLATEAU.XMLAttributeExtractor",
+    description = "Extracts attributes from XML fragments based on a schema definition",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [ATTRIBUTE_FEATURE_PORT, SUMMARY_PORT, FILE_PATH_PORT],
+    param = XmlAttributeExtractorParam,
+    schema = false,
+    error = PlateauProcessorError::XmlAttributeExtractorFactory,
+)]
 pub struct XmlAttributeExtractorFactory;
 
-impl ProcessorFactory for XmlAttributeExtractorFactory {
-    fn name(&self) -> &str {
-        "PLATEAU.XMLAttributeExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts attributes from XML fragments based on a schema definition"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            ATTRIBUTE_FEATURE_PORT.clone(),
-            SUMMARY_PORT.clone(),
-            FILE_PATH_PORT.clone(),
-        ]
-    }
-
-    fn build(
+impl XmlAttributeExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: XmlAttributeExtractorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: XmlAttributeExtractorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                PlateauProcessorError::XmlAttributeExtractorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                PlateauProcessorError::XmlAttributeExtractorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(PlateauProcessorError::XmlAttributeExtractorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let xpath_to_properties = {
             let schema_json = params.schema_json.clone().ok_or(
                 PlateauProcessorError::XmlAttributeExtractorFactory(
@@ -841,6 +803,10 @@ impl Processor for XmlAttributeExtractor {
     fn name(&self) -> &str {
         "XmlAttributeExtractor"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
 }
 
 fn create_feature_response(
@@ -5,70 +5,34 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, Expr, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::AttributeProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "AttributeAggregator",
+    description = "Aggregates features by attributes",
+    categories = ["Attribute"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = AttributeAggregatorParam,
+    error = AttributeProcessorError::AggregatorFactory,
+)]
 pub struct AttributeAggregatorFactory;
 
-impl ProcessorFactory for AttributeAggregatorFactory {
-    fn name(&self) -> &str {
-        "AttributeAggregator"
-    }
-
-    fn description(&self) -> &str {
-        "Aggregates features by attributes"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(AttributeAggregatorParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Attribute"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl AttributeAggregatorFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: AttributeAggregatorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: AttributeAggregatorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                AttributeProcessorError::AggregatorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                AttributeProcessorError::AggregatorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(AttributeProcessorError::AggregatorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let mut aggregate_attributes = Vec::<CompliledAggregateAttribute>::new();
         for aggregte_attribute in &params.aggregate_attributes {
@@ -220,6 +184,22 @@ impl Processor for AttributeAggregator {
     fn name(&self) -> &str {
         "AttributeAggregator"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = serde_json::to_vec(&self.buffer)
+            .map_err(|e| AttributeProcessorError::Aggregator(e.to_string()))?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        self.buffer = serde_json::from_slice(state)
+            .map_err(|e| AttributeProcessorError::Aggregator(e.to_string()))?;
+        Ok(())
+    }
 }
 
 fn generate_aggregate_key(values: &[String]) -> String {
@@ -1,77 +1,44 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
+    spill::{FeatureBuffer, FeatureBufferState},
 };
-use reearth_flow_types::{Attribute, AttributeValue, Feature};
+use reearth_flow_types::{Attribute, AttributeValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::AttributeProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "AttributeDuplicateFilter",
+    description = "Filters features by duplicate attributes",
+    categories = ["Attribute"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = AttributeDuplicateFilterParam,
+    error = AttributeProcessorError::DuplicateFilterFactory,
+)]
 pub struct AttributeDuplicateFilterFactory;
 
-impl ProcessorFactory for AttributeDuplicateFilterFactory {
-    fn name(&self) -> &str {
-        "AttributeDuplicateFilter"
-    }
-
-    fn description(&self) -> &str {
-        "Filters features by duplicate attributes"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(AttributeDuplicateFilterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Attribute"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl AttributeDuplicateFilterFactory {
+    fn build_processor(
         &self,
-        _ctx: NodeContext,
+        ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: AttributeDuplicateFilterParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: AttributeDuplicateFilterParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                AttributeProcessorError::DuplicateFilterFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                AttributeProcessorError::DuplicateFilterFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(AttributeProcessorError::DuplicateFilterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let process = AttributeDuplicateFilter {
             params,
-            buffer: HashMap::new(),
+            buffer: FeatureBuffer::new(Arc::clone(&ctx.spill_manager)),
+            latest: HashMap::new(),
         };
         Ok(Box::new(process))
     }
@@ -80,7 +47,16 @@ impl ProcessorFactory for AttributeDuplicateFilterFactory {
 #[derive(Debug, Clone)]
 pub struct AttributeDuplicateFilter {
     params: AttributeDuplicateFilterParam,
-    buffer: HashMap<AttributeValue, Feature>,
+    buffer: FeatureBuffer,
+    /// Position in the buffer of the last feature of each key.
+    latest: HashMap<AttributeValue, usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AttributeDuplicateFilterState {
+    buffer: FeatureBufferState,
+    latest: Vec<(AttributeValue, usize)>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
@@ -109,8 +85,11 @@ impl Processor for AttributeDuplicateFilter {
             .flat_map(|attribute| feature.get(attribute))
             .collect::<Vec<_>>();
         let key_values = key_values.iter().map(|&v| v.clone()).collect::<Vec<_>>();
+        self.latest
+            .insert(AttributeValue::Array(key_values), self.buffer.len());
         self.buffer
-            .insert(AttributeValue::Array(key_values), feature.clone());
+            .push(ctx.feature)
+            .map_err(|e| AttributeProcessorError::DuplicateFilter(e.to_string()))?;
         Ok(())
     }
 
@@ -119,10 +98,16 @@ impl Processor for AttributeDuplicateFilter {
         ctx: NodeContext,
         fw: &mut dyn ProcessorChannelForwarder,
     ) -> Result<(), BoxedError> {
-        for feature in self.buffer.values() {
+        let latest = self.latest.values().collect::<HashSet<_>>();
+        for (index, feature) in self.buffer.iter().enumerate() {
+            let feature =
+                feature.map_err(|e| AttributeProcessorError::DuplicateFilter(e.to_string()))?;
+            if !latest.contains(&index) {
+                continue;
+            }
             fw.send(ExecutorContext::new_with_node_context_feature_and_port(
                 &ctx,
-                feature.clone(),
+                feature,
                 DEFAULT_PORT.clone(),
             ));
         }
@@ -132,4 +117,32 @@ impl Processor for AttributeDuplicateFilter {
     fn name(&self) -> &str {
         "AttributeDuplicateFilter"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = AttributeDuplicateFilterState {
+            buffer: self.buffer.state(),
+            latest: self
+                .latest
+                .iter()
+                .map(|(key, index)| (key.clone(), *index))
+                .collect(),
+        };
+        let state = serde_json::to_vec(&state)
+            .map_err(|e| AttributeProcessorError::DuplicateFilter(e.to_string()))?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        let state: AttributeDuplicateFilterState = serde_json::from_slice(state)
+            .map_err(|e| AttributeProcessorError::DuplicateFilter(e.to_string()))?;
+        self.buffer
+            .restore(state.buffer)
+            .map_err(|e| AttributeProcessorError::DuplicateFilter(e.to_string()))?;
+        self.latest = state.latest.into_iter().collect();
+        Ok(())
+    }
 }
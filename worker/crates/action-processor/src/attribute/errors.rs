@@ -25,6 +25,8 @@ pub(super) enum AttributeProcessorError {
     FilePathInfoExtractor(String),
     #[error("StatisticsCalculator Factory error: {0}")]
     StatisticsCalculatorFactory(String),
+    #[error("StatisticsCalculator error: {0}")]
+    StatisticsCalculator(String),
 }
 
 #[allow(dead_code)]
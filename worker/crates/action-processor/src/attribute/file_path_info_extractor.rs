@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs, os::unix::fs::MetadataExt, path::Path};
+use std::{fs, os::unix::fs::MetadataExt, path::Path};
 
 use reearth_flow_common::fs::get_dir_size;
 use reearth_flow_runtime::{
@@ -6,69 +6,35 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::{Number, Value};
+use serde_json::Number;
 
 use super::errors::AttributeProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "AttributeFilePathInfoExtractor",
+    description = "Extracts file path information from attributes",
+    categories = ["Attribute"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REJECTED_PORT],
+    param = AttributeFilePathInfoExtractor,
+    error = AttributeProcessorError::FilePathInfoExtractor,
+)]
 pub struct AttributeFilePathInfoExtractorFactory;
 
-impl ProcessorFactory for AttributeFilePathInfoExtractorFactory {
-    fn name(&self) -> &str {
-        "AttributeFilePathInfoExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts file path information from attributes"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(AttributeFilePathInfoExtractor))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Attribute"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl AttributeFilePathInfoExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        processor: AttributeFilePathInfoExtractor,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let processor: AttributeFilePathInfoExtractor = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                AttributeProcessorError::FilePathInfoExtractor(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                AttributeProcessorError::FilePathInfoExtractor(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(AttributeProcessorError::FilePathInfoExtractor(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(processor))
     }
 }
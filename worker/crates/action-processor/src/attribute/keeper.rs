@@ -5,63 +5,34 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::Attribute;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::AttributeProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "AttributeKeeper",
+    description = "Keeps only specified attributes",
+    categories = ["Attribute"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = AttributeKeeper,
+    error = AttributeProcessorError::Keeper,
+)]
 pub struct AttributeKeeperFactory;
 
-impl ProcessorFactory for AttributeKeeperFactory {
-    fn name(&self) -> &str {
-        "AttributeKeeper"
-    }
-
-    fn description(&self) -> &str {
-        "Keeps only specified attributes"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(AttributeKeeper))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Attribute"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl AttributeKeeperFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        processor: AttributeKeeper,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let processor: AttributeKeeper = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                AttributeProcessorError::Keeper(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                AttributeProcessorError::Keeper(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(AttributeProcessorError::Keeper(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(processor))
     }
 }
@@ -1,11 +1,11 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use rhai::Dynamic;
 
@@ -13,62 +13,29 @@ use reearth_flow_eval_expr::engine::Engine;
 use reearth_flow_types::{Expr, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::AttributeProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "AttributeManager",
+    description = "Manages attributes",
+    categories = ["Attribute"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = AttributeManagerParam,
+    error = AttributeProcessorError::ManagerFactory,
+)]
 pub struct AttributeManagerFactory;
 
-impl ProcessorFactory for AttributeManagerFactory {
-    fn name(&self) -> &str {
-        "AttributeManager"
-    }
-
-    fn description(&self) -> &str {
-        "Manages attributes"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(AttributeManagerParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Attribute"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl AttributeManagerFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: AttributeManagerParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: AttributeManagerParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                AttributeProcessorError::ManagerFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                AttributeProcessorError::ManagerFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(AttributeProcessorError::ManagerFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let operations = convert_single_operation(&params.operations, Arc::clone(&expr_engine));
 
@@ -11,66 +11,31 @@ use reearth_flow_runtime::{
 use reearth_flow_types::{Attribute, AttributeValue, Expr, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::AttributeProcessorError;
 
 pub static COMPLETE_PORT: Lazy<Port> = Lazy::new(|| Port::new("complete"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "StatisticsCalculator",
+    description = "Calculates statistics of features",
+    categories = ["Attribute"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, COMPLETE_PORT],
+    param = StatisticsCalculatorParam,
+    error = AttributeProcessorError::StatisticsCalculatorFactory,
+)]
 pub struct StatisticsCalculatorFactory;
 
-impl ProcessorFactory for StatisticsCalculatorFactory {
-    fn name(&self) -> &str {
-        "StatisticsCalculator"
-    }
-
-    fn description(&self) -> &str {
-        "Calculates statistics of features"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(StatisticsCalculatorParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Attribute"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), COMPLETE_PORT.clone()]
-    }
-
-    fn build(
+impl StatisticsCalculatorFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: StatisticsCalculatorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: StatisticsCalculatorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                AttributeProcessorError::StatisticsCalculatorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                AttributeProcessorError::StatisticsCalculatorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(AttributeProcessorError::StatisticsCalculatorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let mut calculations = Vec::<CompliledCalculation>::new();
         for calculation in &params.calculations {
@@ -209,4 +174,20 @@ impl Processor for StatisticsCalculator {
     fn name(&self) -> &str {
         "StatisticsCalculator"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = serde_json::to_vec(&self.aggregate_buffer)
+            .map_err(|e| AttributeProcessorError::StatisticsCalculator(e.to_string()))?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        self.aggregate_buffer = serde_json::from_slice(state)
+            .map_err(|e| AttributeProcessorError::StatisticsCalculator(e.to_string()))?;
+        Ok(())
+    }
 }
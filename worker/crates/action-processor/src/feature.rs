@@ -7,3 +7,4 @@ pub mod reader;
 pub mod rhai;
 pub mod sorter;
 pub mod transformer;
+pub mod wasm;
@@ -9,12 +9,11 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::FeatureProcessorError;
 
@@ -54,57 +53,43 @@ impl AtomicCounterMap {
         let result = counter.fetch_add(1, Ordering::SeqCst);
         result as i64
     }
-}
-
-#[derive(Debug, Clone, Default)]
-pub struct FeatureCounterFactory;
-
-impl ProcessorFactory for FeatureCounterFactory {
-    fn name(&self) -> &str {
-        "FeatureCounter"
-    }
 
-    fn description(&self) -> &str {
-        "Counts features"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(FeatureCounterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Feature"]
+    fn counts(&self) -> HashMap<String, usize> {
+        self.inner
+            .lock()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.load(Ordering::SeqCst)))
+            .collect()
     }
 
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
+    fn restore(&self, counts: HashMap<String, usize>) {
+        *self.inner.lock() = counts
+            .into_iter()
+            .map(|(k, v)| (k, AtomicUsize::new(v)))
+            .collect();
     }
+}
 
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![REJECTED_PORT.clone()]
-    }
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "FeatureCounter",
+    description = "Counts features",
+    categories = ["Feature"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [REJECTED_PORT],
+    param = FeatureCounterParam,
+    error = FeatureProcessorError::CounterFactory,
+)]
+pub struct FeatureCounterFactory;
 
-    fn build(
+impl FeatureCounterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: FeatureCounterParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: FeatureCounterParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                FeatureProcessorError::CounterFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                FeatureProcessorError::CounterFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(FeatureProcessorError::CounterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let process = FeatureCounter {
             counter: AtomicCounterMap::new(params.count_start),
             params,
@@ -177,4 +162,21 @@ impl Processor for FeatureCounter {
     fn name(&self) -> &str {
         "FeatureCounter"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = serde_json::to_vec(&self.counter.counts())
+            .map_err(|e| FeatureProcessorError::Counter(e.to_string()))?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        let counts = serde_json::from_slice(state)
+            .map_err(|e| FeatureProcessorError::Counter(e.to_string()))?;
+        self.counter.restore(counts);
+        Ok(())
+    }
 }
@@ -31,6 +31,10 @@ pub(crate) enum FeatureProcessorError {
     RhaiCallerFactory(String),
     #[error("RhaiCaller error: {0}")]
     RhaiCaller(String),
+    #[error("WasmProcessorFactory error: {0}")]
+    WasmProcessorFactory(String),
+    #[error("WasmProcessor error: {0}")]
+    WasmProcessor(String),
 }
 
 #[allow(dead_code)]
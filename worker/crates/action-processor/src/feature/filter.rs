@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use reearth_flow_action_log::action_error_log;
 use reearth_flow_runtime::{
@@ -11,58 +11,29 @@ use reearth_flow_runtime::{
 use reearth_flow_types::Expr;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::FeatureProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "FeatureFilter",
+    description = "Filters features based on conditions",
+    categories = ["Feature"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [REJECTED_PORT],
+    param = FeatureFilterParam,
+    error = FeatureProcessorError::FilterFactory,
+)]
 pub struct FeatureFilterFactory;
 
-impl ProcessorFactory for FeatureFilterFactory {
-    fn name(&self) -> &str {
-        "FeatureFilter"
-    }
-
-    fn description(&self) -> &str {
-        "Filters features based on conditions"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(FeatureFilterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Feature"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl FeatureFilterFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: FeatureFilterParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: FeatureFilterParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                FeatureProcessorError::FilterFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                FeatureProcessorError::FilterFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(FeatureProcessorError::FilterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let mut conditions = Vec::new();
         for condition in &params.conditions {
@@ -6,7 +6,7 @@ use reearth_flow_runtime::node::{NodeKind, ProcessorFactory};
 use super::{
     counter::FeatureCounterFactory, filter::FeatureFilterFactory, merger::FeatureMergerFactory,
     reader::FeatureReaderFactory, rhai::RhaiCallerFactory, sorter::FeatureSorterFactory,
-    transformer::FeatureTransformerFactory,
+    transformer::FeatureTransformerFactory, wasm::WasmProcessorFactory,
 };
 
 pub static ACTION_MAPPINGS: Lazy<HashMap<String, NodeKind>> = Lazy::new(|| {
@@ -18,6 +18,7 @@ pub static ACTION_MAPPINGS: Lazy<HashMap<String, NodeKind>> = Lazy::new(|| {
         Box::<FeatureCounterFactory>::default(),
         Box::<FeatureReaderFactory>::default(),
         Box::<RhaiCallerFactory>::default(),
+        Box::<WasmProcessorFactory>::default(),
     ];
     factories
         .into_iter()
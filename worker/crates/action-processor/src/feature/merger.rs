@@ -10,11 +10,11 @@ use reearth_flow_runtime::{
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
     node::{Port, Processor, ProcessorFactory},
+    spill::{FeatureBuffer, FeatureBufferState},
 };
 use reearth_flow_types::{Expr, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::FeatureProcessorError;
 
@@ -23,54 +23,26 @@ static SUPPLIER_PORT: Lazy<Port> = Lazy::new(|| Port::new("supplier"));
 static MERGED_PORT: Lazy<Port> = Lazy::new(|| Port::new("merged"));
 static UNMERGED_PORT: Lazy<Port> = Lazy::new(|| Port::new("unmerged"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "FeatureMerger",
+    description = "Merges features by attributes",
+    categories = ["Feature"],
+    input_ports = [REQUESTOR_PORT, SUPPLIER_PORT],
+    output_ports = [MERGED_PORT, UNMERGED_PORT],
+    param = FeatureMergerParam,
+    error = FeatureProcessorError::MergerFactory,
+)]
 pub struct FeatureMergerFactory;
 
-impl ProcessorFactory for FeatureMergerFactory {
-    fn name(&self) -> &str {
-        "FeatureMerger"
-    }
-
-    fn description(&self) -> &str {
-        "Merges features by attributes"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(FeatureMergerParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Feature"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![REQUESTOR_PORT.clone(), SUPPLIER_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![MERGED_PORT.clone(), UNMERGED_PORT.clone()]
-    }
-
-    fn build(
+impl FeatureMergerFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: FeatureMergerParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: FeatureMergerParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                FeatureProcessorError::MergerFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                FeatureProcessorError::MergerFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(FeatureProcessorError::MergerFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let requestor_attribute = expr_engine
             .compile(params.requestor_attribute.as_ref())
@@ -94,7 +66,7 @@ impl ProcessorFactory for FeatureMergerFactory {
                 requestor_attribute,
                 supplier_attribute,
             },
-            request_features: vec![],
+            request_features: FeatureBuffer::new(Arc::clone(&ctx.spill_manager)),
             supplier_buffer: HashMap::new(),
         };
         Ok(Box::new(process))
@@ -104,7 +76,15 @@ impl ProcessorFactory for FeatureMergerFactory {
 #[derive(Debug, Clone)]
 pub struct FeatureMerger {
     params: CompliledParam,
-    request_features: Vec<Feature>,
+    /// Requestors may be spilled to storage. Suppliers are kept in memory to be looked up.
+    request_features: FeatureBuffer,
+    supplier_buffer: HashMap<String, Vec<Feature>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FeatureMergerState {
+    request_features: FeatureBufferState,
     supplier_buffer: HashMap<String, Vec<Feature>>,
 }
 
@@ -136,7 +116,9 @@ impl Processor for FeatureMerger {
         match ctx.port {
             port if port == REQUESTOR_PORT.clone() => {
                 let feature = ctx.feature;
-                self.request_features.push(feature);
+                self.request_features
+                    .push(feature)
+                    .map_err(|e| FeatureProcessorError::Merger(e.to_string()))?;
             }
             port if port == SUPPLIER_PORT.clone() => {
                 let feature = ctx.feature;
@@ -171,6 +153,8 @@ impl Processor for FeatureMerger {
     ) -> Result<(), BoxedError> {
         let expr_engine = Arc::clone(&ctx.expr_engine);
         for request_feature in self.request_features.iter() {
+            let request_feature =
+                request_feature.map_err(|e| FeatureProcessorError::Merger(e.to_string()))?;
             let scope = request_feature.new_scope(expr_engine.clone());
             let request_value = scope
                 .eval_ast::<String>(&self.params.requestor_attribute)
@@ -208,4 +192,28 @@ impl Processor for FeatureMerger {
     fn name(&self) -> &str {
         "FeatureMerger"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = FeatureMergerState {
+            request_features: self.request_features.state(),
+            supplier_buffer: self.supplier_buffer.clone(),
+        };
+        let state =
+            serde_json::to_vec(&state).map_err(|e| FeatureProcessorError::Merger(e.to_string()))?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        let state: FeatureMergerState = serde_json::from_slice(state)
+            .map_err(|e| FeatureProcessorError::Merger(e.to_string()))?;
+        self.request_features
+            .restore(state.request_features)
+            .map_err(|e| FeatureProcessorError::Merger(e.to_string()))?;
+        self.supplier_buffer = state.supplier_buffer;
+        Ok(())
+    }
 }
@@ -1,72 +1,42 @@
 mod citygml;
 mod csv;
 
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::Expr;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors;
 use super::errors::FeatureProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "FeatureReader",
+    description = "Filters features based on conditions",
+    categories = ["Feature"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = FeatureReaderParam,
+    error = FeatureProcessorError::FilterFactory,
+)]
 pub struct FeatureReaderFactory;
 
-impl ProcessorFactory for FeatureReaderFactory {
-    fn name(&self) -> &str {
-        "FeatureReader"
-    }
-
-    fn description(&self) -> &str {
-        "Filters features based on conditions"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(FeatureReaderParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Feature"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl FeatureReaderFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: FeatureReaderParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: FeatureReaderParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                FeatureProcessorError::FilterFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                FeatureProcessorError::FilterFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(FeatureProcessorError::FilterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let expr_engine = Arc::clone(&ctx.expr_engine);
         match params {
             FeatureReaderParam::CityGML { common_property } => {
@@ -1,72 +1,39 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, Expr};
 use rhai::Dynamic;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::FeatureProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "RhaiCaller",
+    description = "Calls Rhai script",
+    categories = ["Feature"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = RhaiCallerParam,
+    error = FeatureProcessorError::RhaiCallerFactory,
+)]
 pub struct RhaiCallerFactory;
 
-impl ProcessorFactory for RhaiCallerFactory {
-    fn name(&self) -> &str {
-        "RhaiCaller"
-    }
-
-    fn description(&self) -> &str {
-        "Calls Rhai script"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(RhaiCallerParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Feature"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl RhaiCallerFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: RhaiCallerParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: RhaiCallerParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                FeatureProcessorError::RhaiCallerFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                FeatureProcessorError::RhaiCallerFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(FeatureProcessorError::RhaiCallerFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let is_target_ast = expr_engine
             .compile(params.is_target.into_inner().as_str())
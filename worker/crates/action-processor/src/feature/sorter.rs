@@ -1,70 +1,45 @@
-use std::{cmp::Ordering, collections::HashMap};
+use std::{cmp::Ordering, sync::Arc};
 
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
+    spill::FeatureBuffer,
 };
 use reearth_flow_types::{Attribute, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::FeatureProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "FeatureSorter",
+    description = "Sorts features by attributes",
+    categories = ["Feature"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = FeatureSorterParam,
+    error = FeatureProcessorError::SorterFactory,
+)]
 pub struct FeatureSorterFactory;
 
-impl ProcessorFactory for FeatureSorterFactory {
-    fn name(&self) -> &str {
-        "FeatureSorter"
-    }
-
-    fn description(&self) -> &str {
-        "Sorts features by attributes"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(FeatureSorterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Feature"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl FeatureSorterFactory {
+    fn build_processor(
         &self,
-        _ctx: NodeContext,
+        ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: FeatureSorterParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: FeatureSorterParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                FeatureProcessorError::SorterFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                FeatureProcessorError::SorterFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(FeatureProcessorError::SorterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
+        let sort_by = params.sort_by.clone();
         let process = FeatureSorter {
-            params,
-            buffer: vec![],
+            buffer: FeatureBuffer::sorted(
+                Arc::clone(&ctx.spill_manager),
+                Arc::new(move |a, b| compare(&sort_by, a, b)),
+            ),
         };
         Ok(Box::new(process))
     }
@@ -72,8 +47,7 @@ impl ProcessorFactory for FeatureSorterFactory {
 
 #[derive(Debug, Clone)]
 pub struct FeatureSorter {
-    params: FeatureSorterParam,
-    buffer: Vec<Feature>,
+    buffer: FeatureBuffer,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
@@ -110,7 +84,9 @@ impl Processor for FeatureSorter {
         _fw: &mut dyn ProcessorChannelForwarder,
     ) -> Result<(), BoxedError> {
         let feature = ctx.feature;
-        self.buffer.push(feature);
+        self.buffer
+            .push(feature)
+            .map_err(|e| FeatureProcessorError::Sorter(e.to_string()))?;
         Ok(())
     }
 
@@ -119,35 +95,8 @@ impl Processor for FeatureSorter {
         ctx: NodeContext,
         fw: &mut dyn ProcessorChannelForwarder,
     ) -> Result<(), BoxedError> {
-        let mut features = self.buffer.clone();
-        features.sort_by(|a, b| {
-            let cmp = self
-                .params
-                .sort_by
-                .iter()
-                .map(|sort_by| {
-                    let attribute = &sort_by.attribute;
-                    let order = &sort_by.order;
-                    let a = a.attributes.get(attribute);
-                    let b = b.attributes.get(attribute);
-                    match (a, b) {
-                        (Some(a), Some(b)) => {
-                            if *order == Order::Asc {
-                                a.partial_cmp(b)
-                            } else {
-                                b.partial_cmp(a)
-                            }
-                        }
-                        _ => None,
-                    }
-                })
-                .collect::<Vec<_>>();
-            cmp.iter().fold(Ordering::Equal, |acc, item| match acc {
-                Ordering::Equal if item.is_some() => item.unwrap(),
-                _ => acc,
-            })
-        });
-        for feature in features {
+        for feature in self.buffer.iter() {
+            let feature = feature.map_err(|e| FeatureProcessorError::Sorter(e.to_string()))?;
             fw.send(ExecutorContext::new_with_node_context_feature_and_port(
                 &ctx,
                 feature,
@@ -160,4 +109,125 @@ impl Processor for FeatureSorter {
     fn name(&self) -> &str {
         "FeatureSorter"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = serde_json::to_vec(&self.buffer.state())
+            .map_err(|e| FeatureProcessorError::Sorter(e.to_string()))?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        let state = serde_json::from_slice(state)
+            .map_err(|e| FeatureProcessorError::Sorter(e.to_string()))?;
+        self.buffer
+            .restore(state)
+            .map_err(|e| FeatureProcessorError::Sorter(e.to_string()))?;
+        Ok(())
+    }
+}
+
+fn compare(sort_by: &[SortBy], a: &Feature, b: &Feature) -> Ordering {
+    let cmp = sort_by
+        .iter()
+        .map(|sort_by| {
+            let attribute = &sort_by.attribute;
+            let order = &sort_by.order;
+            let a = a.attributes.get(attribute);
+            let b = b.attributes.get(attribute);
+            match (a, b) {
+                (Some(a), Some(b)) => {
+                    if *order == Order::Asc {
+                        a.partial_cmp(b)
+                    } else {
+                        b.partial_cmp(a)
+                    }
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    cmp.iter().fold(Ordering::Equal, |acc, item| match acc {
+        Ordering::Equal if item.is_some() => item.unwrap(),
+        _ => acc,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use reearth_flow_common::uri::Uri;
+    use reearth_flow_runtime::spill::SpillManager;
+    use reearth_flow_storage::resolve::StorageResolver;
+    use reearth_flow_types::AttributeValue;
+
+    use crate::tests::utils::{
+        create_default_execute_context, CollectingProcessorChannelForwarder,
+    };
+
+    use super::*;
+
+    fn build_sorter(storage_resolver: &StorageResolver) -> Box<dyn Processor> {
+        let spill_manager =
+            SpillManager::new(200, &Uri::for_test("ram:///spill/"), storage_resolver)
+                .unwrap()
+                .keep_files();
+        let ctx = NodeContext {
+            spill_manager: Arc::new(spill_manager),
+            ..Default::default()
+        };
+        let params = FeatureSorterParam {
+            sort_by: vec![SortBy {
+                attribute: Attribute::new("value"),
+                order: Order::Asc,
+            }],
+        };
+        FeatureSorterFactory
+            .build_processor(ctx, EventHub::new(1), "FeatureSorter".to_string(), params)
+            .unwrap()
+    }
+
+    fn feature(value: i64) -> Feature {
+        let mut feature = Feature::new();
+        feature.insert("value", AttributeValue::Number(value.into()));
+        feature
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let storage_resolver = StorageResolver::new();
+        let values = (0..40).map(|i| (i * 17) % 40).collect::<Vec<_>>();
+        let (before, after) = values.split_at(25);
+
+        // The job stops after a checkpoint, with some of the features spilled.
+        let mut sorter = build_sorter(&storage_resolver);
+        let mut fw = CollectingProcessorChannelForwarder::default();
+        for value in before {
+            let ctx = create_default_execute_context(&feature(*value));
+            sorter.process(ctx, &mut fw).unwrap();
+        }
+        let state = sorter.serialize_state().unwrap().unwrap();
+        drop(sorter);
+
+        let mut sorter = build_sorter(&storage_resolver);
+        sorter.restore_state(&state).unwrap();
+        for value in after {
+            let ctx = create_default_execute_context(&feature(*value));
+            sorter.process(ctx, &mut fw).unwrap();
+        }
+        sorter.finish(NodeContext::default(), &mut fw).unwrap();
+        let sorted = fw
+            .send_features
+            .iter()
+            .map(|feature| feature.get(&"value").cloned().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sorted,
+            (0..40)
+                .map(|value| AttributeValue::Number(value.into()))
+                .collect::<Vec<_>>()
+        );
+    }
 }
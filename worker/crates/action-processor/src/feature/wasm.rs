@@ -0,0 +1,442 @@
+use std::{fmt::Debug, str::FromStr, sync::Arc};
+
+use parking_lot::Mutex;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::{
+    channels::ProcessorChannelForwarder,
+    errors::BoxedError,
+    event::EventHub,
+    executor_operation::{ExecutorContext, NodeContext},
+    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+};
+use reearth_flow_types::Feature;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use wasmtime::{
+    Caller, Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc,
+};
+
+use super::errors::FeatureProcessorError;
+
+const HOST_MODULE: &str = "reearth_flow";
+const DEFAULT_FUEL: u64 = 1_000_000_000;
+const DEFAULT_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Runs a WebAssembly module on each feature.
+///
+/// The module runs without access to the host besides the functions below. Features are passed
+/// as the JSON of a [`Feature`], in the same format as the feature store. The module exports:
+///
+/// - `memory`: its linear memory.
+/// - `alloc(len: i32) -> i32`: returns a pointer to `len` bytes the host writes the input to. The
+///   module owns the buffer afterwards.
+/// - `process(ptr: i32, len: i32) -> i32`: handles a feature, returning `0` on success.
+/// - `finish() -> i32` (optional): called once after the last feature, returning `0` on success.
+///
+/// The module sends features with the import `reearth_flow.emit(port_ptr: i32, port_len: i32,
+/// ptr: i32, len: i32)`, where the first pair is the UTF-8 name of the output port and the second
+/// the JSON of the feature. Nothing is sent for a feature the module does not emit. The ports
+/// are chosen by the module, so the processor declares none and its edges may use any port.
+///
+/// Each call of `process` and `finish` is given `fuel` units of fuel, and the memory of the
+/// module is limited to `memoryLimit` bytes. The module keeps its state between calls, until a
+/// call fails.
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "WasmProcessor",
+    description = "Processes features with a WebAssembly module",
+    categories = ["Feature"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [],
+    param = WasmProcessorParam,
+    error = FeatureProcessorError::WasmProcessorFactory,
+)]
+pub struct WasmProcessorFactory;
+
+impl WasmProcessorFactory {
+    fn build_processor(
+        &self,
+        ctx: NodeContext,
+        _event_hub: EventHub,
+        _action: String,
+        params: WasmProcessorParam,
+    ) -> Result<Box<dyn Processor>, BoxedError> {
+        let uri = Uri::from_str(params.module.as_str()).map_err(|e| {
+            FeatureProcessorError::WasmProcessorFactory(format!("Invalid module uri: {:?}", e))
+        })?;
+        let storage = ctx
+            .storage_resolver
+            .resolve(&uri)
+            .map_err(|e| FeatureProcessorError::WasmProcessorFactory(format!("{:?}", e)))?;
+        let bytes = storage
+            .get_sync(uri.path().as_path())
+            .map_err(|e| FeatureProcessorError::WasmProcessorFactory(format!("{:?}", e)))?;
+
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| {
+            FeatureProcessorError::WasmProcessorFactory(format!("Failed to create engine: {}", e))
+        })?;
+        let module = Module::new(&engine, bytes.as_ref()).map_err(|e| {
+            FeatureProcessorError::WasmProcessorFactory(format!("Failed to compile module: {}", e))
+        })?;
+        let process = WasmProcessor {
+            engine,
+            module,
+            fuel: params.fuel.unwrap_or(DEFAULT_FUEL),
+            memory_limit: params.memory_limit.unwrap_or(DEFAULT_MEMORY_LIMIT),
+            instance: Default::default(),
+        };
+        // Fails early on a module that does not follow the ABI.
+        process
+            .instantiate()
+            .map_err(FeatureProcessorError::WasmProcessorFactory)?;
+        Ok(Box::new(process))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WasmProcessorParam {
+    /// Location of the `.wasm` module.
+    module: String,
+    /// Fuel given to each call of the module. Defaults to 1,000,000,000.
+    fuel: Option<u64>,
+    /// Maximum size of the memory of the module in bytes. Defaults to 64 MiB.
+    memory_limit: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WasmProcessor {
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    memory_limit: usize,
+    /// Created on the first feature, so that each copy of the processor has its own.
+    instance: Arc<Mutex<Option<WasmInstance>>>,
+}
+
+struct HostState {
+    limits: StoreLimits,
+    emitted: Vec<(Port, Feature)>,
+}
+
+struct WasmInstance {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    process: TypedFunc<(i32, i32), i32>,
+    finish: Option<TypedFunc<(), i32>>,
+}
+
+impl Debug for WasmInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmInstance").finish_non_exhaustive()
+    }
+}
+
+impl WasmProcessor {
+    fn instantiate(&self) -> Result<WasmInstance, String> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.memory_limit)
+            .instances(1)
+            .build();
+        let mut store = Store::new(
+            &self.engine,
+            HostState {
+                limits,
+                emitted: vec![],
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        let mut linker = Linker::new(&self.engine);
+        linker
+            .func_wrap(HOST_MODULE, "emit", emit)
+            .map_err(|e| format!("Failed to link emit: {}", e))?;
+        let instance: Instance = linker
+            .instantiate(&mut store, &self.module)
+            .map_err(|e| format!("Failed to instantiate module: {}", e))?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("The module does not export `memory`")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| format!("Invalid export `alloc`: {}", e))?;
+        let process = instance
+            .get_typed_func::<(i32, i32), i32>(&mut store, "process")
+            .map_err(|e| format!("Invalid export `process`: {}", e))?;
+        let finish = match instance.get_func(&mut store, "finish") {
+            Some(func) => Some(
+                func.typed::<(), i32>(&store)
+                    .map_err(|e| format!("Invalid export `finish`: {}", e))?,
+            ),
+            None => None,
+        };
+        Ok(WasmInstance {
+            store,
+            memory,
+            alloc,
+            process,
+            finish,
+        })
+    }
+}
+
+impl WasmInstance {
+    fn process(&mut self, feature: &Feature, fuel: u64) -> Result<Vec<(Port, Feature)>, String> {
+        let input = serde_json::to_vec(feature)
+            .map_err(|e| format!("Failed to serialize feature: {}", e))?;
+        let len = i32::try_from(input.len()).map_err(|_| "The feature is too large")?;
+        self.store.set_fuel(fuel).map_err(|e| e.to_string())?;
+        let ptr = self
+            .alloc
+            .call(&mut self.store, len)
+            .map_err(|e| format!("`alloc` failed: {}", e))?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, &input)
+            .map_err(|e| format!("Failed to write feature: {}", e))?;
+        let code = self
+            .process
+            .call(&mut self.store, (ptr, len))
+            .map_err(|e| format!("`process` failed: {}", e))?;
+        self.take_emitted(code, "process")
+    }
+
+    fn finish(&mut self, fuel: u64) -> Result<Vec<(Port, Feature)>, String> {
+        let Some(finish) = self.finish.clone() else {
+            return Ok(vec![]);
+        };
+        self.store.set_fuel(fuel).map_err(|e| e.to_string())?;
+        let code = finish
+            .call(&mut self.store, ())
+            .map_err(|e| format!("`finish` failed: {}", e))?;
+        self.take_emitted(code, "finish")
+    }
+
+    fn take_emitted(&mut self, code: i32, func: &str) -> Result<Vec<(Port, Feature)>, String> {
+        let emitted = std::mem::take(&mut self.store.data_mut().emitted);
+        if code != 0 {
+            return Err(format!("`{}` returned {}", func, code));
+        }
+        Ok(emitted)
+    }
+}
+
+fn emit(
+    mut caller: Caller<'_, HostState>,
+    port_ptr: i32,
+    port_len: i32,
+    ptr: i32,
+    len: i32,
+) -> wasmtime::Result<()> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("The module does not export `memory`"))?;
+    let data = memory.data(&caller);
+    let port = read(data, port_ptr, port_len)?;
+    let port = std::str::from_utf8(port)
+        .map_err(|e| wasmtime::Error::msg(format!("Invalid port: {}", e)))?;
+    let port = Port::new(port);
+    let feature: Feature = serde_json::from_slice(read(data, ptr, len)?)
+        .map_err(|e| wasmtime::Error::msg(format!("Invalid feature: {}", e)))?;
+    caller.data_mut().emitted.push((port, feature));
+    Ok(())
+}
+
+fn read(data: &[u8], ptr: i32, len: i32) -> wasmtime::Result<&[u8]> {
+    let start = ptr as u32 as usize;
+    let end = start.saturating_add(len as u32 as usize);
+    data.get(start..end)
+        .ok_or_else(|| wasmtime::Error::msg("Out of bounds memory access"))
+}
+
+impl Processor for WasmProcessor {
+    fn initialize(&mut self, _ctx: NodeContext) {
+        self.instance = Default::default();
+    }
+
+    fn process(
+        &mut self,
+        ctx: ExecutorContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        let mut instance = self.instance.lock();
+        if instance.is_none() {
+            *instance = Some(
+                self.instantiate()
+                    .map_err(FeatureProcessorError::WasmProcessor)?,
+            );
+        }
+        let emitted = match instance.as_mut().unwrap().process(&ctx.feature, self.fuel) {
+            Ok(emitted) => emitted,
+            Err(e) => {
+                // A trap may leave the module in any state, so the next feature gets a new one.
+                *instance = None;
+                return Err(FeatureProcessorError::WasmProcessor(e).into());
+            }
+        };
+        for (port, feature) in emitted {
+            fw.send(ctx.new_with_feature_and_port(feature, port));
+        }
+        Ok(())
+    }
+
+    fn finish(
+        &self,
+        ctx: NodeContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        let mut instance = self.instance.lock();
+        let Some(instance) = instance.as_mut() else {
+            return Ok(());
+        };
+        let emitted = instance
+            .finish(self.fuel)
+            .map_err(FeatureProcessorError::WasmProcessor)?;
+        for (port, feature) in emitted {
+            fw.send(ExecutorContext::new_with_node_context_feature_and_port(
+                &ctx, feature, port,
+            ));
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "WasmProcessor"
+    }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use reearth_flow_storage::resolve::StorageResolver;
+
+    use crate::tests::utils::{create_default_execute_context, MockProcessorChannelForwarder};
+
+    use super::*;
+
+    /// Sends every feature back on the port `out`.
+    const ECHO: &str = r#"
+        (module
+          (import "reearth_flow" "emit" (func $emit (param i32 i32 i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "out")
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "process") (param $ptr i32) (param $len i32) (result i32)
+            (call $emit (i32.const 0) (i32.const 3) (local.get $ptr) (local.get $len))
+            (i32.const 0)))
+    "#;
+
+    fn build(module: &str, fuel: Option<u64>, memory_limit: Option<usize>) -> Box<dyn Processor> {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let uri = Uri::from_str("ram:///wasm/module.wat").unwrap();
+        storage_resolver
+            .resolve(&uri)
+            .unwrap()
+            .put_sync(uri.path().as_path(), Bytes::from(module.to_string()))
+            .unwrap();
+        let ctx = NodeContext {
+            storage_resolver,
+            ..Default::default()
+        };
+        let params = WasmProcessorParam {
+            module: "ram:///wasm/module.wat".to_string(),
+            fuel,
+            memory_limit,
+        };
+        WasmProcessorFactory
+            .build_processor(ctx, EventHub::new(1), "WasmProcessor".to_string(), params)
+            .unwrap()
+    }
+
+    fn process(
+        processor: &mut Box<dyn Processor>,
+        fw: &mut MockProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        processor.process(create_default_execute_context(&Feature::new()), fw)
+    }
+
+    #[test]
+    fn test_emit() {
+        let mut processor = build(ECHO, None, None);
+        let feature = Feature::new();
+        let mut fw = MockProcessorChannelForwarder::default();
+        processor
+            .process(create_default_execute_context(&feature), &mut fw)
+            .unwrap();
+        assert_eq!(fw.send_port, Port::new("out"));
+        assert_eq!(fw.send_feature.id, feature.id);
+    }
+
+    #[test]
+    fn test_fuel_limit() {
+        let module = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "process") (param i32 i32) (result i32)
+                (loop $forever (br $forever))
+                (i32.const 0)))
+        "#;
+        let mut processor = build(module, Some(10_000), None);
+        let mut fw = MockProcessorChannelForwarder::default();
+        assert!(process(&mut processor, &mut fw).is_err());
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        // Grows the memory by 1 MiB on each feature, failing when it cannot.
+        let module = r#"
+            (module
+              (memory (export "memory") 1)
+              (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "process") (param i32 i32) (result i32)
+                (if (i32.eq (memory.grow (i32.const 16)) (i32.const -1))
+                  (then (return (i32.const 1))))
+                (i32.const 0)))
+        "#;
+        let mut fw = MockProcessorChannelForwarder::default();
+        let mut processor = build(module, None, Some(4 * 1024 * 1024));
+        for _ in 0..3 {
+            process(&mut processor, &mut fw).unwrap();
+        }
+        let err = process(&mut processor, &mut fw).unwrap_err();
+        assert!(err.to_string().contains("`process` returned 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_new_instance_after_trap() {
+        // Traps on the second call, and emits each feature on the port named after the number of
+        // calls of its instance.
+        let module = r#"
+            (module
+              (import "reearth_flow" "emit" (func $emit (param i32 i32 i32 i32)))
+              (memory (export "memory") 1)
+              (global $calls (mut i32) (i32.const 0))
+              (data (i32.const 0) "123")
+              (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+              (func (export "process") (param $ptr i32) (param $len i32) (result i32)
+                (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+                (if (i32.eq (global.get $calls) (i32.const 2)) (then unreachable))
+                (call $emit
+                  (i32.sub (global.get $calls) (i32.const 1))
+                  (i32.const 1)
+                  (local.get $ptr)
+                  (local.get $len))
+                (i32.const 0)))
+        "#;
+        let mut processor = build(module, None, None);
+        let mut fw = MockProcessorChannelForwarder::default();
+        process(&mut processor, &mut fw).unwrap();
+        assert_eq!(fw.send_port, Port::new("1"));
+        assert!(process(&mut processor, &mut fw).is_err());
+        process(&mut processor, &mut fw).unwrap();
+        assert_eq!(fw.send_port, Port::new("1"));
+    }
+}
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use reearth_flow_geometry::algorithm::bool_ops::BooleanOps;
@@ -19,48 +17,26 @@ use reearth_flow_types::AttributeValue;
 use reearth_flow_types::{Feature, Geometry, GeometryValue};
 use serde::{Deserialize, Serialize};
 use serde_json::Number;
-use serde_json::Value;
 
 pub static AREA_PORT: Lazy<Port> = Lazy::new(|| Port::new("area"));
 pub static REMNANTS_PORT: Lazy<Port> = Lazy::new(|| Port::new("remnants"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "AreaOnAreaOverlayer",
+    description = "Overlays an area on another area",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [AREA_PORT, REMNANTS_PORT, REJECTED_PORT],
+)]
 pub struct AreaOnAreaOverlayerFactory;
 
-impl ProcessorFactory for AreaOnAreaOverlayerFactory {
-    fn name(&self) -> &str {
-        "AreaOnAreaOverlayer"
-    }
-
-    fn description(&self) -> &str {
-        "Overlays an area on another area"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            AREA_PORT.clone(),
-            REMNANTS_PORT.clone(),
-            REJECTED_PORT.clone(),
-        ]
-    }
-    fn build(
+impl AreaOnAreaOverlayerFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         Ok(Box::new(AreaOnAreaOverlayer))
     }
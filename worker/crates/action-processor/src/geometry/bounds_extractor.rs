@@ -7,14 +7,12 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, Feature};
 use reearth_flow_types::{CityGmlGeometry, GeometryValue};
 
 use num_traits::NumCast;
-use serde_json::Value;
-use std::collections::HashMap;
 use std::fmt::Debug;
 
 #[derive(Debug, Clone)]
@@ -48,40 +46,22 @@ impl Bounds {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "BoundsExtractor",
+    description = "Bounds Extractor",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REJECTED_PORT],
+)]
 pub struct BoundsExtractorFactory;
 
-impl ProcessorFactory for BoundsExtractorFactory {
-    fn name(&self) -> &str {
-        "BoundsExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Bounds Extractor"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl BoundsExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         let process = BoundsExtractor {};
         Ok(Box::new(process))
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use reearth_flow_geometry::algorithm::bufferable::Bufferable;
 use reearth_flow_geometry::types::geometry::Geometry2D;
 use reearth_flow_geometry::types::geometry::Geometry3D;
@@ -10,68 +8,34 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Feature, Geometry, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "Bufferer",
+    description = "Buffers a geometry",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REJECTED_PORT],
+    param = Bufferer,
+    error = GeometryProcessorError::BuffererFactory,
+)]
 pub struct BuffererFactory;
 
-impl ProcessorFactory for BuffererFactory {
-    fn name(&self) -> &str {
-        "Bufferer"
-    }
-
-    fn description(&self) -> &str {
-        "Buffers a geometry"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(Bufferer))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-    fn build(
+impl BuffererFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        bufferer: Bufferer,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let bufferer: Bufferer = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::BuffererFactory(format!(
-                    "Failed to serialize 'with' parameter: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::BuffererFactory(format!(
-                    "Failed to deserialize 'with' parameter: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::BuffererFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(bufferer))
     }
 }
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use once_cell::sync::Lazy;
 use reearth_flow_geometry::algorithm::centroid::Centroid;
 use reearth_flow_geometry::types::geometry::{Geometry2D, Geometry3D};
@@ -13,43 +11,25 @@ use reearth_flow_runtime::{
 };
 use reearth_flow_types::{Feature, Geometry, GeometryValue};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 pub static POINT_PORT: Lazy<Port> = Lazy::new(|| Port::new("point"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "CenterPointReplacer",
+    description = "Replaces the geometry of the feature with a point that is either in the center of the feature's bounding box, at the center of mass of the feature, or somewhere guaranteed to be inside the feature's area.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [POINT_PORT, REJECTED_PORT],
+)]
 pub struct CenterPointReplacerFactory;
 
-impl ProcessorFactory for CenterPointReplacerFactory {
-    fn name(&self) -> &str {
-        "CenterPointReplacer"
-    }
-
-    fn description(&self) -> &str {
-        "Replaces the geometry of the feature with a point that is either in the center of the feature's bounding box, at the center of mass of the feature, or somewhere guaranteed to be inside the feature's area."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![POINT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-    fn build(
+impl CenterPointReplacerFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         Ok(Box::new(CenterPointReplacer))
     }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::sync::Arc;
 
 use itertools::Itertools;
 use once_cell::sync::Lazy;
@@ -13,66 +13,55 @@ use reearth_flow_runtime::{
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
     node::{Port, Processor, ProcessorFactory},
+    spill::{FeatureBuffer, FeatureBufferState},
 };
 use reearth_flow_types::{Feature, Geometry, GeometryValue};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+
+use super::errors::GeometryProcessorError;
 
 pub static CLIPPER_PORT: Lazy<Port> = Lazy::new(|| Port::new("clipper"));
 pub static CANDIDATE_PORT: Lazy<Port> = Lazy::new(|| Port::new("candidate"));
 pub static INSIDE_PORT: Lazy<Port> = Lazy::new(|| Port::new("inside"));
 pub static OUTSIDE_PORT: Lazy<Port> = Lazy::new(|| Port::new("outside"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "Clipper",
+    description = "Divides Candidate features using Clipper features, so that Candidates and parts of Candidates that are inside or outside of the Clipper features are output separately",
+    categories = ["Geometry"],
+    input_ports = [CLIPPER_PORT, CANDIDATE_PORT],
+    output_ports = [INSIDE_PORT, OUTSIDE_PORT, REJECTED_PORT],
+)]
 pub struct ClipperFactory;
 
-impl ProcessorFactory for ClipperFactory {
-    fn name(&self) -> &str {
-        "Clipper"
-    }
-
-    fn description(&self) -> &str {
-        "Divides Candidate features using Clipper features, so that Candidates and parts of Candidates that are inside or outside of the Clipper features are output separately"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![CLIPPER_PORT.clone(), CANDIDATE_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            INSIDE_PORT.clone(),
-            OUTSIDE_PORT.clone(),
-            REJECTED_PORT.clone(),
-        ]
-    }
-
-    fn build(
+impl ClipperFactory {
+    fn build_processor(
         &self,
-        _ctx: NodeContext,
+        ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         Ok(Box::new(Clipper {
             clippers: Vec::new(),
-            candidates: Vec::new(),
+            candidates: FeatureBuffer::new(Arc::clone(&ctx.spill_manager)),
         }))
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Clippers are kept in memory, as every candidate is clipped by all of them. Candidates may be
+/// spilled to storage.
+#[derive(Debug, Clone)]
 pub struct Clipper {
     clippers: Vec<Feature>,
-    candidates: Vec<Feature>,
+    candidates: FeatureBuffer,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ClipperState {
+    clippers: Vec<Feature>,
+    candidates: FeatureBufferState,
 }
 
 impl Processor for Clipper {
@@ -99,7 +88,10 @@ impl Processor for Clipper {
             GeometryValue::FlowGeometry2D(_) | GeometryValue::FlowGeometry3D(_) => {
                 match &ctx.port {
                     port if port == &*CLIPPER_PORT => self.clippers.push(feature.clone()),
-                    port if port == &*CANDIDATE_PORT => self.candidates.push(feature.clone()),
+                    port if port == &*CANDIDATE_PORT => self
+                        .candidates
+                        .push(feature.clone())
+                        .map_err(|e| GeometryProcessorError::Clipper(e.to_string()))?,
                     _ => {
                         fw.send(
                             ctx.new_with_feature_and_port(feature.clone(), REJECTED_PORT.clone()),
@@ -152,10 +144,12 @@ impl Processor for Clipper {
             })
             .collect_vec();
         if clip_regions2d.is_empty() && clip_regions3d.is_empty() {
-            for candidate in &self.candidates {
+            for candidate in self.candidates.iter() {
+                let candidate =
+                    candidate.map_err(|e| GeometryProcessorError::Clipper(e.to_string()))?;
                 fw.send(ExecutorContext::new_with_node_context_feature_and_port(
                     &ctx,
-                    candidate.clone(),
+                    candidate,
                     REJECTED_PORT.clone(),
                 ));
             }
@@ -168,7 +162,9 @@ impl Processor for Clipper {
             }
             return Ok(());
         }
-        for candidate in &self.candidates {
+        for candidate in self.candidates.iter() {
+            let candidate =
+                &candidate.map_err(|e| GeometryProcessorError::Clipper(e.to_string()))?;
             let geometry = candidate.geometry.as_ref().map(|g| g.value.clone());
             match geometry {
                 Some(GeometryValue::FlowGeometry2D(geos)) => {
@@ -206,6 +202,30 @@ impl Processor for Clipper {
     fn name(&self) -> &str {
         "Clipper"
     }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = ClipperState {
+            clippers: self.clippers.clone(),
+            candidates: self.candidates.state(),
+        };
+        let state = serde_json::to_vec(&state)
+            .map_err(|e| GeometryProcessorError::Clipper(e.to_string()))?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        let state: ClipperState = serde_json::from_slice(state)
+            .map_err(|e| GeometryProcessorError::Clipper(e.to_string()))?;
+        self.clippers = state.clippers;
+        self.candidates
+            .restore(state.candidates)
+            .map_err(|e| GeometryProcessorError::Clipper(e.to_string()))?;
+        Ok(())
+    }
 }
 
 fn handle_2d_geometry(
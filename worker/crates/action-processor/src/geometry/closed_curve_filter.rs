@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use once_cell::sync::Lazy;
 use reearth_flow_geometry::types::geometry::Geometry2D;
 use reearth_flow_geometry::types::geometry::Geometry3D;
@@ -13,49 +11,26 @@ use reearth_flow_runtime::{
     node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Feature, GeometryValue};
-use serde_json::Value;
 
 pub static CLOSED_PORT: Lazy<Port> = Lazy::new(|| Port::new("closed"));
 pub static OPEN_PORT: Lazy<Port> = Lazy::new(|| Port::new("line"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "ClosedCurveFilter",
+    description = "Checks if curves form closed loops",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [CLOSED_PORT, OPEN_PORT, REJECTED_PORT],
+)]
 pub struct ClosedCurveFilterFactory;
 
-impl ProcessorFactory for ClosedCurveFilterFactory {
-    fn name(&self) -> &str {
-        "ClosedCurveFilter"
-    }
-
-    fn description(&self) -> &str {
-        "Checks if curves form closed loops"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            CLOSED_PORT.clone(),
-            OPEN_PORT.clone(),
-            REJECTED_PORT.clone(),
-        ]
-    }
-
-    fn build(
+impl ClosedCurveFilterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         Ok(Box::new(ClosedCurveFilter))
     }
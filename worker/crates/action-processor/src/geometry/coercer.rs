@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use reearth_flow_geometry::types::geometry::Geometry2D;
 use reearth_flow_geometry::types::geometry::Geometry3D;
 use reearth_flow_geometry::types::multi_line_string::{MultiLineString2D, MultiLineString3D};
@@ -8,68 +6,34 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{CityGmlGeometry, Feature, Geometry, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "GeometryCoercer",
+    description = "Coerces the geometry of a feature to a specific geometry",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = GeometryCoercer,
+    error = GeometryProcessorError::GeometryCoercerFactory,
+)]
 pub struct GeometryCoercerFactory;
 
-impl ProcessorFactory for GeometryCoercerFactory {
-    fn name(&self) -> &str {
-        "GeometryCoercer"
-    }
-
-    fn description(&self) -> &str {
-        "Coerces the geometry of a feature to a specific geometry"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(GeometryCoercer))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-    fn build(
+impl GeometryCoercerFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        coercer: GeometryCoercer,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let coercer: GeometryCoercer = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::GeometryCoercerFactory(format!(
-                    "Failed to serialize 'with' parameter: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::GeometryCoercerFactory(format!(
-                    "Failed to deserialize 'with' parameter: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::GeometryCoercerFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(coercer))
     }
 }
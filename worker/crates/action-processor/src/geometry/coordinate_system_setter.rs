@@ -1,73 +1,37 @@
-use std::collections::HashMap;
-
 use nusamai_projection::crs::*;
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::Geometry;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::{errors::GeometryProcessorError, types::SUPPORT_EPSG_CODE};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "CoordinateSystemSetter",
+    description = "Sets the coordinate system of a feature",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = CoordinateSystemSetter,
+    error = GeometryProcessorError::CoordinateSystemSetterFactory,
+)]
 pub struct CoordinateSystemSetterFactory;
 
-impl ProcessorFactory for CoordinateSystemSetterFactory {
-    fn name(&self) -> &str {
-        "CoordinateSystemSetter"
-    }
-
-    fn description(&self) -> &str {
-        "Sets the coordinate system of a feature"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(CoordinateSystemSetter))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-    fn build(
+impl CoordinateSystemSetterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        processor: CoordinateSystemSetter,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let processor: CoordinateSystemSetter = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::CoordinateSystemSetterFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::CoordinateSystemSetterFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::CoordinateSystemSetterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         if !SUPPORT_EPSG_CODE.contains(&processor.epsg_code) {
             return Err(GeometryProcessorError::CoordinateSystemSetterFactory(
                 "Unsupported EPSG code".to_string(),
@@ -1,74 +1,37 @@
-use std::collections::HashMap;
-
 use reearth_flow_common::str::base64_encode;
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "GeometryExtractor",
+    description = "Extracts geometry from a feature and adds it as an attribute.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = GeometryExtractor,
+    error = GeometryProcessorError::GeometryExtractorFactory,
+)]
 pub struct GeometryExtractorFactory;
 
-impl ProcessorFactory for GeometryExtractorFactory {
-    fn name(&self) -> &str {
-        "GeometryExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts geometry from a feature and adds it as an attribute."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(GeometryExtractor))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl GeometryExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        processor: GeometryExtractor,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let processor: GeometryExtractor = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::GeometryExtractorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::GeometryExtractorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::GeometryExtractorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(processor))
     }
 }
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use inflector::cases::camelcase::to_camel_case;
 use once_cell::sync::Lazy;
 use reearth_flow_geometry::types::geometry::{Geometry2D, Geometry3D};
@@ -13,69 +11,31 @@ use reearth_flow_runtime::{
 use reearth_flow_types::{Feature, Geometry, GeometryFeatureType, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
 pub static UNFILTERED_PORT: Lazy<Port> = Lazy::new(|| Port::new("unfiltered"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "GeometryFilter",
+    description = "Filter geometry by type",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = GeometryFilterParam::all_ports(),
+    param = GeometryFilterParam,
+    error = GeometryProcessorError::GeometryFilterFactory,
+)]
 pub struct GeometryFilterFactory;
 
-impl ProcessorFactory for GeometryFilterFactory {
-    fn name(&self) -> &str {
-        "GeometryFilter"
-    }
-
-    fn description(&self) -> &str {
-        "Filter geometry by type"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(GeometryFilterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        let mut result = vec![UNFILTERED_PORT.clone()];
-        result.extend(GeometryFilterParam::all_ports());
-        result
-    }
-
-    fn build(
+impl GeometryFilterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: GeometryFilterParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: GeometryFilterParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::GeometryFilterFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::GeometryFilterFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::GeometryFilterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let process = GeometryFilter { params };
         Ok(Box::new(process))
     }
@@ -122,6 +82,7 @@ impl GeometryFilterParam {
 
     fn all_ports() -> Vec<Port> {
         let mut result = vec![
+            UNFILTERED_PORT.clone(),
             GeometryFilterParam::None.output_port(),
             GeometryFilterParam::Multiple.output_port(),
         ];
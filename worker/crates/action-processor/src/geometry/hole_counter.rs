@@ -1,73 +1,37 @@
-use std::collections::HashMap;
-
 use reearth_flow_geometry::algorithm::hole::HoleCounter as HoleCounterAlgorithm;
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "HoleCounter",
+    description = "Counts the number of holes in a geometry and adds it as an attribute.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = HoleCounterParam,
+    error = GeometryProcessorError::HoleCounterFactory,
+)]
 pub struct HoleCounterFactory;
 
-impl ProcessorFactory for HoleCounterFactory {
-    fn name(&self) -> &str {
-        "HoleCounter"
-    }
-
-    fn description(&self) -> &str {
-        "Counts the number of holes in a geometry and adds it as an attribute."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(HoleCounterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-    fn build(
+impl HoleCounterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: HoleCounterParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: HoleCounterParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::HoleCounterFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::HoleCounterFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::HoleCounterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(HoleCounter {
             output_attribute: params.output_attribute,
         }))
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, vec};
+use std::vec;
 
 use once_cell::sync::Lazy;
 use reearth_flow_geometry::types::{
@@ -13,48 +13,26 @@ use reearth_flow_runtime::{
     node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REJECTED_PORT},
 };
 use reearth_flow_types::{Feature, GeometryValue};
-use serde_json::Value;
 
 pub static OUTERSHELL_PORT: Lazy<Port> = Lazy::new(|| Port::new("outershell"));
 pub static HOLE_PORT: Lazy<Port> = Lazy::new(|| Port::new("hole"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "HoleExtractor",
+    description = "Extracts holes in a geometry and adds it as an attribute.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [OUTERSHELL_PORT, HOLE_PORT, REJECTED_PORT],
+)]
 pub struct HoleExtractorFactory;
 
-impl ProcessorFactory for HoleExtractorFactory {
-    fn name(&self) -> &str {
-        "HoleExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts holes in a geometry and adds it as an attribute."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            OUTERSHELL_PORT.clone(),
-            HOLE_PORT.clone(),
-            REJECTED_PORT.clone(),
-        ]
-    }
-    fn build(
+impl HoleExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         Ok(Box::new(HoleExtractor))
     }
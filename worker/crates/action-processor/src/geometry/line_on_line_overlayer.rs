@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use once_cell::sync::Lazy;
 use reearth_flow_geometry::algorithm::line_intersection::{self, line_intersection};
 use reearth_flow_geometry::types::geometry::Geometry2D;
@@ -18,7 +16,7 @@ use reearth_flow_runtime::{
 use reearth_flow_types::{Attribute, AttributeValue, Feature, Geometry, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::{Number, Value};
+use serde_json::Number;
 
 use super::errors::GeometryProcessorError;
 
@@ -26,60 +24,26 @@ pub static POINT_PORT: Lazy<Port> = Lazy::new(|| Port::new("point"));
 pub static LINE_PORT: Lazy<Port> = Lazy::new(|| Port::new("line"));
 pub static COLLINEAR_PORT: Lazy<Port> = Lazy::new(|| Port::new("collinear"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "LineOnLineOverlayer",
+    description = "Intersection points are turned into point features that can contain the merged list of attributes of the original intersected lines.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [POINT_PORT, LINE_PORT, REJECTED_PORT],
+    param = LineOnLineOverlayerParam,
+    error = GeometryProcessorError::LineOnLineOverlayerFactory,
+)]
 pub struct LineOnLineOverlayerFactory;
 
-impl ProcessorFactory for LineOnLineOverlayerFactory {
-    fn name(&self) -> &str {
-        "LineOnLineOverlayer"
-    }
-
-    fn description(&self) -> &str {
-        "Intersection points are turned into point features that can contain the merged list of attributes of the original intersected lines."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(LineOnLineOverlayerParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![POINT_PORT.clone(), LINE_PORT.clone(), REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl LineOnLineOverlayerFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: LineOnLineOverlayerParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: LineOnLineOverlayerParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::LineOnLineOverlayerFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::LineOnLineOverlayerFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::LineOnLineOverlayerFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(LineOnLineOverlayer {
             output_attribute: params.output_attribute,
         }))
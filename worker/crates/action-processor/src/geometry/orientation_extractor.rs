@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use reearth_flow_geometry::algorithm::winding_order::Winding;
 use reearth_flow_geometry::types::geometry::Geometry3D;
@@ -8,12 +8,11 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
@@ -24,59 +23,26 @@ const INVALID_ORIENTATION: WindingOrderResult = "invalid_orientation";
 const CLOCKWISE_ORIENTATION: WindingOrderResult = "clockwise";
 const COUNTER_CLOCKWISE_ORIENTATION: WindingOrderResult = "counter_clockwise";
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "OrientationExtractor",
+    description = "Extracts the orientation of a geometry from a feature and adds it as an attribute.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = OrientationExtractorParam,
+    error = GeometryProcessorError::OrientationExtractorFactory,
+)]
 pub struct OrientationExtractorFactory;
 
-impl ProcessorFactory for OrientationExtractorFactory {
-    fn name(&self) -> &str {
-        "OrientationExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts the orientation of a geometry from a feature and adds it as an attribute."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(OrientationExtractorParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-    fn build(
+impl OrientationExtractorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: OrientationExtractorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: OrientationExtractorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::OrientationExtractorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::OrientationExtractorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::OrientationExtractorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(OrientationExtractor {
             output_attribute: params.output_attribute,
         }))
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use once_cell::sync::Lazy;
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
@@ -9,45 +7,26 @@ use reearth_flow_runtime::{
     node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{AttributeValue, GeometryValue};
-use serde_json::Value;
 
 pub static PLANARITY_PORT: Lazy<Port> = Lazy::new(|| Port::new("planarity"));
 pub static NOT_PLANARITY_PORT: Lazy<Port> = Lazy::new(|| Port::new("notplanarity"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "PlanarityFilter",
+    description = "Filter geometry by type",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [PLANARITY_PORT, NOT_PLANARITY_PORT],
+)]
 pub struct PlanarityFilterFactory;
 
-impl ProcessorFactory for PlanarityFilterFactory {
-    fn name(&self) -> &str {
-        "PlanarityFilter"
-    }
-
-    fn description(&self) -> &str {
-        "Filter geometry by type"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![PLANARITY_PORT.clone(), NOT_PLANARITY_PORT.clone()]
-    }
-
-    fn build(
+impl PlanarityFilterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         let process = PlanarityFilter {};
         Ok(Box::new(process))
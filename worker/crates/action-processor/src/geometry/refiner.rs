@@ -9,52 +9,32 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT, REMAIN_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT, REMAIN_PORT},
 };
 use reearth_flow_types::geometry::Geometry as TypeGeometry;
 use reearth_flow_types::Feature;
 use reearth_flow_types::{CityGmlGeometry, GeometryValue};
 
-use serde_json::Value;
-use std::collections::HashMap;
 use std::fmt::Debug;
 use std::vec;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "Refiner",
+    description = "Geometry Refiner",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REMAIN_PORT],
+)]
 pub struct RefinerFactory;
 
-impl ProcessorFactory for RefinerFactory {
-    fn name(&self) -> &str {
-        "Refiner"
-    }
-
-    fn description(&self) -> &str {
-        "Geometry Refiner"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REMAIN_PORT.clone()]
-    }
-
-    fn build(
+impl RefinerFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         let process = Refiner {};
         Ok(Box::new(process))
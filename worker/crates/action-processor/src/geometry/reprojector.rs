@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use nusamai_projection::{
     crs::*, ellipsoid::wgs84, etmerc::ExtendedTransverseMercatorProjection, jprect::JPRZone,
 };
@@ -9,70 +7,36 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::GeometryValue;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::{errors::GeometryProcessorError, types::SUPPORT_EPSG_CODE};
 
 const K: f64 = 0.9999;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "Reprojector",
+    description = "Reprojects the geometry of a feature to a specified coordinate system",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = ReprojectorParam,
+    error = GeometryProcessorError::ReprojectorFactory,
+)]
 pub struct ReprojectorFactory;
 
-impl ProcessorFactory for ReprojectorFactory {
-    fn name(&self) -> &str {
-        "Reprojector"
-    }
-
-    fn description(&self) -> &str {
-        "Reprojects the geometry of a feature to a specified coordinate system"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(ReprojectorParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-    fn build(
+impl ReprojectorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: ReprojectorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: ReprojectorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::ReprojectorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::ReprojectorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::ReprojectorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         let projection = if let Some(epsg_code) = params.epsg_code {
             if !SUPPORT_EPSG_CODE.contains(&epsg_code) {
                 return Err(GeometryProcessorError::ReprojectorFactory(
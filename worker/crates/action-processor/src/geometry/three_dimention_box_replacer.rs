@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use reearth_flow_geometry::types::{
     coordinate::Coordinate, geometry::Geometry3D as FlowGeometry3D, rect::Rect,
 };
@@ -8,69 +6,34 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, Geometry, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "ThreeDimentionBoxReplacer",
+    description = "Replaces a three dimention box with a polygon.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = ThreeDimentionBoxReplacer,
+    error = GeometryProcessorError::ThreeDimentionBoxReplacerFactory,
+)]
 pub struct ThreeDimentionBoxReplacerFactory;
 
-impl ProcessorFactory for ThreeDimentionBoxReplacerFactory {
-    fn name(&self) -> &str {
-        "ThreeDimentionBoxReplacer"
-    }
-
-    fn description(&self) -> &str {
-        "Replaces a three dimention box with a polygon."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(ThreeDimentionBoxReplacer))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl ThreeDimentionBoxReplacerFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        processor: ThreeDimentionBoxReplacer,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let processor: ThreeDimentionBoxReplacer = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::ThreeDimentionBoxReplacerFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::ThreeDimentionBoxReplacerFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::ThreeDimentionBoxReplacerFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(processor))
     }
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use reearth_flow_geometry::{algorithm::rotate_3d::Rotate3D, types::point::Point3D};
 use reearth_flow_runtime::{
@@ -6,69 +6,34 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Expr, GeometryValue};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::GeometryProcessorError;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "ThreeDimentionRotator",
+    description = "Replaces a three dimention box with a polygon.",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = ThreeDimentionRotatorParam,
+    error = GeometryProcessorError::ThreeDimentionRotatorFactory,
+)]
 pub struct ThreeDimentionRotatorFactory;
 
-impl ProcessorFactory for ThreeDimentionRotatorFactory {
-    fn name(&self) -> &str {
-        "ThreeDimentionRotator"
-    }
-
-    fn description(&self) -> &str {
-        "Replaces a three dimention box with a polygon."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(ThreeDimentionRotatorParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl ThreeDimentionRotatorFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: ThreeDimentionRotatorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: ThreeDimentionRotatorParam = if let Some(with) = with {
-            let value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::ThreeDimentionRotatorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::LineOnLineOverlayerFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::ThreeDimentionRotatorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let angle_degree = expr_engine
             .compile(params.angle_degree.as_ref())
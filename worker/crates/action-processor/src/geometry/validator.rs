@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use num_traits::FromPrimitive;
 use once_cell::sync::Lazy;
 use reearth_flow_geometry::{
@@ -25,63 +23,26 @@ static SUCCESS_PORT: Lazy<Port> = Lazy::new(|| Port::new("success"));
 static FAILED_PORT: Lazy<Port> = Lazy::new(|| Port::new("failed"));
 static REJECTED_PORT: Lazy<Port> = Lazy::new(|| Port::new("rejected"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "GeometryValidator",
+    description = "Validates the geometry of a feature",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [SUCCESS_PORT, FAILED_PORT, REJECTED_PORT],
+    param = GeometryValidator,
+    error = GeometryProcessorError::GeometryValidatorFactory,
+)]
 pub struct GeometryValidatorFactory;
 
-impl ProcessorFactory for GeometryValidatorFactory {
-    fn name(&self) -> &str {
-        "GeometryValidator"
-    }
-
-    fn description(&self) -> &str {
-        "Validates the geometry of a feature"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(GeometryValidator))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![
-            SUCCESS_PORT.clone(),
-            FAILED_PORT.clone(),
-            REJECTED_PORT.clone(),
-        ]
-    }
-    fn build(
+impl GeometryValidatorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        processor: GeometryValidator,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let processor: GeometryValidator = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                GeometryProcessorError::GeometryValidatorFactory(format!(
-                    "Failed to serialize with: {}",
-                    e
-                ))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                GeometryProcessorError::GeometryValidatorFactory(format!(
-                    "Failed to deserialize with: {}",
-                    e
-                ))
-            })?
-        } else {
-            return Err(GeometryProcessorError::GeometryValidatorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         Ok(Box::new(processor))
     }
 }
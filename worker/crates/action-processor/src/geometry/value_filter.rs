@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use once_cell::sync::Lazy;
 use reearth_flow_runtime::{
     channels::ProcessorChannelForwarder,
@@ -9,47 +7,28 @@ use reearth_flow_runtime::{
     node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::GeometryValue;
-use serde_json::Value;
 
 pub static NONE_PORT: Lazy<Port> = Lazy::new(|| Port::new("none"));
 pub static GEOMETRY_2D_PORT: Lazy<Port> = Lazy::new(|| Port::new("geometry2d"));
 pub static GEOMETRY_3D_PORT: Lazy<Port> = Lazy::new(|| Port::new("geometry3d"));
 pub static CITY_GML_PORT: Lazy<Port> = Lazy::new(|| Port::new("cityGml"));
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "GeometryValueFilter",
+    description = "Filter geometry by value",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = GeometryValueFilterType::all_ports(),
+)]
 pub struct GeometryValueFilterFactory;
 
-impl ProcessorFactory for GeometryValueFilterFactory {
-    fn name(&self) -> &str {
-        "GeometryValueFilter"
-    }
-
-    fn description(&self) -> &str {
-        "Filter geometry by value"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        GeometryValueFilterType::all_ports()
-    }
-
-    fn build(
+impl GeometryValueFilterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         Ok(Box::new(GeometryValueFilter {}))
     }
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use reearth_flow_geometry::algorithm::simplify::Simplify;
 use reearth_flow_geometry::types::geometry::Geometry2D;
 use reearth_flow_geometry::types::geometry::Geometry3D;
@@ -10,48 +8,29 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::Geometry;
 use reearth_flow_types::{Feature, GeometryValue};
-use serde_json::Value;
 
 const EPSILON: f64 = 0.0001;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "VertexRemover",
+    description = "Removes specific vertices from a feature’s geometry",
+    categories = ["Geometry"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT, REJECTED_PORT],
+)]
 pub struct VertexRemoverFactory;
 
-impl ProcessorFactory for VertexRemoverFactory {
-    fn name(&self) -> &str {
-        "VertexRemover"
-    }
-
-    fn description(&self) -> &str {
-        "Removes specific vertices from a feature’s geometry"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Geometry"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
-    }
-
-    fn build(
+impl VertexRemoverFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Processor>, BoxedError> {
         Ok(Box::new(VertexRemover))
     }
@@ -34,6 +34,18 @@ impl ProcessorChannelForwarder for MockProcessorChannelForwarder {
     }
 }
 
+/// Keeps every feature sent, e.g. by a processor that sends what it buffered when it finishes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CollectingProcessorChannelForwarder {
+    pub(crate) send_features: Vec<Feature>,
+}
+
+impl ProcessorChannelForwarder for CollectingProcessorChannelForwarder {
+    fn send(&mut self, ctx: ExecutorContext) {
+        self.send_features.push(ctx.feature);
+    }
+}
+
 pub(crate) fn create_default_execute_context(feature: &Feature) -> ExecutorContext {
     ExecutorContext::new(
         feature.clone(),
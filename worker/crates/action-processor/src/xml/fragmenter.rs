@@ -9,64 +9,35 @@ use reearth_flow_runtime::{
     errors::BoxedError,
     event::EventHub,
     executor_operation::{ExecutorContext, NodeContext},
-    node::{Port, Processor, ProcessorFactory, DEFAULT_PORT},
+    node::{Processor, ProcessorFactory, DEFAULT_PORT},
 };
 use reearth_flow_types::{Attribute, AttributeValue, Expr, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use uuid::Uuid;
 
 use super::errors::{Result, XmlProcessorError};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "XMLFragmenter",
+    description = "Fragment XML",
+    categories = ["XML"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [DEFAULT_PORT],
+    param = XmlFragmenterParam,
+    error = XmlProcessorError::FragmenterFactory,
+)]
 pub struct XmlFragmenterFactory;
 
-impl ProcessorFactory for XmlFragmenterFactory {
-    fn name(&self) -> &str {
-        "XMLFragmenter"
-    }
-
-    fn description(&self) -> &str {
-        "Fragment XML"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(XmlFragmenterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["XML"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn build(
+impl XmlFragmenterFactory {
+    fn build_processor(
         &self,
         ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: XmlFragmenterParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: XmlFragmenterParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                XmlProcessorError::FragmenterFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                XmlProcessorError::FragmenterFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(XmlProcessorError::FragmenterFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
         let XmlFragmenterParam::Url { property } = &params;
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let elements_to_match_ast = expr_engine
@@ -22,7 +22,6 @@ use reearth_flow_runtime::{
 use reearth_flow_types::{Attribute, AttributeValue, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 
 use super::errors::{Result, XmlProcessorError};
 
@@ -86,55 +85,26 @@ impl From<ValidationResult> for HashMap<String, AttributeValue> {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "XMLValidator",
+    description = "Validates XML content",
+    categories = ["PLATEAU"],
+    input_ports = [DEFAULT_PORT],
+    output_ports = [SUCCESS_PORT, FAILED_PORT],
+    param = XmlValidatorParam,
+    error = XmlProcessorError::ValidatorFactory,
+)]
 pub struct XmlValidatorFactory;
 
-impl ProcessorFactory for XmlValidatorFactory {
-    fn name(&self) -> &str {
-        "XMLValidator"
-    }
-
-    fn description(&self) -> &str {
-        "Validates XML content"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(XmlValidatorParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["PLATEAU"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![SUCCESS_PORT.clone(), FAILED_PORT.clone()]
-    }
-
-    fn build(
+impl XmlValidatorFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: XmlValidatorParam,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let params: XmlValidatorParam = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                XmlProcessorError::ValidatorFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                XmlProcessorError::ValidatorFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(XmlProcessorError::ValidatorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
-
         let process = XmlValidator {
             params,
             schema_store: Arc::new(parking_lot::RwLock::new(HashMap::new())),
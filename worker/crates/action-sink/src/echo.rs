@@ -1,46 +1,26 @@
 use reearth_flow_action_log::action_log;
 use serde_json::Value;
-use std::collections::HashMap;
 
 use reearth_flow_runtime::errors::BoxedError;
 use reearth_flow_runtime::event::EventHub;
 use reearth_flow_runtime::executor_operation::{ExecutorContext, NodeContext};
-use reearth_flow_runtime::node::{Port, Sink, SinkFactory, DEFAULT_PORT};
-
-#[derive(Debug, Clone, Default)]
+use reearth_flow_runtime::node::{Sink, SinkFactory, DEFAULT_PORT};
+
+#[derive(Debug, Clone, Default, SinkFactory)]
+#[factory(
+    name = "Echo",
+    description = "Echo features",
+    categories = ["Debug"],
+    input_ports = [DEFAULT_PORT],
+)]
 pub struct EchoSinkFactory;
 
-impl SinkFactory for EchoSinkFactory {
-    fn name(&self) -> &str {
-        "Echo"
-    }
-
-    fn description(&self) -> &str {
-        "Echo features"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        None
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["Debug"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn prepare(&self) -> Result<(), BoxedError> {
-        Ok(())
-    }
-
-    fn build(
+impl EchoSinkFactory {
+    fn build_sink(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        _with: Option<HashMap<String, Value>>,
     ) -> Result<Box<dyn Sink>, BoxedError> {
         Ok(Box::new(Echo))
     }
@@ -1,2 +1,8 @@
+mod crs;
 mod excel;
+mod flatgeobuf;
+mod geojson;
+mod gpkg;
+mod parquet;
+mod shapefile;
 pub mod writer;
@@ -0,0 +1,228 @@
+use nusamai_projection::crs::{
+    EpsgCode, EPSG_JGD2000_JPRECT_I, EPSG_JGD2011_GEOGRAPHIC_2D, EPSG_JGD2011_GEOGRAPHIC_3D,
+    EPSG_JGD2011_JPRECT_I, EPSG_WGS84_GEOGRAPHIC_2D,
+};
+use serde_json::{json, Value};
+
+/// JGD2000 (EPSG:4612).
+const EPSG_JGD2000_GEOGRAPHIC_2D: EpsgCode = 4612;
+/// Latitude and longitude of the origins of the zones of the Japan Plane Rectangular CS.
+const JPRECT_ORIGINS: [(f64, f64); 19] = [
+    (33.0, 129.5),
+    (33.0, 131.0),
+    (36.0, 132.166666666666667),
+    (33.0, 133.5),
+    (36.0, 134.333333333333333),
+    (36.0, 136.0),
+    (36.0, 137.166666666666667),
+    (36.0, 138.5),
+    (36.0, 139.833333333333333),
+    (40.0, 140.833333333333333),
+    (44.0, 140.25),
+    (44.0, 142.25),
+    (44.0, 144.25),
+    (26.0, 142.0),
+    (26.0, 127.5),
+    (26.0, 124.0),
+    (26.0, 131.0),
+    (20.0, 136.0),
+    (26.0, 154.0),
+];
+const ROMAN_NUMERALS: [&str; 19] = [
+    "I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX", "X", "XI", "XII", "XIII", "XIV", "XV",
+    "XVI", "XVII", "XVIII", "XIX",
+];
+const PROJJSON_SCHEMA: &str = "https://proj.org/schemas/v0.7/projjson.schema.json";
+
+/// Writes the ESRI WKT of the coordinate systems of WGS 84, JGD2000 and JGD2011, including the
+/// zones of the Japan Plane Rectangular CS.
+pub(super) fn prj_from_epsg(epsg: EpsgCode) -> Option<String> {
+    let geographic = |name: &str, datum: &str| {
+        let spheroid = if datum == "D_WGS_1984" {
+            r#"SPHEROID["WGS_1984",6378137.0,298.257223563]"#
+        } else {
+            r#"SPHEROID["GRS_1980",6378137.0,298.257222101]"#
+        };
+        format!(
+            r#"GEOGCS["{}",DATUM["{}",{}],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#,
+            name, datum, spheroid
+        )
+    };
+    let projected = |name: String, geographic: String, (latitude, longitude): (f64, f64)| {
+        format!(
+            concat!(
+                r#"PROJCS["{}",{},PROJECTION["Transverse_Mercator"],"#,
+                r#"PARAMETER["False_Easting",0.0],PARAMETER["False_Northing",0.0],"#,
+                r#"PARAMETER["Central_Meridian",{:?}],PARAMETER["Scale_Factor",0.9999],"#,
+                r#"PARAMETER["Latitude_Of_Origin",{:?}],UNIT["Meter",1.0]]"#
+            ),
+            name, geographic, longitude, latitude
+        )
+    };
+    let jgd2011 = geographic("GCS_JGD_2011", "D_JGD_2011");
+    let jgd2000 = geographic("GCS_JGD_2000", "D_JGD_2000");
+    let prj = match epsg {
+        EPSG_WGS84_GEOGRAPHIC_2D => geographic("GCS_WGS_1984", "D_WGS_1984"),
+        EPSG_JGD2011_GEOGRAPHIC_2D | EPSG_JGD2011_GEOGRAPHIC_3D => jgd2011,
+        EPSG_JGD2000_GEOGRAPHIC_2D => jgd2000,
+        _ => {
+            let (prefix, geographic, zone) = if epsg >= EPSG_JGD2011_JPRECT_I {
+                (
+                    "JGD_2011",
+                    jgd2011,
+                    epsg.checked_sub(EPSG_JGD2011_JPRECT_I)?,
+                )
+            } else {
+                (
+                    "JGD_2000",
+                    jgd2000,
+                    epsg.checked_sub(EPSG_JGD2000_JPRECT_I)?,
+                )
+            };
+            let origin = *JPRECT_ORIGINS.get(zone as usize)?;
+            let name = format!("{}_Japan_Zone_{}", prefix, zone + 1);
+            projected(name, geographic, origin)
+        }
+    };
+    Some(prj)
+}
+
+/// Writes the PROJJSON of the same coordinate systems as `prj_from_epsg`, with their EPSG
+/// codes.
+pub(super) fn projjson_from_epsg(epsg: EpsgCode) -> Option<Value> {
+    let id = |code: EpsgCode| json!({ "authority": "EPSG", "code": code });
+    let geographic = |code: EpsgCode, height: bool| {
+        let (name, datum, inverse_flattening) = match code {
+            EPSG_WGS84_GEOGRAPHIC_2D => ("WGS 84", "World Geodetic System 1984", 298.257223563),
+            EPSG_JGD2000_GEOGRAPHIC_2D => {
+                ("JGD2000", "Japanese Geodetic Datum 2000", 298.257222101)
+            }
+            _ => ("JGD2011", "Japanese Geodetic Datum 2011", 298.257222101),
+        };
+        let ellipsoid = if code == EPSG_WGS84_GEOGRAPHIC_2D {
+            "WGS 84"
+        } else {
+            "GRS 1980"
+        };
+        let mut axis = vec![
+            json!({ "name": "Geodetic latitude", "abbreviation": "Lat", "direction": "north", "unit": "degree" }),
+            json!({ "name": "Geodetic longitude", "abbreviation": "Lon", "direction": "east", "unit": "degree" }),
+        ];
+        if height {
+            axis.push(json!({ "name": "Ellipsoidal height", "abbreviation": "h", "direction": "up", "unit": "metre" }));
+        }
+        json!({
+            "type": "GeographicCRS",
+            "name": name,
+            "datum": {
+                "type": "GeodeticReferenceFrame",
+                "name": datum,
+                "ellipsoid": {
+                    "name": ellipsoid,
+                    "semi_major_axis": 6378137.0,
+                    "inverse_flattening": inverse_flattening,
+                },
+            },
+            "coordinate_system": { "subtype": "ellipsoidal", "axis": axis },
+        })
+    };
+    let with_id = |mut crs: Value, code: EpsgCode| {
+        crs["$schema"] = json!(PROJJSON_SCHEMA);
+        crs["id"] = id(code);
+        crs
+    };
+    let crs = match epsg {
+        EPSG_WGS84_GEOGRAPHIC_2D | EPSG_JGD2011_GEOGRAPHIC_2D | EPSG_JGD2000_GEOGRAPHIC_2D => {
+            geographic(epsg, false)
+        }
+        EPSG_JGD2011_GEOGRAPHIC_3D => geographic(EPSG_JGD2011_GEOGRAPHIC_2D, true),
+        _ => {
+            let (base, zone) = if epsg >= EPSG_JGD2011_JPRECT_I {
+                (
+                    EPSG_JGD2011_GEOGRAPHIC_2D,
+                    epsg.checked_sub(EPSG_JGD2011_JPRECT_I)?,
+                )
+            } else {
+                (
+                    EPSG_JGD2000_GEOGRAPHIC_2D,
+                    epsg.checked_sub(EPSG_JGD2000_JPRECT_I)?,
+                )
+            };
+            let (latitude, longitude) = *JPRECT_ORIGINS.get(zone as usize)?;
+            let base_crs = with_id(geographic(base, false), base);
+            let parameter = |name: &str, value: f64, unit: &str, code: EpsgCode| json!({ "name": name, "value": value, "unit": unit, "id": id(code) });
+            let numeral = ROMAN_NUMERALS[zone as usize];
+            json!({
+                "type": "ProjectedCRS",
+                "name": format!(
+                    "{} / Japan Plane Rectangular CS {}",
+                    base_crs["name"].as_str().unwrap_or_default(),
+                    numeral
+                ),
+                "base_crs": base_crs,
+                "conversion": {
+                    "name": format!("Japan Plane Rectangular CS zone {}", numeral),
+                    "method": { "name": "Transverse Mercator", "id": id(9807) },
+                    "parameters": [
+                        parameter("Latitude of natural origin", latitude, "degree", 8801),
+                        parameter("Longitude of natural origin", longitude, "degree", 8802),
+                        parameter("Scale factor at natural origin", 0.9999, "unity", 8805),
+                        parameter("False easting", 0.0, "metre", 8806),
+                        parameter("False northing", 0.0, "metre", 8807),
+                    ],
+                },
+                "coordinate_system": {
+                    "subtype": "Cartesian",
+                    "axis": [
+                        { "name": "Northing", "abbreviation": "X", "direction": "north", "unit": "metre" },
+                        { "name": "Easting", "abbreviation": "Y", "direction": "east", "unit": "metre" },
+                    ],
+                },
+            })
+        }
+    };
+    Some(with_id(crs, epsg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prj_from_epsg() {
+        assert!(prj_from_epsg(6677)
+            .unwrap()
+            .starts_with(r#"PROJCS["JGD_2011_Japan_Zone_9",GEOGCS["GCS_JGD_2011""#));
+        assert!(prj_from_epsg(2451)
+            .unwrap()
+            .starts_with(r#"PROJCS["JGD_2000_Japan_Zone_9",GEOGCS["GCS_JGD_2000""#));
+        assert!(prj_from_epsg(6697)
+            .unwrap()
+            .starts_with(r#"GEOGCS["GCS_JGD_2011""#));
+        assert_eq!(prj_from_epsg(3857), None);
+    }
+
+    #[test]
+    fn test_projjson_from_epsg() {
+        let crs = projjson_from_epsg(6677).unwrap();
+        assert_eq!(crs["type"], "ProjectedCRS");
+        assert_eq!(crs["name"], "JGD2011 / Japan Plane Rectangular CS IX");
+        assert_eq!(crs["id"], json!({ "authority": "EPSG", "code": 6677 }));
+        assert_eq!(crs["base_crs"]["id"]["code"], 6668);
+        assert_eq!(
+            crs["conversion"]["parameters"][1]["value"],
+            139.833333333333333
+        );
+        let crs = projjson_from_epsg(6697).unwrap();
+        assert_eq!(crs["type"], "GeographicCRS");
+        assert_eq!(
+            crs["coordinate_system"]["axis"].as_array().unwrap().len(),
+            3
+        );
+        assert_eq!(
+            projjson_from_epsg(4326).unwrap()["datum"]["ellipsoid"]["name"],
+            "WGS 84"
+        );
+        assert_eq!(projjson_from_epsg(3857), None);
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use flatgeobuf::{ColumnType, FgbCrs, FgbWriter, FgbWriterOptions, GeometryType};
+use geozero::{wkb::Wkb, ColumnValue, FeatureProcessor, GeozeroGeometry, PropertyProcessor};
+use nusamai_projection::crs::EpsgCode;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::spill::FeatureBuffer;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::wkb::{geometry_value_to_wkb, WkbGeometryType};
+use reearth_flow_types::{Attribute, AttributeValue, Feature};
+
+use crate::errors::SinkError;
+
+use super::writer::{close_output, create_output};
+
+/// Writes the features as a FlatGeobuf file with a spatial index, named after the output file.
+///
+/// The buffer is read twice: once to infer the columns, typed by the values of the attributes,
+/// and the coordinate system, which must be the same for all the features, then to write the
+/// features. Attributes with values of several types become strings, and arrays and maps JSON.
+pub(super) fn write_flatgeobuf(
+    output: &Uri,
+    features: &FeatureBuffer,
+    storage_resolver: Arc<StorageResolver>,
+) -> Result<(), SinkError> {
+    let mut epsg = None::<EpsgCode>;
+    let mut geometry_types = HashSet::new();
+    let mut has_z = false;
+    let mut types = BTreeMap::<Attribute, Option<FieldType>>::new();
+    for feature in features.iter() {
+        let feature = feature.map_err(SinkError::file_writer)?;
+        for (name, value) in feature.attributes.iter() {
+            let field_type = types.entry(name.clone()).or_default();
+            if let Some(value_type) = FieldType::of(value) {
+                *field_type = Some(FieldType::merge(*field_type, value_type));
+            }
+        }
+        let Some(geometry) = &feature.geometry else {
+            continue;
+        };
+        match (epsg, geometry.epsg) {
+            (Some(current), Some(code)) if current != code => {
+                return Err(SinkError::FileWriter(format!(
+                    "Features of several coordinate systems: EPSG:{} and EPSG:{}",
+                    current, code
+                )))
+            }
+            (_, Some(code)) => epsg = Some(code),
+            _ => {}
+        }
+        if let Some(wkb) = geometry_value_to_wkb(&geometry.value) {
+            geometry_types.insert(wkb.geometry_type);
+            has_z |= wkb.z;
+        }
+    }
+    let columns = types
+        .into_iter()
+        .map(|(name, field_type)| (name, field_type.unwrap_or(FieldType::String)))
+        .collect::<Vec<_>>();
+    let geometry_type = match geometry_types.iter().next() {
+        Some(geometry_type) if geometry_types.len() == 1 => fgb_geometry_type(*geometry_type),
+        _ => GeometryType::Unknown,
+    };
+
+    let name = output
+        .path()
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("features")
+        .to_string();
+    let mut fgb = FgbWriter::create_with_options(
+        &name,
+        geometry_type,
+        FgbWriterOptions {
+            write_index: true,
+            detect_type: false,
+            promote_to_multi: false,
+            has_z,
+            crs: FgbCrs {
+                code: epsg.map_or(0, i32::from),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .map_err(SinkError::file_writer)?;
+    for (name, field_type) in columns.iter() {
+        fgb.add_column(&name.inner(), field_type.column_type(), |_, column| {
+            column.nullable = true;
+        });
+    }
+    fgb.dataset_begin(Some(&name))
+        .map_err(SinkError::file_writer)?;
+    for (index, feature) in features.iter().enumerate() {
+        let feature = feature.map_err(SinkError::file_writer)?;
+        write_feature(&mut fgb, index as u64, &feature, &columns)
+            .map_err(SinkError::file_writer)?;
+    }
+    fgb.dataset_end().map_err(SinkError::file_writer)?;
+    let mut writer = create_output(output, &storage_resolver)?;
+    fgb.write(&mut writer).map_err(SinkError::file_writer)?;
+    close_output(writer)
+}
+
+/// Adds a feature, whose properties are written in the order of the columns. Null and missing
+/// values are left out.
+fn write_feature(
+    fgb: &mut FgbWriter,
+    index: u64,
+    feature: &Feature,
+    columns: &[(Attribute, FieldType)],
+) -> geozero::error::Result<()> {
+    fgb.feature_begin(index)?;
+    fgb.properties_begin()?;
+    for (column, (name, field_type)) in columns.iter().enumerate() {
+        let Some(value) = feature.attributes.get(name) else {
+            continue;
+        };
+        let text;
+        let value = match (field_type, value) {
+            (_, AttributeValue::Null) => continue,
+            (FieldType::Bool, AttributeValue::Bool(value)) => ColumnValue::Bool(*value),
+            (FieldType::Long, AttributeValue::Number(number)) => {
+                ColumnValue::Long(number.as_i64().unwrap_or_default())
+            }
+            (FieldType::Double, AttributeValue::Number(number)) => {
+                ColumnValue::Double(number.as_f64().unwrap_or(f64::NAN))
+            }
+            (FieldType::Binary, AttributeValue::Bytes(bytes)) => ColumnValue::Binary(bytes),
+            (FieldType::String, AttributeValue::String(value)) => ColumnValue::String(value),
+            (FieldType::DateTime, AttributeValue::DateTime(datetime)) => {
+                text = datetime.to_raw();
+                ColumnValue::DateTime(&text)
+            }
+            (field_type, value) => {
+                text = serde_json::Value::from(value.clone()).to_string();
+                match field_type {
+                    FieldType::Json => ColumnValue::Json(&text),
+                    _ => ColumnValue::String(&text),
+                }
+            }
+        };
+        fgb.property(column, &name.inner(), &value)?;
+    }
+    fgb.properties_end()?;
+    fgb.geometry_begin()?;
+    if let Some(wkb) = feature
+        .geometry
+        .as_ref()
+        .and_then(|geometry| geometry_value_to_wkb(&geometry.value))
+    {
+        Wkb(wkb.data).process_geom(fgb)?;
+    }
+    fgb.geometry_end()?;
+    fgb.feature_end(index)
+}
+
+fn fgb_geometry_type(geometry_type: WkbGeometryType) -> GeometryType {
+    match geometry_type {
+        WkbGeometryType::Point => GeometryType::Point,
+        WkbGeometryType::LineString => GeometryType::LineString,
+        WkbGeometryType::Polygon => GeometryType::Polygon,
+        WkbGeometryType::MultiPoint => GeometryType::MultiPoint,
+        WkbGeometryType::MultiLineString => GeometryType::MultiLineString,
+        WkbGeometryType::MultiPolygon => GeometryType::MultiPolygon,
+        WkbGeometryType::GeometryCollection => GeometryType::GeometryCollection,
+    }
+}
+
+/// The type of a column, inferred from the values of an attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Bool,
+    Long,
+    Double,
+    String,
+    Json,
+    DateTime,
+    Binary,
+}
+
+impl FieldType {
+    fn of(value: &AttributeValue) -> Option<Self> {
+        match value {
+            AttributeValue::Null => None,
+            AttributeValue::Bool(_) => Some(Self::Bool),
+            AttributeValue::Number(number) if number.is_i64() => Some(Self::Long),
+            AttributeValue::Number(_) => Some(Self::Double),
+            AttributeValue::String(_) => Some(Self::String),
+            AttributeValue::DateTime(_) => Some(Self::DateTime),
+            AttributeValue::Array(_) | AttributeValue::Map(_) => Some(Self::Json),
+            AttributeValue::Bytes(_) => Some(Self::Binary),
+        }
+    }
+
+    fn merge(current: Option<Self>, value: Self) -> Self {
+        match (current, value) {
+            (None, value) => value,
+            (Some(current), value) if current == value => value,
+            (Some(Self::Long), Self::Double) | (Some(Self::Double), Self::Long) => Self::Double,
+            _ => Self::String,
+        }
+    }
+
+    fn column_type(self) -> ColumnType {
+        match self {
+            Self::Bool => ColumnType::Bool,
+            Self::Long => ColumnType::Long,
+            Self::Double => ColumnType::Double,
+            Self::String => ColumnType::String,
+            Self::Json => ColumnType::Json,
+            Self::DateTime => ColumnType::DateTime,
+            Self::Binary => ColumnType::Binary,
+        }
+    }
+}
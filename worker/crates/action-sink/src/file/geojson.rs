@@ -0,0 +1,99 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use nusamai_projection::crs::EpsgCode;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::spill::FeatureBuffer;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::geojson::{crs_member, feature_to_geojson, GEOJSON_DEFAULT_EPSG};
+use reearth_flow_types::Feature;
+
+use crate::errors::SinkError;
+
+use super::writer::{close_output, create_output};
+
+/// Writes the features as a FeatureCollection, or as a GeoJSON text sequence with one feature
+/// per line when `sequence` is set. The features are written as they are read from the buffer.
+///
+/// Coordinates are written as they are. When the features are not in WGS 84, their coordinate
+/// system is named by a legacy `crs` member, of the collection or of each feature of a sequence.
+/// Features in different coordinate systems cannot be written to the same collection.
+pub(super) fn write_geojson(
+    output: &Uri,
+    features: &FeatureBuffer,
+    sequence: bool,
+    storage_resolver: Arc<StorageResolver>,
+) -> Result<(), SinkError> {
+    if sequence {
+        let mut writer = create_output(output, &storage_resolver)?;
+        for feature in features.iter() {
+            let feature = feature.map_err(SinkError::file_writer)?;
+            let mut geojson = feature_to_geojson(&feature);
+            if let Some(epsg) = crs_epsg(&feature) {
+                geojson.foreign_members = Some(
+                    [("crs".to_string(), crs_member(epsg))]
+                        .into_iter()
+                        .collect(),
+                );
+            }
+            serde_json::to_writer(&mut writer, &geojson).map_err(SinkError::file_writer)?;
+            writer.write_all(b"\n").map_err(SinkError::file_writer)?;
+        }
+        return close_output(writer);
+    }
+
+    // The coordinate system of the collection precedes its features, so the buffer is read once
+    // to find it and once more to write the features.
+    let mut epsg = None;
+    for feature in features.iter() {
+        check_epsg(&mut epsg, &feature.map_err(SinkError::file_writer)?)?;
+    }
+    let mut writer = create_output(output, &storage_resolver)?;
+    writer
+        .write_all(br#"{"type":"FeatureCollection","#)
+        .map_err(SinkError::file_writer)?;
+    if let Some(epsg) = epsg.filter(|epsg| *epsg != GEOJSON_DEFAULT_EPSG) {
+        writer
+            .write_all(br#""crs":"#)
+            .map_err(SinkError::file_writer)?;
+        serde_json::to_writer(&mut writer, &crs_member(epsg)).map_err(SinkError::file_writer)?;
+        writer.write_all(b",").map_err(SinkError::file_writer)?;
+    }
+    writer
+        .write_all(br#""features":["#)
+        .map_err(SinkError::file_writer)?;
+    for (index, feature) in features.iter().enumerate() {
+        let feature = feature.map_err(SinkError::file_writer)?;
+        if index > 0 {
+            writer.write_all(b",").map_err(SinkError::file_writer)?;
+        }
+        serde_json::to_writer(&mut writer, &feature_to_geojson(&feature))
+            .map_err(SinkError::file_writer)?;
+    }
+    writer.write_all(b"]}").map_err(SinkError::file_writer)?;
+    close_output(writer)
+}
+
+fn crs_epsg(feature: &Feature) -> Option<EpsgCode> {
+    feature
+        .geometry
+        .as_ref()
+        .and_then(|geometry| geometry.epsg)
+        .filter(|epsg| *epsg != GEOJSON_DEFAULT_EPSG)
+}
+
+fn check_epsg(epsg: &mut Option<EpsgCode>, feature: &Feature) -> Result<(), SinkError> {
+    let Some(current) = feature.geometry.as_ref().and_then(|geometry| geometry.epsg) else {
+        return Ok(());
+    };
+    match epsg {
+        Some(epsg) if *epsg != current => Err(SinkError::FileWriter(format!(
+            "Features have different coordinate systems: EPSG:{} and EPSG:{}",
+            epsg, current
+        ))),
+        _ => {
+            *epsg = Some(current);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use nusamai_projection::crs::EpsgCode;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::spill::FeatureBuffer;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::gpkg::geometry_value_to_gpkg;
+use reearth_flow_types::wkb::{geometry_value_to_wkb, WkbGeometryType};
+use reearth_flow_types::{Attribute, AttributeValue, Feature};
+use rusqlite::{params, types::Value, Connection, Statement};
+
+use crate::errors::SinkError;
+
+use super::crs::prj_from_epsg;
+use super::writer::{close_output, create_output};
+
+const APPLICATION_ID: i32 = 0x4750_4B47;
+const USER_VERSION: i32 = 10300;
+const FID_COLUMN: &str = "fid";
+const GEOMETRY_COLUMN: &str = "geom";
+
+const CORE_TABLES: &str = r#"
+CREATE TABLE gpkg_spatial_ref_sys (
+    srs_name TEXT NOT NULL,
+    srs_id INTEGER PRIMARY KEY,
+    organization TEXT NOT NULL,
+    organization_coordsys_id INTEGER NOT NULL,
+    definition TEXT NOT NULL,
+    description TEXT
+);
+CREATE TABLE gpkg_contents (
+    table_name TEXT NOT NULL PRIMARY KEY,
+    data_type TEXT NOT NULL,
+    identifier TEXT UNIQUE,
+    description TEXT DEFAULT '',
+    last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    min_x DOUBLE,
+    min_y DOUBLE,
+    max_x DOUBLE,
+    max_y DOUBLE,
+    srs_id INTEGER,
+    CONSTRAINT fk_gc_r_srs_id FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys(srs_id)
+);
+CREATE TABLE gpkg_geometry_columns (
+    table_name TEXT NOT NULL,
+    column_name TEXT NOT NULL,
+    geometry_type_name TEXT NOT NULL,
+    srs_id INTEGER NOT NULL,
+    z TINYINT NOT NULL,
+    m TINYINT NOT NULL,
+    CONSTRAINT pk_geom_cols PRIMARY KEY (table_name, column_name),
+    CONSTRAINT uk_gc_table_name UNIQUE (table_name),
+    CONSTRAINT fk_gc_tn FOREIGN KEY (table_name) REFERENCES gpkg_contents(table_name),
+    CONSTRAINT fk_gc_srs FOREIGN KEY (srs_id) REFERENCES gpkg_spatial_ref_sys (srs_id)
+);
+CREATE TABLE gpkg_extensions (
+    table_name TEXT,
+    column_name TEXT,
+    extension_name TEXT NOT NULL,
+    definition TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    CONSTRAINT ge_tce UNIQUE (table_name, column_name, extension_name)
+);
+INSERT INTO gpkg_spatial_ref_sys VALUES
+    ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', 'undefined cartesian coordinate reference system'),
+    ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', 'undefined geographic coordinate reference system'),
+    ('WGS 84 geodetic', 4326, 'EPSG', 4326, 'GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4326"]]', 'longitude/latitude coordinates in decimal degrees on the WGS 84 spheroid');
+"#;
+
+/// Triggers keeping the spatial index of a table up to date, as defined by the GeoPackage
+/// specification. `{t}`, `{c}`, `{i}` and `{r}` stand for the quoted table, geometry column,
+/// primary key and index.
+const RTREE_TRIGGERS: &str = r#"
+CREATE TRIGGER {insert} AFTER INSERT ON {t}
+WHEN (new.{c} NOT NULL AND NOT ST_IsEmpty(NEW.{c}))
+BEGIN
+    INSERT OR REPLACE INTO {r} VALUES (
+        NEW.{i}, ST_MinX(NEW.{c}), ST_MaxX(NEW.{c}), ST_MinY(NEW.{c}), ST_MaxY(NEW.{c})
+    );
+END;
+CREATE TRIGGER {update1} AFTER UPDATE OF {c} ON {t}
+WHEN OLD.{i} = NEW.{i} AND (NEW.{c} NOTNULL AND NOT ST_IsEmpty(NEW.{c}))
+BEGIN
+    INSERT OR REPLACE INTO {r} VALUES (
+        NEW.{i}, ST_MinX(NEW.{c}), ST_MaxX(NEW.{c}), ST_MinY(NEW.{c}), ST_MaxY(NEW.{c})
+    );
+END;
+CREATE TRIGGER {update2} AFTER UPDATE OF {c} ON {t}
+WHEN OLD.{i} = NEW.{i} AND (NEW.{c} ISNULL OR ST_IsEmpty(NEW.{c}))
+BEGIN
+    DELETE FROM {r} WHERE id = OLD.{i};
+END;
+CREATE TRIGGER {update3} AFTER UPDATE ON {t}
+WHEN OLD.{i} != NEW.{i} AND (NEW.{c} NOTNULL AND NOT ST_IsEmpty(NEW.{c}))
+BEGIN
+    DELETE FROM {r} WHERE id = OLD.{i};
+    INSERT OR REPLACE INTO {r} VALUES (
+        NEW.{i}, ST_MinX(NEW.{c}), ST_MaxX(NEW.{c}), ST_MinY(NEW.{c}), ST_MaxY(NEW.{c})
+    );
+END;
+CREATE TRIGGER {update4} AFTER UPDATE ON {t}
+WHEN OLD.{i} != NEW.{i} AND (NEW.{c} ISNULL OR ST_IsEmpty(NEW.{c}))
+BEGIN
+    DELETE FROM {r} WHERE id IN (OLD.{i}, NEW.{i});
+END;
+CREATE TRIGGER {delete} AFTER DELETE ON {t}
+WHEN old.{c} NOT NULL
+BEGIN
+    DELETE FROM {r} WHERE id = OLD.{i};
+END;
+"#;
+
+/// Writes the features to a GeoPackage with a feature table for each layer, along with its
+/// spatial reference system and an R-tree spatial index.
+///
+/// The buffer is read twice: once to name the layer of each feature and infer the columns of
+/// each table, then to insert the rows. Each attribute becomes a column, whose type is inferred
+/// from its values. The features of a layer must share a coordinate system, which is named by
+/// its EPSG code. Its definition is the WKT written to the `.prj` files of shapefiles, so the
+/// coordinate systems that shapefiles do not support are defined as `undefined`.
+pub(super) fn write_gpkg(
+    output: &Uri,
+    features: &FeatureBuffer,
+    layer_name: impl Fn(&Feature) -> Result<String, SinkError>,
+    storage_resolver: Arc<StorageResolver>,
+) -> Result<(), SinkError> {
+    let mut layers = Vec::<Layer>::new();
+    let mut indices = HashMap::<String, usize>::new();
+    let mut assigned = vec![];
+    for feature in features.iter() {
+        let feature = feature.map_err(SinkError::file_writer)?;
+        let name = layer_name(&feature)?;
+        let index = match indices.get(&name) {
+            Some(index) => *index,
+            None => {
+                indices.insert(name.clone(), layers.len());
+                layers.push(Layer::new(name));
+                layers.len() - 1
+            }
+        };
+        layers[index].add(&feature)?;
+        assigned.push(index);
+    }
+
+    let file = tempfile::NamedTempFile::new().map_err(SinkError::file_writer)?;
+    let mut connection = Connection::open(file.path()).map_err(SinkError::file_writer)?;
+    connection
+        .pragma_update(None, "application_id", APPLICATION_ID)
+        .map_err(SinkError::file_writer)?;
+    connection
+        .pragma_update(None, "user_version", USER_VERSION)
+        .map_err(SinkError::file_writer)?;
+    let transaction = connection.transaction().map_err(SinkError::file_writer)?;
+    transaction
+        .execute_batch(CORE_TABLES)
+        .map_err(SinkError::file_writer)?;
+    let tables = layers
+        .into_iter()
+        .map(|layer| create_table(&transaction, layer))
+        .collect::<Result<Vec<_>, _>>()?;
+    {
+        let mut statements = tables
+            .iter()
+            .map(|table| table.statements(&transaction))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (feature, index) in features.iter().zip(assigned) {
+            let feature = feature.map_err(SinkError::file_writer)?;
+            let (insert, insert_rtree) = &mut statements[index];
+            tables[index].insert(&transaction, insert, insert_rtree, &feature)?;
+        }
+    }
+    for table in tables.iter() {
+        write_rtree_triggers(&transaction, &table.name)?;
+    }
+    transaction.commit().map_err(SinkError::file_writer)?;
+    connection
+        .close()
+        .map_err(|(_, e)| SinkError::file_writer(e))?;
+    let mut writer = create_output(output, &storage_resolver)?;
+    let mut content = std::fs::File::open(file.path()).map_err(SinkError::file_writer)?;
+    std::io::copy(&mut content, &mut writer).map_err(SinkError::file_writer)?;
+    close_output(writer)
+}
+
+/// What the features of a layer tell about its table, gathered before it is created.
+struct Layer {
+    name: String,
+    epsg: Option<EpsgCode>,
+    geometry_types: HashSet<WkbGeometryType>,
+    z: bool,
+    bbox: Option<[f64; 4]>,
+    column_types: BTreeMap<Attribute, Option<ColumnType>>,
+}
+
+impl Layer {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            epsg: None,
+            geometry_types: HashSet::new(),
+            z: false,
+            bbox: None,
+            column_types: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, feature: &Feature) -> Result<(), SinkError> {
+        for (attribute, value) in feature.attributes.iter() {
+            let column_type = self.column_types.entry(attribute.clone()).or_default();
+            if let Some(value) = ColumnType::of(value) {
+                *column_type = Some(ColumnType::merge(*column_type, value));
+            }
+        }
+        let Some(geometry) = &feature.geometry else {
+            return Ok(());
+        };
+        match (self.epsg, geometry.epsg) {
+            (Some(epsg), Some(current)) if epsg != current => {
+                return Err(SinkError::FileWriter(format!(
+                    "Features of layer {} have different coordinate systems: EPSG:{} and EPSG:{}",
+                    self.name, epsg, current
+                )))
+            }
+            (None, current) => self.epsg = current,
+            _ => {}
+        }
+        let Some(wkb) = geometry_value_to_wkb(&geometry.value) else {
+            return Ok(());
+        };
+        self.geometry_types.insert(wkb.geometry_type);
+        self.z |= wkb.z;
+        if let Some([b0, b1, b2, b3]) = wkb.bbox {
+            self.bbox = Some(match self.bbox {
+                Some([a0, a1, a2, a3]) => [a0.min(b0), a1.min(b1), a2.max(b2), a3.max(b3)],
+                None => [b0, b1, b2, b3],
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A feature table, created with its spatial index.
+struct Table {
+    name: String,
+    srs_id: i32,
+    columns: Vec<(Attribute, String, ColumnType)>,
+}
+
+impl Table {
+    fn statements<'a>(
+        &self,
+        connection: &'a Connection,
+    ) -> Result<(Statement<'a>, Statement<'a>), SinkError> {
+        let insert = connection
+            .prepare(&format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                quote(&self.name),
+                std::iter::once(GEOMETRY_COLUMN)
+                    .chain(self.columns.iter().map(|(_, name, _)| name.as_str()))
+                    .map(quote)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                (1..=self.columns.len() + 1)
+                    .map(|i| format!("?{}", i))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .map_err(SinkError::file_writer)?;
+        let insert_rtree = connection
+            .prepare(&format!(
+                "INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5)",
+                quote(&rtree_name(&self.name))
+            ))
+            .map_err(SinkError::file_writer)?;
+        Ok((insert, insert_rtree))
+    }
+
+    /// Inserts a row, with its extent in the spatial index since the triggers are created once
+    /// the table is filled.
+    fn insert(
+        &self,
+        connection: &Connection,
+        insert: &mut Statement,
+        insert_rtree: &mut Statement,
+        feature: &Feature,
+    ) -> Result<(), SinkError> {
+        let geometry = feature
+            .geometry
+            .as_ref()
+            .and_then(|geometry| geometry_value_to_gpkg(&geometry.value, self.srs_id));
+        let mut values = vec![geometry
+            .as_ref()
+            .map_or(Value::Null, |(blob, _)| Value::Blob(blob.clone()))];
+        values.extend(self.columns.iter().map(|(attribute, _, column_type)| {
+            column_type.value(feature.attributes.get(attribute))
+        }));
+        insert
+            .execute(rusqlite::params_from_iter(values))
+            .map_err(SinkError::file_writer)?;
+        if let Some([min_x, min_y, max_x, max_y]) = geometry.as_ref().and_then(|(_, wkb)| wkb.bbox)
+        {
+            insert_rtree
+                .execute(params![
+                    connection.last_insert_rowid(),
+                    min_x,
+                    max_x,
+                    min_y,
+                    max_y
+                ])
+                .map_err(SinkError::file_writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Creates the feature table of a layer and its spatial index, and registers them.
+fn create_table(connection: &Connection, layer: Layer) -> Result<Table, SinkError> {
+    let srs_id = match layer.epsg {
+        Some(epsg) => {
+            let definition = prj_from_epsg(epsg).unwrap_or_else(|| "undefined".to_string());
+            connection
+                .execute(
+                    "INSERT OR IGNORE INTO gpkg_spatial_ref_sys VALUES (?1, ?2, 'EPSG', ?2, ?3, NULL)",
+                    params![format!("EPSG:{}", epsg), epsg, definition],
+                )
+                .map_err(SinkError::file_writer)?;
+            epsg as i32
+        }
+        None => -1,
+    };
+    let geometry_type_name = match layer.geometry_types.iter().next() {
+        Some(geometry_type) if layer.geometry_types.len() == 1 => geometry_type.name(),
+        _ => "Geometry",
+    }
+    .to_ascii_uppercase();
+    let columns = attribute_columns(layer.column_types);
+    let mut definitions = vec![
+        format!(
+            "{} INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL",
+            quote(FID_COLUMN)
+        ),
+        format!("{} {}", quote(GEOMETRY_COLUMN), geometry_type_name),
+    ];
+    definitions.extend(
+        columns
+            .iter()
+            .map(|(_, name, column_type)| format!("{} {}", quote(name), column_type.name())),
+    );
+    let table = layer.name;
+    connection
+        .execute_batch(&format!(
+            "CREATE TABLE {} ({})",
+            quote(&table),
+            definitions.join(", ")
+        ))
+        .map_err(SinkError::file_writer)?;
+    let [min_x, min_y, max_x, max_y] = layer.bbox.map_or([None; 4], |bbox| bbox.map(Some));
+    connection
+        .execute(
+            "INSERT INTO gpkg_contents (table_name, data_type, identifier, min_x, min_y, max_x, max_y, srs_id) \
+             VALUES (?1, 'features', ?1, ?2, ?3, ?4, ?5, ?6)",
+            params![table, min_x, min_y, max_x, max_y, srs_id],
+        )
+        .map_err(SinkError::file_writer)?;
+    connection
+        .execute(
+            "INSERT INTO gpkg_geometry_columns VALUES (?1, ?2, ?3, ?4, ?5, 0)",
+            params![
+                table,
+                GEOMETRY_COLUMN,
+                geometry_type_name,
+                srs_id,
+                layer.z as i32
+            ],
+        )
+        .map_err(SinkError::file_writer)?;
+    connection
+        .execute_batch(&format!(
+            "CREATE VIRTUAL TABLE {} USING rtree(id, minx, maxx, miny, maxy)",
+            quote(&rtree_name(&table))
+        ))
+        .map_err(SinkError::file_writer)?;
+    Ok(Table {
+        name: table,
+        srs_id,
+        columns,
+    })
+}
+
+fn rtree_name(table: &str) -> String {
+    format!("rtree_{}_{}", table, GEOMETRY_COLUMN)
+}
+
+/// Creates the triggers keeping the spatial index of a table up to date, once it is filled,
+/// since the functions they call are not available here.
+fn write_rtree_triggers(connection: &Connection, table: &str) -> Result<(), SinkError> {
+    let index = rtree_name(table);
+    let mut triggers = RTREE_TRIGGERS
+        .replace("{t}", &quote(table))
+        .replace("{c}", &quote(GEOMETRY_COLUMN))
+        .replace("{i}", &quote(FID_COLUMN))
+        .replace("{r}", &quote(&index));
+    for trigger in [
+        "insert", "update1", "update2", "update3", "update4", "delete",
+    ] {
+        triggers = triggers.replace(
+            &format!("{{{}}}", trigger),
+            &quote(&format!("{}_{}", index, trigger)),
+        );
+    }
+    connection
+        .execute_batch(&triggers)
+        .map_err(SinkError::file_writer)?;
+    connection
+        .execute(
+            "INSERT INTO gpkg_extensions VALUES (?1, ?2, 'gpkg_rtree_index', \
+             'http://www.geopackage.org/spec120/#extension_rtree', 'write-only')",
+            params![table, GEOMETRY_COLUMN],
+        )
+        .map_err(SinkError::file_writer)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnType {
+    Boolean,
+    Integer,
+    Real,
+    Text,
+    DateTime,
+    Blob,
+}
+
+impl ColumnType {
+    fn of(value: &AttributeValue) -> Option<Self> {
+        match value {
+            AttributeValue::Null => None,
+            AttributeValue::Bool(_) => Some(Self::Boolean),
+            AttributeValue::Number(number) if number.is_i64() => Some(Self::Integer),
+            AttributeValue::Number(_) => Some(Self::Real),
+            AttributeValue::DateTime(_) => Some(Self::DateTime),
+            AttributeValue::Bytes(_) => Some(Self::Blob),
+            _ => Some(Self::Text),
+        }
+    }
+
+    fn merge(current: Option<Self>, value: Self) -> Self {
+        match (current, value) {
+            (None, value) => value,
+            (Some(current), value) if current == value => value,
+            (Some(Self::Integer), Self::Real) | (Some(Self::Real), Self::Integer) => Self::Real,
+            _ => Self::Text,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Boolean => "BOOLEAN",
+            Self::Integer => "INTEGER",
+            Self::Real => "REAL",
+            Self::Text => "TEXT",
+            Self::DateTime => "DATETIME",
+            Self::Blob => "BLOB",
+        }
+    }
+
+    fn value(self, value: Option<&AttributeValue>) -> Value {
+        match (self, value) {
+            (_, None | Some(AttributeValue::Null)) => Value::Null,
+            (Self::Boolean, Some(AttributeValue::Bool(value))) => Value::Integer(*value as i64),
+            (Self::Integer, Some(AttributeValue::Number(number))) => {
+                number.as_i64().map_or(Value::Null, Value::Integer)
+            }
+            (Self::Real, Some(AttributeValue::Number(number))) => {
+                number.as_f64().map_or(Value::Null, Value::Real)
+            }
+            (Self::DateTime, Some(AttributeValue::DateTime(datetime))) => {
+                Value::Text(datetime.0.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+            }
+            (Self::Blob, Some(AttributeValue::Bytes(bytes))) => Value::Blob(bytes.to_vec()),
+            (_, Some(AttributeValue::String(text))) => Value::Text(text.clone()),
+            (_, Some(value)) => Value::Text(serde_json::Value::from(value.clone()).to_string()),
+        }
+    }
+}
+
+/// The attribute columns of a layer, sorted by name. Names clashing with the primary key or
+/// geometry column, which SQLite compares case-insensitively, are suffixed.
+fn attribute_columns(
+    types: BTreeMap<Attribute, Option<ColumnType>>,
+) -> Vec<(Attribute, String, ColumnType)> {
+    let mut used = [FID_COLUMN, GEOMETRY_COLUMN]
+        .into_iter()
+        .map(str::to_string)
+        .collect::<HashSet<_>>();
+    types
+        .into_iter()
+        .map(|(attribute, column_type)| {
+            let mut name = attribute.inner();
+            let mut suffix = 1;
+            while used.contains(&name.to_lowercase()) {
+                name = format!("{}_{}", attribute.inner(), suffix);
+                suffix += 1;
+            }
+            used.insert(name.to_lowercase());
+            (attribute, name, column_type.unwrap_or(ColumnType::Text))
+        })
+        .collect()
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
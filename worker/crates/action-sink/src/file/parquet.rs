@@ -0,0 +1,440 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, ListArray, StringArray,
+    StructArray, TimestampMicrosecondArray,
+};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use nusamai_projection::crs::EpsgCode;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::{WriterProperties, DEFAULT_MAX_ROW_GROUP_SIZE};
+use parquet::format::KeyValue;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::spill::FeatureBuffer;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::wkb::geometry_value_to_wkb;
+use reearth_flow_types::{Attribute, AttributeValue, Feature};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::errors::SinkError;
+
+use super::crs::projjson_from_epsg;
+use super::writer::{close_output, create_output};
+
+const GEOMETRY_COLUMN: &str = "geometry";
+const GEOPARQUET_VERSION: &str = "1.1.0";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum ParquetCompression {
+    Uncompressed,
+    #[default]
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+}
+
+impl From<ParquetCompression> for Compression {
+    fn from(compression: ParquetCompression) -> Self {
+        match compression {
+            ParquetCompression::Uncompressed => Compression::UNCOMPRESSED,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Gzip => Compression::GZIP(GzipLevel::default()),
+            ParquetCompression::Lz4 => Compression::LZ4_RAW,
+            ParquetCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+        }
+    }
+}
+
+/// Type of a column, inferred from the values of an attribute.
+#[derive(Debug, Clone, PartialEq)]
+enum ColumnType {
+    /// No value but nulls yet.
+    Null,
+    Boolean,
+    Int64,
+    Float64,
+    Utf8,
+    Timestamp,
+    Binary,
+    List(Box<ColumnType>),
+    Struct(BTreeMap<String, ColumnType>),
+}
+
+impl ColumnType {
+    fn of(value: &AttributeValue) -> Self {
+        match value {
+            AttributeValue::Null => Self::Null,
+            AttributeValue::Bool(_) => Self::Boolean,
+            AttributeValue::Number(number) if number.is_i64() => Self::Int64,
+            AttributeValue::Number(_) => Self::Float64,
+            AttributeValue::String(_) => Self::Utf8,
+            AttributeValue::DateTime(_) => Self::Timestamp,
+            AttributeValue::Bytes(_) => Self::Binary,
+            AttributeValue::Array(values) => Self::List(Box::new(
+                values.iter().map(Self::of).fold(Self::Null, Self::merge),
+            )),
+            AttributeValue::Map(map) => Self::Struct(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), Self::of(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Null, other) | (other, Self::Null) => other,
+            (current, other) if current == other => current,
+            (Self::Int64, Self::Float64) | (Self::Float64, Self::Int64) => Self::Float64,
+            (Self::List(current), Self::List(other)) => {
+                Self::List(Box::new((*current).merge(*other)))
+            }
+            (Self::Struct(mut current), Self::Struct(other)) => {
+                for (key, other) in other {
+                    let merged = match current.remove(&key) {
+                        Some(column_type) => column_type.merge(other),
+                        None => other,
+                    };
+                    current.insert(key, merged);
+                }
+                Self::Struct(current)
+            }
+            _ => Self::Utf8,
+        }
+    }
+
+    /// The Arrow type of the column. Columns of nulls only, and of empty maps, are strings.
+    fn data_type(&self) -> DataType {
+        match self {
+            Self::Boolean => DataType::Boolean,
+            Self::Int64 => DataType::Int64,
+            Self::Float64 => DataType::Float64,
+            Self::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            Self::Binary => DataType::Binary,
+            Self::List(item) => {
+                DataType::List(Arc::new(Field::new("item", item.data_type(), true)))
+            }
+            Self::Struct(fields) if !fields.is_empty() => DataType::Struct(struct_fields(fields)),
+            Self::Null | Self::Utf8 | Self::Struct(_) => DataType::Utf8,
+        }
+    }
+
+    fn array(&self, values: &[Option<&AttributeValue>]) -> ArrayRef {
+        let values = values
+            .iter()
+            .map(|value| value.filter(|value| !matches!(value, AttributeValue::Null)))
+            .collect::<Vec<_>>();
+        match self {
+            Self::Boolean => Arc::new(BooleanArray::from(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Some(AttributeValue::Bool(value)) => Some(*value),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            Self::Int64 => Arc::new(Int64Array::from(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Some(AttributeValue::Number(number)) => number.as_i64(),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            Self::Float64 => Arc::new(Float64Array::from(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Some(AttributeValue::Number(number)) => number.as_f64(),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            Self::Timestamp => Arc::new(
+                TimestampMicrosecondArray::from(
+                    values
+                        .iter()
+                        .map(|value| match value {
+                            Some(AttributeValue::DateTime(datetime)) => {
+                                Some(datetime.timestamp_micros())
+                            }
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .with_timezone("UTC"),
+            ),
+            Self::Binary => Arc::new(BinaryArray::from(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Some(AttributeValue::Bytes(bytes)) => Some(bytes.as_ref()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            Self::List(item) => {
+                let mut lengths = vec![];
+                let mut items = vec![];
+                let mut valid = vec![];
+                for value in values.iter() {
+                    match value {
+                        Some(AttributeValue::Array(elements)) => {
+                            lengths.push(elements.len());
+                            items.extend(elements.iter().map(Some));
+                            valid.push(true);
+                        }
+                        _ => {
+                            lengths.push(0);
+                            valid.push(false);
+                        }
+                    }
+                }
+                Arc::new(ListArray::new(
+                    Arc::new(Field::new("item", item.data_type(), true)),
+                    OffsetBuffer::from_lengths(lengths),
+                    item.array(&items),
+                    Some(NullBuffer::from(valid)),
+                ))
+            }
+            Self::Struct(fields) if !fields.is_empty() => {
+                let arrays = fields
+                    .iter()
+                    .map(|(key, column_type)| {
+                        let values = values
+                            .iter()
+                            .map(|value| match value {
+                                Some(AttributeValue::Map(map)) => map.get(key),
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>();
+                        column_type.array(&values)
+                    })
+                    .collect();
+                let valid = values
+                    .iter()
+                    .map(|value| matches!(value, Some(AttributeValue::Map(_))))
+                    .collect::<Vec<_>>();
+                Arc::new(StructArray::new(
+                    struct_fields(fields),
+                    arrays,
+                    Some(NullBuffer::from(valid)),
+                ))
+            }
+            Self::Null | Self::Utf8 | Self::Struct(_) => Arc::new(StringArray::from(
+                values
+                    .iter()
+                    .map(|value| value.map(text))
+                    .collect::<Vec<_>>(),
+            )),
+        }
+    }
+}
+
+fn struct_fields(fields: &BTreeMap<String, ColumnType>) -> Fields {
+    fields
+        .iter()
+        .map(|(key, column_type)| Field::new(key, column_type.data_type(), true))
+        .collect()
+}
+
+/// The text of a value in a string column, holding the JSON of arrays and maps.
+fn text(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::String(text) => text.clone(),
+        AttributeValue::DateTime(datetime) => datetime.to_raw(),
+        value => serde_json::Value::from(value.clone()).to_string(),
+    }
+}
+
+/// Writes the features as a GeoParquet file, with a column for each attribute typed by its
+/// values and the geometries as WKB in a `geometry` column.
+///
+/// Attributes with values of several types become strings, lists and maps become lists and
+/// structs. The buffer is read twice: once to infer the columns and the `geo` metadata, then to
+/// write row groups of at most `row_group_size` rows, holding a single row group in memory.
+/// Without geometries, a plain Parquet file is written.
+pub(super) fn write_parquet(
+    output: &Uri,
+    features: &FeatureBuffer,
+    row_group_size: Option<usize>,
+    compression: ParquetCompression,
+    storage_resolver: Arc<StorageResolver>,
+) -> Result<(), SinkError> {
+    let row_group_size = row_group_size.unwrap_or(DEFAULT_MAX_ROW_GROUP_SIZE);
+    if row_group_size == 0 {
+        return Err(SinkError::FileWriter(
+            "Row group size must be positive".to_string(),
+        ));
+    }
+    let mut columns = BTreeMap::<String, ColumnType>::new();
+    let mut geo = GeoMetadata::default();
+    for feature in features.iter() {
+        let feature = feature.map_err(SinkError::file_writer)?;
+        for (name, value) in feature.attributes.iter() {
+            let column_type = columns.remove(&name.inner()).unwrap_or(ColumnType::Null);
+            columns.insert(name.inner(), column_type.merge(ColumnType::of(value)));
+        }
+        geo.add(&feature)?;
+    }
+    let geo = if geo.is_empty() {
+        None
+    } else {
+        if columns.contains_key(GEOMETRY_COLUMN) {
+            return Err(SinkError::FileWriter(format!(
+                "Attribute `{}` conflicts with the geometry column",
+                GEOMETRY_COLUMN
+            )));
+        }
+        Some(geo.to_json())
+    };
+
+    let mut fields = columns
+        .iter()
+        .map(|(name, column_type)| Field::new(name, column_type.data_type(), true))
+        .collect::<Vec<_>>();
+    if geo.is_some() {
+        fields.push(Field::new(GEOMETRY_COLUMN, DataType::Binary, true));
+    }
+    let schema = Arc::new(Schema::new(fields));
+    let properties = WriterProperties::builder()
+        .set_max_row_group_size(row_group_size)
+        .set_compression(compression.into())
+        .set_key_value_metadata(
+            geo.map(|geo| vec![KeyValue::new("geo".to_string(), geo.to_string())]),
+        )
+        .build();
+    let output_writer = create_output(output, &storage_resolver)?;
+    let mut writer = ArrowWriter::try_new(output_writer, Arc::clone(&schema), Some(properties))
+        .map_err(SinkError::file_writer)?;
+    let mut chunk = Vec::with_capacity(row_group_size.min(DEFAULT_MAX_ROW_GROUP_SIZE));
+    let mut features = features.iter().peekable();
+    while let Some(feature) = features.next() {
+        chunk.push(feature.map_err(SinkError::file_writer)?);
+        if chunk.len() < row_group_size && features.peek().is_some() {
+            continue;
+        }
+        let batch = record_batch(&schema, &columns, &chunk)?;
+        writer.write(&batch).map_err(SinkError::file_writer)?;
+        chunk.clear();
+    }
+    close_output(writer.into_inner().map_err(SinkError::file_writer)?)
+}
+
+/// The rows of a row group.
+fn record_batch(
+    schema: &SchemaRef,
+    columns: &BTreeMap<String, ColumnType>,
+    chunk: &[Feature],
+) -> Result<RecordBatch, SinkError> {
+    let mut arrays = columns
+        .iter()
+        .map(|(name, column_type)| {
+            let attribute = Attribute::new(name.clone());
+            let values = chunk
+                .iter()
+                .map(|feature| feature.attributes.get(&attribute))
+                .collect::<Vec<_>>();
+            column_type.array(&values)
+        })
+        .collect::<Vec<_>>();
+    if schema.fields().len() > columns.len() {
+        let wkbs = chunk
+            .iter()
+            .map(|feature| {
+                feature
+                    .geometry
+                    .as_ref()
+                    .and_then(|geometry| geometry_value_to_wkb(&geometry.value))
+            })
+            .collect::<Vec<_>>();
+        arrays.push(Arc::new(BinaryArray::from(
+            wkbs.iter()
+                .map(|wkb| wkb.as_ref().map(|wkb| wkb.data.as_slice()))
+                .collect::<Vec<_>>(),
+        )));
+    }
+    RecordBatch::try_new(Arc::clone(schema), arrays).map_err(SinkError::file_writer)
+}
+
+/// The `geo` metadata of GeoParquet, describing the geometry column, gathered from the
+/// features.
+#[derive(Debug, Default)]
+struct GeoMetadata {
+    epsg: Option<EpsgCode>,
+    geometry_types: BTreeSet<String>,
+    bbox: Option<[f64; 4]>,
+}
+
+impl GeoMetadata {
+    /// Adds the geometry of a feature. Its coordinate system must be the same as those of the
+    /// other features.
+    fn add(&mut self, feature: &Feature) -> Result<(), SinkError> {
+        let Some(geometry) = &feature.geometry else {
+            return Ok(());
+        };
+        let Some(wkb) = geometry_value_to_wkb(&geometry.value) else {
+            return Ok(());
+        };
+        match (self.epsg, geometry.epsg) {
+            (Some(current), Some(code)) if current != code => {
+                return Err(SinkError::FileWriter(format!(
+                    "Features of several coordinate systems: EPSG:{} and EPSG:{}",
+                    current, code
+                )))
+            }
+            (_, Some(code)) => self.epsg = Some(code),
+            _ => {}
+        }
+        self.geometry_types.insert(if wkb.z {
+            format!("{} Z", wkb.geometry_type.name())
+        } else {
+            wkb.geometry_type.name().to_string()
+        });
+        if let Some(b) = wkb.bbox {
+            self.bbox = Some(match self.bbox {
+                Some(a) => [
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[2].max(b[2]),
+                    a[3].max(b[3]),
+                ],
+                None => b,
+            });
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.geometry_types.is_empty()
+    }
+
+    /// The metadata, with the PROJJSON of the coordinate system. The coordinate systems that
+    /// cannot be described are written as unknown with a null `crs`, since a missing one
+    /// stands for OGC:CRS84.
+    fn to_json(&self) -> serde_json::Value {
+        let mut column = json!({
+            "encoding": "WKB",
+            "geometry_types": self.geometry_types,
+            "crs": self.epsg.and_then(projjson_from_epsg),
+        });
+        if let Some(bbox) = self.bbox {
+            column["bbox"] = json!(bbox);
+        }
+        json!({
+            "version": GEOPARQUET_VERSION,
+            "primary_column": GEOMETRY_COLUMN,
+            "columns": { GEOMETRY_COLUMN: column },
+        })
+    }
+}
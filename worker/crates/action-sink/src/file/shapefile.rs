@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+
+use async_zip::base::write::{EntryStreamWriter, ZipFileWriter};
+use async_zip::{Compression, ZipEntryBuilder};
+use encoding_rs::UTF_8;
+use futures::executor::block_on;
+use futures::io::{AllowStdIo, AsyncWrite, AsyncWriteExt};
+use nusamai_projection::crs::EpsgCode;
+use reearth_flow_common::{encoding::encoding_for_label, uri::Uri};
+use reearth_flow_runtime::spill::FeatureBuffer;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::shapefile::dbf::{Table, TableBuilder};
+use reearth_flow_types::shapefile::shp::{RecordWriter, Shape, ShapeLayer, ShapeType};
+use reearth_flow_types::Feature;
+
+use crate::errors::SinkError;
+
+use super::crs::prj_from_epsg;
+use super::writer::{close_output, create_output};
+
+/// The features of a shape type, written to a shapefile.
+struct Layer {
+    key: (ShapeType, bool),
+    shapes: ShapeLayer,
+    table: Table,
+}
+
+/// A file of the output.
+enum File<'a> {
+    Shp(&'a Layer),
+    Shx(&'a Layer),
+    Dbf(&'a Layer),
+    Text(String),
+}
+
+/// Writes the features as shapefiles, one for each shape type, or as a single `.zip` archive
+/// of them when the output ends with `.zip`.
+///
+/// A single shapefile takes the name of the output. Otherwise the name of each is suffixed with
+/// its shape type, e.g. `buildings_polygonz.shp`. Lines become polylines, and surfaces, solids
+/// and CityGML geometries polygons. The attributes are encoded with `encoding`, UTF-8 by default.
+/// A `.prj` file is written for the WGS 84, JGD2000 and JGD2011 coordinate systems.
+///
+/// The headers of the files hold the extent of the shapes and the sizes of the fields, so the
+/// buffer is read once to gather them, and once more for each `.shp`, `.shx` and `.dbf` file.
+pub(super) fn write_shapefile(
+    output: &Uri,
+    features: &FeatureBuffer,
+    encoding: Option<&str>,
+    storage_resolver: Arc<StorageResolver>,
+) -> Result<(), SinkError> {
+    let encoding = match encoding {
+        Some(label) => encoding_for_label(label).map_err(SinkError::file_writer)?,
+        None => UTF_8,
+    };
+    let mut scanned = BTreeMap::<_, (ShapeLayer, TableBuilder, Option<EpsgCode>)>::new();
+    for feature in features.iter() {
+        let feature = feature.map_err(SinkError::file_writer)?;
+        let shape = shape(&feature)?;
+        let (shapes, table, epsg) =
+            scanned
+                .entry((shape.shape_type, shape.z))
+                .or_insert_with(|| {
+                    (
+                        ShapeLayer::new(shape.shape_type, shape.z),
+                        TableBuilder::new(encoding),
+                        None,
+                    )
+                });
+        shapes.add(&shape);
+        table.add(&feature.attributes);
+        if epsg.is_none() {
+            *epsg = feature.geometry.as_ref().and_then(|geometry| geometry.epsg);
+        }
+    }
+    let mut layers = vec![];
+    let mut epsgs = vec![];
+    for (key, (shapes, table, epsg)) in scanned {
+        layers.push(Layer {
+            key,
+            shapes,
+            table: table.build().map_err(SinkError::file_writer)?,
+        });
+        epsgs.push(epsg);
+    }
+
+    let path = output.path();
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or(SinkError::FileWriter("Invalid output path".to_string()))?;
+    let single = layers.len() == 1;
+    let mut files = vec![];
+    for (layer, epsg) in layers.iter().zip(epsgs) {
+        let (shape_type, z) = layer.key;
+        let stem = if single {
+            stem.to_string()
+        } else {
+            format!("{}_{}", stem, shape_type.name(z))
+        };
+        files.push((format!("{}.shp", stem), File::Shp(layer)));
+        files.push((format!("{}.shx", stem), File::Shx(layer)));
+        files.push((format!("{}.dbf", stem), File::Dbf(layer)));
+        if let Some(prj) = epsg.and_then(prj_from_epsg) {
+            files.push((format!("{}.prj", stem), File::Text(prj)));
+        }
+        files.push((
+            format!("{}.cpg", stem),
+            File::Text(encoding.name().to_string()),
+        ));
+    }
+
+    let archive = output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    if archive {
+        let writer = create_output(output, &storage_resolver)?;
+        return close_output(zip(&files, features, writer)?);
+    }
+    let storage = storage_resolver
+        .resolve(output)
+        .map_err(|e| SinkError::FileWriter(format!("{:?}", e)))?;
+    for (name, file) in files.iter() {
+        let writer = storage
+            .writer_sync(path.with_file_name(name).as_path())
+            .map_err(|e| SinkError::FileWriter(format!("{:?}", e)))?;
+        let mut writer = BufWriter::new(writer);
+        write_file(file, features, &mut writer)?;
+        close_output(writer)?;
+    }
+    Ok(())
+}
+
+fn shape(feature: &Feature) -> Result<Shape, SinkError> {
+    Shape::from_geometry(feature.geometry.as_ref().map(|geometry| &geometry.value))
+        .map_err(|e| SinkError::FileWriter(format!("Feature {}: {}", feature.id, e)))
+}
+
+/// Writes a file, reading the features of its layer from the buffer.
+fn write_file(
+    file: &File,
+    features: &FeatureBuffer,
+    writer: &mut impl Write,
+) -> Result<(), SinkError> {
+    let layer = match file {
+        File::Shp(layer) | File::Shx(layer) | File::Dbf(layer) => layer,
+        File::Text(text) => {
+            return writer
+                .write_all(text.as_bytes())
+                .map_err(SinkError::file_writer)
+        }
+    };
+    match file {
+        File::Dbf(_) => layer.table.write_header(writer),
+        _ => writer.write_all(&layer.shapes.header(matches!(file, File::Shx(_)))),
+    }
+    .map_err(SinkError::file_writer)?;
+    let mut records = RecordWriter::new(matches!(file, File::Shx(_)));
+    for feature in features.iter() {
+        let feature = feature.map_err(SinkError::file_writer)?;
+        let shape = shape(&feature)?;
+        if (shape.shape_type, shape.z) != layer.key {
+            continue;
+        }
+        match file {
+            File::Dbf(_) => layer.table.write_record(writer, &feature.attributes),
+            _ => records.write(writer, &shape),
+        }
+        .map_err(SinkError::file_writer)?;
+    }
+    if let File::Dbf(_) = file {
+        layer
+            .table
+            .write_end(writer)
+            .map_err(SinkError::file_writer)?;
+    }
+    Ok(())
+}
+
+/// Writes the files as the entries of a `.zip` archive, returning the writer of the archive.
+fn zip<W: Write + Unpin>(
+    files: &[(String, File)],
+    features: &FeatureBuffer,
+    writer: W,
+) -> Result<W, SinkError> {
+    let mut zip = ZipFileWriter::new(AllowStdIo::new(writer));
+    for (name, file) in files {
+        let entry = ZipEntryBuilder::new(name.clone().into(), Compression::Deflate);
+        let mut entry_writer =
+            EntryWriter(block_on(zip.write_entry_stream(entry)).map_err(SinkError::file_writer)?);
+        write_file(file, features, &mut entry_writer)?;
+        block_on(entry_writer.0.close()).map_err(SinkError::file_writer)?;
+    }
+    block_on(zip.close())
+        .map(AllowStdIo::into_inner)
+        .map_err(SinkError::file_writer)
+}
+
+/// Writes to an entry of an archive from synchronous code. The archive is written to a
+/// synchronous writer, so each write completes at once.
+struct EntryWriter<'a, W: AsyncWrite + Unpin>(EntryStreamWriter<'a, W>);
+
+impl<W: AsyncWrite + Unpin> Write for EntryWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        block_on(self.0.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        block_on(self.0.flush())
+    }
+}
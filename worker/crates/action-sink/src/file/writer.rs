@@ -1,13 +1,14 @@
-use std::collections::HashMap;
+use std::io::{BufWriter, Write};
 use std::{str::FromStr, sync::Arc};
 
-use bytes::Bytes;
 use reearth_flow_common::csv::Delimiter;
 use reearth_flow_runtime::errors::BoxedError;
 use reearth_flow_runtime::event::EventHub;
 use reearth_flow_runtime::executor_operation::{ExecutorContext, NodeContext};
-use reearth_flow_runtime::node::{Port, Sink, SinkFactory, DEFAULT_PORT};
+use reearth_flow_runtime::node::{Sink, SinkFactory, DEFAULT_PORT};
+use reearth_flow_runtime::spill::{FeatureBuffer, FeatureBufferState};
 use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_storage::storage_sync::StorageWriter;
 use reearth_flow_types::{AttributeValue, Expr, Feature};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -18,57 +19,34 @@ use serde_json::Value;
 use crate::errors::SinkError;
 
 use super::excel::write_excel;
+use super::flatgeobuf::write_flatgeobuf;
+use super::geojson::write_geojson;
+use super::gpkg::write_gpkg;
+use super::parquet::{write_parquet, ParquetCompression};
+use super::shapefile::write_shapefile;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, SinkFactory)]
+#[factory(
+    name = "FileWriter",
+    description = "Writes features to a file",
+    categories = ["File"],
+    input_ports = [DEFAULT_PORT],
+    param = FileWriterParam,
+    error = SinkError::BuildFactory,
+)]
 pub struct FileWriterSinkFactory;
 
-impl SinkFactory for FileWriterSinkFactory {
-    fn name(&self) -> &str {
-        "FileWriter"
-    }
-
-    fn description(&self) -> &str {
-        "Writes features to a file"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(FileWriterParam))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["File"]
-    }
-
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-
-    fn prepare(&self) -> Result<(), BoxedError> {
-        Ok(())
-    }
-
-    fn build(
+impl FileWriterSinkFactory {
+    fn build_sink(
         &self,
-        _ctx: NodeContext,
+        ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: FileWriterParam,
     ) -> Result<Box<dyn Sink>, BoxedError> {
-        let params = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with)
-                .map_err(|e| SinkError::BuildFactory(format!("Failed to serialize with: {}", e)))?;
-            serde_json::from_value(value).map_err(|e| {
-                SinkError::BuildFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(
-                SinkError::BuildFactory("Missing required parameter `with`".to_string()).into(),
-            );
-        };
-
         let sink = FileWriter {
             params,
-            buffer: Vec::new(),
+            buffer: FeatureBuffer::new(Arc::clone(&ctx.spill_manager)),
         };
         Ok(Box::new(sink))
     }
@@ -79,12 +57,21 @@ impl SinkFactory for FileWriterSinkFactory {
 pub struct FileWriterParam {
     format: Format,
     pub(super) output: Expr,
+    /// Encoding of the attributes of shapefiles, UTF-8 by default
+    encoding: Option<String>,
+    /// Expression naming the layer of each feature, for formats with layers. The name of the
+    /// output file by default
+    layer: Option<Expr>,
+    /// Maximum number of rows of the row groups of Parquet files
+    row_group_size: Option<usize>,
+    /// Compression of Parquet files, Snappy by default
+    compression: Option<ParquetCompression>,
 }
 
 #[derive(Debug, Clone)]
 pub struct FileWriter {
     pub(super) params: FileWriterParam,
-    pub(super) buffer: Vec<Feature>,
+    pub(super) buffer: FeatureBuffer,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
@@ -97,12 +84,26 @@ enum Format {
     Json,
     #[serde(rename = "excel")]
     Excel,
+    #[serde(rename = "geojson")]
+    GeoJson,
+    #[serde(rename = "geojsonseq")]
+    GeoJsonSeq,
+    #[serde(rename = "shapefile")]
+    Shapefile,
+    #[serde(rename = "gpkg")]
+    Gpkg,
+    #[serde(rename = "flatgeobuf")]
+    FlatGeobuf,
+    #[serde(rename = "parquet")]
+    Parquet,
 }
 
 impl Sink for FileWriter {
     fn initialize(&self, _ctx: NodeContext) {}
     fn process(&mut self, ctx: ExecutorContext) -> Result<(), BoxedError> {
-        self.buffer.push(ctx.feature);
+        self.buffer
+            .push(ctx.feature)
+            .map_err(SinkError::file_writer)?;
         Ok(())
     }
     fn finish(&self, ctx: NodeContext) -> Result<(), BoxedError> {
@@ -116,45 +117,149 @@ impl Sink for FileWriter {
             Format::Json => write_json(&output, &self.buffer, storage_resolver),
             Format::Csv => write_csv(&output, &self.buffer, Delimiter::Comma, storage_resolver),
             Format::Tsv => write_csv(&output, &self.buffer, Delimiter::Tab, storage_resolver),
-            Format::Excel => write_excel(&output, &self.buffer, storage_resolver),
+            Format::Excel => match self.buffer.iter().collect::<Result<Vec<_>, _>>() {
+                Ok(features) => write_excel(&output, &features, storage_resolver),
+                Err(e) => Err(SinkError::file_writer(e)),
+            },
+            Format::GeoJson => write_geojson(&output, &self.buffer, false, storage_resolver),
+            Format::GeoJsonSeq => write_geojson(&output, &self.buffer, true, storage_resolver),
+            Format::Shapefile => write_shapefile(
+                &output,
+                &self.buffer,
+                self.params.encoding.as_deref(),
+                storage_resolver,
+            ),
+            Format::Gpkg => self.layer_name(&ctx, &output).and_then(|layer_name| {
+                write_gpkg(&output, &self.buffer, layer_name, storage_resolver)
+            }),
+            Format::FlatGeobuf => write_flatgeobuf(&output, &self.buffer, storage_resolver),
+            Format::Parquet => write_parquet(
+                &output,
+                &self.buffer,
+                self.params.row_group_size,
+                self.params.compression.unwrap_or_default(),
+                storage_resolver,
+            ),
         };
         match result {
             Ok(_) => Ok(()),
             Err(e) => Err(Box::new(e)),
         }
     }
+
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        let state = serde_json::to_vec(&self.buffer.state()).map_err(SinkError::file_writer)?;
+        Ok(Some(state))
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), BoxedError> {
+        let state: FeatureBufferState =
+            serde_json::from_slice(state).map_err(SinkError::file_writer)?;
+        self.buffer.restore(state).map_err(SinkError::file_writer)?;
+        Ok(())
+    }
+}
+
+impl FileWriter {
+    /// Returns the function naming the layer of a feature with the `layer` expression. The
+    /// stem of the output file names the layer when there is no expression or it evaluates to
+    /// an empty string.
+    fn layer_name(
+        &self,
+        ctx: &NodeContext,
+        output: &Uri,
+    ) -> Result<impl Fn(&Feature) -> Result<String, SinkError>, SinkError> {
+        let default = output
+            .path()
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("features")
+            .to_string();
+        let ast = match &self.params.layer {
+            Some(layer) => Some(ctx.expr_engine.compile(layer.as_ref()).map_err(|e| {
+                SinkError::FileWriter(format!("Failed to compile layer expression: {}", e))
+            })?),
+            None => None,
+        };
+        let expr_engine = Arc::clone(&ctx.expr_engine);
+        Ok(move |feature: &Feature| {
+            let Some(ast) = &ast else {
+                return Ok(default.clone());
+            };
+            let scope = feature.new_scope(Arc::clone(&expr_engine));
+            let name = scope.eval_ast::<String>(ast).map_err(|e| {
+                SinkError::FileWriter(format!("Failed to evaluate layer expression: {}", e))
+            })?;
+            Ok(if name.is_empty() {
+                default.clone()
+            } else {
+                name
+            })
+        })
+    }
+}
+
+/// Opens the output file, written in chunks as the features are read from the buffer.
+pub(super) fn create_output(
+    output: &Uri,
+    storage_resolver: &StorageResolver,
+) -> Result<BufWriter<StorageWriter>, SinkError> {
+    let storage = storage_resolver
+        .resolve(output)
+        .map_err(|e| SinkError::FileWriter(format!("{:?}", e)))?;
+    let writer = storage
+        .writer_sync(output.path().as_path())
+        .map_err(|e| SinkError::FileWriter(format!("{:?}", e)))?;
+    Ok(BufWriter::new(writer))
+}
+
+/// Completes the output file.
+pub(super) fn close_output(writer: BufWriter<StorageWriter>) -> Result<(), SinkError> {
+    writer
+        .into_inner()
+        .map_err(SinkError::file_writer)?
+        .close()
+        .map_err(|e| SinkError::FileWriter(format!("{:?}", e)))
 }
 
 fn write_json(
     output: &Uri,
-    features: &[Feature],
+    features: &FeatureBuffer,
     storage_resolver: Arc<StorageResolver>,
 ) -> Result<(), crate::errors::SinkError> {
-    let json_value: serde_json::Value = features.into();
-    let storage = storage_resolver
-        .resolve(output)
-        .map_err(|e| crate::errors::SinkError::FileWriter(format!("{:?}", e)))?;
-    storage
-        .put_sync(output.path().as_path(), Bytes::from(json_value.to_string()))
-        .map_err(|e| crate::errors::SinkError::FileWriter(format!("{:?}", e)))?;
-    Ok(())
+    let mut writer = create_output(output, &storage_resolver)?;
+    writer.write_all(b"[").map_err(SinkError::file_writer)?;
+    for (index, feature) in features.iter().enumerate() {
+        let feature = feature.map_err(crate::errors::SinkError::file_writer)?;
+        if index > 0 {
+            writer.write_all(b",").map_err(SinkError::file_writer)?;
+        }
+        serde_json::to_writer(&mut writer, &serde_json::Value::from(feature))
+            .map_err(crate::errors::SinkError::file_writer)?;
+    }
+    writer.write_all(b"]").map_err(SinkError::file_writer)?;
+    close_output(writer)
 }
 
 fn write_csv(
     output: &Uri,
-    features: &[Feature],
+    features: &FeatureBuffer,
     delimiter: Delimiter,
     storage_resolver: Arc<StorageResolver>,
 ) -> Result<(), crate::errors::SinkError> {
-    if features.is_empty() {
+    let mut rows = features.iter().map(|feature| {
+        feature
+            .map(AttributeValue::from)
+            .map_err(crate::errors::SinkError::file_writer)
+    });
+    let Some(first) = rows.next().transpose()? else {
         return Ok(());
-    }
+    };
     let mut wtr = csv::WriterBuilder::new()
         .delimiter(delimiter.into())
         .quote_style(csv::QuoteStyle::NonNumeric)
-        .from_writer(vec![]);
-    let rows: Vec<AttributeValue> = features.iter().map(|f| f.clone().into()).collect();
-    let mut fields = get_fields(rows.first().unwrap());
+        .from_writer(create_output(output, &storage_resolver)?);
+    let mut fields = get_fields(&first);
 
     if let Some(ref mut fields) = fields {
         // Remove _id field
@@ -166,7 +271,8 @@ fn write_csv(
         }
     }
 
-    for row in rows {
+    for row in std::iter::once(Ok(first)).chain(rows) {
+        let row = row?;
         match fields {
             Some(ref fields) if !fields.is_empty() => {
                 let values = get_row_values(&row, &fields.clone())?;
@@ -196,20 +302,10 @@ fn write_csv(
             },
         }
     }
-    wtr.flush()
+    let writer = wtr
+        .into_inner()
         .map_err(|e| crate::errors::SinkError::FileWriter(format!("{:?}", e)))?;
-    let data = String::from_utf8(
-        wtr.into_inner()
-            .map_err(|e| crate::errors::SinkError::FileWriter(format!("{:?}", e)))?,
-    )
-    .map_err(|e| crate::errors::SinkError::FileWriter(format!("{:?}", e)))?;
-    let storage = storage_resolver
-        .resolve(output)
-        .map_err(|e| crate::errors::SinkError::FileWriter(format!("{:?}", e)))?;
-    storage
-        .put_sync(output.path().as_path(), Bytes::from(data))
-        .map_err(|e| crate::errors::SinkError::FileWriter(format!("{:?}", e)))?;
-    Ok(())
+    close_output(writer)
 }
 
 fn get_fields(row: &AttributeValue) -> Option<Vec<String>> {
@@ -235,3 +331,49 @@ fn get_row_values(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn build(with: Option<HashMap<String, Value>>) -> Result<Box<dyn Sink>, BoxedError> {
+        FileWriterSinkFactory.build(
+            NodeContext::default(),
+            EventHub::new(1),
+            "FileWriter".to_string(),
+            with,
+        )
+    }
+
+    #[test]
+    fn test_factory() {
+        let factory = FileWriterSinkFactory;
+        assert_eq!(factory.name(), "FileWriter");
+        assert_eq!(factory.categories(), &["File"]);
+        assert_eq!(factory.get_input_ports(), vec![DEFAULT_PORT.clone()]);
+        assert!(factory.parameter_schema().is_some());
+        let with = HashMap::from([
+            ("format".to_string(), Value::String("csv".to_string())),
+            (
+                "output".to_string(),
+                Value::String("\"out.csv\"".to_string()),
+            ),
+        ]);
+        assert!(build(Some(with)).is_ok());
+    }
+
+    #[test]
+    fn test_factory_rejects_invalid_parameters() {
+        let missing_output =
+            HashMap::from([("format".to_string(), Value::String("csv".to_string()))]);
+        for with in [None, Some(missing_output)] {
+            let error = build(with).err().unwrap();
+            assert!(matches!(
+                error.downcast_ref::<SinkError>(),
+                Some(SinkError::BuildFactory(_))
+            ));
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, str::FromStr, sync::Arc};
+use std::{str::FromStr, sync::Arc};
 
 use async_zip::base::read::mem::ZipFileReader;
 use futures::AsyncReadExt;
@@ -14,55 +14,31 @@ use reearth_flow_storage::storage::Storage;
 use reearth_flow_types::{AttributeValue, Expr, Feature, FilePath};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use tokio::sync::mpsc::Sender;
 
-use crate::errors::SourceError;
+use crate::{errors::SourceError, resume};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, SourceFactory)]
+#[factory(
+    name = "FilePathExtractor",
+    description = "Extracts files from a directory or an archive",
+    categories = ["File"],
+    output_ports = [DEFAULT_PORT],
+    param = FilePathExtractor,
+    error = SourceError::FilePathExtractorFactory,
+)]
 pub struct FilePathExtractorFactory;
 
-impl SourceFactory for FilePathExtractorFactory {
-    fn name(&self) -> &str {
-        "FilePathExtractor"
-    }
-
-    fn description(&self) -> &str {
-        "Extracts files from a directory or an archive"
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(FilePathExtractor))
-    }
-
-    fn categories(&self) -> &[&'static str] {
-        &["File"]
-    }
-
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
-    }
-    fn build(
+impl FilePathExtractorFactory {
+    fn build_source(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
-        _state: Option<Vec<u8>>,
+        mut processor: FilePathExtractor,
+        state: Option<Vec<u8>>,
     ) -> Result<Box<dyn Source>, BoxedError> {
-        let processor: FilePathExtractor = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(|e| {
-                SourceError::FilePathExtractorFactory(format!("Failed to serialize with: {}", e))
-            })?;
-            serde_json::from_value(value).map_err(|e| {
-                SourceError::FilePathExtractorFactory(format!("Failed to deserialize with: {}", e))
-            })?
-        } else {
-            return Err(SourceError::FilePathExtractorFactory(
-                "Missing required parameter `with`".to_string(),
-            )
-            .into());
-        };
+        processor.sent = resume::deserialize(state)?;
         Ok(Box::new(processor))
     }
 }
@@ -157,6 +133,9 @@ pub async fn extract(
 pub struct FilePathExtractor {
     source_dataset: Expr,
     extract_archive: bool,
+    /// Number of paths sent, restored when resuming.
+    #[serde(skip)]
+    sent: u64,
 }
 
 #[async_trait::async_trait]
@@ -164,13 +143,28 @@ impl Source for FilePathExtractor {
     async fn initialize(&self, _ctx: NodeContext) {}
 
     async fn serialize_state(&self) -> Result<Vec<u8>, BoxedError> {
-        Ok(vec![])
+        resume::serialize(self.sent)
     }
 
     async fn start(
         &mut self,
         ctx: NodeContext,
         sender: Sender<(Port, IngestionMessage)>,
+    ) -> Result<(), BoxedError> {
+        let extractor = self.clone();
+        resume::forward(&mut self.sent, &sender, |sender| async move {
+            extractor.extract_paths(ctx, sender).await
+        })
+        .await
+    }
+}
+
+impl FilePathExtractor {
+    /// Sends the paths of the files, in the order of the archive or of their names.
+    async fn extract_paths(
+        &self,
+        ctx: NodeContext,
+        sender: Sender<(Port, IngestionMessage)>,
     ) -> Result<(), BoxedError> {
         let source_dataset = get_expr_path(&self.source_dataset, ctx.expr_engine.clone())?;
         if self.is_extractable_archive(&source_dataset) {
@@ -203,10 +197,11 @@ impl Source for FilePathExtractor {
                 .storage_resolver
                 .resolve(&source_dataset)
                 .map_err(|e| crate::errors::SourceError::FilePathExtractor(format!("{:?}", e)))?;
-            let entries = storage
+            let mut entries = storage
                 .list_with_result(Some(source_dataset.path().as_path()), true)
                 .await
                 .map_err(|e| crate::errors::SourceError::FilePathExtractor(format!("{:?}", e)))?;
+            entries.sort_by_key(|entry| entry.to_string());
             for entry in entries {
                 let attribute_value =
                     AttributeValue::try_from(FilePath::try_from(entry).unwrap_or_default())?;
@@ -234,9 +229,7 @@ impl Source for FilePathExtractor {
         }
         Ok(())
     }
-}
 
-impl FilePathExtractor {
     fn is_extractable_archive(&self, path: &Uri) -> bool {
         self.extract_archive
             && !path.is_dir()
@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use flatgeobuf::{FallibleStreamingIterator, FgbFeature, FgbReader, Header, HttpFgbReader};
+use geozero::{ColumnValue, CoordDimensions, FeatureProperties, PropertyProcessor, ToWkb};
+use http_range_client::{AsyncBufferedHttpRangeClient, AsyncHttpRangeClient, HttpError};
+use nusamai_projection::crs::EpsgCode;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::node::{IngestionMessage, Port, DEFAULT_PORT};
+use reearth_flow_storage::{resolve::StorageResolver, storage::Storage};
+use reearth_flow_types::wkb::{geometry_value_from_wkb, geometry_value_to_wkb};
+use reearth_flow_types::{Attribute, AttributeValue, Feature, Geometry};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Number;
+use tokio::sync::mpsc::Sender;
+
+/// The magic bytes a FlatGeobuf file starts with, read to tell whether the storage serves
+/// ranges.
+const MAGIC_LEN: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FlatGeobufPropertySchema {
+    /// Extent of the features to read, as `[minX, minY, maxX, maxY]` in the coordinate system of
+    /// the file. All the features by default
+    pub(super) bbox: Option<[f64; 4]>,
+}
+
+/// Reads the features of a FlatGeobuf file.
+///
+/// With a bounding box, the index of the file is searched with range reads, so that only the
+/// nodes of the index and the features needed are fetched. The whole file is read when there
+/// is no bounding box or the storage does not serve ranges, and the features are filtered one
+/// by one when the file has no index.
+pub(crate) async fn read_flatgeobuf(
+    input_path: Uri,
+    props: &FlatGeobufPropertySchema,
+    storage_resolver: Arc<StorageResolver>,
+    sender: Sender<(Port, IngestionMessage)>,
+) -> Result<(), crate::errors::SourceError> {
+    let storage = storage_resolver
+        .resolve(&input_path)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let path = input_path.path();
+    let features = match props.bbox {
+        Some(bbox) if serves_ranges(&storage, &path).await? => {
+            read_ranges(storage, path, bbox).await?
+        }
+        bbox => read_file(&storage, &path, bbox).await?,
+    };
+    for feature in features {
+        sender
+            .send((
+                DEFAULT_PORT.clone(),
+                IngestionMessage::OperationEvent { feature },
+            ))
+            .await
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    }
+    Ok(())
+}
+
+/// Whether the storage serves ranges of the file. Only an unsupported or ignored range leads
+/// to reading the whole file, other errors are returned.
+async fn serves_ranges(storage: &Storage, path: &Path) -> Result<bool, crate::errors::SourceError> {
+    match storage.get_range(path, 0..MAGIC_LEN).await {
+        Ok(bytes) => Ok(bytes.len() == MAGIC_LEN),
+        Err(object_store::Error::NotSupported { .. }) => Ok(false),
+        Err(e) => Err(crate::errors::SourceError::FileReader(format!("{:?}", e))),
+    }
+}
+
+async fn read_file(
+    storage: &Storage,
+    path: &Path,
+    bbox: Option<[f64; 4]>,
+) -> Result<Vec<Feature>, crate::errors::SourceError> {
+    let result = storage
+        .get(path)
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let byte = result
+        .bytes()
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let reader = FgbReader::open(Cursor::new(byte))
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let header = reader.header();
+    let (epsg, has_z, indexed) = (epsg(&header), header.has_z(), header.index_node_size() > 0);
+    let mut iter = match bbox {
+        Some([min_x, min_y, max_x, max_y]) if indexed => {
+            reader.select_bbox(min_x, min_y, max_x, max_y)
+        }
+        _ => reader.select_all(),
+    }
+    .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let mut features = vec![];
+    while let Some(feature) = iter
+        .next()
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?
+    {
+        features.push(read_feature(feature, epsg, has_z)?);
+    }
+    Ok(filter(features, bbox.filter(|_| !indexed)))
+}
+
+/// Reads the features intersecting `bbox` with range reads of the index and the features.
+async fn read_ranges(
+    storage: Arc<Storage>,
+    path: PathBuf,
+    bbox: [f64; 4],
+) -> Result<Vec<Feature>, crate::errors::SourceError> {
+    let len = storage
+        .head(&path)
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?
+        .size;
+    let url = path.to_string_lossy().into_owned();
+    let client =
+        AsyncBufferedHttpRangeClient::with(StorageRangeClient { storage, path, len }, &url);
+    let reader = HttpFgbReader::new(client)
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let header = reader.header();
+    let (epsg, has_z, indexed) = (epsg(&header), header.has_z(), header.index_node_size() > 0);
+    let [min_x, min_y, max_x, max_y] = bbox;
+    let mut iter = if indexed {
+        reader.select_bbox(min_x, min_y, max_x, max_y).await
+    } else {
+        reader.select_all().await
+    }
+    .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let mut features = vec![];
+    while let Some(feature) = iter
+        .next()
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?
+    {
+        features.push(read_feature(feature, epsg, has_z)?);
+    }
+    Ok(filter(features, (!indexed).then_some(bbox)))
+}
+
+/// Serves the range requests of the FlatGeobuf reader from the storage. The URL is the path
+/// of the file, which is read from the storage it was resolved to. Ranges are cut at the end
+/// of the file, as HTTP servers do, since the reader asks for more than it needs.
+struct StorageRangeClient {
+    storage: Arc<Storage>,
+    path: PathBuf,
+    len: usize,
+}
+
+#[async_trait]
+impl AsyncHttpRangeClient for StorageRangeClient {
+    async fn get_range(&self, _url: &str, range: &str) -> http_range_client::Result<Bytes> {
+        let (start, end) = range
+            .strip_prefix("bytes=")
+            .and_then(|range| range.split_once('-'))
+            .and_then(|(start, end)| {
+                Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?))
+            })
+            .ok_or_else(|| HttpError::HttpError(format!("Invalid range: {}", range)))?;
+        self.storage
+            .get_range(&self.path, start..(end + 1).min(self.len))
+            .await
+            .map_err(|e| HttpError::HttpError(format!("{:?}", e)))
+    }
+
+    async fn head_response_header(
+        &self,
+        _url: &str,
+        _header: &str,
+    ) -> http_range_client::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+fn epsg(header: &Header) -> Option<EpsgCode> {
+    let crs = header.crs()?;
+    match crs.org() {
+        Some(org) if !org.eq_ignore_ascii_case("EPSG") => None,
+        _ => EpsgCode::try_from(crs.code()).ok().filter(|code| *code > 0),
+    }
+}
+
+fn read_feature(
+    feature: &FgbFeature,
+    epsg: Option<EpsgCode>,
+    has_z: bool,
+) -> Result<Feature, crate::errors::SourceError> {
+    let mut properties = Properties::default();
+    feature
+        .process_properties(&mut properties)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let mut result = Feature::new_with_attributes(properties.0);
+    if feature.geometry().is_some() {
+        let dims = if has_z {
+            CoordDimensions::xyz()
+        } else {
+            CoordDimensions::xy()
+        };
+        let wkb = feature
+            .to_wkb(dims)
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        let value = geometry_value_from_wkb(&wkb)
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        result.geometry = Some(Geometry { epsg, value });
+    }
+    Ok(result)
+}
+
+/// Keeps the features intersecting `bbox` if given, for files without an index.
+fn filter(features: Vec<Feature>, bbox: Option<[f64; 4]>) -> Vec<Feature> {
+    let Some([min_x, min_y, max_x, max_y]) = bbox else {
+        return features;
+    };
+    features
+        .into_iter()
+        .filter(|feature| {
+            feature
+                .geometry
+                .as_ref()
+                .and_then(|geometry| geometry_value_to_wkb(&geometry.value))
+                .and_then(|wkb| wkb.bbox)
+                .is_some_and(|[x0, y0, x1, y1]| {
+                    x0 <= max_x && min_x <= x1 && y0 <= max_y && min_y <= y1
+                })
+        })
+        .collect()
+}
+
+/// The attributes of a feature, typed by the columns of the file.
+#[derive(Default)]
+struct Properties(HashMap<Attribute, AttributeValue>);
+
+impl PropertyProcessor for Properties {
+    fn property(
+        &mut self,
+        _index: usize,
+        name: &str,
+        value: &ColumnValue,
+    ) -> geozero::error::Result<bool> {
+        let number =
+            |number: Option<Number>| number.map_or(AttributeValue::Null, AttributeValue::Number);
+        let value = match value {
+            ColumnValue::Byte(value) => number(Some((*value).into())),
+            ColumnValue::UByte(value) => number(Some((*value).into())),
+            ColumnValue::Bool(value) => AttributeValue::Bool(*value),
+            ColumnValue::Short(value) => number(Some((*value).into())),
+            ColumnValue::UShort(value) => number(Some((*value).into())),
+            ColumnValue::Int(value) => number(Some((*value).into())),
+            ColumnValue::UInt(value) => number(Some((*value).into())),
+            ColumnValue::Long(value) => number(Some((*value).into())),
+            ColumnValue::ULong(value) => number(Some((*value).into())),
+            ColumnValue::Float(value) => number(Number::from_f64(*value as f64)),
+            ColumnValue::Double(value) => number(Number::from_f64(*value)),
+            ColumnValue::String(value) => AttributeValue::String(value.to_string()),
+            ColumnValue::Json(value) => serde_json::from_str::<serde_json::Value>(value).map_or(
+                AttributeValue::String(value.to_string()),
+                AttributeValue::from,
+            ),
+            ColumnValue::DateTime(value) => value.parse().map_or(
+                AttributeValue::String(value.to_string()),
+                AttributeValue::DateTime,
+            ),
+            ColumnValue::Binary(value) => AttributeValue::Bytes(value.to_vec().into()),
+        };
+        self.0.insert(Attribute::new(name.to_string()), value);
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use reearth_flow_geometry::types::{
+        coordinate::Coordinate, geometry::Geometry2D, geometry::Geometry3D,
+        line_string::LineString, polygon::Polygon,
+    };
+    use reearth_flow_types::GeometryValue;
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests::utils::{feature_channel, received, write_features};
+
+    fn point(x: f64, y: f64, id: i64) -> Feature {
+        let mut feature = Feature::new_with_attributes(HashMap::from([(
+            Attribute::new("id".to_string()),
+            AttributeValue::Number(Number::from(id)),
+        )]));
+        feature.geometry = Some(Geometry {
+            epsg: Some(6668),
+            value: GeometryValue::FlowGeometry2D(Geometry2D::Point(Coordinate::new_(x, y).into())),
+        });
+        feature
+    }
+
+    fn write(output: &str, features: Vec<Feature>, storage_resolver: &Arc<StorageResolver>) {
+        write_features(
+            json!({
+                "format": "flatgeobuf",
+                "output": format!("\"{}\"", output),
+            }),
+            features,
+            storage_resolver,
+        );
+    }
+
+    async fn read(
+        input: &str,
+        bbox: Option<[f64; 4]>,
+        storage_resolver: &Arc<StorageResolver>,
+    ) -> Result<Vec<Feature>, crate::errors::SourceError> {
+        let (sender, receiver) = feature_channel();
+        read_flatgeobuf(
+            Uri::from_str(input).unwrap(),
+            &FlatGeobufPropertySchema { bbox },
+            Arc::clone(storage_resolver),
+            sender,
+        )
+        .await?;
+        Ok(received(receiver))
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let ring = LineString::new(
+            [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 0.0)]
+                .iter()
+                .map(|(x, y)| Coordinate::new__(*x, *y, 10.0))
+                .collect(),
+        );
+        let mut feature = Feature::new_with_attributes(HashMap::from([
+            (
+                Attribute::new("name".to_string()),
+                AttributeValue::String("庁舎".to_string()),
+            ),
+            (
+                Attribute::new("height".to_string()),
+                AttributeValue::Number(Number::from_f64(12.5).unwrap()),
+            ),
+            (
+                Attribute::new("tags".to_string()),
+                AttributeValue::Array(vec![AttributeValue::String("a".to_string())]),
+            ),
+            (
+                Attribute::new("updated".to_string()),
+                AttributeValue::DateTime("2024-04-01T00:00:00Z".parse().unwrap()),
+            ),
+        ]));
+        feature.geometry = Some(Geometry {
+            epsg: Some(6697),
+            value: GeometryValue::FlowGeometry3D(Geometry3D::Polygon(Polygon::new(ring, vec![]))),
+        });
+        let other = Feature::new_with_attributes(HashMap::from([(
+            Attribute::new("height".to_string()),
+            AttributeValue::Number(Number::from(3)),
+        )]));
+        write(
+            "ram:///fgb/buildings.fgb",
+            vec![feature, other],
+            &storage_resolver,
+        );
+
+        let features = read("ram:///fgb/buildings.fgb", None, &storage_resolver)
+            .await
+            .unwrap();
+        assert_eq!(features.len(), 2);
+        let feature = features
+            .iter()
+            .find(|feature| feature.geometry.is_some())
+            .unwrap();
+        assert_eq!(
+            feature.get(&"name"),
+            Some(&AttributeValue::String("庁舎".to_string()))
+        );
+        assert_eq!(
+            feature.get(&"height"),
+            Some(&AttributeValue::Number(Number::from_f64(12.5).unwrap()))
+        );
+        assert_eq!(
+            feature.get(&"tags"),
+            Some(&AttributeValue::Array(vec![AttributeValue::String(
+                "a".to_string()
+            )]))
+        );
+        assert!(matches!(
+            feature.get(&"updated"),
+            Some(AttributeValue::DateTime(_))
+        ));
+        let geometry = feature.geometry.as_ref().unwrap();
+        assert_eq!(geometry.epsg, Some(6697));
+        let GeometryValue::FlowGeometry3D(Geometry3D::Polygon(polygon)) = &geometry.value else {
+            panic!("Expected a polygon, got {:?}", geometry.value);
+        };
+        assert_eq!(polygon.exterior().0.len(), 4);
+        assert_eq!(polygon.exterior().0[1].z, 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_bbox_search() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let features = (0..100)
+            .map(|i| point((i % 10) as f64, (i / 10) as f64, i))
+            .collect::<Vec<_>>();
+        write("ram:///fgb/points.fgb", features, &storage_resolver);
+        let ids = |features: Vec<Feature>| {
+            let mut ids = features
+                .iter()
+                .map(|feature| feature.get(&"id").cloned().unwrap())
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        let expected = [33, 34, 43, 44, 53, 54]
+            .into_iter()
+            .map(|id| AttributeValue::Number(Number::from(id)))
+            .collect::<Vec<_>>();
+
+        // The storage serves ranges, so only the nodes of the index and the features needed
+        // are read.
+        let features = read(
+            "ram:///fgb/points.fgb",
+            Some([2.5, 2.5, 4.5, 5.5]),
+            &storage_resolver,
+        )
+        .await
+        .unwrap();
+        assert!(features
+            .iter()
+            .all(|feature| feature.geometry.as_ref().unwrap().epsg == Some(6668)));
+        assert_eq!(ids(features), expected);
+
+        let features = read("ram:///fgb/points.fgb", None, &storage_resolver)
+            .await
+            .unwrap();
+        assert_eq!(features.len(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_not_hidden_by_the_fallback() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let result = read(
+            "ram:///fgb/missing.fgb",
+            Some([0.0, 0.0, 1.0, 1.0]),
+            &storage_resolver,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use geojson::GeoJson;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::node::{IngestionMessage, Port, DEFAULT_PORT};
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::geojson::features_from_geojson;
+use tokio::sync::mpsc::Sender;
+
+/// Reads a GeoJSON document, or a GeoJSON text sequence with one document per line.
+pub(crate) async fn read_geojson(
+    input_path: Uri,
+    storage_resolver: Arc<StorageResolver>,
+    sender: Sender<(Port, IngestionMessage)>,
+) -> Result<(), crate::errors::SourceError> {
+    let storage = storage_resolver
+        .resolve(&input_path)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let result = storage
+        .get(input_path.path().as_path())
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let byte = result
+        .bytes()
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let text = String::from_utf8(byte.to_vec())
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let documents = match text.parse::<GeoJson>() {
+        Ok(document) => vec![document],
+        Err(e) => parse_sequence(&text)
+            .map_err(|_| crate::errors::SourceError::FileReader(format!("{:?}", e)))?,
+    };
+    for document in documents {
+        let features = features_from_geojson(document)
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        for feature in features {
+            sender
+                .send((
+                    DEFAULT_PORT.clone(),
+                    IngestionMessage::OperationEvent { feature },
+                ))
+                .await
+                .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Parses newline-delimited GeoJSON, where each line may start with a record separator
+/// (RFC 8142).
+fn parse_sequence(text: &str) -> Result<Vec<GeoJson>, geojson::Error> {
+    text.lines()
+        .map(|line| line.trim_matches(|c: char| c == '\u{1e}' || c.is_whitespace()))
+        .filter(|line| !line.is_empty())
+        .map(str::parse::<GeoJson>)
+        .collect()
+}
@@ -0,0 +1,345 @@
+use std::{collections::HashMap, io::Write, sync::Arc};
+
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::node::{IngestionMessage, Port, DEFAULT_PORT};
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::gpkg::geometry_value_from_gpkg;
+use reearth_flow_types::{Attribute, AttributeValue, Feature, Geometry};
+use rusqlite::{types::ValueRef, Connection, OpenFlags};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Number;
+use tokio::sync::mpsc::Sender;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GpkgPropertySchema {
+    /// Names of the feature tables to read, all of them by default
+    pub(super) layers: Option<Vec<String>>,
+}
+
+/// Reads the feature tables of a GeoPackage. The database is copied to a temporary file for
+/// SQLite to open it.
+pub(crate) async fn read_gpkg(
+    input_path: Uri,
+    props: &GpkgPropertySchema,
+    storage_resolver: Arc<StorageResolver>,
+    sender: Sender<(Port, IngestionMessage)>,
+) -> Result<(), crate::errors::SourceError> {
+    let storage = storage_resolver
+        .resolve(&input_path)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let result = storage
+        .get(input_path.path().as_path())
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let byte = result
+        .bytes()
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let mut file = tempfile::NamedTempFile::new()
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    file.write_all(&byte)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let connection = Connection::open_with_flags(file.path(), OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let tables = feature_tables(&connection)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let tables = match &props.layers {
+        Some(layers) => layers
+            .iter()
+            .map(|layer| {
+                tables
+                    .iter()
+                    .find(|table| table.eq_ignore_ascii_case(layer))
+                    .cloned()
+                    .ok_or(crate::errors::SourceError::FileReader(format!(
+                        "No feature table named {}",
+                        layer
+                    )))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => tables,
+    };
+    for table in tables {
+        let features = read_table(&connection, &table)
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        for feature in features {
+            sender
+                .send((
+                    DEFAULT_PORT.clone(),
+                    IngestionMessage::OperationEvent { feature },
+                ))
+                .await
+                .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        }
+    }
+    Ok(())
+}
+
+fn feature_tables(connection: &Connection) -> rusqlite::Result<Vec<String>> {
+    let mut statement = connection.prepare(
+        "SELECT table_name FROM gpkg_contents WHERE data_type = 'features' ORDER BY table_name",
+    )?;
+    let tables = statement
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(tables)
+}
+
+/// Reads the rows of a feature table, without its primary key. Boolean and date columns are
+/// read by their declared type, since SQLite stores them as integers and text.
+fn read_table(connection: &Connection, table: &str) -> rusqlite::Result<Vec<Feature>> {
+    let (geometry_column, organization, code) = connection.query_row(
+        "SELECT g.column_name, s.organization, s.organization_coordsys_id \
+         FROM gpkg_geometry_columns g JOIN gpkg_spatial_ref_sys s ON g.srs_id = s.srs_id \
+         WHERE g.table_name = ?1",
+        [table],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        },
+    )?;
+    let epsg = if organization.eq_ignore_ascii_case("EPSG") {
+        u16::try_from(code).ok()
+    } else {
+        None
+    };
+    let mut statement = connection.prepare(&format!("PRAGMA table_info({})", quote(table)))?;
+    let columns = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?.to_ascii_uppercase(),
+                row.get::<_, i64>(5)? > 0,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|(_, _, primary_key)| !primary_key)
+        .map(|(name, declared_type, _)| (name, declared_type))
+        .collect::<Vec<_>>();
+    if columns.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut statement = connection.prepare(&format!(
+        "SELECT {} FROM {}",
+        columns
+            .iter()
+            .map(|(name, _)| quote(name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        quote(table)
+    ))?;
+    let mut rows = statement.query([])?;
+    let mut features = vec![];
+    while let Some(row) = rows.next()? {
+        let mut attributes = HashMap::new();
+        let mut geometry = None;
+        for (index, (name, declared_type)) in columns.iter().enumerate() {
+            let value = row.get_ref(index)?;
+            if name.eq_ignore_ascii_case(&geometry_column) {
+                if let ValueRef::Blob(blob) = value {
+                    let value = geometry_value_from_gpkg(blob).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            index,
+                            rusqlite::types::Type::Blob,
+                            Box::new(e),
+                        )
+                    })?;
+                    geometry = Some(Geometry { epsg, value });
+                }
+                continue;
+            }
+            attributes.insert(
+                Attribute::new(name.clone()),
+                attribute_value(value, declared_type),
+            );
+        }
+        let mut feature = Feature::new_with_attributes(attributes);
+        feature.geometry = geometry;
+        features.push(feature);
+    }
+    Ok(features)
+}
+
+fn attribute_value(value: ValueRef, declared_type: &str) -> AttributeValue {
+    match value {
+        ValueRef::Null => AttributeValue::Null,
+        ValueRef::Integer(value) if declared_type == "BOOLEAN" => AttributeValue::Bool(value != 0),
+        ValueRef::Integer(value) => AttributeValue::Number(Number::from(value)),
+        ValueRef::Real(value) => {
+            Number::from_f64(value).map_or(AttributeValue::Null, AttributeValue::Number)
+        }
+        ValueRef::Text(text) => {
+            let text = String::from_utf8_lossy(text).into_owned();
+            match declared_type {
+                "DATETIME" => text
+                    .parse()
+                    .map_or(AttributeValue::String(text), AttributeValue::DateTime),
+                _ => AttributeValue::String(text),
+            }
+        }
+        ValueRef::Blob(blob) => AttributeValue::Bytes(blob.to_vec().into()),
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use reearth_flow_geometry::types::{
+        coordinate::Coordinate, geometry::Geometry2D, line_string::LineString, polygon::Polygon,
+    };
+    use reearth_flow_types::GeometryValue;
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests::utils::{feature_channel, received, write_features};
+
+    fn feature(kind: &str, geometry: Geometry2D) -> Feature {
+        let mut feature = Feature::new_with_attributes(HashMap::from([(
+            Attribute::new("kind"),
+            AttributeValue::String(kind.to_string()),
+        )]));
+        feature.geometry = Some(Geometry {
+            epsg: Some(6677),
+            value: GeometryValue::FlowGeometry2D(geometry),
+        });
+        feature
+    }
+
+    fn point(x: f64, y: f64) -> Geometry2D {
+        Geometry2D::Point(Coordinate::new_(x, y).into())
+    }
+
+    async fn read(
+        layers: Option<Vec<String>>,
+        storage_resolver: &Arc<StorageResolver>,
+    ) -> Vec<Feature> {
+        let (sender, receiver) = feature_channel();
+        read_gpkg(
+            Uri::from_str("ram:///gpkg/layers.gpkg").unwrap(),
+            &GpkgPropertySchema { layers },
+            Arc::clone(storage_resolver),
+            sender,
+        )
+        .await
+        .unwrap();
+        received(receiver)
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let square = Polygon::new(
+            LineString::new(vec![
+                Coordinate::new_(0.0, 0.0),
+                Coordinate::new_(10.0, 0.0),
+                Coordinate::new_(10.0, 10.0),
+                Coordinate::new_(0.0, 10.0),
+                Coordinate::new_(0.0, 0.0),
+            ]),
+            vec![],
+        );
+        write_features(
+            json!({
+                "format": "gpkg",
+                "output": "\"ram:///gpkg/layers.gpkg\"",
+                "layer": r#"env.get("__value").kind"#,
+            }),
+            vec![
+                feature("station", point(1.0, 2.0)),
+                feature("", Geometry2D::Polygon(square)),
+                feature("station", point(3.0, 4.0)),
+            ],
+            &storage_resolver,
+        );
+
+        let features = read(None, &storage_resolver).await;
+        let kinds = features
+            .iter()
+            .map(|feature| feature.get(&"kind").cloned())
+            .collect::<Vec<_>>();
+        let kind = |kind: &str| Some(AttributeValue::String(kind.to_string()));
+        // The features without a layer name go to the layer named after the file.
+        assert_eq!(kinds, vec![kind(""), kind("station"), kind("station")]);
+        assert!(features
+            .iter()
+            .all(|feature| feature.geometry.as_ref().unwrap().epsg == Some(6677)));
+
+        let features = read(Some(vec!["station".to_string()]), &storage_resolver).await;
+        assert_eq!(features.len(), 2);
+        let GeometryValue::FlowGeometry2D(geometry) = &features[1].geometry.as_ref().unwrap().value
+        else {
+            panic!("Expected a 2D geometry");
+        };
+        assert_eq!(geometry, &point(3.0, 4.0));
+
+        let uri = Uri::from_str("ram:///gpkg/layers.gpkg").unwrap();
+        let storage = storage_resolver.resolve(&uri).unwrap();
+        let bytes = storage.get_sync(uri.path().as_path()).unwrap();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&bytes).unwrap();
+        let connection = Connection::open(file.path()).unwrap();
+        let srs = connection
+            .prepare(
+                "SELECT srs_id, organization, definition FROM gpkg_spatial_ref_sys ORDER BY srs_id",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            srs.iter().map(|(id, _, _)| *id).collect::<Vec<_>>(),
+            vec![-1, 0, 4326, 6677]
+        );
+        let (_, organization, definition) = &srs[3];
+        assert_eq!(organization, "EPSG");
+        assert!(definition.starts_with(r#"PROJCS["JGD_2011_Japan_Zone_9""#));
+
+        let extents = connection
+            .prepare("SELECT id, minx, maxx, miny, maxy FROM rtree_station_geom ORDER BY id")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    [
+                        row.get::<_, f64>(1)?,
+                        row.get::<_, f64>(2)?,
+                        row.get::<_, f64>(3)?,
+                        row.get::<_, f64>(4)?,
+                    ],
+                ))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            extents,
+            vec![(1, [1.0, 1.0, 2.0, 2.0]), (2, [3.0, 3.0, 4.0, 4.0])]
+        );
+        let extent = connection
+            .query_row(
+                "SELECT minx, maxx, miny, maxy FROM rtree_layers_geom",
+                [],
+                |row| Ok([row.get::<_, f64>(0)?, row.get(1)?, row.get(2)?, row.get(3)?]),
+            )
+            .unwrap();
+        assert_eq!(extent, [0.0, 10.0, 0.0, 10.0]);
+    }
+}
@@ -0,0 +1,390 @@
+use std::sync::Arc;
+
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{
+    DataType, Date32Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+    TimeUnit, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow::util::display::array_value_to_string;
+use nusamai_projection::crs::{EpsgCode, EPSG_WGS84_GEOGRAPHIC_2D};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::node::{IngestionMessage, Port, DEFAULT_PORT};
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::datetime::DateTime;
+use reearth_flow_types::wkb::geometry_value_from_wkb;
+use reearth_flow_types::{Attribute, AttributeValue, Feature, Geometry};
+use serde_json::Number;
+use tokio::sync::mpsc::Sender;
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+const SECONDS_PER_DAY: i128 = 86_400;
+
+/// The geometry column of a GeoParquet file, with its coordinate system.
+struct GeometryColumn {
+    name: String,
+    epsg: Option<EpsgCode>,
+}
+
+/// Reads the rows of a Parquet file as features. For GeoParquet, the primary geometry column,
+/// encoded as WKB, becomes the geometry of the features.
+pub(crate) async fn read_parquet(
+    input_path: Uri,
+    storage_resolver: Arc<StorageResolver>,
+    sender: Sender<(Port, IngestionMessage)>,
+) -> Result<(), crate::errors::SourceError> {
+    let storage = storage_resolver
+        .resolve(&input_path)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let result = storage
+        .get(input_path.path().as_path())
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let byte = result
+        .bytes()
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(byte)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let geo = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .and_then(|metadata| metadata.iter().find(|kv| kv.key == "geo"))
+        .and_then(|kv| kv.value.as_deref());
+    let geometry_column = match geo {
+        Some(geo) => Some(geometry_column(geo)?),
+        None => None,
+    };
+    let reader = builder
+        .build()
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    for batch in reader {
+        let batch =
+            batch.map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        let schema = batch.schema();
+        for row in 0..batch.num_rows() {
+            let mut feature = Feature::new();
+            for (field, column) in schema.fields().iter().zip(batch.columns()) {
+                match &geometry_column {
+                    Some(geometry) if &geometry.name == field.name() => {
+                        if column.is_null(row) {
+                            continue;
+                        }
+                        let wkb = match column.data_type() {
+                            DataType::Binary => column.as_binary::<i32>().value(row),
+                            DataType::LargeBinary => column.as_binary::<i64>().value(row),
+                            data_type => {
+                                return Err(crate::errors::SourceError::FileReader(format!(
+                                    "Unsupported type of geometry column: {}",
+                                    data_type
+                                )))
+                            }
+                        };
+                        let value = geometry_value_from_wkb(wkb).map_err(|e| {
+                            crate::errors::SourceError::FileReader(format!("{:?}", e))
+                        })?;
+                        feature.geometry = Some(Geometry {
+                            epsg: geometry.epsg,
+                            value,
+                        });
+                    }
+                    _ => {
+                        feature.attributes.insert(
+                            Attribute::new(field.name().clone()),
+                            attribute_value(column.as_ref(), row),
+                        );
+                    }
+                }
+            }
+            sender
+                .send((
+                    DEFAULT_PORT.clone(),
+                    IngestionMessage::OperationEvent { feature },
+                ))
+                .await
+                .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the primary geometry column from the `geo` metadata of GeoParquet. A missing `crs`
+/// stands for OGC:CRS84, read as WGS 84, and only EPSG codes are recognized.
+fn geometry_column(geo: &str) -> Result<GeometryColumn, crate::errors::SourceError> {
+    let geo: serde_json::Value = serde_json::from_str(geo)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let name = geo["primary_column"]
+        .as_str()
+        .ok_or(crate::errors::SourceError::FileReader(
+            "No primary geometry column".to_string(),
+        ))?;
+    let column = &geo["columns"][name];
+    let encoding = column["encoding"].as_str().unwrap_or_default();
+    if !encoding.eq_ignore_ascii_case("WKB") {
+        return Err(crate::errors::SourceError::FileReader(format!(
+            "Unsupported geometry encoding: {}",
+            encoding
+        )));
+    }
+    let epsg = match column.get("crs") {
+        None => Some(EPSG_WGS84_GEOGRAPHIC_2D),
+        Some(crs) => {
+            let id = &crs["id"];
+            let code = match &id["code"] {
+                serde_json::Value::String(code) => code.parse().ok(),
+                code => code.as_u64().and_then(|code| EpsgCode::try_from(code).ok()),
+            };
+            id["authority"]
+                .as_str()
+                .filter(|authority| authority.eq_ignore_ascii_case("EPSG"))
+                .and(code)
+        }
+    };
+    Ok(GeometryColumn {
+        name: name.to_string(),
+        epsg,
+    })
+}
+
+/// Converts a value of a column. Lists become arrays and structs maps, and values of types
+/// without a counterpart are read as text.
+fn attribute_value(array: &dyn Array, row: usize) -> AttributeValue {
+    if array.is_null(row) {
+        return AttributeValue::Null;
+    }
+    let number =
+        |number: Option<Number>| number.map_or(AttributeValue::Null, AttributeValue::Number);
+    match array.data_type() {
+        DataType::Boolean => AttributeValue::Bool(array.as_boolean().value(row)),
+        DataType::Int8 => number(Some(array.as_primitive::<Int8Type>().value(row).into())),
+        DataType::Int16 => number(Some(array.as_primitive::<Int16Type>().value(row).into())),
+        DataType::Int32 => number(Some(array.as_primitive::<Int32Type>().value(row).into())),
+        DataType::Int64 => number(Some(array.as_primitive::<Int64Type>().value(row).into())),
+        DataType::UInt8 => number(Some(array.as_primitive::<UInt8Type>().value(row).into())),
+        DataType::UInt16 => number(Some(array.as_primitive::<UInt16Type>().value(row).into())),
+        DataType::UInt32 => number(Some(array.as_primitive::<UInt32Type>().value(row).into())),
+        DataType::UInt64 => number(Some(array.as_primitive::<UInt64Type>().value(row).into())),
+        DataType::Float32 => number(Number::from_f64(
+            array.as_primitive::<Float32Type>().value(row) as f64,
+        )),
+        DataType::Float64 => number(Number::from_f64(
+            array.as_primitive::<Float64Type>().value(row),
+        )),
+        DataType::Utf8 => AttributeValue::String(array.as_string::<i32>().value(row).to_string()),
+        DataType::LargeUtf8 => {
+            AttributeValue::String(array.as_string::<i64>().value(row).to_string())
+        }
+        DataType::Binary => {
+            AttributeValue::Bytes(array.as_binary::<i32>().value(row).to_vec().into())
+        }
+        DataType::LargeBinary => {
+            AttributeValue::Bytes(array.as_binary::<i64>().value(row).to_vec().into())
+        }
+        DataType::Timestamp(unit, _) => {
+            let (value, nanos) = match unit {
+                TimeUnit::Second => (
+                    array.as_primitive::<TimestampSecondType>().value(row),
+                    NANOS_PER_SECOND,
+                ),
+                TimeUnit::Millisecond => (
+                    array.as_primitive::<TimestampMillisecondType>().value(row),
+                    1_000_000,
+                ),
+                TimeUnit::Microsecond => (
+                    array.as_primitive::<TimestampMicrosecondType>().value(row),
+                    1_000,
+                ),
+                TimeUnit::Nanosecond => (
+                    array.as_primitive::<TimestampNanosecondType>().value(row),
+                    1,
+                ),
+            };
+            datetime(value as i128 * nanos)
+        }
+        DataType::Date32 => datetime(
+            array.as_primitive::<Date32Type>().value(row) as i128
+                * SECONDS_PER_DAY
+                * NANOS_PER_SECOND,
+        ),
+        DataType::List(_) => list(array.as_list::<i32>().value(row).as_ref()),
+        DataType::LargeList(_) => list(array.as_list::<i64>().value(row).as_ref()),
+        DataType::Struct(fields) => AttributeValue::Map(
+            fields
+                .iter()
+                .zip(array.as_struct().columns())
+                .map(|(field, column)| {
+                    (field.name().clone(), attribute_value(column.as_ref(), row))
+                })
+                .collect(),
+        ),
+        _ => array_value_to_string(array, row).map_or(AttributeValue::Null, AttributeValue::String),
+    }
+}
+
+fn list(values: &dyn Array) -> AttributeValue {
+    AttributeValue::Array(
+        (0..values.len())
+            .map(|index| attribute_value(values, index))
+            .collect(),
+    )
+}
+
+/// A date and time from nanoseconds since the epoch, or null when out of range.
+fn datetime(nanos: i128) -> AttributeValue {
+    let seconds = nanos.div_euclid(NANOS_PER_SECOND);
+    let nanos = nanos.rem_euclid(NANOS_PER_SECOND) as u32;
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| DateTime::try_from((seconds, nanos)).ok())
+        .map_or(AttributeValue::Null, AttributeValue::DateTime)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use reearth_flow_geometry::types::{coordinate::Coordinate, geometry::Geometry2D};
+    use reearth_flow_types::GeometryValue;
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests::utils::{feature_channel, received, write_features};
+
+    fn feature(x: f64, y: f64, attributes: Vec<(&str, AttributeValue)>) -> Feature {
+        let mut feature = Feature::new_with_attributes(
+            attributes
+                .into_iter()
+                .map(|(name, value)| (Attribute::new(name), value))
+                .collect(),
+        );
+        feature.geometry = Some(Geometry {
+            epsg: Some(6677),
+            value: GeometryValue::FlowGeometry2D(Geometry2D::Point(Coordinate::new_(x, y).into())),
+        });
+        feature
+    }
+
+    fn string(value: &str) -> AttributeValue {
+        AttributeValue::String(value.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        write_features(
+            json!({
+                "format": "parquet",
+                "output": "\"ram:///parquet/features.parquet\"",
+                "rowGroupSize": 2,
+            }),
+            vec![
+                feature(
+                    1.0,
+                    2.0,
+                    vec![
+                        ("value", AttributeValue::Number(1.into())),
+                        (
+                            "tags",
+                            AttributeValue::Array(vec![string("a"), string("b")]),
+                        ),
+                        (
+                            "address",
+                            AttributeValue::Map(HashMap::from([
+                                ("city".to_string(), string("Tokyo")),
+                                ("code".to_string(), AttributeValue::Number(13.into())),
+                            ])),
+                        ),
+                    ],
+                ),
+                feature(
+                    3.0,
+                    0.0,
+                    vec![
+                        (
+                            "value",
+                            AttributeValue::Number(Number::from_f64(2.5).unwrap()),
+                        ),
+                        ("tags", AttributeValue::Array(vec![])),
+                        (
+                            "address",
+                            AttributeValue::Map(HashMap::from([(
+                                "city".to_string(),
+                                string("Osaka"),
+                            )])),
+                        ),
+                    ],
+                ),
+                feature(2.0, 4.0, vec![]),
+            ],
+            &storage_resolver,
+        );
+
+        let uri = Uri::from_str("ram:///parquet/features.parquet").unwrap();
+        let (sender, receiver) = feature_channel();
+        read_parquet(uri.clone(), Arc::clone(&storage_resolver), sender)
+            .await
+            .unwrap();
+        let features = received(receiver);
+        assert_eq!(features.len(), 3);
+        // Integers and floats of an attribute share a column of floats.
+        let values = features
+            .iter()
+            .map(|feature| match feature.get(&"value") {
+                Some(AttributeValue::Number(number)) => number.as_f64(),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![Some(1.0), Some(2.5), None]);
+        assert_eq!(
+            features[0].get(&"tags"),
+            Some(&AttributeValue::Array(vec![string("a"), string("b")]))
+        );
+        assert_eq!(features[2].get(&"tags"), Some(&AttributeValue::Null));
+        // The fields of the struct are those of all the maps, missing ones being null.
+        let Some(AttributeValue::Map(address)) = features[1].get(&"address") else {
+            panic!("Expected a map");
+        };
+        assert_eq!(address.get("city"), Some(&string("Osaka")));
+        assert_eq!(address.get("code"), Some(&AttributeValue::Null));
+        let Some(AttributeValue::Map(address)) = features[0].get(&"address") else {
+            panic!("Expected a map");
+        };
+        assert_eq!(
+            address.get("code").and_then(|code| match code {
+                AttributeValue::Number(number) => number.as_i64(),
+                _ => None,
+            }),
+            Some(13)
+        );
+        let geometry = features[2].geometry.as_ref().unwrap();
+        assert_eq!(geometry.epsg, Some(6677));
+        let GeometryValue::FlowGeometry2D(point) = &geometry.value else {
+            panic!("Expected a 2D geometry");
+        };
+        assert_eq!(point, &Geometry2D::Point(Coordinate::new_(2.0, 4.0).into()));
+
+        let storage = storage_resolver.resolve(&uri).unwrap();
+        let bytes = storage.get_sync(uri.path().as_path()).unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        let geo = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .and_then(|metadata| metadata.iter().find(|kv| kv.key == "geo"))
+            .and_then(|kv| kv.value.as_deref())
+            .unwrap();
+        let geo = serde_json::from_str::<serde_json::Value>(geo).unwrap();
+        let column = &geo["columns"]["geometry"];
+        assert_eq!(geo["primary_column"], "geometry");
+        assert_eq!(column["geometry_types"], json!(["Point"]));
+        assert_eq!(column["bbox"], json!([1.0, 0.0, 3.0, 4.0]));
+        let crs = &column["crs"];
+        assert_eq!(crs["type"], "ProjectedCRS");
+        assert_eq!(crs["name"], "JGD2011 / Japan Plane Rectangular CS IX");
+        assert_eq!(crs["id"], json!({ "authority": "EPSG", "code": 6677 }));
+    }
+}
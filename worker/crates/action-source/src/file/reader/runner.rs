@@ -11,7 +11,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Sender;
 
-use super::{citygml, csv, json};
+use super::{citygml, csv, flatgeobuf, geojson, gpkg, json, parquet, shapefile};
+use crate::resume;
 
 #[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -46,19 +47,81 @@ pub enum FileReader {
         #[serde(flatten)]
         common_property: CommonPropertySchema,
     },
+    #[serde(rename = "geojson")]
+    GeoJson {
+        #[serde(flatten)]
+        common_property: CommonPropertySchema,
+    },
+    #[serde(rename = "flatgeobuf")]
+    FlatGeobuf {
+        #[serde(flatten)]
+        common_property: CommonPropertySchema,
+        #[serde(flatten)]
+        property: flatgeobuf::FlatGeobufPropertySchema,
+    },
+    #[serde(rename = "gpkg")]
+    Gpkg {
+        #[serde(flatten)]
+        common_property: CommonPropertySchema,
+        #[serde(flatten)]
+        property: gpkg::GpkgPropertySchema,
+    },
+    #[serde(rename = "parquet")]
+    Parquet {
+        #[serde(flatten)]
+        common_property: CommonPropertySchema,
+    },
+    #[serde(rename = "shapefile")]
+    Shapefile {
+        #[serde(flatten)]
+        common_property: CommonPropertySchema,
+        #[serde(flatten)]
+        property: shapefile::ShapefilePropertySchema,
+    },
+}
+
+/// Reads a file with a [`FileReader`]. A resumed job reads the file again, since none of the
+/// formats allow seeking to a feature, and sends the features after those sent before its
+/// checkpoint.
+#[derive(Debug, Clone)]
+pub struct FileReaderSource {
+    reader: FileReader,
+    /// Number of features sent, restored when resuming.
+    sent: u64,
+}
+
+impl FileReaderSource {
+    pub fn new(reader: FileReader, state: Option<Vec<u8>>) -> Result<Self, BoxedError> {
+        Ok(Self {
+            reader,
+            sent: resume::deserialize(state)?,
+        })
+    }
 }
 
 #[async_trait::async_trait]
-impl Source for FileReader {
+impl Source for FileReaderSource {
     async fn initialize(&self, _ctx: NodeContext) {}
 
     async fn serialize_state(&self) -> Result<Vec<u8>, BoxedError> {
-        Ok(vec![])
+        resume::serialize(self.sent)
     }
+
     async fn start(
         &mut self,
         ctx: NodeContext,
         sender: Sender<(Port, IngestionMessage)>,
+    ) -> Result<(), BoxedError> {
+        let reader = &self.reader;
+        resume::forward(&mut self.sent, &sender, |sender| reader.read(ctx, sender)).await
+    }
+}
+
+impl FileReader {
+    async fn read(
+        &self,
+        ctx: NodeContext,
+        sender: Sender<(Port, IngestionMessage)>,
     ) -> Result<(), BoxedError> {
         let storage_resolver = Arc::clone(&ctx.storage_resolver);
         match self {
@@ -114,6 +177,58 @@ impl Source for FileReader {
                     Err(e) => Err(Box::new(e)),
                 }
             }
+            Self::GeoJson { common_property } => {
+                let input_path = get_input_path(&ctx, common_property)?;
+                let result = geojson::read_geojson(input_path, storage_resolver, sender).await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(Box::new(e)),
+                }
+            }
+            Self::FlatGeobuf {
+                common_property,
+                property,
+            } => {
+                let input_path = get_input_path(&ctx, common_property)?;
+                let result =
+                    flatgeobuf::read_flatgeobuf(input_path, property, storage_resolver, sender)
+                        .await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(Box::new(e)),
+                }
+            }
+            Self::Gpkg {
+                common_property,
+                property,
+            } => {
+                let input_path = get_input_path(&ctx, common_property)?;
+                let result = gpkg::read_gpkg(input_path, property, storage_resolver, sender).await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(Box::new(e)),
+                }
+            }
+            Self::Parquet { common_property } => {
+                let input_path = get_input_path(&ctx, common_property)?;
+                let result = parquet::read_parquet(input_path, storage_resolver, sender).await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(Box::new(e)),
+                }
+            }
+            Self::Shapefile {
+                common_property,
+                property,
+            } => {
+                let input_path = get_input_path(&ctx, common_property)?;
+                let result =
+                    shapefile::read_shapefile(input_path, property, storage_resolver, sender).await;
+                match result {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(Box::new(e)),
+                }
+            }
         }
     }
 }
@@ -0,0 +1,568 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use async_zip::base::read::mem::ZipFileReader;
+use encoding_rs::UTF_8;
+use futures::AsyncReadExt;
+use nusamai_projection::crs::{
+    EpsgCode, EPSG_JGD2000_JPRECT_I, EPSG_JGD2011_GEOGRAPHIC_2D, EPSG_JGD2011_JPRECT_I,
+    EPSG_WGS84_GEOGRAPHIC_2D,
+};
+use reearth_flow_common::{encoding::encoding_for_label, uri::Uri};
+use reearth_flow_runtime::node::{IngestionMessage, Port, DEFAULT_PORT};
+use reearth_flow_storage::{resolve::StorageResolver, storage::Storage};
+use reearth_flow_types::shapefile::{dbf, shp};
+use reearth_flow_types::{Feature, Geometry};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+
+/// JGD2000 (EPSG:4612).
+const EPSG_JGD2000_GEOGRAPHIC_2D: EpsgCode = 4612;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShapefilePropertySchema {
+    /// Encoding of the attributes, overriding the `.cpg` file, e.g. `Shift_JIS`
+    pub(super) encoding: Option<String>,
+}
+
+/// The files of a shapefile. Only `shp` is required to read one.
+#[derive(Debug, Clone, Default)]
+struct Shapefile {
+    shp: Vec<u8>,
+    shx: Option<Vec<u8>>,
+    dbf: Option<Vec<u8>>,
+    prj: Option<String>,
+    cpg: Option<String>,
+}
+
+impl Shapefile {
+    /// Reads the features of the shapefile.
+    ///
+    /// The attributes are decoded with `encoding` if given, or else the encoding of the `.cpg`
+    /// file or of the language driver of the `.dbf` file, defaulting to UTF-8. The coordinate
+    /// system is read from the `.prj` file, and left unset when it is not recognized.
+    fn features(&self, encoding: Option<&str>) -> Result<Vec<Feature>, crate::errors::SourceError> {
+        let encoding = match (encoding, self.cpg.as_deref()) {
+            (Some(label), _) | (None, Some(label)) => encoding_for_label(label)
+                .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?,
+            (None, None) => self
+                .dbf
+                .as_deref()
+                .and_then(dbf::language_driver_encoding)
+                .unwrap_or(UTF_8),
+        };
+        let epsg = self.prj.as_deref().and_then(epsg_from_prj);
+        let geometries = shp::read_shapes(&self.shp, self.shx.as_deref())
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        let mut records = match self.dbf.as_deref() {
+            Some(dbf) => dbf::read_records(dbf, encoding)
+                .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?
+                .into_iter(),
+            None => vec![].into_iter(),
+        };
+        let mut features = vec![];
+        for geometry in geometries {
+            let attributes = match records.next() {
+                Some(Some(attributes)) => attributes,
+                // Deleted records stand for deleted shapes.
+                Some(None) => continue,
+                None => Default::default(),
+            };
+            let mut feature = Feature::new_with_attributes(attributes);
+            feature.geometry = geometry.map(|value| Geometry { epsg, value });
+            features.push(feature);
+        }
+        Ok(features)
+    }
+}
+
+/// Reads a `.shp` file with the `.shx`, `.dbf`, `.prj` and `.cpg` files next to it, or every
+/// shapefile of a `.zip` archive.
+pub(crate) async fn read_shapefile(
+    input_path: Uri,
+    props: &ShapefilePropertySchema,
+    storage_resolver: Arc<StorageResolver>,
+    sender: Sender<(Port, IngestionMessage)>,
+) -> Result<(), crate::errors::SourceError> {
+    let storage = storage_resolver
+        .resolve(&input_path)
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    let shapefiles = if input_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    {
+        let result = storage
+            .get(input_path.path().as_path())
+            .await
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        let byte = result
+            .bytes()
+            .await
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        read_archive(byte.to_vec()).await?
+    } else {
+        vec![read_files(&storage, input_path.path().as_path()).await?]
+    };
+    for shapefile in shapefiles {
+        let features = shapefile.features(props.encoding.as_deref())?;
+        for feature in features {
+            sender
+                .send((
+                    DEFAULT_PORT.clone(),
+                    IngestionMessage::OperationEvent { feature },
+                ))
+                .await
+                .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        }
+    }
+    Ok(())
+}
+
+async fn read_files(
+    storage: &Storage,
+    path: &Path,
+) -> Result<Shapefile, crate::errors::SourceError> {
+    // Sibling files follow the case of the extension of the `.shp` file.
+    let upper = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext == "SHP");
+    let mut files = HashMap::new();
+    for ext in ["shp", "shx", "dbf", "prj", "cpg"] {
+        let sibling = if upper {
+            path.with_extension(ext.to_ascii_uppercase())
+        } else {
+            path.with_extension(ext)
+        };
+        if ext != "shp"
+            && !storage
+                .exists(sibling.as_path())
+                .await
+                .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?
+        {
+            continue;
+        }
+        let result = storage
+            .get(sibling.as_path())
+            .await
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        let byte = result
+            .bytes()
+            .await
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        files.insert(ext, byte.to_vec());
+    }
+    shapefile_from_files(files)
+}
+
+async fn read_archive(bytes: Vec<u8>) -> Result<Vec<Shapefile>, crate::errors::SourceError> {
+    let reader = ZipFileReader::new(bytes)
+        .await
+        .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+    // Files are grouped by their path without the extension.
+    let mut bundles = HashMap::<String, HashMap<&'static str, Vec<u8>>>::new();
+    for i in 0..reader.file().entries().len() {
+        let entry =
+            reader
+                .file()
+                .entries()
+                .get(i)
+                .ok_or(crate::errors::SourceError::FileReader(
+                    "No entry".to_string(),
+                ))?;
+        let filename = entry
+            .filename()
+            .as_str()
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        let Some((stem, ext)) = filename.rsplit_once('.') else {
+            continue;
+        };
+        let Some(ext) = ["shp", "shx", "dbf", "prj", "cpg"]
+            .into_iter()
+            .find(|known| known.eq_ignore_ascii_case(ext))
+        else {
+            continue;
+        };
+        let stem = stem.to_string();
+        let mut entry_reader = reader
+            .reader_without_entry(i)
+            .await
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        let mut buf = Vec::<u8>::new();
+        entry_reader
+            .read_to_end(&mut buf)
+            .await
+            .map_err(|e| crate::errors::SourceError::FileReader(format!("{:?}", e)))?;
+        bundles.entry(stem).or_default().insert(ext, buf);
+    }
+    let mut stems = bundles
+        .iter()
+        .filter(|(_, files)| files.contains_key("shp"))
+        .map(|(stem, _)| stem.clone())
+        .collect::<Vec<_>>();
+    if stems.is_empty() {
+        return Err(crate::errors::SourceError::FileReader(
+            "No shapefile in the archive".to_string(),
+        ));
+    }
+    stems.sort();
+    stems
+        .into_iter()
+        .filter_map(|stem| bundles.remove(&stem))
+        .map(shapefile_from_files)
+        .collect()
+}
+
+fn shapefile_from_files(
+    mut files: HashMap<&'static str, Vec<u8>>,
+) -> Result<Shapefile, crate::errors::SourceError> {
+    let text = |bytes: Vec<u8>| String::from_utf8_lossy(&bytes).into_owned();
+    Ok(Shapefile {
+        shp: files
+            .remove("shp")
+            .ok_or(crate::errors::SourceError::FileReader(
+                "No .shp file".to_string(),
+            ))?,
+        shx: files.remove("shx"),
+        dbf: files.remove("dbf"),
+        prj: files.remove("prj").map(text),
+        cpg: files.remove("cpg").map(text),
+    })
+}
+
+/// Reads the EPSG code of a `.prj` file, from its authority or from the ESRI name of a
+/// coordinate system of Japan.
+fn epsg_from_prj(prj: &str) -> Option<EpsgCode> {
+    // The authority of the coordinate system comes last, after those of its components.
+    if let Some(start) = prj.rfind("AUTHORITY[\"EPSG\",") {
+        let code = prj[start..].split('"').nth(3)?;
+        return code.parse().ok();
+    }
+    let name = prj.split('"').nth(1)?;
+    let zone = |prefix: &str| {
+        name.strip_prefix(prefix)?
+            .parse::<EpsgCode>()
+            .ok()
+            .filter(|zone| (1..=19).contains(zone))
+    };
+    match name {
+        "GCS_WGS_1984" => Some(EPSG_WGS84_GEOGRAPHIC_2D),
+        "GCS_JGD_2011" => Some(EPSG_JGD2011_GEOGRAPHIC_2D),
+        "GCS_JGD_2000" => Some(EPSG_JGD2000_GEOGRAPHIC_2D),
+        _ => zone("JGD_2011_Japan_Zone_")
+            .map(|zone| EPSG_JGD2011_JPRECT_I + zone - 1)
+            .or_else(|| zone("JGD_2000_Japan_Zone_").map(|zone| EPSG_JGD2000_JPRECT_I + zone - 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bytes::Bytes;
+    use reearth_flow_geometry::types::{
+        coordinate::Coordinate,
+        geometry::{Geometry2D, Geometry3D},
+        line_string::LineString,
+        point::Point,
+        polygon::Polygon,
+    };
+    use reearth_flow_types::{Attribute, AttributeValue, GeometryValue};
+    use serde_json::{json, Number};
+
+    use super::*;
+    use crate::tests::utils::{feature_channel, received, write_features};
+
+    fn feature(attributes: &[(&str, AttributeValue)], value: GeometryValue) -> Feature {
+        let attributes = attributes
+            .iter()
+            .map(|(k, v)| (Attribute::new(k.to_string()), v.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut feature = Feature::new_with_attributes(attributes);
+        feature.geometry = Some(Geometry {
+            epsg: Some(6677),
+            value,
+        });
+        feature
+    }
+
+    fn name(value: &str) -> AttributeValue {
+        AttributeValue::String(value.to_string())
+    }
+
+    fn square(size: f64) -> Geometry2D {
+        let ring = |coords: &[(f64, f64)]| {
+            LineString::new(
+                coords
+                    .iter()
+                    .map(|(x, y)| Coordinate::new_(*x, *y))
+                    .collect(),
+            )
+        };
+        Geometry2D::Polygon(Polygon::new(
+            ring(&[
+                (0.0, 0.0),
+                (size, 0.0),
+                (size, size),
+                (0.0, size),
+                (0.0, 0.0),
+            ]),
+            vec![ring(&[
+                (2.0, 2.0),
+                (2.0, 4.0),
+                (4.0, 4.0),
+                (4.0, 2.0),
+                (2.0, 2.0),
+            ])],
+        ))
+    }
+
+    fn write(
+        output: &str,
+        encoding: &str,
+        features: Vec<Feature>,
+        storage_resolver: &Arc<StorageResolver>,
+    ) {
+        write_features(
+            json!({
+                "format": "shapefile",
+                "output": format!("\"{}\"", output),
+                "encoding": encoding,
+            }),
+            features,
+            storage_resolver,
+        );
+    }
+
+    async fn read(
+        input: &str,
+        encoding: Option<&str>,
+        storage_resolver: &Arc<StorageResolver>,
+    ) -> Vec<Feature> {
+        let (sender, receiver) = feature_channel();
+        let props = ShapefilePropertySchema {
+            encoding: encoding.map(str::to_string),
+        };
+        read_shapefile(
+            Uri::from_str(input).unwrap(),
+            &props,
+            Arc::clone(storage_resolver),
+            sender,
+        )
+        .await
+        .unwrap();
+        received(receiver)
+    }
+
+    fn file(path: &str, storage_resolver: &StorageResolver) -> Option<Vec<u8>> {
+        let uri = Uri::from_str(path).unwrap();
+        let storage = storage_resolver.resolve(&uri).unwrap();
+        storage
+            .get_sync(uri.path().as_path())
+            .ok()
+            .map(|bytes| bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let polygon = feature(
+            &[
+                ("名称", name("東京都")),
+                (
+                    "height",
+                    AttributeValue::Number(Number::from_f64(1.25).unwrap()),
+                ),
+            ],
+            GeometryValue::FlowGeometry2D(square(10.0)),
+        );
+        let point = feature(
+            &[("count", AttributeValue::Number(Number::from(3)))],
+            GeometryValue::FlowGeometry2D(Geometry2D::Point(Coordinate::new_(1.0, 2.0).into())),
+        );
+        write(
+            "ram:///shp/round_trip.shp",
+            "Shift_JIS",
+            vec![polygon, point],
+            &storage_resolver,
+        );
+        assert_eq!(
+            file("ram:///shp/round_trip_polygon.cpg", &storage_resolver),
+            Some(b"Shift_JIS".to_vec())
+        );
+        let prj = file("ram:///shp/round_trip_polygon.prj", &storage_resolver).unwrap();
+        assert!(prj.starts_with(br#"PROJCS["JGD_2011_Japan_Zone_9""#));
+
+        let features = read("ram:///shp/round_trip_polygon.shp", None, &storage_resolver).await;
+        assert_eq!(features.len(), 1);
+        let feature = &features[0];
+        assert_eq!(feature.get(&"名称"), Some(&name("東京都")));
+        assert_eq!(
+            feature.get(&"height"),
+            Some(&AttributeValue::Number(Number::from_f64(1.25).unwrap()))
+        );
+        let geometry = feature.geometry.as_ref().unwrap();
+        assert_eq!(geometry.epsg, Some(6677));
+        let GeometryValue::FlowGeometry2D(Geometry2D::Polygon(polygon)) = &geometry.value else {
+            panic!("Expected a polygon, got {:?}", geometry.value);
+        };
+        assert_eq!(polygon.exterior().0.len(), 5);
+        assert_eq!(polygon.interiors().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_one_file_per_geometry_type() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let line = LineString::new(vec![Coordinate::new_(0.0, 0.0), Coordinate::new_(1.0, 1.0)]);
+        let features = vec![
+            feature(
+                &[("name", name("point"))],
+                GeometryValue::FlowGeometry2D(Geometry2D::Point(Coordinate::new_(1.0, 2.0).into())),
+            ),
+            feature(
+                &[("name", name("polyline"))],
+                GeometryValue::FlowGeometry2D(Geometry2D::LineString(line)),
+            ),
+            feature(
+                &[("name", name("polygon"))],
+                GeometryValue::FlowGeometry2D(square(10.0)),
+            ),
+            feature(
+                &[("name", name("pointz"))],
+                GeometryValue::FlowGeometry3D(Geometry3D::Point(Point(Coordinate::new__(
+                    1.0, 2.0, 3.0,
+                )))),
+            ),
+        ];
+        write("ram:///shp/types.shp", "UTF-8", features, &storage_resolver);
+        for shape_type in ["point", "polyline", "polygon", "pointz"] {
+            for ext in ["shp", "shx", "dbf", "prj", "cpg"] {
+                let path = format!("ram:///shp/types_{}.{}", shape_type, ext);
+                assert!(file(&path, &storage_resolver).is_some(), "{}", path);
+            }
+            let path = format!("ram:///shp/types_{}.shp", shape_type);
+            let features = read(&path, None, &storage_resolver).await;
+            assert_eq!(features.len(), 1);
+            assert_eq!(features[0].get(&"name"), Some(&name(shape_type)));
+            let value = &features[0].geometry.as_ref().unwrap().value;
+            match shape_type {
+                "point" => assert!(matches!(
+                    value,
+                    GeometryValue::FlowGeometry2D(Geometry2D::Point(_))
+                )),
+                "polyline" => assert!(matches!(
+                    value,
+                    GeometryValue::FlowGeometry2D(Geometry2D::LineString(_))
+                )),
+                "polygon" => assert!(matches!(
+                    value,
+                    GeometryValue::FlowGeometry2D(Geometry2D::Polygon(_))
+                )),
+                _ => {
+                    let GeometryValue::FlowGeometry3D(Geometry3D::Point(point)) = value else {
+                        panic!("Expected a point with z, got {:?}", value);
+                    };
+                    assert_eq!(point.0.z, 3.0);
+                }
+            }
+        }
+        assert!(file("ram:///shp/types.shp", &storage_resolver).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_decodes_with_the_code_page_of_the_cpg_file() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let features = vec![feature(
+            &[("名称", name("東京都"))],
+            GeometryValue::FlowGeometry2D(square(10.0)),
+        )];
+        write(
+            "ram:///shp/cpg.shp",
+            "Shift_JIS",
+            features,
+            &storage_resolver,
+        );
+        let uri = Uri::from_str("ram:///shp/cpg.shp").unwrap();
+        let storage = storage_resolver.resolve(&uri).unwrap();
+        // Only the `.cpg` file tells the encoding of the attributes.
+        let mut dbf = file("ram:///shp/cpg.dbf", &storage_resolver).unwrap();
+        dbf[29] = 0;
+        storage
+            .put_sync(uri.path().with_extension("dbf").as_path(), Bytes::from(dbf))
+            .unwrap();
+        storage
+            .put_sync(
+                uri.path().with_extension("cpg").as_path(),
+                Bytes::from("932"),
+            )
+            .unwrap();
+
+        let features = read("ram:///shp/cpg.shp", None, &storage_resolver).await;
+        assert_eq!(features[0].get(&"名称"), Some(&name("東京都")));
+
+        storage
+            .delete_sync(uri.path().with_extension("cpg").as_path())
+            .unwrap();
+        let features = read("ram:///shp/cpg.shp", None, &storage_resolver).await;
+        assert_ne!(features[0].get(&"名称"), Some(&name("東京都")));
+        let features = read("ram:///shp/cpg.shp", Some("CP932"), &storage_resolver).await;
+        assert_eq!(features[0].get(&"名称"), Some(&name("東京都")));
+    }
+
+    #[tokio::test]
+    async fn test_zip_round_trip() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let features = vec![
+            feature(
+                &[("name", name("polygon"))],
+                GeometryValue::FlowGeometry2D(square(10.0)),
+            ),
+            feature(
+                &[("name", name("point"))],
+                GeometryValue::FlowGeometry2D(Geometry2D::Point(Coordinate::new_(1.0, 2.0).into())),
+            ),
+        ];
+        write(
+            "ram:///shp/bundle.zip",
+            "UTF-8",
+            features,
+            &storage_resolver,
+        );
+        assert!(file("ram:///shp/bundle_point.shp", &storage_resolver).is_none());
+
+        // The shapefiles of the archive are read in the order of their names.
+        let features = read("ram:///shp/bundle.zip", None, &storage_resolver).await;
+        let names = features
+            .iter()
+            .map(|feature| feature.get(&"name").cloned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Some(name("point")), Some(name("polygon"))]);
+        assert!(features
+            .iter()
+            .all(|feature| feature.geometry.as_ref().unwrap().epsg == Some(6677)));
+    }
+
+    #[test]
+    fn test_epsg_from_prj() {
+        assert_eq!(
+            epsg_from_prj(r#"PROJCS["JGD_2011_Japan_Zone_9",GEOGCS["GCS_JGD_2011"]]"#),
+            Some(6677)
+        );
+        assert_eq!(
+            epsg_from_prj(r#"PROJCS["JGD_2000_Japan_Zone_9",GEOGCS["GCS_JGD_2000"]]"#),
+            Some(2451)
+        );
+        assert_eq!(
+            epsg_from_prj(r#"GEOGCS["GCS_JGD_2011",DATUM["D_JGD_2011"]]"#),
+            Some(6668)
+        );
+        assert_eq!(
+            epsg_from_prj(
+                r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0],UNIT["degree",0.0174532925199433],AUTHORITY["EPSG","4326"]]"#
+            ),
+            Some(4326)
+        );
+        assert_eq!(epsg_from_prj(r#"PROJCS["Unknown"]"#), None);
+    }
+}
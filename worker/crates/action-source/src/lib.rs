@@ -2,3 +2,7 @@ pub mod errors;
 pub mod feature_creator;
 pub mod file;
 pub mod mapping;
+mod resume;
+
+#[cfg(test)]
+pub(crate) mod tests;
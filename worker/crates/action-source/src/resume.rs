@@ -0,0 +1,131 @@
+use std::future::Future;
+
+use reearth_flow_runtime::{
+    errors::BoxedError,
+    node::{IngestionMessage, Port, DEFAULT_PORT},
+};
+use tokio::sync::mpsc::{channel, Sender};
+
+/// The state of a source whose features come in the same order on every run: the number of
+/// features it has sent.
+pub(crate) fn serialize(sent: u64) -> Result<Vec<u8>, BoxedError> {
+    Ok(serde_json::to_vec(&sent)?)
+}
+
+/// The number of features sent before the checkpoint a job resumes from, or 0 for a new job.
+pub(crate) fn deserialize(state: Option<Vec<u8>>) -> Result<u64, BoxedError> {
+    match state {
+        Some(state) => Ok(serde_json::from_slice(&state)?),
+        None => Ok(0),
+    }
+}
+
+/// Runs `read` and forwards the features it sends after the first `sent` ones, which were sent
+/// before the checkpoint the job resumes from. The state of the source is sent before the first
+/// feature and after each one, with `sent` counting the features sent so far.
+pub(crate) async fn forward<F, Fut>(
+    sent: &mut u64,
+    sender: &Sender<(Port, IngestionMessage)>,
+    read: F,
+) -> Result<(), BoxedError>
+where
+    F: FnOnce(Sender<(Port, IngestionMessage)>) -> Fut,
+    Fut: Future<Output = Result<(), BoxedError>>,
+{
+    let skip = *sent;
+    send_state(*sent, sender).await?;
+    let (read_sender, mut receiver) = channel(1);
+    let forward = async {
+        let mut position = 0;
+        while let Some((port, message)) = receiver.recv().await {
+            let IngestionMessage::OperationEvent { feature } = message else {
+                continue;
+            };
+            position += 1;
+            if position <= skip {
+                continue;
+            }
+            sender
+                .send((port, IngestionMessage::OperationEvent { feature }))
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+            *sent = position;
+            send_state(*sent, sender).await?;
+        }
+        Ok::<_, BoxedError>(())
+    };
+    let (read, forward) = tokio::join!(read(read_sender), forward);
+    read?;
+    forward
+}
+
+async fn send_state(
+    sent: u64,
+    sender: &Sender<(Port, IngestionMessage)>,
+) -> Result<(), BoxedError> {
+    let state = serialize(sent)?;
+    sender
+        .send((DEFAULT_PORT.clone(), IngestionMessage::State { state }))
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use reearth_flow_types::{Attribute, AttributeValue, Feature};
+
+    use super::*;
+    use crate::tests::utils::feature_channel;
+
+    #[tokio::test]
+    async fn test_forward_skips_the_features_sent_before_the_checkpoint() {
+        let (sender, mut receiver) = feature_channel();
+        let mut sent = 2;
+        forward(&mut sent, &sender, |sender| async move {
+            for value in 0..4 {
+                let feature = Feature::new_with_attributes(
+                    [(
+                        Attribute::new("value"),
+                        AttributeValue::Number(value.into()),
+                    )]
+                    .into(),
+                );
+                sender
+                    .send((
+                        DEFAULT_PORT.clone(),
+                        IngestionMessage::OperationEvent { feature },
+                    ))
+                    .await
+                    .map_err(|e| format!("{:?}", e))?;
+            }
+            Ok::<_, BoxedError>(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(sent, 4);
+
+        let mut messages = vec![];
+        while let Ok((_, message)) = receiver.try_recv() {
+            messages.push(match message {
+                IngestionMessage::OperationEvent { feature } => {
+                    format!("feature {:?}", feature.get(&"value").unwrap())
+                }
+                IngestionMessage::State { state } => {
+                    format!("state {}", deserialize(Some(state)).unwrap())
+                }
+            });
+        }
+        let value = |value: i64| format!("feature {:?}", AttributeValue::Number(value.into()));
+        assert_eq!(
+            messages,
+            vec![
+                "state 2".to_string(),
+                value(2),
+                "state 3".to_string(),
+                value(3),
+                "state 4".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use reearth_flow_action_sink::file::writer::FileWriterSinkFactory;
+use reearth_flow_runtime::{
+    event::EventHub,
+    executor_operation::{ExecutorContext, NodeContext},
+    node::{IngestionMessage, Port, Sink, SinkFactory, DEFAULT_PORT},
+};
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::Feature;
+use serde_json::Value;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+/// Writes `features` with a `FileWriter` given the parameters `with`, so that the readers can be
+/// tested against what the writers produce.
+pub(crate) fn write_features(
+    with: Value,
+    features: Vec<Feature>,
+    storage_resolver: &Arc<StorageResolver>,
+) {
+    let ctx = NodeContext {
+        storage_resolver: Arc::clone(storage_resolver),
+        ..Default::default()
+    };
+    let with = serde_json::from_value::<HashMap<String, Value>>(with).unwrap();
+    let mut sink = FileWriterSinkFactory
+        .build(
+            ctx.clone(),
+            EventHub::new(1),
+            "FileWriter".to_string(),
+            Some(with),
+        )
+        .unwrap();
+    for feature in features {
+        sink.process(ExecutorContext::new_with_node_context_feature_and_port(
+            &ctx,
+            feature,
+            DEFAULT_PORT.clone(),
+        ))
+        .unwrap();
+    }
+    sink.finish(ctx).unwrap();
+}
+
+/// A channel large enough to hold the features a reader sends in the tests.
+pub(crate) fn feature_channel() -> (
+    Sender<(Port, IngestionMessage)>,
+    Receiver<(Port, IngestionMessage)>,
+) {
+    channel(1024)
+}
+
+/// The features sent to the channel, once the reader has finished.
+pub(crate) fn received(mut receiver: Receiver<(Port, IngestionMessage)>) -> Vec<Feature> {
+    let mut features = vec![];
+    while let Ok((_, message)) = receiver.try_recv() {
+        if let IngestionMessage::OperationEvent { feature } = message {
+            features.push(feature);
+        }
+    }
+    features
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use clap::{Arg, ArgMatches, Command};
+use reearth_flow_runtime::cache::NodeCache;
+use reearth_flow_state::State;
+use reearth_flow_storage::resolve;
+use tracing::debug;
+
+use crate::run::node_cache_uri;
+
+pub fn build_cache_prune_command() -> Command {
+    Command::new("cache-prune")
+        .about("Delete cached node outputs.")
+        .long_about("Delete the node outputs cached by previous jobs.")
+        .arg(older_than_cli_arg())
+}
+
+fn older_than_cli_arg() -> Arg {
+    Arg::new("older_than")
+        .long("older-than")
+        .help("Only delete outputs cached more than this many days ago")
+        .env("REEARTH_FLOW_CACHE_PRUNE_OLDER_THAN")
+        .value_parser(clap::value_parser!(u32))
+        .required(false)
+        .display_order(1)
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct CachePruneCliCommand {
+    older_than: Option<u32>,
+}
+
+impl CachePruneCliCommand {
+    pub fn parse_cli_args(mut matches: ArgMatches) -> crate::Result<Self> {
+        let older_than = matches.remove_one::<u32>("older_than");
+        Ok(CachePruneCliCommand { older_than })
+    }
+
+    pub fn execute(&self) -> crate::Result<()> {
+        debug!(args = ?self, "cache-prune");
+        let storage_resolver = resolve::StorageResolver::new();
+        let state =
+            State::new(&node_cache_uri()?, &storage_resolver).map_err(crate::Error::init)?;
+        let cache = NodeCache::new(Arc::new(state));
+        let before = self
+            .older_than
+            .map(|days| chrono::Utc::now() - chrono::Duration::days(days.into()));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(crate::Error::init)?;
+        let deleted = runtime
+            .block_on(cache.prune(before))
+            .map_err(crate::Error::run)?;
+        println!("Deleted {} cached node outputs", deleted);
+        Ok(())
+    }
+}
@@ -3,10 +3,12 @@ use std::env;
 use clap::{ArgMatches, Command};
 use tracing::Level;
 
+use crate::cache_prune::{build_cache_prune_command, CachePruneCliCommand};
 use crate::dot::{build_dot_command, DotCliCommand};
 use crate::run::{build_run_command, RunCliCommand};
 use crate::schema_action::{build_schema_action_command, SchemaActionCliCommand};
 use crate::schema_workflow::{build_schema_workflow_command, SchemaWorkflowCliCommand};
+use crate::validate::{build_validate_command, ValidateCliCommand};
 
 pub fn build_cli() -> Command {
     Command::new("Re:Earth Flow")
@@ -14,6 +16,8 @@ pub fn build_cli() -> Command {
         .subcommand(build_dot_command().display_order(2))
         .subcommand(build_schema_action_command().display_order(3))
         .subcommand(build_schema_workflow_command().display_order(4))
+        .subcommand(build_validate_command().display_order(5))
+        .subcommand(build_cache_prune_command().display_order(6))
         .arg_required_else_help(true)
         .disable_help_subcommand(true)
         .subcommand_required(true)
@@ -25,6 +29,8 @@ pub enum CliCommand {
     Dot(DotCliCommand),
     SchemaAction(SchemaActionCliCommand),
     SchemaWorkflow(SchemaWorkflowCliCommand),
+    Validate(ValidateCliCommand),
+    CachePrune(CachePruneCliCommand),
 }
 
 impl CliCommand {
@@ -37,6 +43,8 @@ impl CliCommand {
             CliCommand::Dot(_) => Level::WARN,
             CliCommand::SchemaAction(_) => Level::WARN,
             CliCommand::SchemaWorkflow(_) => Level::WARN,
+            CliCommand::Validate(_) => Level::WARN,
+            CliCommand::CachePrune(_) => Level::WARN,
         })
     }
 
@@ -49,6 +57,10 @@ impl CliCommand {
             "dot" => DotCliCommand::parse_cli_args(submatches).map(CliCommand::Dot),
             "schema-action" => Ok(CliCommand::SchemaAction(SchemaActionCliCommand)),
             "schema-workflow" => Ok(CliCommand::SchemaWorkflow(SchemaWorkflowCliCommand)),
+            "validate" => ValidateCliCommand::parse_cli_args(submatches).map(CliCommand::Validate),
+            "cache-prune" => {
+                CachePruneCliCommand::parse_cli_args(submatches).map(CliCommand::CachePrune)
+            }
             _ => Err(crate::Error::unknown_command(subcommand)),
         }
     }
@@ -59,6 +71,8 @@ impl CliCommand {
             CliCommand::Dot(subcommand) => subcommand.execute(),
             CliCommand::SchemaAction(subcommand) => subcommand.execute(),
             CliCommand::SchemaWorkflow(subcommand) => subcommand.execute(),
+            CliCommand::Validate(subcommand) => subcommand.execute(),
+            CliCommand::CachePrune(subcommand) => subcommand.execute(),
         }
     }
 }
@@ -63,9 +63,16 @@ impl DotCliCommand {
             "Router".to_string(),
             NodeKind::Processor(Box::<RouterFactory>::default()),
         );
-        let workflow = Workflow::try_from_str(&json);
-        let dag =
-            DagSchemas::from_graphs(workflow.entry_graph_id, workflow.graphs, factories, None);
+        let workflow = Workflow::try_from_str(&json)
+            .map_err(|e| crate::Error::parse(e.with_path(&self.workflow_path)))?;
+        let dag = DagSchemas::from_graphs(
+            workflow.entry_graph_id,
+            workflow.graphs,
+            factories,
+            None,
+            &storage_resolver,
+        )
+        .map_err(crate::Error::run)?;
         println!("{}", dag.to_dot());
         Ok(())
     }
@@ -1,3 +1,4 @@
+pub mod cache_prune;
 pub mod cli;
 pub mod dot;
 pub(crate) mod factory;
@@ -5,6 +6,7 @@ pub mod logger;
 pub mod run;
 pub mod schema_action;
 pub mod schema_workflow;
+pub mod validate;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -32,6 +34,10 @@ impl Error {
         Self::Init(message.to_string())
     }
 
+    pub(crate) fn run<T: ToString>(message: T) -> Self {
+        Self::Run(message.to_string())
+    }
+
     pub(crate) fn unknown_command<T: ToString>(message: T) -> Self {
         Self::UnknownCommand(message.to_string())
     }
@@ -18,7 +18,7 @@ fn main() -> Result<()> {
     let command = CliCommand::parse_cli_args(matches)?;
     logger::setup_logging_and_tracing(command.default_log_level(), true);
     let return_code: i32 = if let Err(err) = command.execute() {
-        eprintln!("{} Command failed: {:?}\n", "✘".color(RED_COLOR), err);
+        eprintln!("{} Command failed: {}\n", "✘".color(RED_COLOR), err);
         1
     } else {
         0
@@ -1,8 +1,11 @@
-use std::{collections::HashMap, fs, io, path::Path, str::FromStr, sync::Arc};
+use std::{collections::HashMap, fs, io, path::Path, str::FromStr, sync::Arc, time::Duration};
 
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use directories::ProjectDirs;
-use reearth_flow_runner::runner::Runner;
+use reearth_flow_runner::runner::{Runner, RunnerOptions};
+use reearth_flow_runtime::{
+    cache::NodeCache, feature_store::FeatureStoreFormat, replay::ReplayOptions,
+};
 use reearth_flow_state::State;
 use reearth_flow_types::Workflow;
 use tracing::debug;
@@ -22,6 +25,20 @@ pub fn build_run_command() -> Command {
         .arg(dataframe_state_cli_arg())
         .arg(action_log_cli_arg())
         .arg(vars_arg())
+        .arg(kv_store_cli_arg())
+        .arg(resume_cli_arg())
+        .arg(checkpoint_interval_cli_arg())
+        .arg(replay_job_id_cli_arg())
+        .arg(replay_edge_id_cli_arg())
+        .arg(feature_store_format_cli_arg())
+        .arg(channel_buffer_size_cli_arg())
+        .arg(event_hub_capacity_cli_arg())
+        .arg(thread_pool_size_cli_arg())
+        .arg(worker_threads_cli_arg())
+        .arg(summary_cli_arg())
+        .arg(timeout_cli_arg())
+        .arg(buffer_memory_limit_cli_arg())
+        .arg(no_cache_cli_arg())
 }
 
 fn workflow_cli_arg() -> Arg {
@@ -69,6 +86,140 @@ fn vars_arg() -> Arg {
         .display_order(5)
 }
 
+fn kv_store_cli_arg() -> Arg {
+    Arg::new("kv_store")
+        .long("kv-store")
+        .help("Persistent key-value store location. Overrides the workflow config.")
+        .env("REEARTH_FLOW_KV_STORE")
+        .required(false)
+        .display_order(6)
+}
+
+fn resume_cli_arg() -> Arg {
+    Arg::new("resume")
+        .long("resume")
+        .help("Resume the job with this id from its last checkpoint")
+        .value_name("JOB_ID")
+        .required(false)
+        .display_order(7)
+}
+
+fn checkpoint_interval_cli_arg() -> Arg {
+    Arg::new("checkpoint_interval")
+        .long("checkpoint-interval")
+        .help("Checkpoint the job at this interval in seconds, so that it can be resumed")
+        .env("REEARTH_FLOW_CHECKPOINT_INTERVAL")
+        .value_parser(clap::value_parser!(u64))
+        .required(false)
+        .display_order(8)
+}
+
+fn replay_job_id_cli_arg() -> Arg {
+    Arg::new("replay_job_id")
+        .long("replay-job-id")
+        .help("Job id whose feature store is replayed")
+        .requires("replay_edge_id")
+        .required(false)
+        .display_order(9)
+}
+
+fn replay_edge_id_cli_arg() -> Arg {
+    Arg::new("replay_edge_id")
+        .long("replay-edge-id")
+        .help("Edge to replay. Only the part of the workflow downstream of this edge is run.")
+        .requires("replay_job_id")
+        .required(false)
+        .display_order(10)
+}
+
+fn feature_store_format_cli_arg() -> Arg {
+    Arg::new("feature_store_format")
+        .long("feature-store-format")
+        .help("Format of the features stored for each edge")
+        .env("REEARTH_FLOW_FEATURE_STORE_FORMAT")
+        .value_parser(["json", "jsonl"])
+        .default_value("json")
+        .display_order(11)
+}
+
+fn channel_buffer_size_cli_arg() -> Arg {
+    Arg::new("channel_buffer_size")
+        .long("channel-buffer-size")
+        .help("Capacity of the channel between two nodes. Overrides the workflow config.")
+        .env("REEARTH_FLOW_CHANNEL_BUFFER_SIZE")
+        .value_parser(clap::value_parser!(usize))
+        .required(false)
+        .display_order(12)
+}
+
+fn event_hub_capacity_cli_arg() -> Arg {
+    Arg::new("event_hub_capacity")
+        .long("event-hub-capacity")
+        .help("Capacity of the event hub. Overrides the workflow config.")
+        .env("REEARTH_FLOW_EVENT_HUB_CAPACITY")
+        .value_parser(clap::value_parser!(usize))
+        .required(false)
+        .display_order(13)
+}
+
+fn thread_pool_size_cli_arg() -> Arg {
+    Arg::new("thread_pool_size")
+        .long("thread-pool-size")
+        .help("Upper bound of the threads of each processor. Overrides the workflow config.")
+        .env("REEARTH_FLOW_THREAD_POOL_SIZE")
+        .value_parser(clap::value_parser!(usize))
+        .required(false)
+        .display_order(14)
+}
+
+fn worker_threads_cli_arg() -> Arg {
+    Arg::new("worker_threads")
+        .long("worker-threads")
+        .help("Number of async runtime worker threads. Overrides the workflow config.")
+        .env("REEARTH_FLOW_WORKER_THREADS")
+        .value_parser(clap::value_parser!(usize))
+        .required(false)
+        .display_order(15)
+}
+
+fn summary_cli_arg() -> Arg {
+    Arg::new("summary")
+        .long("summary")
+        .help("Job summary location. Defaults to summary.json in the action log location.")
+        .env("REEARTH_FLOW_SUMMARY")
+        .required(false)
+        .display_order(16)
+}
+
+fn timeout_cli_arg() -> Arg {
+    Arg::new("timeout")
+        .long("timeout")
+        .help("Cancel the job after this many seconds")
+        .env("REEARTH_FLOW_TIMEOUT")
+        .value_parser(clap::value_parser!(u64))
+        .required(false)
+        .display_order(17)
+}
+
+fn buffer_memory_limit_cli_arg() -> Arg {
+    Arg::new("buffer_memory_limit")
+        .long("buffer-memory-limit")
+        .help("Memory in bytes for buffering features before spilling them. Overrides the workflow config.")
+        .env("REEARTH_FLOW_BUFFER_MEMORY_LIMIT")
+        .value_parser(clap::value_parser!(usize))
+        .required(false)
+        .display_order(18)
+}
+
+fn no_cache_cli_arg() -> Arg {
+    Arg::new("no_cache")
+        .long("no-cache")
+        .help("Run every node instead of reusing cached outputs, e.g. when input data changed.")
+        .env("REEARTH_FLOW_NO_CACHE")
+        .action(ArgAction::SetTrue)
+        .display_order(19)
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct RunCliCommand {
     workflow_path: String,
@@ -76,6 +227,20 @@ pub struct RunCliCommand {
     dataframe_state_uri: Option<String>,
     action_log_uri: Option<String>,
     vars: HashMap<String, String>,
+    kv_store_uri: Option<String>,
+    resume: Option<String>,
+    checkpoint_interval: Option<u64>,
+    replay_job_id: Option<String>,
+    replay_edge_id: Option<String>,
+    feature_store_format: FeatureStoreFormat,
+    channel_buffer_size: Option<usize>,
+    event_hub_capacity: Option<usize>,
+    thread_pool_size: Option<usize>,
+    worker_threads: Option<usize>,
+    summary_uri: Option<String>,
+    timeout: Option<u64>,
+    buffer_memory_limit: Option<usize>,
+    no_cache: bool,
 }
 
 impl RunCliCommand {
@@ -86,6 +251,23 @@ impl RunCliCommand {
         let job_id = matches.remove_one::<String>("job_id");
         let dataframe_state_uri = matches.remove_one::<String>("dataframe_state");
         let action_log_uri = matches.remove_one::<String>("action_log");
+        let kv_store_uri = matches.remove_one::<String>("kv_store");
+        let resume = matches.remove_one::<String>("resume");
+        let checkpoint_interval = matches.remove_one::<u64>("checkpoint_interval");
+        let replay_job_id = matches.remove_one::<String>("replay_job_id");
+        let replay_edge_id = matches.remove_one::<String>("replay_edge_id");
+        let feature_store_format = match matches.remove_one::<String>("feature_store_format") {
+            Some(format) if format == "jsonl" => FeatureStoreFormat::JsonLines,
+            _ => FeatureStoreFormat::Json,
+        };
+        let channel_buffer_size = matches.remove_one::<usize>("channel_buffer_size");
+        let event_hub_capacity = matches.remove_one::<usize>("event_hub_capacity");
+        let thread_pool_size = matches.remove_one::<usize>("thread_pool_size");
+        let worker_threads = matches.remove_one::<usize>("worker_threads");
+        let summary_uri = matches.remove_one::<String>("summary");
+        let timeout = matches.remove_one::<u64>("timeout");
+        let buffer_memory_limit = matches.remove_one::<usize>("buffer_memory_limit");
+        let no_cache = matches.get_flag("no_cache");
         let vars = matches.remove_many::<String>("var");
         let vars = if let Some(vars) = vars {
             vars.into_iter()
@@ -107,6 +289,20 @@ impl RunCliCommand {
             dataframe_state_uri,
             action_log_uri,
             vars,
+            kv_store_uri,
+            resume,
+            checkpoint_interval,
+            replay_job_id,
+            replay_edge_id,
+            feature_store_format,
+            channel_buffer_size,
+            event_hub_capacity,
+            thread_pool_size,
+            worker_threads,
+            summary_uri,
+            timeout,
+            buffer_memory_limit,
+            no_cache,
         })
     }
 
@@ -125,9 +321,13 @@ impl RunCliCommand {
                 .map_err(crate::Error::init)?;
             String::from_utf8(bytes.to_vec()).map_err(crate::Error::init)?
         };
-        let mut workflow = Workflow::try_from_str(&json);
-        workflow.merge_with(self.vars.clone());
-        let job_id = match &self.job_id {
+        let mut workflow = Workflow::try_from_str(&json)
+            .map_err(|e| crate::Error::parse(e.with_path(&self.workflow_path)))?;
+        workflow
+            .merge_with(self.vars.clone())
+            .map_err(crate::Error::init)?;
+        self.override_config(&mut workflow);
+        let job_id = match self.resume.as_ref().or(self.job_id.as_ref()) {
             Some(job_id) => uuid::Uuid::from_str(job_id.as_str()).map_err(crate::Error::init)?,
             None => uuid::Uuid::new_v4(),
         };
@@ -145,28 +345,175 @@ impl RunCliCommand {
                 Uri::for_test(format!("file://{}", p).as_str())
             }
         };
-        let state_uri = {
-            let p = ProjectDirs::from("reearth", "flow", "worker").unwrap();
-            let p = p.cache_dir().to_str().unwrap();
-            let p = format!("{}/feature-store/{}", p, job_id);
-            let _ = fs::create_dir_all(Path::new(p.as_str()));
-            Uri::for_test(format!("file://{}", p).as_str())
+        let summary_uri = match &self.summary_uri {
+            Some(uri) => Uri::from_str(uri).map_err(crate::Error::init)?,
+            None => action_log_uri
+                .join("summary.json")
+                .map_err(crate::Error::init)?,
+        };
+        let state_uri = feature_store_uri(job_id.to_string().as_str())?;
+        let state =
+            Arc::new(State::new(&state_uri, &storage_resolver).map_err(crate::Error::init)?);
+        let replay = match (&self.replay_job_id, &self.replay_edge_id) {
+            (Some(replay_job_id), Some(replay_edge_id)) => {
+                let replay_job_id =
+                    uuid::Uuid::from_str(replay_job_id.as_str()).map_err(crate::Error::init)?;
+                let edge_id =
+                    uuid::Uuid::from_str(replay_edge_id.as_str()).map_err(crate::Error::init)?;
+                let replay_state_uri = feature_store_uri(replay_job_id.to_string().as_str())?;
+                let replay_state =
+                    State::new(&replay_state_uri, &storage_resolver).map_err(crate::Error::init)?;
+                Some(ReplayOptions {
+                    edge_id,
+                    state: Arc::new(replay_state),
+                })
+            }
+            _ => None,
+        };
+        // A resumed job restores the state of every node, so no node is replaced.
+        let cache = if self.no_cache || self.resume.is_some() {
+            None
+        } else {
+            let cache_state =
+                State::new(&node_cache_uri()?, &storage_resolver).map_err(crate::Error::init)?;
+            Some(NodeCache::new(Arc::new(cache_state)))
+        };
+        // Checkpointing is opt-in, as it keeps the spill files of failed jobs to resume them.
+        let checkpoint_state = if self.checkpoint_interval.is_some() || self.resume.is_some() {
+            let checkpoint_state = State::new(&cache_dir_uri("checkpoint")?, &storage_resolver)
+                .map_err(crate::Error::init)?;
+            Some(Arc::new(checkpoint_state))
+        } else {
+            None
         };
-
-        let state = Arc::new(State::new(&state_uri, &storage_resolver).unwrap());
 
         let logger_factory = Arc::new(LoggerFactory::new(
             create_root_logger(action_log_uri.path()),
             action_log_uri.path(),
         ));
-        Runner::run(
+        let summary = Runner::run_with_options(
             job_id.to_string(),
             workflow,
             ALL_ACTION_FACTORIES.clone(),
             logger_factory,
             storage_resolver,
             state,
+            RunnerOptions {
+                checkpoint_state,
+                checkpoint_interval: self.checkpoint_interval.map(Duration::from_secs),
+                resume: self.resume.is_some(),
+                replay,
+                cache,
+                feature_store_format: self.feature_store_format,
+                summary: Some(summary_uri),
+                event_handlers: vec![],
+                timeout: self.timeout.map(Duration::from_secs),
+                cancel_on_signal: true,
+            },
         );
-        Ok(())
+        match summary.error {
+            Some(error) => Err(crate::Error::run(error)),
+            None => Ok(()),
+        }
+    }
+
+    /// Replaces the workflow config with the options given on the command line.
+    fn override_config(&self, workflow: &mut Workflow) {
+        if let Some(kv_store_uri) = &self.kv_store_uri {
+            workflow.config_mut().kv_store = Some(kv_store_uri.clone());
+        }
+        if let Some(channel_buffer_size) = self.channel_buffer_size {
+            workflow.config_mut().channel_buffer_size = Some(channel_buffer_size);
+        }
+        if let Some(event_hub_capacity) = self.event_hub_capacity {
+            workflow.config_mut().event_hub_capacity = Some(event_hub_capacity);
+        }
+        if let Some(thread_pool_size) = self.thread_pool_size {
+            workflow.config_mut().thread_pool_size = Some(thread_pool_size);
+        }
+        if let Some(worker_threads) = self.worker_threads {
+            workflow.config_mut().worker_threads = Some(worker_threads);
+        }
+        if let Some(buffer_memory_limit) = self.buffer_memory_limit {
+            workflow.config_mut().buffer_memory_limit = Some(buffer_memory_limit);
+        }
+    }
+}
+
+fn feature_store_uri(job_id: &str) -> crate::Result<Uri> {
+    cache_dir_uri(&format!("feature-store/{}", job_id))
+}
+
+pub(crate) fn node_cache_uri() -> crate::Result<Uri> {
+    cache_dir_uri("node-cache")
+}
+
+/// A directory in the cache directory of the worker, created if missing.
+fn cache_dir_uri(name: &str) -> crate::Result<Uri> {
+    let p = ProjectDirs::from("reearth", "flow", "worker")
+        .ok_or(crate::Error::init("No cache directory"))?;
+    let p = p
+        .cache_dir()
+        .to_str()
+        .ok_or(crate::Error::init("Invalid cache directory"))?;
+    let p = format!("{}/{}", p, name);
+    fs::create_dir_all(Path::new(p.as_str())).map_err(crate::Error::init)?;
+    Ok(Uri::for_test(format!("file://{}", p).as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workflow() -> Workflow {
+        Workflow::try_from_str(
+            r#"{
+                "id": "a7fc8f35-b84f-496b-a2cb-65be3bfec285",
+                "name": "test",
+                "entryGraphId": "3e3450c8-2344-4728-afa9-5fdb81eec33a",
+                "config": {"channelBufferSize": 5, "threadPoolSize": 4},
+                "graphs": []
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn parse(args: &[&str]) -> RunCliCommand {
+        let matches = build_run_command()
+            .try_get_matches_from([&["run", "--workflow", "workflow.json"], args].concat())
+            .unwrap();
+        RunCliCommand::parse_cli_args(matches).unwrap()
+    }
+
+    #[test]
+    fn test_executor_options_override_config() {
+        let mut workflow = workflow();
+        parse(&[
+            "--channel-buffer-size",
+            "10",
+            "--event-hub-capacity",
+            "64",
+            "--thread-pool-size",
+            "8",
+            "--worker-threads",
+            "2",
+        ])
+        .override_config(&mut workflow);
+        let config = workflow.config.unwrap();
+        assert_eq!(config.channel_buffer_size, Some(10));
+        assert_eq!(config.event_hub_capacity, Some(64));
+        assert_eq!(config.thread_pool_size, Some(8));
+        assert_eq!(config.worker_threads, Some(2));
+    }
+
+    #[test]
+    fn test_config_is_kept_without_executor_options() {
+        let mut workflow = workflow();
+        parse(&[]).override_config(&mut workflow);
+        let config = workflow.config.unwrap();
+        assert_eq!(config.channel_buffer_size, Some(5));
+        assert_eq!(config.event_hub_capacity, None);
+        assert_eq!(config.thread_pool_size, Some(4));
+        assert_eq!(config.worker_threads, None);
     }
 }
@@ -0,0 +1,91 @@
+use std::io;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use reearth_flow_runtime::validation::validate_workflow;
+use reearth_flow_types::Workflow;
+use tracing::debug;
+
+use reearth_flow_common::uri::Uri;
+use reearth_flow_storage::resolve;
+
+use crate::factory::ALL_ACTION_FACTORIES;
+
+pub fn build_validate_command() -> Command {
+    Command::new("validate")
+        .about("Validate a workflow.")
+        .long_about("Validate a workflow without running it.")
+        .arg(workflow_cli_arg())
+        .arg(json_cli_arg())
+}
+
+fn workflow_cli_arg() -> Arg {
+    Arg::new("workflow")
+        .long("workflow")
+        .help("Workflow file location. Use '-' to read from stdin.")
+        .env("REEARTH_FLOW_WORKFLOW")
+        .required(true)
+        .display_order(1)
+}
+
+fn json_cli_arg() -> Arg {
+    Arg::new("json")
+        .long("json")
+        .help("Print diagnostics as JSON")
+        .action(ArgAction::SetTrue)
+        .display_order(2)
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct ValidateCliCommand {
+    workflow_path: String,
+    json: bool,
+}
+
+impl ValidateCliCommand {
+    pub fn parse_cli_args(mut matches: ArgMatches) -> crate::Result<Self> {
+        let workflow_path = matches
+            .remove_one::<String>("workflow")
+            .ok_or(crate::Error::init("No workflow uri provided"))?;
+        let json = matches.get_flag("json");
+        Ok(ValidateCliCommand {
+            workflow_path,
+            json,
+        })
+    }
+
+    pub fn execute(&self) -> crate::Result<()> {
+        debug!(args = ?self, "validate");
+        let storage_resolver = resolve::StorageResolver::new();
+        let json = if self.workflow_path == "-" {
+            io::read_to_string(io::stdin()).map_err(crate::Error::init)?
+        } else {
+            let path = Uri::for_test(self.workflow_path.as_str());
+            let storage = storage_resolver
+                .resolve(&path)
+                .map_err(crate::Error::init)?;
+            let bytes = storage
+                .get_sync(path.path().as_path())
+                .map_err(crate::Error::init)?;
+            String::from_utf8(bytes.to_vec()).map_err(crate::Error::init)?
+        };
+        let workflow = Workflow::try_from_str(&json)
+            .map_err(|e| crate::Error::parse(e.with_path(&self.workflow_path)))?;
+        let diagnostics = validate_workflow(&workflow, &ALL_ACTION_FACTORIES, &storage_resolver);
+        if self.json {
+            let output = serde_json::to_string_pretty(&diagnostics).map_err(crate::Error::run)?;
+            println!("{}", output);
+        } else {
+            for diagnostic in diagnostics.iter() {
+                println!("{}", diagnostic);
+            }
+        }
+        let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+        if errors > 0 {
+            return Err(crate::Error::run(format!(
+                "Workflow has {} validation error(s)",
+                errors
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use encoding_rs::{Encoding, BIG5, EUC_KR, GBK, SHIFT_JIS, UTF_8};
+
+/// Resolves the name of an encoding, as a WHATWG label or a Windows code page as found in
+/// `.cpg` files, e.g. `932`.
+pub fn encoding_for_label(label: &str) -> crate::Result<&'static Encoding> {
+    let label = label.trim();
+    let code_page = label
+        .to_ascii_lowercase()
+        .trim_start_matches("cp")
+        .parse::<u32>()
+        .ok();
+    let encoding = match code_page {
+        Some(932) => Some(SHIFT_JIS),
+        Some(936) => Some(GBK),
+        Some(949) => Some(EUC_KR),
+        Some(950) => Some(BIG5),
+        Some(65001) => Some(UTF_8),
+        Some(code_page) => Encoding::for_label(format!("windows-{}", code_page).as_bytes()),
+        None => Encoding::for_label(label.as_bytes()),
+    };
+    encoding.ok_or_else(|| crate::Error::encoding(format!("Unknown encoding: {}", label)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_for_label() {
+        assert_eq!(encoding_for_label("932").unwrap(), SHIFT_JIS);
+        assert_eq!(encoding_for_label("CP932").unwrap(), SHIFT_JIS);
+        assert_eq!(encoding_for_label("UTF-8").unwrap(), UTF_8);
+        assert_eq!(encoding_for_label("1252").unwrap().name(), "windows-1252");
+        assert!(encoding_for_label("unknown").is_err());
+    }
+}
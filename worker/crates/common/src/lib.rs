@@ -6,6 +6,9 @@ pub enum Error {
     #[error("CSVUtilError: {0}")]
     Csv(String),
 
+    #[error("EncodingError: {0}")]
+    Encoding(String),
+
     #[error("FSError: {0}")]
     Fs(String),
 
@@ -37,6 +40,10 @@ impl Error {
         Self::Csv(message.to_string())
     }
 
+    pub fn encoding<T: ToString>(message: T) -> Self {
+        Self::Encoding(message.to_string())
+    }
+
     pub fn fs<T: ToString>(message: T) -> Self {
         Self::Fs(message.to_string())
     }
@@ -68,6 +75,7 @@ pub mod collection;
 pub mod color;
 pub mod csv;
 pub mod dir;
+pub mod encoding;
 pub mod fs;
 pub mod future;
 pub mod json;
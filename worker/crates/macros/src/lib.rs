@@ -5,7 +5,7 @@ extern crate proc_macro;
 use proc_macro2::TokenStream;
 use quote::quote;
 use std::env;
-use syn::DeriveInput;
+use syn::{DeriveInput, Expr, LitBool, LitStr, Path, Token, Type};
 
 fn debug_print_generated(ast: &DeriveInput, toks: &TokenStream) {
     let debug = env::var("FLOW_MACRO_DEBUG");
@@ -20,24 +20,420 @@ fn debug_print_generated(ast: &DeriveInput, toks: &TokenStream) {
     }
 }
 
-#[proc_macro_derive(PropertySchema)]
-pub fn property_schema(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+/// Implements `reearth_flow_runtime::node::ProcessorFactory`.
+///
+/// The factory is described with the `factory` attribute:
+///
+/// ```ignore
+/// #[derive(Debug, Clone, Default, ProcessorFactory)]
+/// #[factory(
+///     name = "RhaiCaller",
+///     description = "Calls Rhai script",
+///     categories = ["Feature"],
+///     input_ports = [DEFAULT_PORT],
+///     output_ports = [DEFAULT_PORT],
+///     param = RhaiCallerParam,
+///     error = FeatureProcessorError::RhaiCallerFactory,
+/// )]
+/// pub struct RhaiCallerFactory;
+/// ```
+///
+/// `name` is a string literal or a `&str` constant. Ports are either a list of `Port` statics or
+/// an expression returning a `Vec<Port>`. When `param` is given, `with` is deserialized into it,
+/// failures are mapped with `error`, and the schema of the parameters is derived from it unless
+/// `schema = false`. The processor itself is built by an inherent method of the factory:
+///
+/// ```ignore
+/// fn build_processor(&self, ctx: NodeContext, event_hub: EventHub, action: String, params: P)
+///     -> Result<Box<dyn Processor>, BoxedError>
+/// ```
+///
+/// where `params` is omitted without `param`.
+#[proc_macro_derive(ProcessorFactory, attributes(factory))]
+pub fn processor_factory(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
-    let name = &ast.ident;
-    let gen = quote! {
-        impl TryFrom<reearth_flow_workflow::graph::NodeProperty> for #name {
-            type Error = anyhow::Error;
+    let gen = FactoryAttrs::parse(&ast)
+        .and_then(|attrs| attrs.processor_factory(&ast))
+        .unwrap_or_else(syn::Error::into_compile_error);
+    debug_print_generated(&ast, &gen);
+    gen.into()
+}
 
-            fn try_from(node_property: reearth_flow_workflow::graph::NodeProperty) -> Result<Self, anyhow::Error> {
-                serde_json::from_value(Value::Object(node_property)).map_err(|e| {
-                    anyhow!(
-                        "Failed to convert NodeProperty to PropertySchema with {}",
-                        e
-                    )
-                })
-            }
-        }
-    };
+/// Implements `reearth_flow_runtime::node::SinkFactory`. Takes the same attributes as
+/// `ProcessorFactory` without `output_ports`, and calls `build_sink` instead of
+/// `build_processor`.
+#[proc_macro_derive(SinkFactory, attributes(factory))]
+pub fn sink_factory(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+    let gen = FactoryAttrs::parse(&ast)
+        .and_then(|attrs| attrs.sink_factory(&ast))
+        .unwrap_or_else(syn::Error::into_compile_error);
+    debug_print_generated(&ast, &gen);
+    gen.into()
+}
+
+/// Implements `reearth_flow_runtime::node::SourceFactory`. Takes the same attributes as
+/// `ProcessorFactory` without `input_ports`, and calls `build_source` instead of
+/// `build_processor`, with the state of the source as last argument.
+#[proc_macro_derive(SourceFactory, attributes(factory))]
+pub fn source_factory(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+    let gen = FactoryAttrs::parse(&ast)
+        .and_then(|attrs| attrs.source_factory(&ast))
+        .unwrap_or_else(syn::Error::into_compile_error);
     debug_print_generated(&ast, &gen);
     gen.into()
 }
+
+#[derive(Default)]
+struct FactoryAttrs {
+    name: Option<Expr>,
+    description: Option<LitStr>,
+    categories: Vec<LitStr>,
+    input_ports: Option<Expr>,
+    output_ports: Option<Expr>,
+    param: Option<Type>,
+    schema: Option<LitBool>,
+    error: Option<Path>,
+}
+
+impl FactoryAttrs {
+    fn parse(ast: &DeriveInput) -> syn::Result<Self> {
+        let mut attrs = Self::default();
+        for attr in ast
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("factory"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    attrs.name = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("description") {
+                    attrs.description = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("categories") {
+                    let value = meta.value()?;
+                    let content;
+                    syn::bracketed!(content in value);
+                    attrs.categories = content
+                        .parse_terminated(|input| input.parse::<LitStr>(), Token![,])?
+                        .into_iter()
+                        .collect();
+                } else if meta.path.is_ident("input_ports") {
+                    attrs.input_ports = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("output_ports") {
+                    attrs.output_ports = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("param") {
+                    attrs.param = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("schema") {
+                    attrs.schema = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("error") {
+                    attrs.error = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("unsupported factory attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if attrs.name.is_none() {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "missing `#[factory(name = \"...\")]`",
+            ));
+        }
+        if attrs.param.is_some() && attrs.error.is_none() {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "`param` requires `error`, the error variant of invalid parameters",
+            ));
+        }
+        Ok(attrs)
+    }
+
+    /// Methods shared by all factories.
+    fn common(&self) -> TokenStream {
+        let name = &self.name;
+        let description = self.description.as_ref().map(|description| {
+            quote! {
+                fn description(&self) -> &str {
+                    #description
+                }
+            }
+        });
+        let schema = match &self.param {
+            Some(param) if self.schema.as_ref().map_or(true, LitBool::value) => {
+                quote! { Some(::schemars::schema_for!(#param)) }
+            }
+            _ => quote! { None },
+        };
+        let categories = &self.categories;
+        quote! {
+            fn name(&self) -> &str {
+                #name
+            }
+
+            #description
+
+            fn parameter_schema(&self) -> Option<::schemars::schema::RootSchema> {
+                #schema
+            }
+
+            fn categories(&self) -> &[&'static str] {
+                &[#(#categories),*]
+            }
+        }
+    }
+
+    fn ports(ident: &syn::Ident, ports: &Option<Expr>, key: &str) -> syn::Result<TokenStream> {
+        match ports {
+            Some(Expr::Array(array)) => {
+                let ports = array.elems.iter();
+                Ok(quote! { vec![#(#ports.clone()),*] })
+            }
+            Some(ports) => Ok(quote! { #ports }),
+            None => Err(syn::Error::new_spanned(
+                ident,
+                format!("missing `#[factory({} = [...])]`", key),
+            )),
+        }
+    }
+
+    /// Returns the name of the `with` argument, the statements deserializing it into `params`
+    /// and the arguments passed on to the build method of the factory.
+    fn params(&self) -> (TokenStream, TokenStream, TokenStream) {
+        match (&self.param, &self.error) {
+            (Some(param), Some(error)) => (
+                quote! { with },
+                quote! {
+                    let params: #param = ::reearth_flow_runtime::node::parse_params(with)
+                        .map_err(#error)?;
+                },
+                quote! { params, },
+            ),
+            _ => (quote! { _with }, quote! {}, quote! {}),
+        }
+    }
+
+    fn processor_factory(&self, ast: &DeriveInput) -> syn::Result<TokenStream> {
+        let ident = &ast.ident;
+        let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+        let common = self.common();
+        let input_ports = Self::ports(ident, &self.input_ports, "input_ports")?;
+        let output_ports = Self::ports(ident, &self.output_ports, "output_ports")?;
+        let (with, parse, params) = self.params();
+        Ok(quote! {
+            impl #impl_generics ::reearth_flow_runtime::node::ProcessorFactory for #ident #ty_generics #where_clause {
+                #common
+
+                fn get_input_ports(&self) -> Vec<::reearth_flow_runtime::node::Port> {
+                    #input_ports
+                }
+
+                fn get_output_ports(&self) -> Vec<::reearth_flow_runtime::node::Port> {
+                    #output_ports
+                }
+
+                fn build(
+                    &self,
+                    ctx: ::reearth_flow_runtime::executor_operation::NodeContext,
+                    event_hub: ::reearth_flow_runtime::event::EventHub,
+                    action: String,
+                    #with: Option<::std::collections::HashMap<String, ::serde_json::Value>>,
+                ) -> Result<
+                    Box<dyn ::reearth_flow_runtime::node::Processor>,
+                    ::reearth_flow_runtime::errors::BoxedError,
+                > {
+                    #parse
+                    self.build_processor(ctx, event_hub, action, #params)
+                }
+            }
+        })
+    }
+
+    fn sink_factory(&self, ast: &DeriveInput) -> syn::Result<TokenStream> {
+        let ident = &ast.ident;
+        let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+        let common = self.common();
+        let input_ports = Self::ports(ident, &self.input_ports, "input_ports")?;
+        let (with, parse, params) = self.params();
+        Ok(quote! {
+            impl #impl_generics ::reearth_flow_runtime::node::SinkFactory for #ident #ty_generics #where_clause {
+                #common
+
+                fn get_input_ports(&self) -> Vec<::reearth_flow_runtime::node::Port> {
+                    #input_ports
+                }
+
+                fn prepare(&self) -> Result<(), ::reearth_flow_runtime::errors::BoxedError> {
+                    Ok(())
+                }
+
+                fn build(
+                    &self,
+                    ctx: ::reearth_flow_runtime::executor_operation::NodeContext,
+                    event_hub: ::reearth_flow_runtime::event::EventHub,
+                    action: String,
+                    #with: Option<::std::collections::HashMap<String, ::serde_json::Value>>,
+                ) -> Result<
+                    Box<dyn ::reearth_flow_runtime::node::Sink>,
+                    ::reearth_flow_runtime::errors::BoxedError,
+                > {
+                    #parse
+                    self.build_sink(ctx, event_hub, action, #params)
+                }
+            }
+        })
+    }
+
+    fn source_factory(&self, ast: &DeriveInput) -> syn::Result<TokenStream> {
+        let ident = &ast.ident;
+        let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+        let common = self.common();
+        let output_ports = Self::ports(ident, &self.output_ports, "output_ports")?;
+        let (with, parse, params) = self.params();
+        Ok(quote! {
+            impl #impl_generics ::reearth_flow_runtime::node::SourceFactory for #ident #ty_generics #where_clause {
+                #common
+
+                fn get_output_ports(&self) -> Vec<::reearth_flow_runtime::node::Port> {
+                    #output_ports
+                }
+
+                fn build(
+                    &self,
+                    ctx: ::reearth_flow_runtime::executor_operation::NodeContext,
+                    event_hub: ::reearth_flow_runtime::event::EventHub,
+                    action: String,
+                    #with: Option<::std::collections::HashMap<String, ::serde_json::Value>>,
+                    state: Option<Vec<u8>>,
+                ) -> Result<
+                    Box<dyn ::reearth_flow_runtime::node::Source>,
+                    ::reearth_flow_runtime::errors::BoxedError,
+                > {
+                    #parse
+                    self.build_source(ctx, event_hub, action, #params state)
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn parse_error(ast: DeriveInput) -> String {
+        match FactoryAttrs::parse(&ast) {
+            Ok(_) => panic!("{} was parsed", ast.ident),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_processor_factory() {
+        let ast: DeriveInput = parse_quote! {
+            #[factory(
+                name = "Echo",
+                description = "Sends features as they are",
+                categories = ["Feature"],
+                input_ports = [DEFAULT_PORT],
+                output_ports = ports(),
+                param = EchoParam,
+                error = Error::Factory,
+            )]
+            struct EchoFactory;
+        };
+        let gen = FactoryAttrs::parse(&ast)
+            .and_then(|attrs| attrs.processor_factory(&ast))
+            .unwrap();
+        let item: syn::ItemImpl = syn::parse2(gen.clone()).unwrap();
+        let (_, path, _) = item.trait_.unwrap();
+        assert_eq!(
+            quote!(#path).to_string(),
+            quote!(::reearth_flow_runtime::node::ProcessorFactory).to_string()
+        );
+        let gen = gen.to_string();
+        for expected in [
+            quote!(vec![DEFAULT_PORT.clone()]),
+            quote!(
+                fn get_output_ports(&self) -> Vec<::reearth_flow_runtime::node::Port> {
+                    ports()
+                }
+            ),
+            quote!(::schemars::schema_for!(EchoParam)),
+            quote!(::reearth_flow_runtime::node::parse_params(with).map_err(Error::Factory)?;),
+            quote!(self.build_processor(ctx, event_hub, action, params,)),
+        ] {
+            assert!(gen.contains(&expected.to_string()), "{}", gen);
+        }
+    }
+
+    #[test]
+    fn test_source_factory_without_param() {
+        let ast: DeriveInput = parse_quote! {
+            #[factory(name = SOURCE_ACTION, output_ports = [])]
+            struct EmptySourceFactory;
+        };
+        let gen = FactoryAttrs::parse(&ast)
+            .and_then(|attrs| attrs.source_factory(&ast))
+            .unwrap()
+            .to_string();
+        for expected in [
+            quote!(
+                fn name(&self) -> &str {
+                    SOURCE_ACTION
+                }
+            ),
+            quote!(_with: Option<::std::collections::HashMap<String, ::serde_json::Value>>),
+            quote!(self.build_source(ctx, event_hub, action, state)),
+            quote!(
+                fn parameter_schema(&self) -> Option<::schemars::schema::RootSchema> {
+                    None
+                }
+            ),
+        ] {
+            assert!(gen.contains(&expected.to_string()), "{}", gen);
+        }
+    }
+
+    #[test]
+    fn test_invalid_attributes() {
+        assert_eq!(
+            parse_error(parse_quote! {
+                #[factory(input_ports = [DEFAULT_PORT])]
+                struct NamelessFactory;
+            }),
+            "missing `#[factory(name = \"...\")]`"
+        );
+        assert_eq!(
+            parse_error(parse_quote! {
+                #[factory(name = "Sink", param = SinkParam)]
+                struct SinkFactory;
+            }),
+            "`param` requires `error`, the error variant of invalid parameters"
+        );
+        assert_eq!(
+            parse_error(parse_quote! {
+                #[factory(name = "Sink", ports = [DEFAULT_PORT])]
+                struct SinkFactory;
+            }),
+            "unsupported factory attribute"
+        );
+    }
+
+    #[test]
+    fn test_missing_ports() {
+        let ast: DeriveInput = parse_quote! {
+            #[factory(name = "Sink")]
+            struct PortlessFactory;
+        };
+        let error = FactoryAttrs::parse(&ast)
+            .and_then(|attrs| attrs.sink_factory(&ast))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing `#[factory(input_ports = [...])]`"
+        );
+    }
+}
@@ -21,6 +21,8 @@ pub enum OrchestrationError {
     ConnectionNotFound(String),
     #[error("Pipeline validation failed")]
     PipelineValidationError,
+    #[error("Workflow validation failed: {0}")]
+    WorkflowValidation(String),
     #[error(transparent)]
     ExecutionError(#[from] ExecutionError),
     #[error("Output table {0} not used in any sink")]
@@ -31,6 +33,16 @@ pub enum OrchestrationError {
     EmptySinks,
     #[error("Failed to read organisation name. Error: {0}")]
     FailedToReadOrganisationName(#[source] io::Error),
+    #[error("Failed to initialize kv store: {0}")]
+    KvStore(String),
+    #[error("Failed to initialize spill storage: {0}")]
+    Spill(String),
+    #[error("Checkpoint error: {0}")]
+    Checkpoint(String),
+    #[error("Failed to write job summary: {0}")]
+    Summary(String),
+    #[error("Job was cancelled")]
+    Cancelled,
     #[error("Command was aborted")]
     Aborted,
     #[error("This feature is only supported in enterprise: {0}")]
@@ -2,14 +2,17 @@ use std::{collections::HashMap, sync::Arc};
 
 use reearth_flow_common::future::SharedFuture;
 use reearth_flow_runtime::{
+    errors::ExecutionError,
     executor::dag_executor::DagExecutor,
     executor_operation::{ExecutorOptions, NodeContext},
     node::{NodeKind, RouterFactory},
     shutdown::ShutdownReceiver,
+    validation::validate_workflow,
 };
 use reearth_flow_state::State;
 use reearth_flow_types::workflow::Workflow;
 use tokio::runtime::Runtime;
+use tracing::warn;
 
 use crate::errors::OrchestrationError;
 
@@ -28,6 +31,22 @@ impl Executor {
             "Router".to_string(),
             NodeKind::Processor(Box::<RouterFactory>::default()),
         );
+        let (errors, warnings): (Vec<_>, Vec<_>) =
+            validate_workflow(&workflow, &factories, &ctx.storage_resolver)
+                .into_iter()
+                .partition(|diagnostic| diagnostic.is_error());
+        for warning in warnings {
+            warn!("{}", warning);
+        }
+        if !errors.is_empty() {
+            return Err(OrchestrationError::WorkflowValidation(
+                errors
+                    .iter()
+                    .map(|error| error.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ));
+        }
         let executor = DagExecutor::new(
             ctx,
             workflow.entry_graph_id,
@@ -55,10 +74,11 @@ pub fn run_dag_executor(
         ctx.storage_resolver.clone(),
         ctx.logger.clone(),
         ctx.kv_store.clone(),
+        ctx.spill_manager.clone(),
         state,
     ));
-    join_handle
-        .unwrap()
-        .join()
-        .map_err(OrchestrationError::ExecutionError)
+    join_handle.unwrap().join().map_err(|e| match e {
+        ExecutionError::Interrupted => OrchestrationError::Cancelled,
+        e => OrchestrationError::ExecutionError(e),
+    })
 }
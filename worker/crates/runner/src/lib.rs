@@ -2,3 +2,4 @@ pub mod errors;
 pub mod executor;
 pub mod orchestrator;
 pub mod runner;
+pub mod summary;
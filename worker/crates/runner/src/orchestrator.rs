@@ -1,22 +1,39 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::stream::FuturesUnordered;
 use futures::{FutureExt, StreamExt};
 use reearth_flow_action_log::factory::LoggerFactory;
+use reearth_flow_common::uri::Uri;
 use reearth_flow_eval_expr::engine::Engine;
+use reearth_flow_runtime::checkpoint::{Checkpoint, CheckpointOptions};
+use reearth_flow_runtime::event::{Event, EventHub};
 use reearth_flow_runtime::executor_operation::{ExecutorOptions, NodeContext};
-use reearth_flow_runtime::kvs::create_kv_store;
-use reearth_flow_runtime::node::NodeKind;
+use reearth_flow_runtime::kvs::{create_kv_store, create_persistent_kv_store, KvStore};
+use reearth_flow_runtime::node::{NodeHandle, NodeKind};
 use reearth_flow_runtime::shutdown::ShutdownReceiver;
+use reearth_flow_runtime::spill::{SpillManager, DEFAULT_MEMORY_LIMIT};
+use reearth_flow_runtime::stats::JobStats;
 use reearth_flow_state::State;
 use reearth_flow_storage::resolve::StorageResolver;
-use reearth_flow_types::workflow::Workflow;
+use reearth_flow_types::workflow::{Workflow, WorkflowConfig};
 use tokio::runtime::Runtime;
 use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
 use crate::errors::OrchestrationError;
 use crate::executor::{run_dag_executor, Executor};
+use crate::runner::RunnerOptions;
+
+const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_CHANNEL_BUFFER_SIZE: usize = 20;
+// Every node sends a progress event each second, so the hub holds a few seconds of events of a
+// large workflow before slow handlers lag behind and miss some.
+const DEFAULT_EVENT_HUB_CAPACITY: usize = 4096;
+const DEFAULT_THREAD_POOL_SIZE: usize = 30;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Clone)]
 pub struct Orchestrator {
@@ -31,30 +48,75 @@ impl Orchestrator {
     #[allow(clippy::too_many_arguments)]
     pub async fn run_apps(
         &self,
-        _job_id: String,
-        workflow: Workflow,
+        job_id: String,
+        mut workflow: Workflow,
         factories: HashMap<String, NodeKind>,
         shutdown: ShutdownReceiver,
         logger_factory: Arc<LoggerFactory>,
         storage_resolver: Arc<StorageResolver>,
         state: Arc<State>,
+        runner_options: RunnerOptions,
+        stats: Arc<JobStats>,
+        event_hub: EventHub,
     ) -> Result<(), OrchestrationError> {
         let executor = Executor {};
+        let checkpoint_state = runner_options.checkpoint_state.clone();
+        let resume_from = match &checkpoint_state {
+            Some(checkpoint_state) if runner_options.resume => Some(
+                Checkpoint::load(checkpoint_state, job_id.as_str())
+                    .await
+                    .map_err(|e| {
+                        OrchestrationError::Checkpoint(format!(
+                            "No checkpoint to resume job {} from: {}",
+                            job_id, e
+                        ))
+                    })?,
+            ),
+            _ => None,
+        };
+        let config = workflow.config.clone().unwrap_or_default();
         let options = ExecutorOptions {
-            channel_buffer_sz: 20,
-            event_hub_capacity: 20,
-            error_threshold: None,
-            thread_pool_size: 30,
+            channel_buffer_sz: channel_buffer_size(&config),
+            event_hub,
+            error_threshold: config.error_threshold,
+            thread_pool_size: thread_pool_size(&config),
+            preserve_order: config.preserve_order.unwrap_or_default(),
+            feature_store_format: runner_options.feature_store_format,
+            checkpoint: checkpoint_state
+                .as_ref()
+                .map(|checkpoint_state| CheckpointOptions {
+                    job_id: job_id.clone(),
+                    interval: runner_options
+                        .checkpoint_interval
+                        .unwrap_or(DEFAULT_CHECKPOINT_INTERVAL),
+                    state: Arc::clone(checkpoint_state),
+                }),
+            resume_from,
+            replay: runner_options.replay.clone(),
+            cache: runner_options.cache.clone(),
+            stats,
         };
+        workflow
+            .resolve_parameters()
+            .map_err(|e| OrchestrationError::WorkflowValidation(e.to_string()))?;
         let expr_engine = Engine::new();
         if let Some(with) = &workflow.with {
             expr_engine.append(with);
         }
+        let kv_store = create_workflow_kv_store(&workflow, &storage_resolver)?;
+        let mut spill_manager = create_spill_manager(&workflow, &job_id, &storage_resolver)?;
+        // Checkpoints refer to the spill files, so they are kept until the job completes, or as
+        // long as a failed job can be resumed from its checkpoint.
+        if checkpoint_state.is_some() {
+            spill_manager = spill_manager.keep_files();
+        }
+        let spill_manager = Arc::new(spill_manager);
         let ctx = NodeContext {
             expr_engine: Arc::new(expr_engine),
             storage_resolver: storage_resolver.clone(),
             logger: logger_factory.clone(),
-            kv_store: Arc::new(create_kv_store()),
+            kv_store: Arc::new(kv_store),
+            spill_manager: Arc::clone(&spill_manager),
         };
         let dag_executor = executor
             .create_dag_executor(ctx.clone(), workflow, factories, options)
@@ -74,10 +136,37 @@ impl Orchestrator {
         let mut futures = FuturesUnordered::new();
         futures.push(flatten_join_handle(pipeline_future).boxed());
 
-        while let Some(result) = futures.next().await {
-            result?;
+        // A job whose sources were stopped early by the shutdown fails as cancelled, keeping its
+        // checkpoint to resume it later.
+        let result = async {
+            while let Some(result) = futures.next().await {
+                result?;
+            }
+            Ok::<_, OrchestrationError>(())
         }
-        Ok(())
+        .await;
+
+        if let Some(checkpoint_state) = checkpoint_state {
+            let resumable = match &result {
+                // The job has completed, so there is nothing left to resume.
+                Ok(()) => {
+                    let _ = Checkpoint::delete(&checkpoint_state, job_id.as_str()).await;
+                    false
+                }
+                Err(_) => Checkpoint::load(&checkpoint_state, job_id.as_str())
+                    .await
+                    .is_ok(),
+            };
+            if resumable {
+                info!(
+                    "Keeping the spill files of job {} until it is resumed to completion",
+                    job_id
+                );
+            } else if let Err(e) = spill_manager.remove_files() {
+                warn!("Failed to remove spill files of job {}: {}", job_id, e);
+            }
+        }
+        result
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -90,21 +179,142 @@ impl Orchestrator {
         logger_factory: Arc<LoggerFactory>,
         storage_resolver: Arc<StorageResolver>,
         state: Arc<State>,
+        runner_options: RunnerOptions,
+        stats: Arc<JobStats>,
     ) -> Result<(), OrchestrationError> {
         let pipeline_shutdown = shutdown.clone();
-        self.run_apps(
+        let event_hub = EventHub::new(event_hub_capacity(
+            workflow
+                .config
+                .as_ref()
+                .unwrap_or(&WorkflowConfig::default()),
+        ));
+        // Subscribe before the first event is sent, so that handlers see the whole job.
+        let dispatchers = runner_options
+            .event_handlers
+            .iter()
+            .map(|handler| {
+                self.runtime.spawn(EventHub::dispatch(
+                    event_hub.subscribe(),
+                    Arc::clone(handler),
+                ))
+            })
+            .collect::<Vec<_>>();
+        let workflow_id = workflow.id;
+        event_hub.send(Event::JobStarted {
+            job_id: job_id.clone(),
+            workflow_id,
+        });
+        let progress = self
+            .runtime
+            .spawn(report_progress(event_hub.clone(), Arc::clone(&stats)));
+        let result = self
+            .run_apps(
+                job_id.clone(),
+                workflow,
+                factories,
+                pipeline_shutdown,
+                logger_factory,
+                storage_resolver,
+                state,
+                runner_options,
+                stats,
+                event_hub.clone(),
+            )
+            .await;
+        progress.abort();
+        event_hub.send(Event::JobFinished {
             job_id,
-            workflow,
-            factories,
-            pipeline_shutdown,
-            logger_factory,
-            storage_resolver,
-            state,
-        )
-        .await
+            workflow_id,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        for dispatcher in dispatchers {
+            let _ = dispatcher.await;
+        }
+        result
+    }
+}
+
+/// Periodically sends the number of features each node has received and sent so far.
+async fn report_progress(event_hub: EventHub, stats: Arc<JobStats>) {
+    let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+    loop {
+        interval.tick().await;
+        for node in stats.summary() {
+            event_hub.send(Event::NodeProgress {
+                node: NodeHandle::new(node.id),
+                features_in: node.features_in.values().sum(),
+                features_out: node.features_out.values().sum(),
+            });
+        }
     }
 }
 
+fn create_workflow_kv_store(
+    workflow: &Workflow,
+    storage_resolver: &StorageResolver,
+) -> Result<Box<dyn KvStore>, OrchestrationError> {
+    let Some(kv_store) = workflow
+        .config
+        .as_ref()
+        .and_then(|config| config.kv_store.as_ref())
+    else {
+        return Ok(create_kv_store());
+    };
+    let uri = Uri::from_str(kv_store).map_err(|e| OrchestrationError::KvStore(e.to_string()))?;
+    create_persistent_kv_store(&uri, storage_resolver)
+        .map_err(|e| OrchestrationError::KvStore(e.to_string()))
+}
+
+/// Spill files go to a directory of the job in the temporary directory, unless the workflow sets
+/// another location.
+fn create_spill_manager(
+    workflow: &Workflow,
+    job_id: &str,
+    storage_resolver: &StorageResolver,
+) -> Result<SpillManager, OrchestrationError> {
+    let config = workflow.config.clone().unwrap_or_default();
+    let root = match config.spill_storage {
+        Some(root) => format!("{}/{}", root.trim_end_matches('/'), job_id),
+        None => format!(
+            "file://{}/reearth-flow/spill/{}",
+            std::env::temp_dir().display(),
+            job_id
+        ),
+    };
+    let root = Uri::from_str(&root).map_err(|e| OrchestrationError::Spill(e.to_string()))?;
+    SpillManager::new(
+        config.buffer_memory_limit.unwrap_or(DEFAULT_MEMORY_LIMIT),
+        &root,
+        storage_resolver,
+    )
+    .map_err(|e| OrchestrationError::Spill(e.to_string()))
+}
+
+/// Capacity of the channels between nodes. Channels panic without any capacity.
+fn channel_buffer_size(config: &WorkflowConfig) -> usize {
+    config
+        .channel_buffer_size
+        .unwrap_or(DEFAULT_CHANNEL_BUFFER_SIZE)
+        .max(1)
+}
+
+/// Upper bound of the threads of each processor node.
+fn thread_pool_size(config: &WorkflowConfig) -> usize {
+    config
+        .thread_pool_size
+        .unwrap_or(DEFAULT_THREAD_POOL_SIZE)
+        .max(1)
+}
+
+/// Capacity of the event hub. A broadcast channel panics without any capacity.
+fn event_hub_capacity(config: &WorkflowConfig) -> usize {
+    config
+        .event_hub_capacity
+        .unwrap_or(DEFAULT_EVENT_HUB_CAPACITY)
+        .max(1)
+}
+
 async fn flatten_join_handle(
     handle: JoinHandle<Result<(), OrchestrationError>>,
 ) -> Result<(), OrchestrationError> {
@@ -114,3 +324,39 @@ async fn flatten_join_handle(
         Err(err) => Err(OrchestrationError::JoinError(err)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_executor_settings_default() {
+        let config = WorkflowConfig::default();
+        assert_eq!(channel_buffer_size(&config), DEFAULT_CHANNEL_BUFFER_SIZE);
+        assert_eq!(event_hub_capacity(&config), DEFAULT_EVENT_HUB_CAPACITY);
+        assert_eq!(thread_pool_size(&config), DEFAULT_THREAD_POOL_SIZE);
+    }
+
+    #[test]
+    fn test_executor_settings_from_config() {
+        let config = WorkflowConfig {
+            channel_buffer_size: Some(5),
+            event_hub_capacity: Some(64),
+            thread_pool_size: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(channel_buffer_size(&config), 5);
+        assert_eq!(event_hub_capacity(&config), 64);
+        assert_eq!(thread_pool_size(&config), 4);
+        // Channels need some capacity and nodes some threads.
+        let config = WorkflowConfig {
+            channel_buffer_size: Some(0),
+            event_hub_capacity: Some(0),
+            thread_pool_size: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(channel_buffer_size(&config), 1);
+        assert_eq!(event_hub_capacity(&config), 1);
+        assert_eq!(thread_pool_size(&config), 1);
+    }
+}
@@ -1,13 +1,58 @@
-use std::{collections::HashMap, sync::Arc, time::Instant};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use reearth_flow_action_log::factory::LoggerFactory;
-use reearth_flow_runtime::{node::NodeKind, shutdown};
+use chrono::Utc;
+use reearth_flow_action_log::{action_log, factory::LoggerFactory, ActionLogger};
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::{
+    cache::NodeCache,
+    event::EventHandler,
+    feature_store::FeatureStoreFormat,
+    node::NodeKind,
+    replay::ReplayOptions,
+    shutdown::{self, ShutdownSender},
+    stats::JobStats,
+};
 use reearth_flow_state::State;
 use reearth_flow_storage::resolve::StorageResolver;
 use reearth_flow_types::workflow::Workflow;
-use tracing::{error, info, info_span};
+use tracing::{error, info, info_span, warn};
 
+use crate::errors::OrchestrationError;
 use crate::orchestrator::Orchestrator;
+use crate::summary::{JobStatus, JobSummary};
+
+const DEFAULT_WORKER_THREADS: usize = 30;
+static SERVICE_NAME: &str = "reearth-flow-worker";
+
+#[derive(Debug, Clone, Default)]
+pub struct RunnerOptions {
+    /// Persist checkpoints of the job into this state, keyed by job id. The spill files of a job
+    /// that fails after a checkpoint are kept, for resuming it, until it is resumed to
+    /// completion; those of a job failing before its first checkpoint are deleted.
+    pub checkpoint_state: Option<Arc<State>>,
+    /// Interval between checkpoints. Defaults to 60 seconds.
+    pub checkpoint_interval: Option<Duration>,
+    /// Resume the job from its last checkpoint in `checkpoint_state`.
+    pub resume: bool,
+    /// Run only the part of the workflow downstream of a stored edge.
+    pub replay: Option<ReplayOptions>,
+    /// Reuse the outputs of the nodes that did not change since a previous job.
+    pub cache: Option<NodeCache>,
+    /// Format of the features persisted for each edge.
+    pub feature_store_format: FeatureStoreFormat,
+    /// Write the job summary as JSON to this location.
+    pub summary: Option<Uri>,
+    /// Receive the lifecycle events of the job, e.g. to report progress.
+    pub event_handlers: Vec<Arc<dyn EventHandler>>,
+    /// Cancel the job when it runs longer than this.
+    pub timeout: Option<Duration>,
+    /// Cancel the job on SIGINT or SIGTERM instead of killing the process.
+    pub cancel_on_signal: bool,
+}
 
 pub struct Runner;
 
@@ -20,13 +65,34 @@ impl Runner {
         storage_resolver: Arc<StorageResolver>,
         state: Arc<State>,
     ) {
+        Self::run_with_options(
+            job_id,
+            workflow,
+            factories,
+            logger_factory,
+            storage_resolver,
+            state,
+            RunnerOptions::default(),
+        );
+    }
+
+    pub fn run_with_options(
+        job_id: String,
+        workflow: Workflow,
+        factories: HashMap<String, NodeKind>,
+        logger_factory: Arc<LoggerFactory>,
+        storage_resolver: Arc<StorageResolver>,
+        state: Arc<State>,
+        options: RunnerOptions,
+    ) -> JobSummary {
         let runtime = tokio::runtime::Builder::new_multi_thread()
-            .worker_threads(30)
+            .worker_threads(worker_threads(&workflow))
             .enable_all()
             .build()
             .unwrap();
 
         let start = Instant::now();
+        let started_at = Utc::now();
         let span = info_span!(
             "root",
             "otel.name" = workflow.name.as_str(),
@@ -34,12 +100,34 @@ impl Runner {
             "workflow.id" = workflow.id.to_string().as_str(),
         );
         let workflow_name = workflow.name.clone();
+        let workflow_id = workflow.id;
+        let summary_job_id = job_id.clone();
+        let summary_uri = options.summary.clone();
+        let summary_storage_resolver = Arc::clone(&storage_resolver);
+        // The statistics of the job record into the meter provider installed before they are
+        // created.
+        let meter_provider = {
+            let _guard = runtime.enter();
+            reearth_flow_telemetry::install_metrics(SERVICE_NAME.to_string()).unwrap_or_else(|e| {
+                warn!("Failed to install the meter provider: {:?}", e);
+                None
+            })
+        };
+        let stats = Arc::new(JobStats::new());
         info!(parent: &span, "Start workflow = {:?}", workflow_name.as_str());
-        let (_shutdown_sender, shutdown_receiver) = shutdown::new(&runtime);
+        let (shutdown_sender, shutdown_receiver) = shutdown::new(&runtime);
+        let canceller = runtime.spawn(wait_for_cancellation(
+            shutdown_sender,
+            options.timeout,
+            options.cancel_on_signal,
+            logger_factory.action_logger("runner"),
+            span.clone(),
+        ));
         let runtime = Arc::new(runtime);
         let orchestraotr = Orchestrator::new(runtime.clone());
-        runtime.block_on(async move {
-            let result = orchestraotr
+        let job_stats = Arc::clone(&stats);
+        let result = runtime.block_on(async move {
+            orchestraotr
                 .run_all(
                     job_id,
                     workflow,
@@ -48,12 +136,353 @@ impl Runner {
                     logger_factory,
                     storage_resolver,
                     state,
+                    options,
+                    job_stats,
                 )
-                .await;
-            if let Err(e) = result {
-                error!("Failed to workflow: {:?}", e);
-            }
+                .await
         });
+        // The canceller owns the shutdown sender, so it has finished if the job was cancelled.
+        let cancel_reason = match &result {
+            Err(OrchestrationError::Cancelled) => runtime.block_on(canceller).ok(),
+            _ => {
+                canceller.abort();
+                None
+            }
+        };
+        match &result {
+            Ok(_) | Err(OrchestrationError::Cancelled) => {}
+            Err(e) => error!("Failed to workflow: {:?}", e),
+        }
         info!(parent: &span, "Finish workflow = {:?}, duration = {:?}", workflow_name.as_str(), start.elapsed());
+        let summary = JobSummary {
+            job_id: summary_job_id,
+            workflow_id,
+            workflow_name,
+            status: match &result {
+                Ok(_) => JobStatus::Succeeded,
+                Err(OrchestrationError::Cancelled) => JobStatus::Cancelled,
+                Err(_) => JobStatus::Failed,
+            },
+            error: match result {
+                Ok(_) => None,
+                Err(OrchestrationError::Cancelled) => Some(format!(
+                    "Job was cancelled: {}",
+                    cancel_reason.unwrap_or_else(|| "unknown reason".to_string())
+                )),
+                Err(e) => Some(e.to_string()),
+            },
+            started_at,
+            finished_at: Utc::now(),
+            duration_ms: start.elapsed().as_millis() as u64,
+            nodes: stats.summary(),
+        };
+        if let Some(uri) = summary_uri {
+            if let Err(e) = summary.write(&uri, &summary_storage_resolver) {
+                error!("Failed to write job summary: {:?}", e);
+            }
+        }
+        // Exports the metrics recorded since the last periodic export.
+        if let Some(meter_provider) = meter_provider {
+            if let Err(e) = meter_provider.shutdown() {
+                warn!("Failed to export metrics: {:?}", e);
+            }
+        }
+        summary
+    }
+}
+
+/// Number of worker threads of the async runtime of a job.
+fn worker_threads(workflow: &Workflow) -> usize {
+    workflow
+        .config
+        .as_ref()
+        .and_then(|config| config.worker_threads)
+        .unwrap_or(DEFAULT_WORKER_THREADS)
+        .max(1)
+}
+
+/// Requests the shutdown of the job on timeout or signal. Sources stop, and the remaining nodes
+/// finish with the features received so far.
+async fn wait_for_cancellation(
+    shutdown_sender: ShutdownSender,
+    timeout: Option<Duration>,
+    cancel_on_signal: bool,
+    logger: ActionLogger,
+    span: tracing::Span,
+) -> String {
+    let reason = tokio::select! {
+        signal = wait_for_signal(), if cancel_on_signal => format!("received {}", signal),
+        _ = tokio::time::sleep(timeout.unwrap_or_default()), if timeout.is_some() => {
+            format!("timed out after {:?}", timeout.unwrap_or_default())
+        }
+        else => std::future::pending::<String>().await,
+    };
+    action_log!(parent: span, logger, "Cancelling job: {}", reason);
+    shutdown_sender.shutdown();
+    // The signal handlers replace the default ones, so a second signal exits without waiting for
+    // the nodes to finish.
+    if cancel_on_signal {
+        tokio::spawn(async {
+            let signal = wait_for_signal().await;
+            error!(
+                "Received {} again, exiting without finishing the job",
+                signal
+            );
+            std::process::exit(match signal {
+                "SIGTERM" => 143,
+                _ => 130,
+            });
+        });
+    }
+    reason
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            warn!("Failed to listen for SIGTERM: {:?}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return "SIGINT";
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+        _ = sigterm.recv() => "SIGTERM",
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "Ctrl-C"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use reearth_flow_runtime::event::Event;
+    use reearth_flow_runtime::tests::utils::{
+        create_factories, create_state, CollectingSinkFactory,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    const FAILING_ID: &str = "7a1b2c3d-0000-4000-8000-000000000002";
+
+    #[derive(Debug, Default)]
+    struct RecordingHandler {
+        events: Mutex<Vec<Event>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EventHandler for RecordingHandler {
+        async fn on_event(&self, event: &Event) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+
+    /// Sends 3 features from a source through a node failing once for each, which skips them,
+    /// to a sink.
+    fn workflow() -> Workflow {
+        let node = |id: &str, name: &str, action: &str, with: serde_json::Value| {
+            json!({
+                "id": id,
+                "name": name,
+                "type": "action",
+                "action": action,
+                "with": with
+            })
+        };
+        let edge = |id: &str, from: &str, to: &str| {
+            json!({
+                "id": id,
+                "from": from,
+                "to": to,
+                "fromPort": "default",
+                "toPort": "default"
+            })
+        };
+        let workflow = json!({
+            "id": "7a1b2c3d-0000-4000-8000-000000000000",
+            "name": "events",
+            "entryGraphId": "7a1b2c3d-0000-4000-8000-0000000000a0",
+            "graphs": [{
+                "id": "7a1b2c3d-0000-4000-8000-0000000000a0",
+                "name": "entry",
+                "nodes": [
+                    node(
+                        "7a1b2c3d-0000-4000-8000-000000000001",
+                        "source",
+                        "NumberSource",
+                        json!({"count": 3}),
+                    ),
+                    node(FAILING_ID, "failing", "Failing", json!({"failures": 1})),
+                    node(
+                        "7a1b2c3d-0000-4000-8000-000000000003",
+                        "sink",
+                        "Collecting",
+                        json!({}),
+                    ),
+                ],
+                "edges": [
+                    edge(
+                        "7a1b2c3d-0000-4000-8000-0000000000e1",
+                        "7a1b2c3d-0000-4000-8000-000000000001",
+                        FAILING_ID,
+                    ),
+                    edge(
+                        "7a1b2c3d-0000-4000-8000-0000000000e2",
+                        FAILING_ID,
+                        "7a1b2c3d-0000-4000-8000-000000000003",
+                    ),
+                ]
+            }]
+        });
+        Workflow::try_from_str(&workflow.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_event_handlers_receive_job_events_in_order() {
+        let handler = Arc::new(RecordingHandler::default());
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let logger_factory = Arc::new(LoggerFactory::new(
+            ActionLogger::root(
+                reearth_flow_action_log::Discard,
+                reearth_flow_action_log::o!(),
+            ),
+            Uri::for_test("ram:///log/").path(),
+        ));
+        let summary = Runner::run_with_options(
+            "7a1b2c3d-0000-4000-8000-0000000000f0".to_string(),
+            workflow(),
+            create_factories(&CollectingSinkFactory::default()),
+            logger_factory,
+            Arc::clone(&storage_resolver),
+            create_state("ram:///feature-store/", &storage_resolver),
+            RunnerOptions {
+                event_handlers: vec![handler.clone()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(summary.error, None);
+
+        // Progress is reported periodically, at no particular point of the job.
+        let events = handler
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| !matches!(event, Event::NodeProgress { .. }))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(matches!(events.first(), Some(Event::JobStarted { .. })));
+        assert!(matches!(
+            events.last(),
+            Some(Event::JobFinished { error: None, .. })
+        ));
+        // Position of the only event matching `expected`.
+        let position = |expected: &dyn Fn(&Event) -> bool| {
+            let positions = events
+                .iter()
+                .enumerate()
+                .filter(|(_, event)| expected(event))
+                .map(|(position, _)| position)
+                .collect::<Vec<_>>();
+            assert_eq!(positions.len(), 1, "{:?}", events);
+            positions[0]
+        };
+        for name in ["source", "failing", "sink"] {
+            let started =
+                position(&|event| matches!(event, Event::NodeStarted { name: n, .. } if n == name));
+            let finished = position(
+                &|event| matches!(event, Event::NodeFinished { name: n, .. } if n == name),
+            );
+            assert!(0 < started && started < finished && finished < events.len() - 1);
+        }
+
+        // The failing node skips each feature after its error.
+        let failing = uuid::Uuid::parse_str(FAILING_ID).unwrap();
+        let errors = events
+            .iter()
+            .enumerate()
+            .filter_map(|(position, event)| match event {
+                Event::FeatureError { node, message, .. } => {
+                    Some((position, node.id, message.as_str()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 3);
+        let failing_finished = position(
+            &|event| matches!(event, Event::NodeFinished { name, .. } if name == "failing"),
+        );
+        for (position, node, message) in errors {
+            assert_eq!((node, message), (failing, "Attempt 1 failed"));
+            assert!(position < failing_finished);
+        }
+    }
+
+    #[test]
+    fn test_worker_threads() {
+        let mut workflow = Workflow::try_from_str(
+            r#"{
+                "id": "a7fc8f35-b84f-496b-a2cb-65be3bfec285",
+                "name": "test",
+                "entryGraphId": "3e3450c8-2344-4728-afa9-5fdb81eec33a",
+                "graphs": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(worker_threads(&workflow), DEFAULT_WORKER_THREADS);
+        workflow.config_mut().worker_threads = Some(2);
+        assert_eq!(worker_threads(&workflow), 2);
+        // The runtime needs a worker thread.
+        workflow.config_mut().worker_threads = Some(0);
+        assert_eq!(worker_threads(&workflow), 1);
+    }
+
+    #[test]
+    fn test_cancel_on_timeout() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (shutdown_sender, shutdown_receiver) = shutdown::new(&runtime);
+        let logger = ActionLogger::root(
+            reearth_flow_action_log::Discard,
+            reearth_flow_action_log::o!(),
+        );
+        let reason = runtime.block_on(wait_for_cancellation(
+            shutdown_sender,
+            Some(Duration::from_millis(10)),
+            false,
+            logger,
+            tracing::Span::none(),
+        ));
+        assert_eq!(reason, "timed out after 10ms");
+        assert!(shutdown_receiver.is_shutdown());
+    }
+
+    #[test]
+    fn test_no_cancellation_without_timeout_or_signal() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (shutdown_sender, shutdown_receiver) = shutdown::new(&runtime);
+        let logger = ActionLogger::root(
+            reearth_flow_action_log::Discard,
+            reearth_flow_action_log::o!(),
+        );
+        let canceller = runtime.spawn(wait_for_cancellation(
+            shutdown_sender,
+            None,
+            false,
+            logger,
+            tracing::Span::none(),
+        ));
+        runtime.block_on(tokio::time::sleep(Duration::from_millis(50)));
+        assert!(!canceller.is_finished());
+        assert!(!shutdown_receiver.is_shutdown());
     }
 }
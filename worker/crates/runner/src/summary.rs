@@ -0,0 +1,48 @@
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use reearth_flow_common::uri::Uri;
+use reearth_flow_runtime::stats::NodeSummary;
+use reearth_flow_storage::resolve::StorageResolver;
+use serde::Serialize;
+
+use crate::errors::OrchestrationError;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Machine-readable report of a finished job.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSummary {
+    pub job_id: String,
+    pub workflow_id: uuid::Uuid,
+    pub workflow_name: String,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub nodes: Vec<NodeSummary>,
+}
+
+impl JobSummary {
+    pub fn write(
+        &self,
+        uri: &Uri,
+        storage_resolver: &StorageResolver,
+    ) -> Result<(), OrchestrationError> {
+        let storage = storage_resolver
+            .resolve(uri)
+            .map_err(|e| OrchestrationError::Summary(format!("{:?}", e)))?;
+        let content = serde_json::to_vec_pretty(self)
+            .map_err(|e| OrchestrationError::Summary(format!("{:?}", e)))?;
+        storage
+            .put_sync(uri.path().as_path(), Bytes::from(content))
+            .map_err(|e| OrchestrationError::Summary(format!("{:?}", e)))
+    }
+}
@@ -5,14 +5,17 @@ use std::{
 };
 
 use petgraph::graph::NodeIndex;
+use reearth_flow_types::workflow::ErrorPolicy;
 
 use crate::{
+    checkpoint::Checkpoint,
     dag_schemas::{DagSchemas, EdgeHavePorts, SchemaEdgeKind},
     errors::ExecutionError,
     event::EventHub,
     executor_operation::NodeContext,
     node::{
-        EdgeId, GraphId, NodeHandle, NodeId, NodeKind as DagNodeKind, Port, Processor, Sink, Source,
+        EdgeId, GraphId, NodeHandle, NodeId, NodeKind as DagNodeKind, Port, Processor, Sink,
+        Source, SourceState,
     },
 };
 
@@ -20,7 +23,13 @@ use crate::{
 pub struct NodeType {
     pub handle: NodeHandle,
     pub name: String,
+    pub action: String,
     pub kind: NodeKind,
+    pub error_policy: ErrorPolicy,
+    /// Overrides the number of threads of a processor.
+    pub num_threads: Option<usize>,
+    /// Overrides whether a processor sends features in the order it received them.
+    pub preserve_order: Option<bool>,
 }
 
 impl Eq for NodeType {}
@@ -32,11 +41,23 @@ impl PartialEq for NodeType {
 }
 
 impl NodeType {
-    pub fn new(id: NodeId, name: String, kind: NodeKind) -> Self {
+    pub fn new(
+        id: NodeId,
+        name: String,
+        action: String,
+        kind: NodeKind,
+        error_policy: ErrorPolicy,
+        num_threads: Option<usize>,
+        preserve_order: Option<bool>,
+    ) -> Self {
         Self {
             handle: NodeHandle { id },
             name,
+            action,
             kind,
+            error_policy,
+            num_threads,
+            preserve_order,
         }
     }
 }
@@ -100,7 +121,8 @@ impl BuilderDag {
     pub async fn new(
         ctx: NodeContext,
         dag_schemas: DagSchemas,
-        event_hub_capacity: usize,
+        event_hub: EventHub,
+        checkpoint: Option<&Checkpoint>,
     ) -> Result<Self, ExecutionError> {
         let graph_id = dag_schemas.id;
         // Collect sources that may affect a node.
@@ -118,7 +140,6 @@ impl BuilderDag {
             .collect::<Vec<_>>();
 
         // Build the sinks and load checkpoint.
-        let event_hub = EventHub::new(event_hub_capacity);
         let mut graph = petgraph::graph::DiGraph::<NodeType, EdgeType>::new();
         let mut source_id_to_sinks = HashMap::<NodeHandle, Vec<NodeIndex>>::new();
         let mut node_index_map: HashMap<NodeIndex, NodeIndex> = HashMap::new();
@@ -155,6 +176,10 @@ impl BuilderDag {
                         node.with.clone(),
                     )
                     .map_err(ExecutionError::Factory)?;
+                if let Some(state) = checkpoint.and_then(|c| c.sinks.get(&handle.id)) {
+                    sink.restore_state(state)
+                        .map_err(ExecutionError::Checkpoint)?;
+                }
 
                 let state = sink.get_source_state().map_err(ExecutionError::Sink)?;
                 if let Some(state) = state {
@@ -173,7 +198,11 @@ impl BuilderDag {
                 let new_node_index = graph.add_node(NodeType {
                     handle: handle.clone(),
                     name: node.name.clone(),
+                    action: node.node.action().to_string(),
                     kind: NodeKind::Sink(sink),
+                    error_policy: node.node.on_error(),
+                    num_threads: node.node.num_threads(),
+                    preserve_order: node.node.preserve_order(),
                 });
                 node_index_map.insert(node_index, new_node_index);
                 source_id_to_sinks
@@ -201,13 +230,27 @@ impl BuilderDag {
                             node.node.action().to_string(),
                         ));
                     }
+                    let state = match checkpoint.and_then(|c| c.sources.get(&node.handle.id)) {
+                        Some(SourceState::Restartable(state)) => Some(state.clone()),
+                        Some(SourceState::NonRestartable) => {
+                            return Err(ExecutionError::Checkpoint(
+                                format!(
+                                    "Cannot resume {}, as {} does not checkpoint its state",
+                                    node.name,
+                                    source.name()
+                                )
+                                .into(),
+                            ))
+                        }
+                        Some(SourceState::NotStarted) | None => source_states.remove(&node.handle),
+                    };
                     let source = source
                         .build(
                             ctx.clone(),
                             event_hub.clone(),
                             node.node.action().to_string(),
                             node.with.clone(),
-                            source_states.remove(&node.handle),
+                            state,
                         )
                         .map_err(ExecutionError::Factory)?;
 
@@ -228,7 +271,11 @@ impl BuilderDag {
                     NodeType {
                         handle: node.handle,
                         name: node.name,
+                        action: node.node.action().to_string(),
                         kind: NodeKind::Source(source),
+                        error_policy: node.node.on_error(),
+                        num_threads: node.node.num_threads(),
+                        preserve_order: node.node.preserve_order(),
                     }
                 }
                 DagNodeKind::Processor(processor) => {
@@ -239,7 +286,7 @@ impl BuilderDag {
                             node.node.action().to_string(),
                         ));
                     }
-                    let processor = processor
+                    let mut processor = processor
                         .build(
                             ctx.clone(),
                             event_hub.clone(),
@@ -247,10 +294,32 @@ impl BuilderDag {
                             node.with.clone(),
                         )
                         .map_err(ExecutionError::Factory)?;
+                    if let Some(checkpoint) = checkpoint {
+                        match checkpoint.processors.get(&node.handle.id) {
+                            Some(state) => processor
+                                .restore_state(state)
+                                .map_err(ExecutionError::Checkpoint)?,
+                            None if processor.is_stateful() => {
+                                return Err(ExecutionError::Checkpoint(
+                                    format!(
+                                        "Cannot resume {}, as {} does not checkpoint its state",
+                                        node.name,
+                                        processor.name()
+                                    )
+                                    .into(),
+                                ))
+                            }
+                            None => {}
+                        }
+                    }
                     NodeType {
                         handle: node.handle,
                         name: node.name,
+                        action: node.node.action().to_string(),
                         kind: NodeKind::Processor(processor),
+                        error_policy: node.node.on_error(),
+                        num_threads: node.node.num_threads(),
+                        preserve_order: node.node.preserve_order(),
                     }
                 }
                 DagNodeKind::Sink(_) => continue,
@@ -0,0 +1,536 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_state::State;
+use reearth_flow_types::Feature;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::dag_schemas::{DagSchemas, SchemaEdgeType, SchemaNodeType};
+use crate::errors::ExecutionError;
+use crate::executor_operation::NodeContext;
+use crate::feature_store::{FeatureWriter, FeatureWriterError, StreamingFeatureWriter};
+use crate::for_each::FOR_EACH_ACTION;
+use crate::looping::LOOP_ACTION;
+use crate::node::{NodeId, NodeKind, Port};
+use crate::replay::feature_store_source;
+
+/// Reuses the outputs of the nodes whose action, parameters and inputs did not change since a
+/// previous job.
+///
+/// The key of a node hashes its action, its `with` parameters, the resolved `with` of the workflow
+/// and the keys of the nodes upstream together with the ports connecting them. The features a
+/// node sends are stored per output port once it has finished. Nodes whose outputs are all stored
+/// are replaced with sources reading them on the next job.
+///
+/// The key of a source also hashes the size, modification time and ETag of the files its
+/// parameters refer to. Sources referring to no file, e.g. reading a database, are never cached,
+/// as a change of their data cannot be noticed. Neither are `forEach` and `loop` nodes, nor the
+/// nodes downstream of these.
+#[derive(Debug, Clone)]
+pub struct NodeCache {
+    state: Arc<State>,
+}
+
+/// Stored next to the features of an output port of a node once the node has finished.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+    pub node: String,
+    pub action: String,
+    pub features: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NodeCache {
+    pub fn new(state: Arc<State>) -> Self {
+        Self { state }
+    }
+
+    /// Replaces the nodes whose outputs are stored with sources. Returns the keys of the nodes left
+    /// to run, whose outputs are stored when they finish.
+    pub(crate) async fn apply(
+        &self,
+        dag_schemas: &mut DagSchemas,
+        ctx: &NodeContext,
+        workflow_with: Option<&serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<CachePlan, ExecutionError> {
+        let graph = dag_schemas.graph();
+        let mut fingerprints = HashMap::new();
+        for node_index in graph.node_indices() {
+            if !matches!(graph[node_index].kind, Some(NodeKind::Source(_))) {
+                continue;
+            }
+            if let Some(fingerprint) = source_fingerprint(&graph[node_index], ctx).await {
+                fingerprints.insert(node_index, fingerprint);
+            }
+        }
+        let keys = node_keys(graph, &fingerprints, workflow_with);
+        let mut hits = HashSet::new();
+        let mut nodes = HashMap::new();
+        for (node_index, key) in keys.iter() {
+            let node = &graph[*node_index];
+            let ports = output_ports(graph, *node_index);
+            if self.is_stored(key, &ports).await {
+                tracing::info!("Using the cached outputs of node {}", node.name);
+                hits.insert(*node_index);
+                continue;
+            }
+            // Clear what a failed job may have left, so that a partial output is never read.
+            for port in ports.iter() {
+                let id = entry_id(key, port).to_string();
+                let _ = self.state.delete(id.as_str()).await;
+                let _ = self.state.delete_lines(id.as_str()).await;
+            }
+            nodes.insert(
+                node.handle.id,
+                CachedNode {
+                    key: key.clone(),
+                    name: node.name.clone(),
+                    action: node.node.action().to_string(),
+                },
+            );
+        }
+        dag_schemas.replace_with_sources(&hits, |node_index, port| {
+            let id = entry_id(&keys[&node_index], port);
+            feature_store_source(id, &self.state, vec![id], port.clone())
+        });
+        Ok(CachePlan {
+            state: Arc::clone(&self.state),
+            nodes,
+        })
+    }
+
+    async fn is_stored(&self, key: &str, ports: &[Port]) -> bool {
+        if ports.is_empty() {
+            return false;
+        }
+        for port in ports {
+            let id = entry_id(key, port).to_string();
+            if self.state.get::<CacheEntry>(id.as_str()).await.is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Deletes the entries created before `before`, or every entry. Returns the number of deleted
+    /// entries.
+    pub async fn prune(&self, before: Option<DateTime<Utc>>) -> Result<usize, ExecutionError> {
+        let ids = self
+            .state
+            .list_ids()
+            .await
+            .map_err(|e| ExecutionError::Cache(e.to_string()))?;
+        let mut deleted = 0;
+        for id in ids {
+            let Ok(entry) = self.state.get::<CacheEntry>(id.as_str()).await else {
+                continue;
+            };
+            if before.is_some_and(|before| entry.created_at >= before) {
+                continue;
+            }
+            self.state
+                .delete(id.as_str())
+                .await
+                .map_err(|e| ExecutionError::Cache(e.to_string()))?;
+            self.state
+                .delete_lines(id.as_str())
+                .await
+                .map_err(|e| ExecutionError::Cache(e.to_string()))?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedNode {
+    key: String,
+    name: String,
+    action: String,
+}
+
+/// Nodes of a job whose outputs are stored in the cache.
+#[derive(Debug, Clone)]
+pub(crate) struct CachePlan {
+    state: Arc<State>,
+    nodes: HashMap<NodeId, CachedNode>,
+}
+
+impl CachePlan {
+    /// Wraps the feature writer of an output port so that it also stores the features in the
+    /// cache.
+    pub(crate) fn writer(
+        &self,
+        node_id: NodeId,
+        port: &Port,
+        inner: Box<dyn FeatureWriter>,
+    ) -> Box<dyn FeatureWriter> {
+        let Some(node) = self.nodes.get(&node_id) else {
+            return inner;
+        };
+        let id = entry_id(&node.key, port);
+        Box::new(CacheFeatureWriter {
+            inner,
+            cache: StreamingFeatureWriter::new(id, Arc::clone(&self.state)),
+            state: Arc::clone(&self.state),
+            id,
+            entry: CacheEntry {
+                node: node.name.clone(),
+                action: node.action.clone(),
+                features: 0,
+                created_at: Utc::now(),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheFeatureWriter {
+    inner: Box<dyn FeatureWriter>,
+    cache: StreamingFeatureWriter,
+    state: Arc<State>,
+    id: uuid::Uuid,
+    entry: CacheEntry,
+}
+
+#[async_trait::async_trait]
+impl FeatureWriter for CacheFeatureWriter {
+    fn write(&mut self, feature: &Feature) -> Result<(), FeatureWriterError> {
+        self.inner.write(feature)?;
+        self.cache.write(feature)?;
+        self.entry.features += 1;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), FeatureWriterError> {
+        self.inner.flush().await?;
+        self.cache.flush().await?;
+        // The entry is written last, as it marks the stored features as complete.
+        let entry = CacheEntry {
+            created_at: Utc::now(),
+            ..self.entry.clone()
+        };
+        self.state
+            .save(&entry, self.id.to_string().as_str())
+            .await
+            .map_err(|e| FeatureWriterError::Flush(e.to_string()))
+    }
+}
+
+/// Output ports of a node that are connected to another node.
+fn output_ports(
+    graph: &DiGraph<SchemaNodeType, SchemaEdgeType>,
+    node_index: NodeIndex,
+) -> Vec<Port> {
+    if matches!(graph[node_index].kind, Some(NodeKind::Sink(_))) {
+        return vec![];
+    }
+    let mut ports = graph
+        .edges_directed(node_index, Direction::Outgoing)
+        .map(|edge| edge.weight().from.clone())
+        .collect::<Vec<_>>();
+    ports.sort();
+    ports.dedup();
+    ports
+}
+
+/// Fingerprints of the files the parameters of a source refer to, once evaluated. `None` when
+/// they refer to no file.
+async fn source_fingerprint(node: &SchemaNodeType, ctx: &NodeContext) -> Option<Vec<String>> {
+    let mut values = vec![];
+    if let Some(with) = &node.with {
+        with.values()
+            .for_each(|value| collect_strings(value, &mut values));
+    }
+    let scope = ctx.expr_engine.new_scope();
+    let mut fingerprints = vec![];
+    for value in values {
+        let value = scope.eval::<String>(&value).unwrap_or(value);
+        let Ok(uri) = Uri::from_str(&value) else {
+            continue;
+        };
+        let Ok(storage) = ctx.storage_resolver.resolve(&uri) else {
+            continue;
+        };
+        let Ok(meta) = storage.head(uri.path().as_path()).await else {
+            continue;
+        };
+        fingerprints.push(format!(
+            "{}:{}:{}:{}",
+            uri,
+            meta.size,
+            meta.last_modified.to_rfc3339(),
+            meta.e_tag.unwrap_or_default()
+        ));
+    }
+    fingerprints.sort();
+    (!fingerprints.is_empty()).then_some(fingerprints)
+}
+
+fn collect_strings(value: &serde_json::Value, strings: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(value) => strings.push(value.clone()),
+        serde_json::Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_strings(value, strings)),
+        serde_json::Value::Object(values) => values
+            .values()
+            .for_each(|value| collect_strings(value, strings)),
+        _ => {}
+    }
+}
+
+/// Computes the keys of the nodes, upstream first. Nodes without a key are never cached.
+fn node_keys(
+    graph: &DiGraph<SchemaNodeType, SchemaEdgeType>,
+    fingerprints: &HashMap<NodeIndex, Vec<String>>,
+    workflow_with: Option<&serde_json::Map<String, serde_json::Value>>,
+) -> HashMap<NodeIndex, String> {
+    let mut keys = HashMap::new();
+    let Ok(order) = petgraph::algo::toposort(graph, None) else {
+        return keys;
+    };
+    let workflow_with = workflow_with.map(|with| with.iter().collect::<BTreeMap<_, _>>());
+    for node_index in order {
+        let node = &graph[node_index];
+        let action = node.node.action();
+        if node.kind.is_none() || action == FOR_EACH_ACTION || action == LOOP_ACTION {
+            continue;
+        }
+        let files = fingerprints.get(&node_index);
+        if matches!(node.kind, Some(NodeKind::Source(_))) && files.is_none() {
+            continue;
+        }
+        let inputs = graph
+            .edges_directed(node_index, Direction::Incoming)
+            .map(|edge| {
+                keys.get(&edge.source())
+                    .map(|key| format!("{}:{}:{}", key, edge.weight().from, edge.weight().to))
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(mut inputs) = inputs else {
+            continue;
+        };
+        inputs.sort();
+        let with = node
+            .with
+            .as_ref()
+            .map(|with| with.iter().collect::<BTreeMap<_, _>>());
+        let content = serde_json::json!({
+            "action": action,
+            "with": with,
+            "workflowWith": workflow_with,
+            "files": files,
+            "inputs": inputs,
+        });
+        keys.insert(node_index, hash(content.to_string().as_bytes()));
+    }
+    keys
+}
+
+fn hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Id of the stored features of an output port of a node.
+fn entry_id(key: &str, port: &Port) -> uuid::Uuid {
+    let digest = Sha256::digest(format!("{}/{}", key, port).as_bytes());
+    uuid::Builder::from_slice(&digest[..16])
+        .expect("A digest is longer than an uuid")
+        .into_uuid()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use reearth_flow_storage::resolve::StorageResolver;
+    use reearth_flow_types::workflow::Workflow;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::tests::utils::{create_factories, create_state, CollectingSinkFactory};
+
+    const INPUT: &str = "ram:///cache-input/input.txt";
+
+    fn id(n: u32) -> uuid::Uuid {
+        uuid::Uuid::from_str(&format!("3f9b1e20-0000-4000-8000-{:012}", n)).unwrap()
+    }
+
+    /// A source with the given parameters, followed by a processor and a sink.
+    fn dag_schemas(source_with: Value, storage_resolver: &StorageResolver) -> DagSchemas {
+        let workflow = json!({
+            "id": id(0),
+            "name": "cache",
+            "entryGraphId": id(100),
+            "graphs": [{
+                "id": id(100),
+                "name": "entry",
+                "nodes": [
+                    {
+                        "id": id(1),
+                        "name": "source",
+                        "type": "action",
+                        "action": "NumberSource",
+                        "with": source_with
+                    },
+                    {"id": id(2), "name": "pass", "type": "action", "action": "PassThrough"},
+                    {"id": id(3), "name": "sink", "type": "action", "action": "Collecting"}
+                ],
+                "edges": [
+                    {
+                        "id": id(101),
+                        "from": id(1),
+                        "to": id(2),
+                        "fromPort": "default",
+                        "toPort": "default"
+                    },
+                    {
+                        "id": id(102),
+                        "from": id(2),
+                        "to": id(3),
+                        "fromPort": "default",
+                        "toPort": "default"
+                    }
+                ]
+            }]
+        });
+        let workflow = Workflow::try_from_str(&workflow.to_string()).unwrap();
+        DagSchemas::from_graphs(
+            workflow.entry_graph_id,
+            workflow.graphs,
+            create_factories(&CollectingSinkFactory::default()),
+            workflow.with,
+            storage_resolver,
+        )
+        .unwrap()
+    }
+
+    fn stored_entry() -> CacheEntry {
+        CacheEntry {
+            node: "pass".to_string(),
+            action: "PassThrough".to_string(),
+            features: 3,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_uses_stored_outputs_until_inputs_change() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///cache-apply/", &storage_resolver);
+        let cache = NodeCache::new(Arc::clone(&state));
+        let ctx = NodeContext {
+            storage_resolver: Arc::clone(&storage_resolver),
+            ..Default::default()
+        };
+        let input = Uri::from_str(INPUT).unwrap();
+        let storage = storage_resolver.resolve(&input).unwrap();
+        storage
+            .put(input.path().as_path(), Bytes::from("1,2,3"))
+            .await
+            .unwrap();
+        let source_with = json!({"count": 3, "path": INPUT});
+
+        // Nothing is stored yet: every node runs.
+        let mut dag = dag_schemas(source_with.clone(), &storage_resolver);
+        let plan = cache.apply(&mut dag, &ctx, None).await.unwrap();
+        assert!(plan.nodes.contains_key(&id(1)));
+        assert!(plan.nodes.contains_key(&id(2)));
+        let key = plan.nodes[&id(2)].key.clone();
+        state
+            .save(
+                &stored_entry(),
+                entry_id(&key, &Port::new("default")).to_string().as_str(),
+            )
+            .await
+            .unwrap();
+
+        // The outputs of the processor are stored: it is replaced with a source.
+        let mut dag = dag_schemas(source_with.clone(), &storage_resolver);
+        let plan = cache.apply(&mut dag, &ctx, None).await.unwrap();
+        assert!(!plan.nodes.contains_key(&id(2)));
+        assert!(dag.node_index_by_node_id(id(2)).is_none());
+
+        // Another workflow `with` changes the keys.
+        let workflow_with = json!({"threshold": 1}).as_object().cloned();
+        let mut dag = dag_schemas(source_with.clone(), &storage_resolver);
+        let plan = cache
+            .apply(&mut dag, &ctx, workflow_with.as_ref())
+            .await
+            .unwrap();
+        assert_ne!(plan.nodes[&id(2)].key, key);
+        assert!(dag.node_index_by_node_id(id(2)).is_some());
+
+        // So does a change of the file the source reads.
+        storage
+            .put(input.path().as_path(), Bytes::from("1,2,3,4"))
+            .await
+            .unwrap();
+        let mut dag = dag_schemas(source_with, &storage_resolver);
+        let plan = cache.apply(&mut dag, &ctx, None).await.unwrap();
+        assert_ne!(plan.nodes[&id(2)].key, key);
+        assert!(dag.node_index_by_node_id(id(2)).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_apply_skips_sources_without_files() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///cache-apply/", &storage_resolver);
+        let cache = NodeCache::new(state);
+        let ctx = NodeContext {
+            storage_resolver: Arc::clone(&storage_resolver),
+            ..Default::default()
+        };
+        let mut dag = dag_schemas(json!({"count": 3}), &storage_resolver);
+        let plan = cache.apply(&mut dag, &ctx, None).await.unwrap();
+        assert!(plan.nodes.is_empty());
+        assert!(dag.node_index_by_node_id(id(2)).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let state =
+            Arc::new(State::new(&Uri::for_test("ram:///cache"), &StorageResolver::new()).unwrap());
+        let cache = NodeCache::new(Arc::clone(&state));
+        let entry = |created_at| CacheEntry {
+            node: "node".to_string(),
+            action: "action".to_string(),
+            features: 0,
+            created_at,
+        };
+        let old = entry_id("old", &Port::new("default"));
+        let new = entry_id("new", &Port::new("default"));
+        state
+            .save(
+                &entry(Utc::now() - chrono::Duration::days(2)),
+                old.to_string().as_str(),
+            )
+            .await
+            .unwrap();
+        state
+            .save(&entry(Utc::now()), new.to_string().as_str())
+            .await
+            .unwrap();
+        let deleted = cache
+            .prune(Some(Utc::now() - chrono::Duration::days(1)))
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert!(state
+            .get::<CacheEntry>(new.to_string().as_str())
+            .await
+            .is_ok());
+        assert_eq!(cache.prune(None).await.unwrap(), 1);
+    }
+}
@@ -3,3 +3,13 @@ use crate::executor_operation::ExecutorContext;
 pub trait ProcessorChannelForwarder {
     fn send(&mut self, ctx: ExecutorContext);
 }
+
+/// Keeps the features sent by a processor instead of forwarding them.
+#[derive(Debug, Default)]
+pub(crate) struct Collector(pub(crate) Vec<ExecutorContext>);
+
+impl ProcessorChannelForwarder for Collector {
+    fn send(&mut self, ctx: ExecutorContext) {
+        self.0.push(ctx);
+    }
+}
@@ -0,0 +1,128 @@
+use std::{collections::HashMap, io, sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+use reearth_flow_state::State;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::node::{NodeHandle, SourceState};
+
+/// A consistent snapshot of a running job.
+///
+/// Checkpoints are taken with aligned barriers: every source state is recorded when the barrier
+/// is emitted, and every processor and sink state is recorded once the barrier has arrived on all
+/// of its inputs. Sources restart from their recorded state, and a job cannot be resumed from a
+/// checkpoint taken after a source that does not report its state has sent features.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub epoch: u64,
+    pub sources: HashMap<uuid::Uuid, SourceState>,
+    pub processors: HashMap<uuid::Uuid, Vec<u8>>,
+    pub sinks: HashMap<uuid::Uuid, Vec<u8>>,
+}
+
+impl Checkpoint {
+    pub async fn load(state: &State, job_id: &str) -> io::Result<Self> {
+        state.get(job_id).await
+    }
+
+    pub async fn delete(state: &State, job_id: &str) -> io::Result<()> {
+        state.delete(job_id).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckpointOptions {
+    pub job_id: String,
+    pub interval: Duration,
+    pub state: Arc<State>,
+}
+
+#[derive(Debug, Default)]
+struct PendingCheckpoint {
+    checkpoint: Checkpoint,
+    acks: usize,
+}
+
+/// Collects the states reported by processors and sinks for each epoch and persists the
+/// checkpoint once every node has acknowledged the barrier.
+#[derive(Debug)]
+pub(crate) struct CheckpointCoordinator {
+    options: CheckpointOptions,
+    expected_acks: usize,
+    pending: Mutex<HashMap<u64, PendingCheckpoint>>,
+}
+
+impl CheckpointCoordinator {
+    pub(crate) fn new(options: CheckpointOptions, expected_acks: usize) -> Self {
+        Self {
+            options,
+            expected_acks,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn interval(&self) -> Duration {
+        self.options.interval
+    }
+
+    pub(crate) fn begin(&self, epoch: u64, sources: HashMap<uuid::Uuid, SourceState>) {
+        let mut pending = self.pending.lock();
+        let entry = pending.entry(epoch).or_default();
+        entry.checkpoint.epoch = epoch;
+        entry.checkpoint.sources = sources;
+        drop(pending);
+        if self.expected_acks == 0 {
+            self.complete(epoch);
+        }
+    }
+
+    pub(crate) fn ack_processor(&self, epoch: u64, node: &NodeHandle, state: Option<Vec<u8>>) {
+        self.ack(epoch, |checkpoint| {
+            if let Some(state) = state {
+                checkpoint.processors.insert(node.id, state);
+            }
+        });
+    }
+
+    pub(crate) fn ack_sink(&self, epoch: u64, node: &NodeHandle, state: Option<Vec<u8>>) {
+        self.ack(epoch, |checkpoint| {
+            if let Some(state) = state {
+                checkpoint.sinks.insert(node.id, state);
+            }
+        });
+    }
+
+    fn ack(&self, epoch: u64, f: impl FnOnce(&mut Checkpoint)) {
+        let mut pending = self.pending.lock();
+        let entry = pending.entry(epoch).or_default();
+        f(&mut entry.checkpoint);
+        entry.acks += 1;
+        let completed = entry.acks >= self.expected_acks;
+        drop(pending);
+        if completed {
+            self.complete(epoch);
+        }
+    }
+
+    fn complete(&self, epoch: u64) {
+        let Some(pending) = self.pending.lock().remove(&epoch) else {
+            return;
+        };
+        match self
+            .options
+            .state
+            .save_sync(&pending.checkpoint, self.options.job_id.as_str())
+        {
+            Ok(()) => info!(
+                "Saved checkpoint: job id = {}, epoch = {}",
+                self.options.job_id, epoch
+            ),
+            Err(e) => error!(
+                "Failed to save checkpoint: job id = {}, epoch = {}, error = {:?}",
+                self.options.job_id, epoch, e
+            ),
+        }
+    }
+}
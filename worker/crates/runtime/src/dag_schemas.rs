@@ -4,12 +4,19 @@ use std::hash::Hash;
 
 use petgraph::dot::Dot;
 use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
-use petgraph::visit::EdgeRef;
+use petgraph::visit::{Dfs, EdgeRef};
 use petgraph::Direction;
 
+use reearth_flow_storage::resolve::StorageResolver;
 use reearth_flow_types::workflow::{Graph, Node};
 
-use crate::node::{EdgeId, GraphId, NodeHandle, NodeId, NodeKind, Port, ROUTING_PARAM_KEY};
+use crate::errors::ExecutionError;
+use crate::for_each::{ForEachFactory, FOR_EACH_ACTION};
+use crate::graph_ref::GraphRefResolver;
+use crate::looping::{LoopFactory, LOOP_ACTION};
+use crate::node::{
+    EdgeId, GraphId, NodeHandle, NodeId, NodeKind, Port, ProcessorFactory, ROUTING_PARAM_KEY,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Endpoint {
@@ -109,55 +116,52 @@ pub struct DagSchemas {
 }
 
 impl DagSchemas {
+    /// Builds the DAG of the entry graph, with every sub graph expanded in place. Graphs
+    /// referenced by `graphRef` nodes are loaded through `storage_resolver`.
     pub fn from_graphs(
         entry_graph_id: GraphId,
         graphs: Vec<Graph>,
         factories: HashMap<String, NodeKind>,
         global_params: Option<serde_json::Map<String, serde_json::Value>>,
-    ) -> Self {
-        let entry_graph = graphs.iter().find(|dag| dag.id == entry_graph_id).unwrap();
-        let other_graphs = graphs
+        storage_resolver: &StorageResolver,
+    ) -> Result<Self, ExecutionError> {
+        let graphs = GraphRefResolver::new(storage_resolver).resolve(graphs)?;
+        let graphs = graphs
             .iter()
-            .filter(|graph| graph.id != entry_graph_id)
             .map(|graph| (graph.id, graph))
             .collect::<HashMap<_, _>>();
+        DagSchemas::expand_graph(
+            entry_graph_id,
+            &graphs,
+            &factories,
+            &global_params,
+            &mut vec![],
+        )
+    }
 
-        let mut other_graph_schemas = HashMap::new();
-        for (_, graph) in other_graphs.iter() {
-            let mut graph_schema = DagSchemas::from_graph(graph, &factories, &global_params);
-            let graph_nodes = graph_schema.collect_graph_nodes();
-            for node in graph_nodes.iter() {
-                let Node::SubGraph {
-                    sub_graph_id,
-                    entity,
-                } = &node.node
-                else {
-                    continue;
-                };
-                let subgraph = other_graphs.get(sub_graph_id).unwrap();
-                let params = if let Some(with) = &entity.with {
-                    if let Some(global_params) = &global_params {
-                        let mut global_with = global_params.clone();
-                        global_with.extend(with.clone());
-                        Some(global_with)
-                    } else {
-                        Some(with.clone())
-                    }
-                } else {
-                    global_params.clone()
-                };
-                let subgraph = DagSchemas::from_graph(subgraph, &factories, &params);
-                graph_schema.add_subgraph_after_node(node.handle.id, &params, &subgraph);
-                let Some(target_node) = graph_schema.node_index_by_node_id(node.handle.id) else {
-                    continue;
-                };
-                graph_schema.graph.remove_node(*target_node);
-            }
-            other_graph_schemas.insert(graph_schema.id, graph_schema);
+    /// Builds the DAG of a graph, expanding its sub graphs recursively.
+    fn expand_graph(
+        graph_id: GraphId,
+        graphs: &HashMap<GraphId, &Graph>,
+        factories: &HashMap<String, NodeKind>,
+        params: &Option<serde_json::Map<String, serde_json::Value>>,
+        stack: &mut Vec<GraphId>,
+    ) -> Result<Self, ExecutionError> {
+        let Some(graph) = graphs.get(&graph_id) else {
+            return Err(ExecutionError::GraphRef(format!(
+                "Graph not found: {}",
+                graph_id
+            )));
+        };
+        if stack.contains(&graph_id) {
+            return Err(ExecutionError::GraphRef(format!(
+                "Sub graph {} contains itself",
+                graph_id
+            )));
         }
-        let mut entry_graph = DagSchemas::from_graph(entry_graph, &factories, &global_params);
-        let graph_nodes = entry_graph.collect_graph_nodes();
-        for node in graph_nodes.iter() {
+        stack.push(graph_id);
+        let mut graph_schema = DagSchemas::from_graph(graph, factories, params);
+        for node in graph_schema.collect_graph_nodes() {
             let Node::SubGraph {
                 sub_graph_id,
                 entity,
@@ -166,24 +170,33 @@ impl DagSchemas {
                 continue;
             };
             let params = if let Some(with) = &entity.with {
-                if let Some(global_params) = &global_params {
-                    let mut global_with = global_params.clone();
-                    global_with.extend(with.clone());
-                    Some(global_with)
+                if let Some(params) = params {
+                    let mut params = params.clone();
+                    params.extend(with.clone());
+                    Some(params)
                 } else {
                     Some(with.clone())
                 }
             } else {
-                global_params.clone()
+                params.clone()
             };
-            let subgraph = other_graph_schemas.get(sub_graph_id).unwrap();
-            entry_graph.add_subgraph_after_node(node.handle.id, &params, subgraph);
-            let Some(target_node) = entry_graph.node_index_by_node_id(node.handle.id) else {
+            let subgraph =
+                DagSchemas::expand_graph(*sub_graph_id, graphs, factories, &params, stack)?;
+            if let Some(for_each) = &entity.for_each {
+                let factory = ForEachFactory::new(subgraph.graph, for_each.clone());
+                graph_schema.replace_with_processor(node.handle.id, FOR_EACH_ACTION, factory);
                 continue;
-            };
-            entry_graph.graph.remove_node(*target_node);
+            }
+            if let Some(repeat) = &entity.repeat {
+                let factory = LoopFactory::new(subgraph.graph, repeat.clone());
+                graph_schema.replace_with_processor(node.handle.id, LOOP_ACTION, factory);
+                continue;
+            }
+            graph_schema.add_subgraph_after_node(node.handle.id, &params, &subgraph);
+            graph_schema.remove_node_by_node_id(node.handle.id);
         }
-        entry_graph
+        stack.pop();
+        Ok(graph_schema)
     }
 
     fn from_graph(
@@ -216,7 +229,7 @@ impl DagSchemas {
                     };
                     Some(kind.clone())
                 }
-                Node::SubGraph { .. } => None,
+                Node::SubGraph { .. } | Node::GraphRef { .. } => None,
             };
             let index = dag.add_node(SchemaNodeType::new(
                 node.id(),
@@ -252,6 +265,138 @@ impl DagSchemas {
         dag
     }
 
+    /// Keeps only the nodes downstream of `edge_id`. Every edge entering the kept part is fed by a
+    /// source created by `create_source`, which also gets the ids of the edges sharing the output
+    /// port of that edge.
+    pub fn retain_downstream_of_edge(
+        &mut self,
+        edge_id: EdgeId,
+        create_source: impl Fn(&SchemaEdgeType, Vec<EdgeId>) -> SchemaNodeType,
+    ) -> Result<(), ExecutionError> {
+        let Some(start) = self
+            .graph
+            .edge_references()
+            .find(|edge| edge.weight().id == edge_id)
+            .map(|edge| edge.target())
+        else {
+            return Err(ExecutionError::Replay(format!(
+                "Edge not found: {}",
+                edge_id
+            )));
+        };
+        let mut downstream = HashSet::new();
+        let mut dfs = Dfs::new(&self.graph, start);
+        while let Some(node_index) = dfs.next(&self.graph) {
+            downstream.insert(node_index);
+        }
+        let cut_edges = self
+            .graph
+            .edge_references()
+            .filter(|edge| {
+                !downstream.contains(&edge.source()) && downstream.contains(&edge.target())
+            })
+            .map(|edge| {
+                let siblings = self
+                    .graph
+                    .edges(edge.source())
+                    .filter(|sibling| sibling.weight().from == edge.weight().from)
+                    .map(|sibling| sibling.weight().id)
+                    .filter(|id| *id != edge.weight().id);
+                let edge_ids = std::iter::once(edge.weight().id)
+                    .chain(siblings)
+                    .collect::<Vec<_>>();
+                (edge.target(), edge.weight().clone(), edge_ids)
+            })
+            .collect::<Vec<_>>();
+
+        // `filter_map` keeps the relative order of the retained nodes.
+        let index_map = self
+            .graph
+            .node_indices()
+            .filter(|node_index| downstream.contains(node_index))
+            .enumerate()
+            .map(|(new, old)| (old, NodeIndex::new(new)))
+            .collect::<HashMap<_, _>>();
+        self.graph = self.graph.filter_map(
+            |node_index, node| downstream.contains(&node_index).then(|| node.clone()),
+            |_, edge| Some(edge.clone()),
+        );
+        for (target, edge, edge_ids) in cut_edges {
+            let source = self.graph.add_node(create_source(&edge, edge_ids));
+            self.graph.add_edge(
+                source,
+                index_map[&target],
+                SchemaEdgeType::new(
+                    edge.id,
+                    edge.from,
+                    edge.to,
+                    Some(SchemaEdgeKind::FromSource),
+                ),
+            );
+        }
+        self.node_lookup_table = self
+            .graph
+            .node_indices()
+            .map(|node_index| (self.graph[node_index].handle.id, node_index))
+            .collect();
+        Ok(())
+    }
+
+    /// Removes `nodes`. Every edge leaving a removed node for a kept one is fed by a source created
+    /// by `create_source`, once per output port of the removed node.
+    pub fn replace_with_sources(
+        &mut self,
+        nodes: &HashSet<NodeIndex>,
+        create_source: impl Fn(NodeIndex, &Port) -> SchemaNodeType,
+    ) {
+        let cut_edges = self
+            .graph
+            .edge_references()
+            .filter(|edge| nodes.contains(&edge.source()) && !nodes.contains(&edge.target()))
+            .map(|edge| (edge.source(), edge.target(), edge.weight().clone()))
+            .collect::<Vec<_>>();
+        let mut sources = HashMap::new();
+        for (source, _, edge) in cut_edges.iter() {
+            sources
+                .entry((*source, edge.from.clone()))
+                .or_insert_with(|| create_source(*source, &edge.from));
+        }
+
+        // `filter_map` keeps the relative order of the retained nodes.
+        let index_map = self
+            .graph
+            .node_indices()
+            .filter(|node_index| !nodes.contains(node_index))
+            .enumerate()
+            .map(|(new, old)| (old, NodeIndex::new(new)))
+            .collect::<HashMap<_, _>>();
+        self.graph = self.graph.filter_map(
+            |node_index, node| (!nodes.contains(&node_index)).then(|| node.clone()),
+            |_, edge| Some(edge.clone()),
+        );
+        let sources = sources
+            .into_iter()
+            .map(|(key, source)| (key, self.graph.add_node(source)))
+            .collect::<HashMap<_, _>>();
+        for (source, target, edge) in cut_edges {
+            self.graph.add_edge(
+                sources[&(source, edge.from.clone())],
+                index_map[&target],
+                SchemaEdgeType::new(
+                    edge.id,
+                    edge.from,
+                    edge.to,
+                    Some(SchemaEdgeKind::FromSource),
+                ),
+            );
+        }
+        self.node_lookup_table = self
+            .graph
+            .node_indices()
+            .map(|node_index| (self.graph[node_index].handle.id, node_index))
+            .collect();
+    }
+
     pub fn into_graph(self) -> DiGraph<SchemaNodeType, SchemaEdgeType> {
         self.graph
     }
@@ -277,6 +422,49 @@ impl DagSchemas {
             .collect()
     }
 
+    /// Turns a sub graph node into a processor running the sub graph itself, e.g. per group of
+    /// features.
+    fn replace_with_processor(
+        &mut self,
+        node_id: NodeId,
+        action: &str,
+        factory: impl ProcessorFactory + 'static,
+    ) {
+        let Some(node_index) = self.node_index_by_node_id(node_id).copied() else {
+            return;
+        };
+        let node = &mut self.graph[node_index];
+        let Node::SubGraph { entity, .. } = node.node.clone() else {
+            return;
+        };
+        node.node = Node::Action {
+            entity,
+            action: action.to_string(),
+        };
+        node.kind = Some(NodeKind::Processor(Box::new(factory)));
+        let outgoing = self
+            .graph
+            .edges_directed(node_index, Direction::Outgoing)
+            .map(|edge| edge.id())
+            .collect::<Vec<_>>();
+        for edge in outgoing {
+            self.graph[edge].edge_kind = Some(SchemaEdgeKind::FromProcessor);
+        }
+    }
+
+    /// Removes a node. Removing shifts the index of another node, so the lookup table is rebuilt.
+    fn remove_node_by_node_id(&mut self, node_id: NodeId) {
+        let Some(node_index) = self.node_index_by_node_id(node_id).copied() else {
+            return;
+        };
+        self.graph.remove_node(node_index);
+        self.node_lookup_table = self
+            .graph
+            .node_indices()
+            .map(|node_index| (self.graph[node_index].handle.id, node_index))
+            .collect();
+    }
+
     pub fn remove_edge(&mut self, edge: petgraph::graph::EdgeIndex) {
         self.graph.remove_edge(edge);
     }
@@ -1,16 +1,26 @@
-use std::sync::atomic::AtomicU32;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 
+use parking_lot::Mutex;
+use reearth_flow_types::{workflow::ErrorPolicy, AttributeValue};
+use tokio::sync::broadcast::Sender;
 use tracing::error_span;
 
-use crate::errors::BoxedError;
+use crate::errors::{BoxedError, ExecutionError};
+use crate::event::Event;
+use crate::executor_operation::ExecutorContext;
+use crate::node::{NodeHandle, ERROR_ATTRIBUTE, REJECTED_PORT};
+use crate::stats::NodeStats;
 
 /// `ErrorManager` records and counts the number of errors happened.
 ///
-/// It panics when an error threshold is set and reached.
+/// The job fails when a node reports a fatal error or when an error threshold is set and
+/// reached. Nodes stop once [`ErrorManager::check`] returns the failure.
 #[derive(Debug)]
 pub struct ErrorManager {
     threshold: Option<u32>,
     count: AtomicU32,
+    failure: Mutex<Option<String>>,
 }
 
 impl ErrorManager {
@@ -18,6 +28,7 @@ impl ErrorManager {
         Self {
             threshold: Some(threshold),
             count: AtomicU32::new(0),
+            failure: Mutex::new(None),
         }
     }
 
@@ -25,6 +36,7 @@ impl ErrorManager {
         Self {
             threshold: None,
             count: AtomicU32::new(0),
+            failure: Mutex::new(None),
         }
     }
 
@@ -32,11 +44,122 @@ impl ErrorManager {
         let err_span = error_span!("reported error", error = true, e = error);
         let _error_guard = err_span.enter();
 
-        let count = self.count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let count = self.count.fetch_add(1, Ordering::SeqCst);
         if let Some(threshold) = self.threshold {
             if count >= threshold {
-                panic!("Error threshold reached: {}", threshold);
+                self.fail(format!(
+                    "Error threshold reached: {}, last error = {}",
+                    threshold, error
+                ));
             }
         }
     }
+
+    /// Fails the job. Only the first failure is kept.
+    pub fn fail(&self, message: String) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        self.failure.lock().get_or_insert(message);
+    }
+
+    pub fn error_count(&self) -> u32 {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    pub fn check(&self) -> Result<(), ExecutionError> {
+        match self.failure.lock().as_ref() {
+            Some(failure) => Err(ExecutionError::JobFailed(format!(
+                "{} ({} errors reported)",
+                failure,
+                self.error_count()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Applies the error policy of a node to the features it failed to process.
+#[derive(Debug, Clone)]
+pub(crate) struct NodeErrorHandler {
+    node: NodeHandle,
+    name: String,
+    policy: ErrorPolicy,
+    error_manager: Arc<ErrorManager>,
+    stats: Arc<NodeStats>,
+    event_sender: Sender<Event>,
+}
+
+impl NodeErrorHandler {
+    pub(crate) fn new(
+        node: NodeHandle,
+        name: String,
+        policy: ErrorPolicy,
+        error_manager: Arc<ErrorManager>,
+        stats: Arc<NodeStats>,
+        event_sender: Sender<Event>,
+    ) -> Self {
+        Self {
+            node,
+            name,
+            policy,
+            error_manager,
+            stats,
+            event_sender,
+        }
+    }
+
+    /// Number of times a feature is processed before the error is handled.
+    pub(crate) fn max_attempts(&self) -> u32 {
+        match self.policy {
+            ErrorPolicy::Retry { max_retries } => max_retries.saturating_add(1),
+            _ => 1,
+        }
+    }
+
+    /// Whether the feature must be kept around in case processing it fails, to be retried or
+    /// rejected. What a failed attempt sent and changed in the processor is then discarded.
+    /// Otherwise what is sent is forwarded at once.
+    pub(crate) fn keeps_feature(&self) -> bool {
+        matches!(self.policy, ErrorPolicy::Reject | ErrorPolicy::Retry { .. })
+    }
+
+    /// Handles the error of the last attempt. Returns the operation to send to the rejected port
+    /// when the feature is rejected.
+    pub(crate) fn handle(
+        &self,
+        feature_id: uuid::Uuid,
+        ctx: Option<ExecutorContext>,
+        error: BoxedError,
+    ) -> Option<ExecutorContext> {
+        self.stats.record_error();
+        let _ = self.event_sender.send(Event::FeatureError {
+            node: self.node.clone(),
+            feature_id,
+            message: error.to_string(),
+        });
+        match &self.policy {
+            ErrorPolicy::Fail | ErrorPolicy::Retry { .. } => {
+                self.error_manager.fail(format!(
+                    "Node {} ({}) failed to process feature {}: {}",
+                    self.name, self.node, feature_id, error
+                ));
+                None
+            }
+            ErrorPolicy::Skip => {
+                self.error_manager.report(error);
+                None
+            }
+            ErrorPolicy::Reject => {
+                let message = error.to_string();
+                self.error_manager.report(error);
+                let ctx = ctx?;
+                let mut feature = ctx.feature.clone();
+                feature.insert(ERROR_ATTRIBUTE, AttributeValue::String(message));
+                Some(ctx.new_with_feature_and_port(feature, REJECTED_PORT.clone()))
+            }
+        }
+    }
+
+    pub(crate) fn check(&self) -> Result<(), ExecutionError> {
+        self.error_manager.check()
+    }
 }
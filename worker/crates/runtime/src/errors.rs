@@ -82,6 +82,8 @@ pub enum ExecutionError {
     AppSourceConnectionAlreadyExists(String),
     #[error("Factory error: {0}")]
     Factory(#[source] BoxedError),
+    #[error("Build factory error: {0}")]
+    BuildFactory(String),
     #[error("Failed to restore record writer: {0}")]
     RestoreRecordWriter(#[source] DeserializationError),
     #[error("Source error: {0}")]
@@ -92,12 +94,24 @@ pub enum ExecutionError {
     SourceStateConflict(NodeHandle),
     #[error("Action name mismatch for node {0}: {1} != {2}")]
     ActionNameMismatch(String, String, String),
+    #[error("Checkpoint error: {0}")]
+    Checkpoint(#[source] BoxedError),
     #[error("Checkpoint writer thread panicked")]
     CheckpointWriterThreadPanicked,
     #[error("Failed to serialize record writer: {0}")]
     SerializeRecordWriter(#[source] SerializationError),
     #[error("InValid Sink: {0}")]
     InvalidSink(String),
+    #[error("Replay error: {0}")]
+    Replay(String),
+    #[error("Graph reference error: {0}")]
+    GraphRef(String),
+    #[error("Node cache error: {0}")]
+    Cache(String),
+    #[error("Job failed: {0}")]
+    JobFailed(String),
+    #[error("Job was shut down before its sources were exhausted")]
+    Interrupted,
 }
 
 impl<T> From<crossbeam::channel::SendError<T>> for ExecutionError {
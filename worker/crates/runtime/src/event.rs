@@ -1,10 +1,61 @@
-use tokio::sync::broadcast::{Receiver, Sender};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::broadcast::{error::RecvError, Receiver, Sender};
+use tracing::warn;
 
 use crate::node::NodeHandle;
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
 pub enum Event {
-    SinkFlushed { node: NodeHandle },
+    #[serde(rename_all = "camelCase")]
+    JobStarted {
+        job_id: String,
+        workflow_id: uuid::Uuid,
+    },
+    #[serde(rename_all = "camelCase")]
+    JobFinished {
+        job_id: String,
+        workflow_id: uuid::Uuid,
+        /// The reason of the failure, if the job failed.
+        error: Option<String>,
+    },
+    NodeStarted {
+        node: NodeHandle,
+        name: String,
+    },
+    NodeFinished {
+        node: NodeHandle,
+        name: String,
+    },
+    /// Number of features a node has received and sent so far. Sent periodically while the job
+    /// is running.
+    #[serde(rename_all = "camelCase")]
+    NodeProgress {
+        node: NodeHandle,
+        features_in: u64,
+        features_out: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    FeatureError {
+        node: NodeHandle,
+        feature_id: uuid::Uuid,
+        message: String,
+    },
+    SourceExhausted {
+        node: NodeHandle,
+    },
+    SinkFlushed {
+        node: NodeHandle,
+    },
+}
+
+/// Receives the events of a running job.
+#[async_trait::async_trait]
+pub trait EventHandler: Send + Sync + Debug {
+    async fn on_event(&self, event: &Event);
 }
 
 #[derive(Debug)]
@@ -18,6 +69,33 @@ impl EventHub {
         let (sender, receiver) = tokio::sync::broadcast::channel(capacity);
         Self { sender, receiver }
     }
+
+    pub fn send(&self, event: Event) {
+        // Nobody may be listening, which is fine.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Forwards the events to `handler` until the job has finished.
+    pub async fn dispatch(mut receiver: Receiver<Event>, handler: Arc<dyn EventHandler>) {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    handler.on_event(&event).await;
+                    if matches!(event, Event::JobFinished { .. }) {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Event handler lagged behind, skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
 }
 
 impl Clone for EventHub {
@@ -3,5 +3,6 @@ pub mod execution_dag;
 pub mod node;
 pub mod processor_node;
 pub mod receiver_loop;
+mod reorder_buffer;
 pub mod sink_node;
 pub mod source_node;
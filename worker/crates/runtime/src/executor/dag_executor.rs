@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
 use std::thread::{self, Builder};
@@ -17,9 +18,12 @@ use super::node::Node;
 use super::processor_node::ProcessorNode;
 use super::sink_node::SinkNode;
 use crate::builder_dag::{BuilderDag, NodeKind};
+use crate::cache::CachePlan;
 use crate::dag_schemas::DagSchemas;
+use crate::error_manager::ErrorManager;
 use crate::errors::ExecutionError;
 use crate::executor_operation::{ExecutorOptions, NodeContext};
+use crate::spill::SpillManager;
 
 use super::execution_dag::ExecutionDag;
 use super::source_node::{create_source_node, SourceNode};
@@ -27,10 +31,13 @@ use super::source_node::{create_source_node, SourceNode};
 pub struct DagExecutor {
     builder_dag: BuilderDag,
     options: ExecutorOptions,
+    cache_plan: Option<CachePlan>,
 }
 
 pub struct DagExecutorJoinHandle {
     join_handles: Vec<JoinHandle<Result<(), ExecutionError>>>,
+    error_manager: Arc<ErrorManager>,
+    interrupted: Arc<AtomicBool>,
 }
 
 impl DagExecutor {
@@ -42,12 +49,38 @@ impl DagExecutor {
         factories: HashMap<String, crate::node::NodeKind>,
         global_params: Option<serde_json::Map<String, serde_json::Value>>,
     ) -> Result<Self, ExecutionError> {
-        let dag_schemas = DagSchemas::from_graphs(entry_graph_id, graphs, factories, global_params);
-        let builder_dag = BuilderDag::new(ctx, dag_schemas, options.event_hub_capacity).await?;
+        let mut dag_schemas = DagSchemas::from_graphs(
+            entry_graph_id,
+            graphs,
+            factories,
+            global_params.clone(),
+            &ctx.storage_resolver,
+        )?;
+        if let Some(replay) = &options.replay {
+            dag_schemas.retain_downstream_of_edge(replay.edge_id, |edge, edge_ids| {
+                replay.create_source(edge, edge_ids)
+            })?;
+        }
+        let cache_plan = match &options.cache {
+            Some(cache) => Some(
+                cache
+                    .apply(&mut dag_schemas, &ctx, global_params.as_ref())
+                    .await?,
+            ),
+            None => None,
+        };
+        let builder_dag = BuilderDag::new(
+            ctx,
+            dag_schemas,
+            options.event_hub.clone(),
+            options.resume_from.as_ref(),
+        )
+        .await?;
 
         Ok(Self {
             builder_dag,
             options,
+            cache_plan,
         })
     }
 
@@ -60,6 +93,7 @@ impl DagExecutor {
         storage_resolver: Arc<StorageResolver>,
         logger: Arc<LoggerFactory>,
         kv_store: Arc<Box<dyn crate::kvs::KvStore>>,
+        spill_manager: Arc<SpillManager>,
         state: Arc<State>,
     ) -> Result<DagExecutorJoinHandle, ExecutionError> {
         // Construct execution dag.
@@ -68,14 +102,20 @@ impl DagExecutor {
             self.options.channel_buffer_sz,
             self.options.error_threshold,
             Arc::clone(&state),
+            self.options.feature_store_format,
+            self.cache_plan.as_ref(),
+            self.options.checkpoint.clone(),
+            Arc::clone(&self.options.stats),
         )?;
         let node_indexes = execution_dag.graph().node_indices().collect::<Vec<_>>();
+        let error_manager = Arc::clone(execution_dag.error_manager());
 
         let ctx = NodeContext::new(
             Arc::clone(&expr_engine),
             Arc::clone(&storage_resolver),
             Arc::clone(&logger),
             Arc::clone(&kv_store),
+            Arc::clone(&spill_manager),
         );
         // Start the threads.
         let source_node = create_source_node(
@@ -86,6 +126,7 @@ impl DagExecutor {
             runtime.clone(),
         )
         .await;
+        let interrupted = source_node.interrupted();
         let mut join_handles = vec![start_source(source_node)?];
         for node_index in node_indexes {
             let Some(node) = execution_dag.graph()[node_index].kind.as_ref() else {
@@ -99,6 +140,7 @@ impl DagExecutor {
                         Arc::clone(&storage_resolver),
                         Arc::clone(&logger),
                         Arc::clone(&kv_store),
+                        Arc::clone(&spill_manager),
                     );
                     let processor_node = ProcessorNode::new(
                         ctx,
@@ -106,6 +148,8 @@ impl DagExecutor {
                         node_index,
                         shutdown.clone(),
                         runtime.clone(),
+                        self.options.thread_pool_size,
+                        self.options.preserve_order,
                     )
                     .await;
                     join_handles.push(start_processor(processor_node)?);
@@ -116,6 +160,7 @@ impl DagExecutor {
                         Arc::clone(&storage_resolver),
                         Arc::clone(&logger),
                         Arc::clone(&kv_store),
+                        Arc::clone(&spill_manager),
                     );
                     let sink_node = SinkNode::new(
                         ctx,
@@ -129,11 +174,17 @@ impl DagExecutor {
             }
         }
 
-        Ok(DagExecutorJoinHandle { join_handles })
+        Ok(DagExecutorJoinHandle {
+            join_handles,
+            error_manager,
+            interrupted,
+        })
     }
 }
 
 impl DagExecutorJoinHandle {
+    /// Waits for all the nodes to finish. Fails with [`ExecutionError::Interrupted`] when the
+    /// shutdown stopped the sources early, even though the other nodes finished normally.
     pub fn join(mut self) -> Result<(), ExecutionError> {
         loop {
             let Some(finished) = self
@@ -147,9 +198,17 @@ impl DagExecutorJoinHandle {
                 continue;
             };
             let handle = self.join_handles.swap_remove(finished);
-            handle.join().unwrap()?;
+            if let Err(e) = handle.join().unwrap() {
+                // Other nodes stop with channel errors once a node fails, so report the cause.
+                self.error_manager.check()?;
+                return Err(e);
+            }
 
             if self.join_handles.is_empty() {
+                self.error_manager.check()?;
+                if self.interrupted.load(Ordering::SeqCst) {
+                    return Err(ExecutionError::Interrupted);
+                }
                 return Ok(());
             }
         }
@@ -202,3 +261,163 @@ fn start_sink<F: Send + 'static + Future + Unpin + Debug>(
         })
         .map_err(ExecutionError::CannotSpawnWorkerThread)
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::checkpoint::{Checkpoint, CheckpointOptions};
+    use crate::node::SourceState;
+    use crate::tests::utils::{
+        create_executor_options, create_factories, create_state, execute, execute_until_shutdown,
+        CollectingSinkFactory,
+    };
+
+    const SOURCE_ID: &str = "7c1d2e3f-0000-4000-8000-000000000001";
+
+    fn workflow(count: i64, interval_ms: u64) -> String {
+        json!({
+            "id": "7c1d2e3f-0000-4000-8000-000000000000",
+            "name": "shutdown",
+            "entryGraphId": "7c1d2e3f-0000-4000-8000-0000000000a0",
+            "graphs": [{
+                "id": "7c1d2e3f-0000-4000-8000-0000000000a0",
+                "name": "entry",
+                "nodes": [
+                    {
+                        "id": SOURCE_ID,
+                        "name": "source",
+                        "type": "action",
+                        "action": "NumberSource",
+                        "with": {"count": count, "intervalMs": interval_ms}
+                    },
+                    {
+                        "id": "7c1d2e3f-0000-4000-8000-000000000002",
+                        "name": "sink",
+                        "type": "action",
+                        "action": "Collecting"
+                    }
+                ],
+                "edges": [{
+                    "id": "7c1d2e3f-0000-4000-8000-0000000000e1",
+                    "from": "7c1d2e3f-0000-4000-8000-000000000001",
+                    "to": "7c1d2e3f-0000-4000-8000-000000000002",
+                    "fromPort": "default",
+                    "toPort": "default"
+                }]
+            }]
+        })
+        .to_string()
+    }
+
+    fn run(
+        count: i64,
+        interval_ms: u64,
+        shutdown_after: Duration,
+    ) -> (Result<(), ExecutionError>, CollectingSinkFactory) {
+        let sink = CollectingSinkFactory::default();
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///feature-store/", &storage_resolver);
+        let result = execute_until_shutdown(
+            &workflow(count, interval_ms),
+            create_factories(&sink),
+            create_executor_options(),
+            storage_resolver,
+            state,
+            Some(shutdown_after),
+        );
+        (result, sink)
+    }
+
+    #[test]
+    fn test_shutdown_interrupts_sources() {
+        let (result, sink) = run(1000, 10, Duration::from_millis(200));
+        assert!(matches!(result, Err(ExecutionError::Interrupted)));
+        // The sink still finishes with the features sent before the shutdown.
+        let received = sink.values().len();
+        assert!(received > 0 && received < 1000);
+    }
+
+    #[test]
+    fn test_job_completed_before_shutdown_succeeds() {
+        let (result, sink) = run(3, 0, Duration::from_secs(5));
+        assert!(result.is_ok());
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+    }
+
+    fn resume(
+        count: i64,
+        source_state: SourceState,
+    ) -> (Result<(), ExecutionError>, CollectingSinkFactory) {
+        let sink = CollectingSinkFactory::default();
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///feature-store/", &storage_resolver);
+        let options = ExecutorOptions {
+            resume_from: Some(Checkpoint {
+                epoch: 1,
+                sources: HashMap::from([(SOURCE_ID.parse().unwrap(), source_state)]),
+                ..Default::default()
+            }),
+            ..create_executor_options()
+        };
+        let result = execute(
+            &workflow(count, 0),
+            create_factories(&sink),
+            options,
+            storage_resolver,
+            state,
+        );
+        (result, sink)
+    }
+
+    #[test]
+    fn test_resume_restarts_sources_from_their_state() {
+        let (result, sink) = resume(5, SourceState::Restartable(b"3".to_vec()));
+        assert!(result.is_ok());
+        assert_eq!(sink.values(), vec![3, 4]);
+
+        let (result, sink) = resume(3, SourceState::NotStarted);
+        assert!(result.is_ok());
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_resume_fails_for_sources_without_state() {
+        let (result, sink) = resume(3, SourceState::NonRestartable);
+        assert!(matches!(result, Err(ExecutionError::Checkpoint(_))));
+        assert!(sink.values().is_empty());
+    }
+
+    #[test]
+    fn test_checkpoint_records_source_states() {
+        let sink = CollectingSinkFactory::default();
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///checkpoint/", &storage_resolver);
+        let options = ExecutorOptions {
+            checkpoint: Some(CheckpointOptions {
+                job_id: "job".to_string(),
+                interval: Duration::ZERO,
+                state: Arc::clone(&state),
+            }),
+            ..create_executor_options()
+        };
+        execute(
+            &workflow(5, 0),
+            create_factories(&sink),
+            options,
+            Arc::clone(&storage_resolver),
+            Arc::clone(&state),
+        )
+        .unwrap();
+        let checkpoint = Runtime::new()
+            .unwrap()
+            .block_on(Checkpoint::load(&state, "job"))
+            .unwrap();
+        // Taken after the state of the source following its last feature.
+        assert_eq!(
+            checkpoint.sources.get(&SOURCE_ID.parse().unwrap()),
+            Some(&SourceState::Restartable(b"5".to_vec()))
+        );
+    }
+}
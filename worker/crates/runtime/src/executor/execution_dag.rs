@@ -6,18 +6,23 @@ use std::{
 
 use reearth_flow_common::collection::insert_vec_element;
 use reearth_flow_state::State;
+use reearth_flow_types::workflow::ErrorPolicy;
+use tokio::runtime::Runtime;
 use tokio::sync::Mutex;
 
 use crate::{
     builder_dag::{BuilderDag, NodeKind},
+    cache::CachePlan,
+    checkpoint::{CheckpointCoordinator, CheckpointOptions},
     dag_schemas::{EdgeHavePorts, SchemaEdgeKind},
     error_manager::ErrorManager,
     errors::ExecutionError,
     event::EventHub,
-    executor_operation::ExecutorOperation,
-    feature_store::{create_feature_writer, FeatureWriter},
+    executor_operation::{ExecutorOperation, InnerNodeContext},
+    feature_store::{create_feature_writer, FeatureStoreFormat, FeatureWriter},
     forwarder::SenderWithPortMapping,
     node::{GraphId, NodeHandle, Port},
+    stats::{JobStats, NodeStats, NodeStatsKind},
 };
 use crossbeam::channel::{bounded, Receiver, Sender};
 use petgraph::{visit::EdgeRef, Direction};
@@ -25,7 +30,12 @@ use petgraph::{visit::EdgeRef, Direction};
 #[derive(Debug)]
 pub struct NodeType {
     pub handle: NodeHandle,
+    pub name: String,
+    pub action: String,
     pub kind: Option<NodeKind>,
+    pub error_policy: ErrorPolicy,
+    pub num_threads: Option<usize>,
+    pub preserve_order: Option<bool>,
 }
 
 type SharedFeatureWriter = Arc<Mutex<Option<Box<dyn FeatureWriter>>>>;
@@ -52,14 +62,23 @@ pub struct ExecutionDag {
     graph: petgraph::graph::DiGraph<NodeType, EdgeType>,
     event_hub: EventHub,
     error_manager: Arc<ErrorManager>,
+    checkpoint_coordinator: Option<Arc<CheckpointCoordinator>>,
+    stats: Arc<JobStats>,
+    state: Arc<State>,
+    feature_store_format: FeatureStoreFormat,
 }
 
 impl ExecutionDag {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         builder_dag: BuilderDag,
         channel_buffer_sz: usize,
         error_threshold: Option<u32>,
         state: Arc<State>,
+        feature_store_format: FeatureStoreFormat,
+        cache_plan: Option<&CachePlan>,
+        checkpoint: Option<CheckpointOptions>,
+        stats: Arc<JobStats>,
     ) -> Result<Self, ExecutionError> {
         let graph_id = builder_dag.id;
         // We only create record writer once for every output port. Every `HashMap` in this `Vec` tracks if a node's output ports already have the record writer created.
@@ -87,8 +106,16 @@ impl ExecutionDag {
                 .entry(input_port.clone())
             {
                 Entry::Vacant(entry) => {
-                    let feature_writer = Some(create_feature_writer(edge_id, Arc::clone(&state)));
-                    let feature_writer = Arc::new(Mutex::new(feature_writer));
+                    let mut feature_writer =
+                        create_feature_writer(edge_id, Arc::clone(&state), feature_store_format);
+                    if let Some(cache_plan) = cache_plan {
+                        feature_writer = cache_plan.writer(
+                            builder_dag.graph()[source_node_index].handle.id,
+                            &input_port,
+                            feature_writer,
+                        );
+                    }
+                    let feature_writer = Arc::new(Mutex::new(Some(feature_writer)));
                     entry.insert(feature_writer).clone()
                 }
                 Entry::Occupied(entry) => Arc::clone(entry.get()),
@@ -116,11 +143,23 @@ impl ExecutionDag {
             edges.push(Some(edge));
         }
 
+        // Every processor and sink acknowledges each checkpoint barrier.
+        let checkpoint_coordinator = checkpoint.map(|options| {
+            let expected_acks = builder_dag
+                .graph()
+                .node_weights()
+                .filter(|node| !matches!(node.kind, NodeKind::Source(_)))
+                .count();
+            Arc::new(CheckpointCoordinator::new(options, expected_acks))
+        });
+
         // Create new graph.
         let (graph, event_hub) = builder_dag.into_graph_and_event_hub();
         let graph = graph.map(
             |_, node| NodeType {
                 handle: node.handle.clone(),
+                name: node.name.clone(),
+                action: node.action.clone(),
                 kind: {
                     match &node.kind {
                         NodeKind::Source(source) => Some(NodeKind::Source(source.clone())),
@@ -130,6 +169,9 @@ impl ExecutionDag {
                         NodeKind::Sink(sink) => Some(NodeKind::Sink(sink.clone())),
                     }
                 },
+                error_policy: node.error_policy.clone(),
+                num_threads: node.num_threads,
+                preserve_order: node.preserve_order,
             },
             |edge_index, _| {
                 edges[edge_index.index()]
@@ -146,6 +188,10 @@ impl ExecutionDag {
             } else {
                 ErrorManager::new_unlimited()
             }),
+            checkpoint_coordinator,
+            stats,
+            state,
+            feature_store_format,
         })
     }
 
@@ -153,6 +199,52 @@ impl ExecutionDag {
         &self.error_manager
     }
 
+    /// Registers the statistics of a node, with the ports of its edges.
+    pub(crate) fn register_stats(
+        &self,
+        node_index: petgraph::graph::NodeIndex,
+        kind: NodeStatsKind,
+    ) -> Arc<NodeStats> {
+        let node = &self.graph[node_index];
+        // `output_port` is the port of the target node, `input_port` the one of the source node.
+        let input_ports = self
+            .graph
+            .edges_directed(node_index, Direction::Incoming)
+            .map(|edge| edge.weight().output_port.clone())
+            .collect::<Vec<_>>();
+        let output_ports = self
+            .graph
+            .edges_directed(node_index, Direction::Outgoing)
+            .map(|edge| edge.weight().input_port.clone())
+            .collect::<Vec<_>>();
+        self.stats.register(
+            self.id,
+            &node.handle,
+            &node.name,
+            &node.action,
+            kind,
+            &input_ports,
+            &output_ports,
+        )
+    }
+
+    /// Context of the nodes run by the processor of a node.
+    pub(crate) fn inner_node_context(&self, runtime: Arc<Runtime>) -> InnerNodeContext {
+        InnerNodeContext {
+            graph_id: self.id,
+            error_manager: Arc::clone(&self.error_manager),
+            stats: Arc::clone(&self.stats),
+            event_sender: self.event_hub.sender.clone(),
+            state: Arc::clone(&self.state),
+            feature_store_format: self.feature_store_format,
+            runtime,
+        }
+    }
+
+    pub(crate) fn checkpoint_coordinator(&self) -> Option<&Arc<CheckpointCoordinator>> {
+        self.checkpoint_coordinator.as_ref()
+    }
+
     pub fn graph(&self) -> &petgraph::graph::DiGraph<NodeType, EdgeType> {
         &self.graph
     }
@@ -14,9 +14,14 @@ use reearth_flow_storage::resolve::StorageResolver;
 use tokio::runtime::Runtime;
 use tracing::{info_span, Span};
 
-use crate::error_manager::ErrorManager;
+use crate::channels::{Collector, ProcessorChannelForwarder};
+use crate::checkpoint::CheckpointCoordinator;
+use crate::error_manager::{ErrorManager, NodeErrorHandler};
+use crate::event::Event;
 use crate::executor_operation::{ExecutorContext, ExecutorOperation, NodeContext};
 use crate::kvs::KvStore;
+use crate::spill::SpillManager;
+use crate::stats::{NodeStats, NodeStatsKind};
 use crate::{
     builder_dag::NodeKind,
     errors::ExecutionError,
@@ -24,7 +29,8 @@ use crate::{
     node::{NodeHandle, Processor},
 };
 
-use super::receiver_loop::init_select;
+use super::receiver_loop::{aligned_checkpoint, init_select};
+use super::reorder_buffer::ReorderBuffer;
 use super::{execution_dag::ExecutionDag, receiver_loop::ReceiverLoop};
 
 /// A processor in the execution DAG.
@@ -32,6 +38,8 @@ use super::{execution_dag::ExecutionDag, receiver_loop::ReceiverLoop};
 pub struct ProcessorNode<F> {
     /// Node handle in description DAG.
     node_handle: NodeHandle,
+    /// Node name in the workflow.
+    name: String,
     /// Input node handles.
     node_handles: Vec<NodeHandle>,
     /// Input data channels.
@@ -48,6 +56,9 @@ pub struct ProcessorNode<F> {
     runtime: Arc<Runtime>,
     #[allow(dead_code)]
     error_manager: Arc<ErrorManager>,
+    error_handler: NodeErrorHandler,
+    stats: Arc<NodeStats>,
+    event_sender: tokio::sync::broadcast::Sender<Event>,
     logger_factory: Arc<LoggerFactory>,
     logger: Arc<ActionLogger>,
     span: tracing::Span,
@@ -56,6 +67,13 @@ pub struct ProcessorNode<F> {
     expr_engine: Arc<Engine>,
     storage_resolver: Arc<StorageResolver>,
     kv_store: Arc<Box<dyn KvStore>>,
+    spill_manager: Arc<SpillManager>,
+    checkpoint_coordinator: Option<Arc<CheckpointCoordinator>>,
+    /// Puts the features sent by the threads back in the order they were received, when the
+    /// node preserves the order.
+    reorder_buffer: Option<Arc<parking_lot::Mutex<ReorderBuffer>>>,
+    /// Sequence number of the next received feature.
+    sequence: u64,
 }
 
 impl<F: Future + Unpin + Debug> ProcessorNode<F> {
@@ -65,15 +83,31 @@ impl<F: Future + Unpin + Debug> ProcessorNode<F> {
         node_index: NodeIndex,
         shutdown: F,
         runtime: Arc<Runtime>,
+        thread_pool_size: usize,
+        preserve_order: bool,
     ) -> Self {
         let node = dag.node_weight_mut(node_index);
         let Some(kind) = node.kind.take() else {
             panic!("Must pass in a node")
         };
         let node_handle = node.handle.clone();
+        let name = node.name.clone();
+        let error_policy = node.error_policy.clone();
+        let node_num_threads = node.num_threads;
+        let preserve_order = node.preserve_order.unwrap_or(preserve_order);
         let NodeKind::Processor(mut processor) = kind else {
             panic!("Must pass in a processor node");
         };
+        let stats = dag.register_stats(node_index, NodeStatsKind::Processor);
+        let event_sender = dag.event_hub().sender.clone();
+        let error_handler = NodeErrorHandler::new(
+            node_handle.clone(),
+            name.clone(),
+            error_policy,
+            dag.error_manager().clone(),
+            Arc::clone(&stats),
+            event_sender.clone(),
+        );
         let (node_handles, receivers) = dag.collect_receivers(node_index);
 
         let senders = dag.collect_senders(node_index);
@@ -84,6 +118,7 @@ impl<F: Future + Unpin + Debug> ProcessorNode<F> {
             record_writers,
             senders,
             dag.error_manager().clone(),
+            Arc::clone(&stats),
             runtime.clone(),
         );
         let span = info_span!(
@@ -101,10 +136,13 @@ impl<F: Future + Unpin + Debug> ProcessorNode<F> {
         let expr_engine = Arc::clone(&ctx.expr_engine);
         let storage_resolver = Arc::clone(&ctx.storage_resolver);
         let kv_store = Arc::clone(&ctx.kv_store);
+        let spill_manager = Arc::clone(&ctx.spill_manager);
+        processor.attach(&dag.inner_node_context(Arc::clone(&runtime)));
         processor.initialize(ctx);
-        let num_threads = processor.num_threads();
+        let num_threads = thread_count(node_num_threads, processor.num_threads(), thread_pool_size);
         Self {
             node_handle,
+            name,
             node_handles,
             receivers,
             processor: Arc::new(parking_lot::RwLock::new(processor)),
@@ -112,6 +150,9 @@ impl<F: Future + Unpin + Debug> ProcessorNode<F> {
             shutdown,
             runtime,
             error_manager: dag.error_manager().clone(),
+            error_handler,
+            stats,
+            event_sender,
             logger_factory,
             logger: Arc::new(logger),
             span,
@@ -123,12 +164,42 @@ impl<F: Future + Unpin + Debug> ProcessorNode<F> {
             expr_engine,
             storage_resolver,
             kv_store,
+            spill_manager,
+            checkpoint_coordinator: dag.checkpoint_coordinator().cloned(),
+            reorder_buffer: preserve_order.then(Default::default),
+            sequence: 0,
         }
     }
 
     pub fn handle(&self) -> &NodeHandle {
         &self.node_handle
     }
+
+    fn wait_for_running_threads(&self) {
+        while self
+            .thread_counter
+            .load(std::sync::atomic::Ordering::SeqCst)
+            != 0
+        {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn on_checkpoint(&mut self, epoch: u64) -> Result<(), ExecutionError> {
+        // Features received before the barrier must be processed and forwarded first.
+        self.wait_for_running_threads();
+        if let Some(coordinator) = &self.checkpoint_coordinator {
+            let state = self
+                .processor
+                .read()
+                .serialize_state()
+                .map_err(ExecutionError::Checkpoint)?;
+            coordinator.ack_processor(epoch, &self.node_handle, state);
+        }
+        self.channel_manager
+            .read()
+            .send_non_op(ExecutorOperation::Checkpoint { epoch })
+    }
 }
 
 impl<F: Future + Unpin + Debug> ReceiverLoop for ProcessorNode<F> {
@@ -144,39 +215,54 @@ impl<F: Future + Unpin + Debug> ReceiverLoop for ProcessorNode<F> {
     {
         let receivers = self.receivers();
         let mut is_terminated = vec![false; receivers.len()];
-        let mut sel = init_select(&receivers);
+        // Inputs that delivered a checkpoint barrier are blocked until the barrier is aligned.
+        let mut barriers = vec![None; receivers.len()];
+        let (mut sel, mut indexes) = init_select(&receivers, |_| true);
+        let _ = self.event_sender.send(Event::NodeStarted {
+            node: self.node_handle.clone(),
+            name: self.name.clone(),
+        });
 
         loop {
+            self.error_handler.check()?;
             if is_terminated.iter().all(|value| *value) {
-                if self
-                    .thread_counter
-                    .load(std::sync::atomic::Ordering::SeqCst)
-                    == 0
-                {
-                    self.on_terminate(NodeContext::new(
-                        self.expr_engine.clone(),
-                        self.storage_resolver.clone(),
-                        self.logger_factory.clone(),
-                        self.kv_store.clone(),
-                    ))?;
-                    return Ok(());
-                }
-                std::thread::sleep(Duration::from_millis(100));
-                continue;
+                self.wait_for_running_threads();
+                self.error_handler.check()?;
+                self.on_terminate(NodeContext::new(
+                    self.expr_engine.clone(),
+                    self.storage_resolver.clone(),
+                    self.logger_factory.clone(),
+                    self.kv_store.clone(),
+                    self.spill_manager.clone(),
+                ))?;
+                let _ = self.event_sender.send(Event::NodeFinished {
+                    node: self.node_handle.clone(),
+                    name: self.name.clone(),
+                });
+                return Ok(());
             }
-            let index = sel.ready();
+            let index = indexes[sel.ready()];
             let op = receivers[index]
                 .recv()
                 .map_err(|e| ExecutionError::CannotReceiveFromChannel(format!("{:?}", e)))?;
             match op {
                 ExecutorOperation::Op { ctx } => {
                     self.on_op(ctx)?;
+                    continue;
                 }
                 ExecutorOperation::Terminate { ctx: _ctx } => {
                     is_terminated[index] = true;
-                    sel.remove(index);
                 }
+                ExecutorOperation::Checkpoint { epoch } => {
+                    barriers[index] = Some(epoch);
+                }
+            }
+            if let Some(epoch) = aligned_checkpoint(&is_terminated, &barriers) {
+                self.on_checkpoint(epoch)?;
+                barriers.fill(None);
             }
+            (sel, indexes) =
+                init_select(&receivers, |i| !is_terminated[i] && barriers[i].is_none());
         }
     }
 
@@ -190,10 +276,27 @@ impl<F: Future + Unpin + Debug> ReceiverLoop for ProcessorNode<F> {
 
         let span = self.span.clone();
         let logger = self.logger.clone();
+        let error_handler = self.error_handler.clone();
+        let stats = Arc::clone(&self.stats);
+        stats.record_in(&ctx.port);
+        let order = self
+            .reorder_buffer
+            .as_ref()
+            .map(|reorder_buffer| (self.sequence, Arc::clone(reorder_buffer)));
+        self.sequence += 1;
         let counter = Arc::clone(&self.thread_counter);
         counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         self.thread_pool.spawn(move || {
-            process(ctx, span, logger, channel_manager, processor);
+            process(
+                ctx,
+                span,
+                logger,
+                channel_manager,
+                processor,
+                error_handler,
+                stats,
+                order,
+            );
             counter.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
         });
         Ok(())
@@ -212,12 +315,27 @@ impl<F: Future + Unpin + Debug> ReceiverLoop for ProcessorNode<F> {
     }
 }
 
+/// Number of threads of a processor node: the `numThreads` of the node, or the default of its
+/// action, clamped to the thread pool size of the workflow.
+fn thread_count(node_num_threads: Option<usize>, default: usize, thread_pool_size: usize) -> usize {
+    node_num_threads
+        .unwrap_or(default)
+        .clamp(1, thread_pool_size.max(1))
+}
+
+/// Sequence number of a received feature and the buffer releasing what is sent for it in order.
+type Order = (u64, Arc<parking_lot::Mutex<ReorderBuffer>>);
+
+#[allow(clippy::too_many_arguments)]
 fn process(
     ctx: ExecutorContext,
     span: Span,
     logger: Arc<ActionLogger>,
     channel_manager: Arc<parking_lot::RwLock<ChannelManager>>,
     processor: Arc<parking_lot::RwLock<Box<dyn Processor>>>,
+    error_handler: NodeErrorHandler,
+    stats: Arc<NodeStats>,
+    order: Option<Order>,
 ) {
     let feature_id = ctx.feature.id;
     let now = time::Instant::now();
@@ -225,13 +343,266 @@ fn process(
     let mut processor_guard = processor.write();
     let channel_manager: &mut ChannelManager = &mut channel_manager_guard;
     let processor: &mut Box<dyn Processor> = &mut processor_guard;
-    let result = processor.process(ctx, channel_manager);
+    let original = error_handler.keeps_feature().then(|| ctx.clone());
+    // What a failed attempt sent is discarded, so each attempt sends into its own collector, and
+    // a stateful processor is restored to what it was before the attempt.
+    let collects = order.is_some() || original.is_some();
+    let snapshot = (original.is_some() && processor.is_stateful()).then(|| processor.clone());
+    let attempt = |processor: &mut Box<dyn Processor>,
+                   channel_manager: &mut ChannelManager,
+                   ctx: ExecutorContext| {
+        let mut collector = Collector::default();
+        let result = if collects {
+            processor.process(ctx, &mut collector)
+        } else {
+            processor.process(ctx, channel_manager)
+        };
+        (result, collector)
+    };
+    let (mut result, mut collector) = attempt(processor, channel_manager, ctx);
+    let mut attempts = 1;
+    while let (Err(e), Some(original)) = (&result, &original) {
+        if let Some(snapshot) = &snapshot {
+            *processor = snapshot.clone();
+        }
+        if attempts >= error_handler.max_attempts() {
+            break;
+        }
+        action_log!(
+            parent: span, logger, "Retrying operation, feature id = {:?}, attempt = {}, error = {:?}", feature_id, attempts, e,
+        );
+        attempts += 1;
+        (result, collector) = attempt(processor, channel_manager, original.clone());
+    }
     if let Err(e) = result {
         action_error_log!(
             parent: span, logger, "Error operation, feature id = {:?}, error = {:?}", feature_id, e,
-        )
+        );
+        collector.0.clear();
+        if let Some(rejected) = error_handler.handle(feature_id, original, e) {
+            collector.send(rejected);
+        }
     }
+    let sent = match order {
+        Some((sequence, reorder_buffer)) => reorder_buffer.lock().push(sequence, collector.0),
+        None => collector.0,
+    };
+    for ctx in sent {
+        channel_manager.send(ctx);
+    }
+    let elapsed = now.elapsed();
+    stats.record_elapsed(elapsed);
     action_log!(
-        parent: span, logger, "Processing operation, feature id = {:?}, elapsed = {:?}", feature_id, now.elapsed(),
+        parent: span, logger, "Processing operation, feature id = {:?}, elapsed = {:?}", feature_id, elapsed,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use reearth_flow_storage::resolve::StorageResolver;
+    use reearth_flow_types::AttributeValue;
+    use serde_json::json;
+
+    use super::*;
+    use crate::node::{NodeKind, ERROR_ATTRIBUTE};
+    use crate::tests::utils::{
+        create_executor_options, create_factories, create_state, execute, node_summary,
+        CollectingSinkFactory,
+    };
+
+    /// Sends 3 features through a `Failing` node with the parameters `with` and the error policy
+    /// `on_error`.
+    fn workflow(with: serde_json::Value, on_error: serde_json::Value) -> String {
+        json!({
+            "id": "3f0e1d2c-0000-4000-8000-000000000000",
+            "name": "error policy",
+            "entryGraphId": "3f0e1d2c-0000-4000-8000-0000000000a0",
+            "graphs": [{
+                "id": "3f0e1d2c-0000-4000-8000-0000000000a0",
+                "name": "entry",
+                "nodes": [
+                    {
+                        "id": "3f0e1d2c-0000-4000-8000-000000000001",
+                        "name": "source",
+                        "type": "action",
+                        "action": "NumberSource",
+                        "with": {"count": 3}
+                    },
+                    {
+                        "id": "3f0e1d2c-0000-4000-8000-000000000002",
+                        "name": "failing",
+                        "type": "action",
+                        "action": "Failing",
+                        "with": with,
+                        "onError": on_error
+                    },
+                    {
+                        "id": "3f0e1d2c-0000-4000-8000-000000000003",
+                        "name": "sink",
+                        "type": "action",
+                        "action": "Collecting"
+                    },
+                    {
+                        "id": "3f0e1d2c-0000-4000-8000-000000000004",
+                        "name": "rejected",
+                        "type": "action",
+                        "action": "RejectedCollecting"
+                    }
+                ],
+                "edges": [
+                    {
+                        "id": "3f0e1d2c-0000-4000-8000-0000000000e1",
+                        "from": "3f0e1d2c-0000-4000-8000-000000000001",
+                        "to": "3f0e1d2c-0000-4000-8000-000000000002",
+                        "fromPort": "default",
+                        "toPort": "default"
+                    },
+                    {
+                        "id": "3f0e1d2c-0000-4000-8000-0000000000e2",
+                        "from": "3f0e1d2c-0000-4000-8000-000000000002",
+                        "to": "3f0e1d2c-0000-4000-8000-000000000003",
+                        "fromPort": "default",
+                        "toPort": "default"
+                    },
+                    {
+                        "id": "3f0e1d2c-0000-4000-8000-0000000000e3",
+                        "from": "3f0e1d2c-0000-4000-8000-000000000002",
+                        "to": "3f0e1d2c-0000-4000-8000-000000000004",
+                        "fromPort": "rejected",
+                        "toPort": "default"
+                    }
+                ]
+            }]
+        })
+        .to_string()
+    }
+
+    /// Runs the workflow with a node failing `failures` times for each feature, returning its
+    /// result, the features of the sink, the rejected features and the errors of the failing node.
+    fn run(
+        failures: usize,
+        on_error: serde_json::Value,
+    ) -> (
+        Result<(), ExecutionError>,
+        CollectingSinkFactory,
+        CollectingSinkFactory,
+        u64,
+    ) {
+        run_with(json!({"failures": failures}), on_error)
+    }
+
+    fn run_with(
+        with: serde_json::Value,
+        on_error: serde_json::Value,
+    ) -> (
+        Result<(), ExecutionError>,
+        CollectingSinkFactory,
+        CollectingSinkFactory,
+        u64,
+    ) {
+        let sink = CollectingSinkFactory::default();
+        let rejected = CollectingSinkFactory::default();
+        let mut factories = create_factories(&sink);
+        factories.insert(
+            "RejectedCollecting".to_string(),
+            NodeKind::Sink(Box::new(rejected.clone())),
+        );
+        let options = create_executor_options();
+        let stats = Arc::clone(&options.stats);
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///feature-store/", &storage_resolver);
+        let result = execute(
+            &workflow(with, on_error),
+            factories,
+            options,
+            storage_resolver,
+            state,
+        );
+        let errors = node_summary(&stats, "failing").unwrap().errors;
+        (result, sink, rejected, errors)
+    }
+
+    #[test]
+    fn test_thread_count_is_clamped_to_thread_pool_size() {
+        assert_eq!(thread_count(None, 1, 4), 1);
+        assert_eq!(thread_count(None, 8, 4), 4);
+        assert_eq!(thread_count(Some(2), 8, 4), 2);
+        assert_eq!(thread_count(Some(16), 1, 4), 4);
+        assert_eq!(thread_count(Some(0), 8, 4), 1);
+        assert_eq!(thread_count(Some(2), 1, 0), 1);
+    }
+
+    #[test]
+    fn test_skip_drops_failed_features() {
+        let (result, sink, rejected, errors) = run(1, json!({"type": "skip"}));
+        assert!(result.is_ok());
+        assert_eq!(errors, 3);
+        // The copies sent before each failure are already forwarded.
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+        assert_eq!(rejected.values(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_skip_is_the_default() {
+        let (result, sink, _, errors) = run(1, serde_json::Value::Null);
+        assert!(result.is_ok());
+        assert_eq!(errors, 3);
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_reject_sends_failed_features_to_rejected_port() {
+        let (result, sink, rejected, errors) = run(1, json!({"type": "reject"}));
+        assert!(result.is_ok());
+        assert_eq!(errors, 3);
+        assert_eq!(sink.values(), Vec::<i64>::new());
+        assert_eq!(rejected.values(), vec![0, 1, 2]);
+        for feature in rejected.features.lock().iter() {
+            assert_eq!(
+                feature.get(&ERROR_ATTRIBUTE),
+                Some(&AttributeValue::String("Attempt 1 failed".to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_retry_forwards_only_successful_attempt() {
+        let (result, sink, rejected, errors) = run(2, json!({"type": "retry", "maxRetries": 2}));
+        assert!(result.is_ok());
+        assert_eq!(errors, 0);
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+        assert_eq!(rejected.values(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_retry_restores_stateful_processor() {
+        let (result, sink, rejected, errors) = run_with(
+            json!({"failures": 2, "buffers": true}),
+            json!({"type": "retry", "maxRetries": 2}),
+        );
+        assert!(result.is_ok());
+        assert_eq!(errors, 0);
+        // What the failed attempts buffered is not sent when the processor finishes.
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+        assert_eq!(rejected.values(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_reject_restores_stateful_processor() {
+        let (result, sink, rejected, errors) = run_with(
+            json!({"failures": 1, "buffers": true}),
+            json!({"type": "reject"}),
+        );
+        assert!(result.is_ok());
+        assert_eq!(errors, 3);
+        assert_eq!(sink.values(), Vec::<i64>::new());
+        assert_eq!(rejected.values(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_retry_fails_job_when_retries_are_exhausted() {
+        let (result, _, _, errors) = run(2, json!({"type": "retry", "maxRetries": 1}));
+        assert!(matches!(result, Err(ExecutionError::JobFailed(_))));
+        assert!(errors > 0);
+    }
+}
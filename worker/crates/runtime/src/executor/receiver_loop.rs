@@ -22,10 +22,30 @@ pub trait ReceiverLoop {
         Self: Sized;
 }
 
-pub(crate) fn init_select(receivers: &Vec<Receiver<ExecutorOperation>>) -> Select {
+/// Builds a select over the receivers for which `is_active` holds. Returns the select and the
+/// receiver index of each of its operations.
+pub(crate) fn init_select(
+    receivers: &[Receiver<ExecutorOperation>],
+    is_active: impl Fn(usize) -> bool,
+) -> (Select, Vec<usize>) {
     let mut sel = Select::new();
-    for r in receivers {
-        sel.recv(r);
+    let mut indexes = vec![];
+    for (index, r) in receivers.iter().enumerate() {
+        if is_active(index) {
+            sel.recv(r);
+            indexes.push(index);
+        }
     }
-    sel
+    (sel, indexes)
+}
+
+/// Returns the epoch of the pending checkpoint barrier once it has arrived on every input that
+/// hasn't terminated.
+pub(crate) fn aligned_checkpoint(is_terminated: &[bool], barriers: &[Option<u64>]) -> Option<u64> {
+    let epoch = barriers.iter().flatten().copied().max()?;
+    is_terminated
+        .iter()
+        .zip(barriers)
+        .all(|(terminated, barrier)| *terminated || barrier.is_some())
+        .then_some(epoch)
 }
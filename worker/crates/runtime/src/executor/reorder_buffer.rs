@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use crate::executor_operation::ExecutorContext;
+
+/// Holds back the features sent for a received feature until those sent for every feature
+/// received before it are released, so that a processor running on several threads sends
+/// features in the order it received them.
+#[derive(Debug, Default)]
+pub(crate) struct ReorderBuffer {
+    /// Sequence number of the next received feature whose output can be released.
+    next: u64,
+    pending: BTreeMap<u64, Vec<ExecutorContext>>,
+}
+
+impl ReorderBuffer {
+    /// Adds the features sent for the received feature with the given sequence number. Returns
+    /// the features that can be released, in order.
+    pub(crate) fn push(
+        &mut self,
+        sequence: u64,
+        sent: Vec<ExecutorContext>,
+    ) -> Vec<ExecutorContext> {
+        self.pending.insert(sequence, sent);
+        let mut released = vec![];
+        while let Some(sent) = self.pending.remove(&self.next) {
+            released.extend(sent);
+            self.next += 1;
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reearth_flow_types::Feature;
+
+    use super::*;
+
+    fn sent(count: usize) -> Vec<ExecutorContext> {
+        (0..count)
+            .map(|_| ExecutorContext {
+                feature: Feature::new(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn ids(sent: &[ExecutorContext]) -> Vec<uuid::Uuid> {
+        sent.iter().map(|ctx| ctx.feature.id).collect()
+    }
+
+    #[test]
+    fn test_push_releases_in_order() {
+        let first = sent(2);
+        let second = sent(1);
+        let fourth = sent(1);
+        let mut buffer = ReorderBuffer::default();
+        assert!(buffer.push(1, second.clone()).is_empty());
+        assert!(buffer.push(2, vec![]).is_empty());
+        let released = buffer.push(0, first.clone());
+        assert_eq!(ids(&released), [ids(&first), ids(&second)].concat());
+        assert_eq!(ids(&buffer.push(3, fourth.clone())), ids(&fourth));
+    }
+}
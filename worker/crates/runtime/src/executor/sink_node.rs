@@ -13,15 +13,17 @@ use tokio::runtime::Runtime;
 
 use crate::{
     builder_dag::NodeKind,
-    error_manager::ErrorManager,
+    checkpoint::CheckpointCoordinator,
+    error_manager::{ErrorManager, NodeErrorHandler},
     errors::ExecutionError,
     event::Event,
     executor_operation::{ExecutorContext, ExecutorOperation, NodeContext},
     node::{NodeHandle, Sink},
+    stats::{NodeStats, NodeStatsKind},
 };
 
 use super::execution_dag::ExecutionDag;
-use super::receiver_loop::ReceiverLoop;
+use super::receiver_loop::{aligned_checkpoint, ReceiverLoop};
 
 const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_millis(20);
 
@@ -83,6 +85,8 @@ impl FlushScheduler {
 pub struct SinkNode<F> {
     /// Node handle in description DAG.
     node_handle: NodeHandle,
+    /// Node name in the workflow.
+    name: String,
     /// Input node handles.
     node_handles: Vec<NodeHandle>,
     /// Input data channels.
@@ -96,6 +100,9 @@ pub struct SinkNode<F> {
     event_sender: tokio::sync::broadcast::Sender<Event>,
     #[allow(dead_code)]
     error_manager: Arc<ErrorManager>,
+    error_handler: NodeErrorHandler,
+    stats: Arc<NodeStats>,
+    checkpoint_coordinator: Option<Arc<CheckpointCoordinator>>,
     /// The shutdown future.
     #[allow(dead_code)]
     shutdown: F,
@@ -117,9 +124,20 @@ impl<F: Future + Unpin + Debug> SinkNode<F> {
             panic!("Must pass in a node")
         };
         let node_handle = node.handle.clone();
+        let name = node.name.clone();
+        let error_policy = node.error_policy.clone();
         let NodeKind::Sink(sink) = kind else {
             panic!("Must pass in a sink node");
         };
+        let stats = dag.register_stats(node_index, NodeStatsKind::Sink);
+        let error_handler = NodeErrorHandler::new(
+            node_handle.clone(),
+            name.clone(),
+            error_policy,
+            dag.error_manager().clone(),
+            Arc::clone(&stats),
+            dag.event_hub().sender.clone(),
+        );
 
         let (node_handles, receivers) = dag.collect_receivers(node_index);
 
@@ -139,6 +157,7 @@ impl<F: Future + Unpin + Debug> SinkNode<F> {
         std::thread::spawn(move || scheduler.run());
         Self {
             node_handle,
+            name,
             node_handles,
             receivers,
             sink,
@@ -148,6 +167,9 @@ impl<F: Future + Unpin + Debug> SinkNode<F> {
             max_flush_interval,
             ops_since_flush: 0,
             error_manager: dag.error_manager().clone(),
+            error_handler,
+            stats,
+            checkpoint_coordinator: dag.checkpoint_coordinator().cloned(),
             shutdown,
             runtime,
         }
@@ -170,6 +192,18 @@ impl<F: Future + Unpin + Debug> SinkNode<F> {
         });
         Ok(())
     }
+
+    fn on_checkpoint(&mut self, epoch: u64) -> Result<(), ExecutionError> {
+        let Some(coordinator) = &self.checkpoint_coordinator else {
+            return Ok(());
+        };
+        let state = self
+            .sink
+            .serialize_state()
+            .map_err(ExecutionError::Checkpoint)?;
+        coordinator.ack_sink(epoch, &self.node_handle, state);
+        Ok(())
+    }
 }
 
 struct Select<'a> {
@@ -177,6 +211,8 @@ struct Select<'a> {
     flush_receiver: &'a Receiver<()>,
     inner: crossbeam::channel::Select<'a>,
     flush_idx: usize,
+    /// Receiver index of each select operation.
+    indexes: Vec<usize>,
 }
 
 enum ReceiverMsg {
@@ -188,10 +224,15 @@ impl<'a> Select<'a> {
     fn new(
         op_receivers: &'a [Receiver<ExecutorOperation>],
         flush_receiver: &'a Receiver<()>,
+        is_active: impl Fn(usize) -> bool,
     ) -> Self {
         let mut inner = crossbeam::channel::Select::new();
-        for recv in op_receivers {
-            let _ = inner.recv(recv);
+        let mut indexes = vec![];
+        for (index, recv) in op_receivers.iter().enumerate() {
+            if is_active(index) {
+                let _ = inner.recv(recv);
+                indexes.push(index);
+            }
         }
         let flush_idx = inner.recv(flush_receiver);
         Self {
@@ -199,19 +240,17 @@ impl<'a> Select<'a> {
             flush_idx,
             op_receivers,
             flush_receiver,
+            indexes,
         }
     }
 
-    fn remove(&mut self, idx: usize) {
-        self.inner.remove(idx);
-    }
-
     fn recv(&mut self) -> Result<ReceiverMsg, ExecutionError> {
         let msg = self.inner.select();
         let index = msg.index();
         let res = if index == self.flush_idx {
             msg.recv(self.flush_receiver).map(|_| ReceiverMsg::Flush)
         } else {
+            let index = self.indexes[index];
             msg.recv(&self.op_receivers[index])
                 .map(|op| ReceiverMsg::Op(index, op))
         };
@@ -241,10 +280,16 @@ impl<F: Future + Unpin + Debug> ReceiverLoop for SinkNode<F> {
             tmp_recv
         };
         let mut is_terminated = vec![false; receivers.len()];
+        // Inputs that delivered a checkpoint barrier are blocked until the barrier is aligned.
+        let mut barriers = vec![None; receivers.len()];
         self.flush_scheduler_sender
             .send(self.max_flush_interval)
             .unwrap();
-        let mut sel = Select::new(&receivers, &should_flush_receiver);
+        let mut sel = Select::new(&receivers, &should_flush_receiver, |_| true);
+        let _ = self.event_sender.send(Event::NodeStarted {
+            node: self.node_handle.clone(),
+            name: self.name.clone(),
+        });
         loop {
             let ReceiverMsg::Op(index, op) = sel.recv()? else {
                 self.flush()?;
@@ -254,23 +299,54 @@ impl<F: Future + Unpin + Debug> ReceiverLoop for SinkNode<F> {
             match op {
                 ExecutorOperation::Op { ctx } => {
                     self.on_op(ctx)?;
+                    self.error_handler.check()?;
+                    continue;
                 }
                 ExecutorOperation::Terminate { ctx } => {
                     is_terminated[index] = true;
-                    sel.remove(index);
                     if is_terminated.iter().all(|value| *value) {
                         self.on_terminate(ctx)?;
+                        let _ = self.event_sender.send(Event::NodeFinished {
+                            node: self.node_handle.clone(),
+                            name: self.name.clone(),
+                        });
                         return Ok(());
                     }
                 }
+                ExecutorOperation::Checkpoint { epoch } => {
+                    barriers[index] = Some(epoch);
+                }
+            }
+            if let Some(epoch) = aligned_checkpoint(&is_terminated, &barriers) {
+                self.on_checkpoint(epoch)?;
+                barriers.fill(None);
             }
+            sel = Select::new(&receivers, &should_flush_receiver, |i| {
+                !is_terminated[i] && barriers[i].is_none()
+            });
         }
     }
 
     fn on_op(&mut self, ctx: ExecutorContext) -> Result<(), ExecutionError> {
-        self.sink
-            .process(ctx)
-            .map_err(|e| ExecutionError::CannotReceiveFromChannel(format!("{:?}", e)))
+        let feature_id = ctx.feature.id;
+        let now = Instant::now();
+        self.stats.record_in(&ctx.port);
+        let original = self.error_handler.keeps_feature().then(|| ctx.clone());
+        let mut result = self.sink.process(ctx);
+        let mut attempts = 1;
+        while let (Err(_), Some(original)) = (&result, &original) {
+            if attempts >= self.error_handler.max_attempts() {
+                break;
+            }
+            attempts += 1;
+            result = self.sink.process(original.clone());
+        }
+        if let Err(e) = result {
+            // Sinks have no output port, so rejected features are dropped.
+            self.error_handler.handle(feature_id, None, e);
+        }
+        self.stats.record_elapsed(now.elapsed());
+        Ok(())
     }
 
     fn on_terminate(&mut self, ctx: NodeContext) -> Result<(), ExecutionError> {
@@ -1,4 +1,14 @@
-use std::{fmt::Debug, future::Future, pin::pin, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use petgraph::visit::IntoNodeIdentifiers;
 
@@ -15,11 +25,15 @@ use tracing::info_span;
 
 use crate::{
     builder_dag::NodeKind,
+    checkpoint::CheckpointCoordinator,
     errors::ExecutionError,
+    event::Event,
     executor_operation::{ExecutorContext, ExecutorOperation, ExecutorOptions, NodeContext},
     forwarder::ChannelManager,
     kvs::KvStore,
     node::{IngestionMessage, Port, Source, SourceState},
+    spill::SpillManager,
+    stats::{NodeStats, NodeStatsKind},
 };
 
 use super::execution_dag::ExecutionDag;
@@ -43,10 +57,23 @@ pub struct SourceNode<F> {
     storage_resolver: Arc<StorageResolver>,
     logger: Arc<LoggerFactory>,
     kv_store: Arc<Box<dyn KvStore>>,
+    spill_manager: Arc<SpillManager>,
+    checkpoint_coordinator: Option<Arc<CheckpointCoordinator>>,
+    event_sender: tokio::sync::broadcast::Sender<Event>,
+    /// The epoch of the last checkpoint barrier.
+    epoch: u64,
+    /// Set when the shutdown stops the sources before they are exhausted.
+    interrupted: Arc<AtomicBool>,
     #[allow(dead_code)]
     span: tracing::Span,
 }
 
+impl<F> SourceNode<F> {
+    pub(crate) fn interrupted(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+}
+
 impl<F: Future + Unpin> Node for SourceNode<F> {
     fn run(mut self) -> Result<(), ExecutionError> {
         let mut handles = vec![];
@@ -56,12 +83,21 @@ impl<F: Future + Unpin> Node for SourceNode<F> {
                 Arc::clone(&self.storage_resolver),
                 Arc::clone(&self.logger),
                 Arc::clone(&self.kv_store),
+                Arc::clone(&self.spill_manager),
             );
             handles.push(Some(self.runtime.spawn(async move {
                 source_runner.source.start(ctx, source_runner.sender).await
             })));
         }
+        for source in self.sources.iter() {
+            let _ = self.event_sender.send(Event::NodeStarted {
+                node: source.channel_manager.owner().clone(),
+                name: source.name.clone(),
+            });
+        }
         let mut num_running_sources = handles.len();
+        let started = Instant::now();
+        let mut last_checkpoint = Instant::now();
 
         let mut stream = pin!(receivers_stream(self.receivers));
         loop {
@@ -72,11 +108,13 @@ impl<F: Future + Unpin> Node for SourceNode<F> {
                 .block_on(futures::future::select(self.shutdown, next))
             {
                 Either::Left((_, _)) => {
+                    self.interrupted.store(true, Ordering::SeqCst);
                     let ctx = NodeContext::new(
                         Arc::clone(&self.expr_engine),
                         Arc::clone(&self.storage_resolver),
                         Arc::clone(&self.logger),
                         Arc::clone(&self.kv_store),
+                        Arc::clone(&self.spill_manager),
                     );
                     send_to_all_nodes(&self.sources, ExecutorOperation::Terminate { ctx })?;
                     return Ok(());
@@ -92,6 +130,21 @@ impl<F: Future + Unpin> Node for SourceNode<F> {
                                 .expect("Shouldn't receive message from dropped receiver"),
                         ) {
                             Ok(Ok(())) => {
+                                let source = &mut self.sources[index];
+                                if source.pending_state {
+                                    // The features it sent last are not in its state.
+                                    source.state = SourceState::NonRestartable;
+                                    source.pending_state = false;
+                                }
+                                source.stats.record_elapsed(started.elapsed());
+                                let node = source.channel_manager.owner().clone();
+                                let _ = self
+                                    .event_sender
+                                    .send(Event::SourceExhausted { node: node.clone() });
+                                let _ = self.event_sender.send(Event::NodeFinished {
+                                    node,
+                                    name: source.name.clone(),
+                                });
                                 num_running_sources -= 1;
                                 if num_running_sources == 0 {
                                     let ctx = NodeContext::new(
@@ -99,6 +152,7 @@ impl<F: Future + Unpin> Node for SourceNode<F> {
                                         Arc::clone(&self.storage_resolver),
                                         Arc::clone(&self.logger),
                                         Arc::clone(&self.kv_store),
+                                        Arc::clone(&self.spill_manager),
                                     );
                                     send_to_all_nodes(
                                         &self.sources,
@@ -116,8 +170,12 @@ impl<F: Future + Unpin> Node for SourceNode<F> {
                     };
                     let source = &mut self.sources[index];
                     match message {
-                        IngestionMessage::OperationEvent { feature, .. } => {
-                            source.state = SourceState::NonRestartable;
+                        IngestionMessage::OperationEvent { feature } => {
+                            match source.state {
+                                // Its state is out of date until the source sends the next one.
+                                SourceState::Restartable(_) => source.pending_state = true,
+                                _ => source.state = SourceState::NonRestartable,
+                            }
                             source.channel_manager.send_op(ExecutorContext::new(
                                 feature,
                                 port,
@@ -127,6 +185,19 @@ impl<F: Future + Unpin> Node for SourceNode<F> {
                                 Arc::clone(&self.kv_store),
                             ))?;
                         }
+                        IngestionMessage::State { state } => {
+                            source.state = SourceState::Restartable(state);
+                            source.pending_state = false;
+                        }
+                    }
+                    if let Some(coordinator) = &self.checkpoint_coordinator {
+                        if last_checkpoint.elapsed() >= coordinator.interval()
+                            && !self.sources.iter().any(|source| source.pending_state)
+                        {
+                            self.epoch += 1;
+                            send_checkpoint(&self.sources, coordinator, self.epoch)?;
+                            last_checkpoint = Instant::now();
+                        }
                     }
                 }
             }
@@ -136,8 +207,12 @@ impl<F: Future + Unpin> Node for SourceNode<F> {
 
 #[derive(Debug)]
 struct RunningSource {
+    name: String,
     channel_manager: ChannelManager,
+    stats: Arc<NodeStats>,
     state: SourceState,
+    /// Set when the source has sent features since its last state, which defers checkpoints.
+    pending_state: bool,
 }
 
 #[derive(Debug)]
@@ -157,6 +232,20 @@ fn send_to_all_nodes(
     Ok(())
 }
 
+/// Records the source states and injects a checkpoint barrier after them.
+fn send_checkpoint(
+    sources: &[RunningSource],
+    coordinator: &CheckpointCoordinator,
+    epoch: u64,
+) -> Result<(), ExecutionError> {
+    let states = sources
+        .iter()
+        .map(|source| (source.channel_manager.owner().id, source.state.clone()))
+        .collect::<HashMap<_, _>>();
+    coordinator.begin(epoch, states);
+    send_to_all_nodes(sources, ExecutorOperation::Checkpoint { epoch })
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn create_source_node<F>(
     ctx: NodeContext,
@@ -180,9 +269,11 @@ pub async fn create_source_node<F>(
         }
         let node = dag.node_weight_mut(node_index);
         let node_handle = node.handle.clone();
+        let name = node.name.clone();
         let NodeKind::Source(source) = node.kind.take().unwrap() else {
             continue;
         };
+        let stats = dag.register_stats(node_index, NodeStatsKind::Source);
 
         let senders = dag.collect_senders(node_index);
         let record_writers = dag.collect_record_writers(node_index).await;
@@ -191,11 +282,15 @@ pub async fn create_source_node<F>(
             record_writers,
             senders,
             dag.error_manager().clone(),
+            Arc::clone(&stats),
             runtime.clone(),
         );
         sources.push(RunningSource {
+            name,
             channel_manager,
+            stats,
             state: SourceState::NotStarted,
+            pending_state: false,
         });
 
         let (sender, receiver) = channel(options.channel_buffer_sz);
@@ -222,6 +317,15 @@ pub async fn create_source_node<F>(
         storage_resolver: Arc::clone(&ctx.storage_resolver),
         logger: Arc::clone(&ctx.logger),
         kv_store: Arc::clone(&ctx.kv_store),
+        spill_manager: Arc::clone(&ctx.spill_manager),
+        checkpoint_coordinator: dag.checkpoint_coordinator().cloned(),
+        event_sender: dag.event_hub().sender.clone(),
+        epoch: options
+            .resume_from
+            .as_ref()
+            .map(|checkpoint| checkpoint.epoch)
+            .unwrap_or_default(),
+        interrupted: Arc::new(AtomicBool::new(false)),
         span,
     }
 }
@@ -3,19 +3,30 @@ use std::sync::Arc;
 use reearth_flow_action_log::factory::LoggerFactory;
 use reearth_flow_common::uri::Uri;
 use reearth_flow_eval_expr::engine::Engine;
+use reearth_flow_state::State;
 use reearth_flow_storage::resolve::StorageResolver;
 use reearth_flow_types::Feature;
+use tokio::runtime::Runtime;
 use tracing::{error_span, info_span};
 
 use crate::{
+    cache::NodeCache,
+    checkpoint::{Checkpoint, CheckpointOptions},
+    error_manager::ErrorManager,
+    event::{Event, EventHub},
+    feature_store::FeatureStoreFormat,
     kvs::KvStore,
-    node::{Port, DEFAULT_PORT},
+    node::{GraphId, Port, DEFAULT_PORT},
+    replay::ReplayOptions,
+    spill::SpillManager,
+    stats::JobStats,
 };
 
 #[derive(Clone, Debug)]
 pub enum ExecutorOperation {
     Op { ctx: ExecutorContext },
     Terminate { ctx: NodeContext },
+    Checkpoint { epoch: u64 },
 }
 
 #[derive(Debug, Clone)]
@@ -124,6 +135,8 @@ pub struct NodeContext {
     pub storage_resolver: Arc<StorageResolver>,
     pub logger: Arc<LoggerFactory>,
     pub kv_store: Arc<Box<dyn KvStore>>,
+    /// Memory budget of the buffers of blocking processors.
+    pub spill_manager: Arc<SpillManager>,
 }
 
 impl Default for NodeContext {
@@ -139,6 +152,7 @@ impl Default for NodeContext {
                 Uri::for_test("ram:///log/").path(),
             )),
             kv_store: Arc::new(crate::kvs::create_kv_store()),
+            spill_manager: Arc::new(SpillManager::unlimited()),
         }
     }
 }
@@ -149,20 +163,51 @@ impl NodeContext {
         storage_resolver: Arc<StorageResolver>,
         logger: Arc<LoggerFactory>,
         kv_store: Arc<Box<dyn KvStore>>,
+        spill_manager: Arc<SpillManager>,
     ) -> Self {
         Self {
             expr_engine,
             storage_resolver,
             logger,
             kv_store,
+            spill_manager,
         }
     }
 }
 
+/// What the nodes a processor runs by itself, like the nodes of a sub graph run for each group,
+/// share with the other nodes of the job.
+#[derive(Debug, Clone)]
+pub struct InnerNodeContext {
+    pub(crate) graph_id: GraphId,
+    pub(crate) error_manager: Arc<ErrorManager>,
+    pub(crate) stats: Arc<JobStats>,
+    pub(crate) event_sender: tokio::sync::broadcast::Sender<Event>,
+    pub(crate) state: Arc<State>,
+    pub(crate) feature_store_format: FeatureStoreFormat,
+    pub(crate) runtime: Arc<Runtime>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ExecutorOptions {
     pub channel_buffer_sz: usize,
-    pub event_hub_capacity: usize,
+    /// Receives the events of every node.
+    pub event_hub: EventHub,
     pub error_threshold: Option<u32>,
     pub thread_pool_size: usize,
+    /// Whether processors send features in the order they were received, unless a node overrides
+    /// it.
+    pub preserve_order: bool,
+    pub feature_store_format: FeatureStoreFormat,
+    /// Periodically persist checkpoints when set.
+    pub checkpoint: Option<CheckpointOptions>,
+    /// Checkpoint to restore sources, processors and sinks from.
+    pub resume_from: Option<Checkpoint>,
+    /// Run only the part of the workflow downstream of an edge recorded by a previous job.
+    pub replay: Option<ReplayOptions>,
+    /// Reuse the outputs of unchanged nodes from previous jobs, and store the outputs of the
+    /// others.
+    pub cache: Option<NodeCache>,
+    /// Collects the statistics of every node.
+    pub stats: Arc<JobStats>,
 }
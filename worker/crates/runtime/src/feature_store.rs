@@ -2,8 +2,11 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use bytes::Bytes;
 use reearth_flow_state::State;
 use reearth_flow_types::Feature;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::node::EdgeId;
@@ -12,10 +15,29 @@ use crate::node::EdgeId;
 pub enum FeatureWriterError {
     #[error("Feature not found")]
     FeatureNotFound,
+    #[error("Write error: {0}")]
+    Write(String),
     #[error("Flush error: {0}")]
     Flush(String),
 }
 
+#[derive(Debug, Error)]
+pub enum FeatureReaderError {
+    #[error("Read error: {0}")]
+    Read(String),
+}
+
+/// How edge features are persisted in the feature store.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum FeatureStoreFormat {
+    /// All features of an edge in one JSON array, `<edge-id>.json`. Held in memory until flushed.
+    #[default]
+    Json,
+    /// Newline-delimited JSON written incrementally in chunks, `<edge-id>/<part>.jsonl`.
+    JsonLines,
+}
+
 pub trait FeatureWriterClone {
     fn clone_box(&self) -> Box<dyn FeatureWriter>;
 }
@@ -41,8 +63,15 @@ pub trait FeatureWriter: Send + Sync + Debug + FeatureWriterClone {
     async fn flush(&self) -> Result<(), FeatureWriterError>;
 }
 
-pub fn create_feature_writer(edge_id: EdgeId, state: Arc<State>) -> Box<dyn FeatureWriter> {
-    Box::new(PrimaryKeyLookupFeatureWriter::new(edge_id, state))
+pub fn create_feature_writer(
+    edge_id: EdgeId,
+    state: Arc<State>,
+    format: FeatureStoreFormat,
+) -> Box<dyn FeatureWriter> {
+    match format {
+        FeatureStoreFormat::Json => Box::new(PrimaryKeyLookupFeatureWriter::new(edge_id, state)),
+        FeatureStoreFormat::JsonLines => Box::new(StreamingFeatureWriter::new(edge_id, state)),
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -78,3 +107,226 @@ impl FeatureWriter for PrimaryKeyLookupFeatureWriter {
             .map_err(|e| FeatureWriterError::Flush(e.to_string()))
     }
 }
+
+/// Size of the buffered records at which a chunk is written out.
+const STREAMING_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub(crate) struct StreamingFeatureWriter {
+    edge_id: EdgeId,
+    buffer: Vec<u8>,
+    part: usize,
+    /// Size of the buffered records at which a chunk is written out.
+    chunk_size: usize,
+    state: Arc<State>,
+}
+
+impl StreamingFeatureWriter {
+    pub(crate) fn new(edge_id: EdgeId, state: Arc<State>) -> Self {
+        Self {
+            edge_id,
+            buffer: Vec::new(),
+            part: 0,
+            chunk_size: STREAMING_CHUNK_SIZE,
+            state,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl FeatureWriter for StreamingFeatureWriter {
+    fn write(&mut self, feature: &Feature) -> Result<(), FeatureWriterError> {
+        let value: serde_json::Value = feature.clone().into();
+        serde_json::to_writer(&mut self.buffer, &value)
+            .map_err(|e| FeatureWriterError::Write(e.to_string()))?;
+        self.buffer.push(b'\n');
+        if self.buffer.len() < self.chunk_size {
+            return Ok(());
+        }
+        let content = Bytes::from(std::mem::take(&mut self.buffer));
+        self.state
+            .save_lines_part_sync(content, self.edge_id.to_string().as_str(), self.part)
+            .map_err(|e| FeatureWriterError::Flush(e.to_string()))?;
+        self.part += 1;
+        Ok(())
+    }
+
+    /// Writes the buffered records as the current chunk. Flushing again before the chunk is full
+    /// rewrites it with the records written since.
+    async fn flush(&self) -> Result<(), FeatureWriterError> {
+        // Always write the first chunk so that an empty edge is distinguishable from a missing one.
+        if self.buffer.is_empty() && self.part > 0 {
+            return Ok(());
+        }
+        self.state
+            .save_lines_part(
+                Bytes::from(self.buffer.clone()),
+                self.edge_id.to_string().as_str(),
+                self.part,
+            )
+            .await
+            .map_err(|e| FeatureWriterError::Flush(e.to_string()))
+    }
+}
+
+/// Reads the features written by [`StreamingFeatureWriter`] one chunk at a time.
+#[derive(Debug, Clone)]
+pub struct StreamingFeatureReader {
+    edge_id: EdgeId,
+    part: usize,
+    state: Arc<State>,
+}
+
+impl StreamingFeatureReader {
+    pub fn new(edge_id: EdgeId, state: Arc<State>) -> Self {
+        Self {
+            edge_id,
+            part: 0,
+            state,
+        }
+    }
+
+    /// Returns the features of the next chunk, or `None` after the last chunk.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<Feature>>, FeatureReaderError> {
+        let chunk = self
+            .state
+            .get_lines_part::<serde_json::Value>(self.edge_id.to_string().as_str(), self.part)
+            .await
+            .map_err(|e| FeatureReaderError::Read(e.to_string()))?;
+        self.part += 1;
+        Ok(chunk.map(|values| values.into_iter().map(Feature::from).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reearth_flow_common::uri::Uri;
+    use reearth_flow_storage::resolve::StorageResolver;
+    use reearth_flow_types::{Attribute, AttributeValue};
+
+    use super::*;
+
+    fn create_state() -> Arc<State> {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        Arc::new(State::new(&Uri::for_test("ram:///feature-store/"), &storage_resolver).unwrap())
+    }
+
+    fn feature(value: i64) -> Feature {
+        Feature::new_with_attributes(HashMap::from([(
+            Attribute::new("value"),
+            AttributeValue::Number(value.into()),
+        )]))
+    }
+
+    fn values(features: &[Feature]) -> Vec<i64> {
+        features
+            .iter()
+            .map(|feature| match feature.get(&"value") {
+                Some(AttributeValue::Number(value)) => value.as_i64().unwrap(),
+                value => panic!("Unexpected value: {:?}", value),
+            })
+            .collect()
+    }
+
+    /// The features of each chunk of the edge.
+    async fn read_chunks(edge_id: EdgeId, state: &Arc<State>) -> Vec<Vec<i64>> {
+        let mut reader = StreamingFeatureReader::new(edge_id, Arc::clone(state));
+        let mut chunks = vec![];
+        while let Some(chunk) = reader.next_chunk().await.unwrap() {
+            chunks.push(values(&chunk));
+        }
+        chunks
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let state = create_state();
+        let edge_id = uuid::Uuid::new_v4();
+        let features = (0..3).map(feature).collect::<Vec<_>>();
+        let mut writer = StreamingFeatureWriter::new(edge_id, Arc::clone(&state));
+        for feature in features.iter() {
+            writer.write(feature).unwrap();
+        }
+        writer.flush().await.unwrap();
+
+        let mut reader = StreamingFeatureReader::new(edge_id, Arc::clone(&state));
+        let chunk = reader.next_chunk().await.unwrap().unwrap();
+        assert_eq!(
+            chunk.iter().map(|feature| feature.id).collect::<Vec<_>>(),
+            features
+                .iter()
+                .map(|feature| feature.id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(values(&chunk), vec![0, 1, 2]);
+        assert!(reader.next_chunk().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_writes_a_chunk_each_time_the_buffer_is_full() {
+        let state = create_state();
+        let edge_id = uuid::Uuid::new_v4();
+        let mut writer = StreamingFeatureWriter::new(edge_id, Arc::clone(&state));
+        writer.write(&feature(0)).unwrap();
+        // Each chunk holds two records.
+        writer.chunk_size = writer.buffer.len() + 1;
+        for value in 1..5 {
+            writer.write(&feature(value)).unwrap();
+        }
+        writer.flush().await.unwrap();
+        assert_eq!(
+            read_chunks(edge_id, &state).await,
+            vec![vec![0, 1], vec![2, 3], vec![4]]
+        );
+
+        // A full last chunk leaves no chunk to flush.
+        let edge_id = uuid::Uuid::new_v4();
+        let mut writer = StreamingFeatureWriter::new(edge_id, Arc::clone(&state));
+        writer.chunk_size = 1;
+        for value in 0..2 {
+            writer.write(&feature(value)).unwrap();
+        }
+        writer.flush().await.unwrap();
+        assert_eq!(read_chunks(edge_id, &state).await, vec![vec![0], vec![1]]);
+    }
+
+    #[tokio::test]
+    async fn test_flushes_while_writing() {
+        let state = create_state();
+        let edge_id = uuid::Uuid::new_v4();
+        let mut writer = StreamingFeatureWriter::new(edge_id, Arc::clone(&state));
+        writer.write(&feature(0)).unwrap();
+        writer.chunk_size = writer.buffer.len() * 2 + 1;
+        writer.flush().await.unwrap();
+        assert_eq!(read_chunks(edge_id, &state).await, vec![vec![0]]);
+
+        // The chunk flushed before is rewritten with the records written since.
+        writer.write(&feature(1)).unwrap();
+        writer.flush().await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(read_chunks(edge_id, &state).await, vec![vec![0, 1]]);
+
+        writer.write(&feature(2)).unwrap();
+        writer.flush().await.unwrap();
+        writer.write(&feature(3)).unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(
+            read_chunks(edge_id, &state).await,
+            vec![vec![0, 1, 2], vec![3]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_edge() {
+        let state = create_state();
+        let edge_id = uuid::Uuid::new_v4();
+        StreamingFeatureWriter::new(edge_id, Arc::clone(&state))
+            .flush()
+            .await
+            .unwrap();
+        assert_eq!(read_chunks(edge_id, &state).await, vec![Vec::<i64>::new()]);
+
+        // An edge that was never written has no chunk at all.
+        assert!(read_chunks(uuid::Uuid::new_v4(), &state).await.is_empty());
+    }
+}
@@ -0,0 +1,519 @@
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+
+use crossbeam::channel::{bounded, unbounded, Receiver, Sender};
+use petgraph::graph::DiGraph;
+use reearth_flow_types::{workflow::ForEach, Feature};
+
+use crate::channels::ProcessorChannelForwarder;
+use crate::dag_schemas::{SchemaEdgeType, SchemaNodeType};
+use crate::errors::{BoxedError, ExecutionError};
+use crate::event::EventHub;
+use crate::executor_operation::{ExecutorContext, InnerNodeContext, NodeContext};
+use crate::node::{Processor, ProcessorFactory};
+use crate::sub_graph::{SubGraphInstance, SubGraphTemplate};
+
+pub static FOR_EACH_ACTION: &str = "ForEach";
+
+/// Number of features waiting for an instance worker before the sender blocks.
+const WORKER_CHANNEL_CAPACITY: usize = 256;
+
+/// Builds the processor running a sub graph per group of features.
+#[derive(Debug, Clone, ProcessorFactory)]
+#[factory(
+    name = FOR_EACH_ACTION,
+    description = "Runs a separate instance of a sub graph for each group of features",
+    input_ports = [],
+    output_ports = [],
+)]
+pub struct ForEachFactory {
+    graph: Arc<DiGraph<SchemaNodeType, SchemaEdgeType>>,
+    for_each: ForEach,
+}
+
+impl ForEachFactory {
+    pub(crate) fn new(graph: DiGraph<SchemaNodeType, SchemaEdgeType>, for_each: ForEach) -> Self {
+        Self {
+            graph: Arc::new(graph),
+            for_each,
+        }
+    }
+
+    fn build_processor(
+        &self,
+        ctx: NodeContext,
+        event_hub: EventHub,
+        _action: String,
+    ) -> Result<Box<dyn Processor>, BoxedError> {
+        let template = SubGraphTemplate::build(&self.graph, ctx, event_hub)?;
+        Ok(Box::new(ForEachProcessor::new(
+            Arc::new(template),
+            self.for_each.clone(),
+        )))
+    }
+}
+
+enum Message {
+    Feature { key: String, ctx: ExecutorContext },
+    Finish { ctx: NodeContext },
+}
+
+type Output = Result<ExecutorContext, BoxedError>;
+
+/// Runs the instances of the groups assigned to a worker, one feature at a time.
+fn run_worker(
+    template: Arc<SubGraphTemplate>,
+    receiver: Receiver<Message>,
+    output: Sender<Output>,
+) {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        run_instances(&template, receiver, &output)
+    }));
+    if result.is_err() {
+        let _ = output.send(Err("Instance worker panicked".into()));
+    }
+}
+
+fn run_instances(
+    template: &SubGraphTemplate,
+    receiver: Receiver<Message>,
+    output: &Sender<Output>,
+) {
+    let mut instances = HashMap::<String, SubGraphInstance>::new();
+    for message in receiver {
+        match message {
+            Message::Feature { key, ctx } => {
+                let instance = instances
+                    .entry(key.clone())
+                    .or_insert_with(|| SubGraphInstance::new(template));
+                match instance.process(template, ctx) {
+                    Ok(features) => features.into_iter().for_each(|ctx| {
+                        let _ = output.send(Ok(ctx));
+                    }),
+                    Err(e) => {
+                        let _ = output.send(Err(format!(
+                            "Failed to process a feature of group {}: {}",
+                            key, e
+                        )
+                        .into()));
+                    }
+                }
+            }
+            Message::Finish { ctx } => {
+                for (key, instance) in instances.iter_mut() {
+                    match instance.finish(template, ctx.clone()) {
+                        Ok(features) => features.into_iter().for_each(|ctx| {
+                            let _ = output.send(Ok(ctx));
+                        }),
+                        Err(e) => {
+                            let _ =
+                                output.send(Err(
+                                    format!("Failed to finish group {}: {}", key, e).into()
+                                ));
+                        }
+                    }
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Sends each feature to the instance of its group. Groups are spread over a fixed number of
+/// worker threads, so instances of different groups run in parallel while the features of a group
+/// keep their order.
+///
+/// Errors of an instance surface on a later call, as the features are processed asynchronously.
+#[derive(Debug)]
+pub struct ForEachProcessor {
+    template: Arc<SubGraphTemplate>,
+    for_each: ForEach,
+    /// Senders to the worker threads, started with the first feature.
+    workers: Vec<Sender<Message>>,
+    output: Option<Receiver<Output>>,
+}
+
+impl ForEachProcessor {
+    fn new(template: Arc<SubGraphTemplate>, for_each: ForEach) -> Self {
+        Self {
+            template,
+            for_each,
+            workers: vec![],
+            output: None,
+        }
+    }
+
+    fn start_workers(&mut self) -> Result<(), BoxedError> {
+        let concurrency = self
+            .for_each
+            .concurrency
+            .unwrap_or_else(|| {
+                thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            })
+            .max(1);
+        let (output_sender, output_receiver) = unbounded();
+        for index in 0..concurrency {
+            let (sender, receiver) = bounded(WORKER_CHANNEL_CAPACITY);
+            let template = Arc::clone(&self.template);
+            let output = output_sender.clone();
+            thread::Builder::new()
+                .name(format!("for-each-{}", index))
+                .spawn(move || run_worker(template, receiver, output))
+                .map_err(ExecutionError::CannotSpawnWorkerThread)?;
+            self.workers.push(sender);
+        }
+        self.output = Some(output_receiver);
+        Ok(())
+    }
+
+    fn group_key(&self, feature: &Feature) -> String {
+        self.for_each
+            .group_by
+            .iter()
+            .map(|attribute| {
+                feature
+                    .attributes
+                    .get(attribute)
+                    .map(|value| value.to_string())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn forward(&self, fw: &mut dyn ProcessorChannelForwarder) -> Result<(), BoxedError> {
+        let Some(output) = &self.output else {
+            return Ok(());
+        };
+        for ctx in output.try_iter() {
+            fw.send(ctx?);
+        }
+        Ok(())
+    }
+}
+
+impl Clone for ForEachProcessor {
+    /// Clones are fresh processors without any instance.
+    fn clone(&self) -> Self {
+        Self::new(Arc::clone(&self.template), self.for_each.clone())
+    }
+}
+
+impl Processor for ForEachProcessor {
+    fn initialize(&mut self, _ctx: NodeContext) {}
+
+    fn attach(&mut self, ctx: &InnerNodeContext) {
+        self.template.attach(ctx);
+    }
+
+    fn process(
+        &mut self,
+        ctx: ExecutorContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        if self.workers.is_empty() {
+            self.start_workers()?;
+        }
+        let key = self.group_key(&ctx.feature);
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let worker = &self.workers[hasher.finish() as usize % self.workers.len()];
+        worker
+            .send(Message::Feature { key, ctx })
+            .map_err(|_| "Instance worker has stopped")?;
+        self.forward(fw)
+    }
+
+    fn finish(
+        &self,
+        ctx: NodeContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        let Some(output) = &self.output else {
+            return Ok(());
+        };
+        for worker in self.workers.iter() {
+            let _ = worker.send(Message::Finish { ctx: ctx.clone() });
+        }
+        // The workers drop their output senders once all of their instances have finished.
+        let mut result = Ok(());
+        for output in output.iter() {
+            match output {
+                Ok(ctx) => fw.send(ctx),
+                Err(e) if result.is_ok() => result = Err(e),
+                Err(_) => {}
+            }
+        }
+        self.template.flush();
+        result
+    }
+
+    fn name(&self) -> &str {
+        FOR_EACH_ACTION
+    }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use reearth_flow_state::State;
+    use reearth_flow_storage::resolve::StorageResolver;
+    use reearth_flow_types::AttributeValue;
+    use serde_json::{json, Value};
+    use tokio::runtime::Runtime;
+
+    use super::*;
+    use crate::node::{NodeKind, Port, DEFAULT_PORT, ERROR_ATTRIBUTE};
+    use crate::stats::JobStats;
+    use crate::tests::utils::{
+        create_executor_options, create_factories, create_state, execute, node_summary,
+        CollectingSinkFactory,
+    };
+
+    const COUNT_ATTRIBUTE: &str = "count";
+
+    fn id(n: u32) -> String {
+        format!("6a2c7d10-0000-4000-8000-{:012}", n)
+    }
+
+    /// Sends 5 features through a sub graph run for each value.
+    fn workflow(nodes: Value, edges: Value) -> String {
+        json!({
+            "id": id(0),
+            "name": "for each",
+            "entryGraphId": id(100),
+            "graphs": [
+                {
+                    "id": id(100),
+                    "name": "entry",
+                    "nodes": [
+                        {
+                            "id": id(1),
+                            "name": "source",
+                            "type": "action",
+                            "action": "NumberSource",
+                            "with": {"count": 5}
+                        },
+                        {
+                            "id": id(2),
+                            "name": "for each value",
+                            "type": "subGraph",
+                            "subGraphId": id(200),
+                            "forEach": {"groupBy": ["value"], "concurrency": 2}
+                        },
+                        {
+                            "id": id(3),
+                            "name": "sink",
+                            "type": "action",
+                            "action": "Collecting"
+                        }
+                    ],
+                    "edges": [
+                        {
+                            "id": id(101),
+                            "from": id(1),
+                            "to": id(2),
+                            "fromPort": "default",
+                            "toPort": "default"
+                        },
+                        {
+                            "id": id(102),
+                            "from": id(2),
+                            "to": id(3),
+                            "fromPort": "default",
+                            "toPort": "default"
+                        }
+                    ]
+                },
+                {"id": id(200), "name": "sub", "nodes": nodes, "edges": edges}
+            ]
+        })
+        .to_string()
+    }
+
+    fn run(nodes: Value, edges: Value) -> (CollectingSinkFactory, Arc<JobStats>, Arc<State>) {
+        let sink = CollectingSinkFactory::default();
+        let mut factories = create_factories(&sink);
+        factories.insert(
+            "Counting".to_string(),
+            NodeKind::Processor(Box::new(CountingFactory)),
+        );
+        let options = create_executor_options();
+        let stats = Arc::clone(&options.stats);
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///for-each/", &storage_resolver);
+        execute(
+            &workflow(nodes, edges),
+            factories,
+            options,
+            storage_resolver,
+            Arc::clone(&state),
+        )
+        .unwrap();
+        (sink, stats, state)
+    }
+
+    #[test]
+    fn test_runs_an_instance_per_group() {
+        let (sink, stats, _) = run(
+            json!([{"id": id(201), "name": "counting", "type": "action", "action": "Counting"}]),
+            json!([]),
+        );
+        assert_eq!(sink.values(), vec![0, 1, 2, 3, 4]);
+        // Each instance only buffered the feature of its own group.
+        for feature in sink.features.lock().iter() {
+            assert_eq!(
+                feature.get(&COUNT_ATTRIBUTE),
+                Some(&AttributeValue::Number(1.into()))
+            );
+        }
+        let counting = node_summary(&stats, "counting").unwrap();
+        assert_eq!(counting.features_in.get("default"), Some(&5));
+        assert_eq!(counting.features_out.get("default"), Some(&5));
+    }
+
+    #[test]
+    fn test_features_enter_every_entry_node() {
+        let (sink, _, _) = run(
+            json!([
+                {"id": id(201), "name": "first", "type": "action", "action": "PassThrough"},
+                {"id": id(202), "name": "second", "type": "action", "action": "PassThrough"}
+            ]),
+            json!([]),
+        );
+        assert_eq!(sink.values(), vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4]);
+    }
+
+    #[test]
+    fn test_inner_nodes_apply_error_policy() {
+        let (sink, stats, state) = run(
+            json!([
+                {
+                    "id": id(201),
+                    "name": "failing",
+                    "type": "action",
+                    "action": "Failing",
+                    "with": {"failures": 1},
+                    "onError": {"type": "reject"}
+                },
+                {"id": id(202), "name": "passed", "type": "action", "action": "PassThrough"},
+                {"id": id(203), "name": "rejected", "type": "action", "action": "PassThrough"}
+            ]),
+            json!([
+                {
+                    "id": id(211),
+                    "from": id(201),
+                    "to": id(202),
+                    "fromPort": "default",
+                    "toPort": "default"
+                },
+                {
+                    "id": id(212),
+                    "from": id(201),
+                    "to": id(203),
+                    "fromPort": "rejected",
+                    "toPort": "default"
+                }
+            ]),
+        );
+        // The copies sent before each failure are discarded.
+        assert_eq!(sink.values(), vec![0, 1, 2, 3, 4]);
+        for feature in sink.features.lock().iter() {
+            assert_eq!(
+                feature.get(&ERROR_ATTRIBUTE),
+                Some(&AttributeValue::String("Attempt 1 failed".to_string()))
+            );
+        }
+        assert_eq!(node_summary(&stats, "failing").unwrap().errors, 5);
+        assert!(node_summary(&stats, "passed")
+            .unwrap()
+            .features_in
+            .is_empty());
+        let rejected = Runtime::new()
+            .unwrap()
+            .block_on(state.get::<Vec<Value>>(&id(212)))
+            .unwrap();
+        assert_eq!(rejected.len(), 5);
+    }
+
+    /// Buffers the features and sends them on finish with the number of buffered features.
+    #[derive(Debug, Clone)]
+    struct CountingFactory;
+
+    impl ProcessorFactory for CountingFactory {
+        fn name(&self) -> &str {
+            "Counting"
+        }
+
+        fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
+            None
+        }
+
+        fn get_input_ports(&self) -> Vec<Port> {
+            vec![DEFAULT_PORT.clone()]
+        }
+
+        fn get_output_ports(&self) -> Vec<Port> {
+            vec![DEFAULT_PORT.clone()]
+        }
+
+        fn build(
+            &self,
+            _ctx: NodeContext,
+            _event_hub: EventHub,
+            _action: String,
+            _with: Option<HashMap<String, Value>>,
+        ) -> Result<Box<dyn Processor>, BoxedError> {
+            Ok(Box::new(Counting { buffer: vec![] }))
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    struct Counting {
+        buffer: Vec<ExecutorContext>,
+    }
+
+    impl Processor for Counting {
+        fn initialize(&mut self, _ctx: NodeContext) {}
+
+        fn process(
+            &mut self,
+            ctx: ExecutorContext,
+            _fw: &mut dyn ProcessorChannelForwarder,
+        ) -> Result<(), BoxedError> {
+            self.buffer.push(ctx);
+            Ok(())
+        }
+
+        fn finish(
+            &self,
+            _ctx: NodeContext,
+            fw: &mut dyn ProcessorChannelForwarder,
+        ) -> Result<(), BoxedError> {
+            for ctx in self.buffer.iter() {
+                let mut feature = ctx.feature.clone();
+                feature.insert(
+                    COUNT_ATTRIBUTE,
+                    AttributeValue::Number(self.buffer.len().into()),
+                );
+                fw.send(ctx.new_with_feature_and_port(feature, ctx.port.clone()));
+            }
+            Ok(())
+        }
+
+        fn name(&self) -> &str {
+            "Counting"
+        }
+    }
+}
@@ -10,6 +10,7 @@ use crate::errors::ExecutionError;
 use crate::executor_operation::{ExecutorContext, ExecutorOperation, NodeContext};
 use crate::feature_store::FeatureWriter;
 use crate::node::{NodeHandle, Port};
+use crate::stats::NodeStats;
 
 #[derive(Debug)]
 pub struct SenderWithPortMapping {
@@ -43,6 +44,7 @@ pub struct ChannelManager {
     feature_writers: HashMap<Port, Box<dyn FeatureWriter>>,
     senders: Vec<SenderWithPortMapping>,
     error_manager: Arc<ErrorManager>,
+    stats: Arc<NodeStats>,
     #[allow(dead_code)]
     runtime: Arc<Runtime>,
 }
@@ -50,6 +52,7 @@ pub struct ChannelManager {
 impl ChannelManager {
     #[inline]
     pub fn send_op(&mut self, ctx: ExecutorContext) -> Result<(), ExecutionError> {
+        self.stats.record_out(&ctx.port);
         if let Some(writer) = self.feature_writers.get_mut(&ctx.port) {
             match writer.write(&ctx.feature) {
                 Ok(()) => {}
@@ -101,6 +104,7 @@ impl ChannelManager {
         feature_writers: HashMap<Port, Box<dyn FeatureWriter>>,
         senders: Vec<SenderWithPortMapping>,
         error_manager: Arc<ErrorManager>,
+        stats: Arc<NodeStats>,
         runtime: Arc<Runtime>,
     ) -> Self {
         Self {
@@ -108,6 +112,7 @@ impl ChannelManager {
             feature_writers,
             senders,
             error_manager,
+            stats,
             runtime,
         }
     }
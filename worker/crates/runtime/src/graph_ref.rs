@@ -0,0 +1,418 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use reearth_flow_common::uri::Uri;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::workflow::{Graph, GraphDefinition, Node, NodeEntity};
+
+use crate::errors::ExecutionError;
+use crate::node::{GraphId, NodeId};
+
+/// Replaces the `graphRef` nodes of a workflow with `subGraph` nodes, adding the graphs of the
+/// referenced files to the workflow.
+///
+/// Files are loaded recursively, once per location. A file referencing itself, directly or
+/// through other files, is an error.
+pub(crate) struct GraphRefResolver<'a> {
+    storage_resolver: &'a StorageResolver,
+    /// Locations of the files loaded so far.
+    loaded: HashSet<String>,
+    /// Failed references with the graph and node of each, when they are collected instead of
+    /// stopping the resolution.
+    failures: Option<Vec<GraphRefFailure>>,
+}
+
+/// A reference that cannot be resolved, or a graph id that the referenced files repeat.
+#[derive(Debug)]
+pub(crate) struct GraphRefFailure {
+    pub(crate) graph_id: GraphId,
+    pub(crate) node_id: Option<NodeId>,
+    pub(crate) error: ExecutionError,
+}
+
+impl<'a> GraphRefResolver<'a> {
+    pub(crate) fn new(storage_resolver: &'a StorageResolver) -> Self {
+        Self {
+            storage_resolver,
+            loaded: HashSet::new(),
+            failures: None,
+        }
+    }
+
+    pub(crate) fn resolve(mut self, graphs: Vec<Graph>) -> Result<Vec<Graph>, ExecutionError> {
+        let mut resolved = vec![];
+        self.resolve_graphs(graphs, &mut vec![], &mut resolved)?;
+        if let Some(failure) = duplicate_graph_ids(&resolved).next() {
+            return Err(failure.error);
+        }
+        Ok(resolved)
+    }
+
+    /// Resolves every reference that can be, leaving the nodes of the others as they are, and
+    /// returns the failures of the others.
+    pub(crate) fn resolve_all(mut self, graphs: Vec<Graph>) -> (Vec<Graph>, Vec<GraphRefFailure>) {
+        self.failures = Some(vec![]);
+        let mut resolved = vec![];
+        // Failures are collected, so resolving the graphs does not fail.
+        let _ = self.resolve_graphs(graphs, &mut vec![], &mut resolved);
+        let mut failures = self.failures.take().unwrap_or_default();
+        failures.extend(duplicate_graph_ids(&resolved));
+        (resolved, failures)
+    }
+
+    fn resolve_graphs(
+        &mut self,
+        graphs: Vec<Graph>,
+        stack: &mut Vec<String>,
+        resolved: &mut Vec<Graph>,
+    ) -> Result<(), ExecutionError> {
+        for mut graph in graphs {
+            for node in graph.nodes.iter_mut() {
+                let Err(error) = self.resolve_node(node, stack, resolved) else {
+                    continue;
+                };
+                match &mut self.failures {
+                    Some(failures) => failures.push(GraphRefFailure {
+                        graph_id: graph.id,
+                        node_id: Some(node.id()),
+                        error,
+                    }),
+                    None => return Err(error),
+                }
+            }
+            resolved.push(graph);
+        }
+        Ok(())
+    }
+
+    fn resolve_node(
+        &mut self,
+        node: &mut Node,
+        stack: &mut Vec<String>,
+        resolved: &mut Vec<Graph>,
+    ) -> Result<(), ExecutionError> {
+        let Node::GraphRef {
+            entity,
+            graph_uri,
+            version,
+        } = node.clone()
+        else {
+            return Ok(());
+        };
+        if stack.contains(&graph_uri) {
+            return Err(ExecutionError::GraphRef(format!(
+                "Cyclic graph reference: {} -> {}",
+                stack.join(" -> "),
+                graph_uri
+            )));
+        }
+        let definition = self.load(&graph_uri)?;
+        if let Some(version) = version {
+            if version != definition.version {
+                return Err(ExecutionError::GraphRef(format!(
+                    "Version mismatch of {}: expected {}, found {}",
+                    graph_uri, version, definition.version
+                )));
+            }
+        }
+        let with = with_defaults(&entity, &graph_uri, &definition)?;
+        let sub_graph_id = definition.entry_graph_id;
+        if self.loaded.insert(graph_uri.clone()) {
+            stack.push(graph_uri);
+            let result = self.resolve_graphs(definition.graphs, stack, resolved);
+            stack.pop();
+            result?;
+        }
+        *node = Node::SubGraph {
+            entity: NodeEntity {
+                with: Some(with),
+                ..entity
+            },
+            sub_graph_id,
+        };
+        Ok(())
+    }
+
+    fn load(&self, graph_uri: &str) -> Result<GraphDefinition, ExecutionError> {
+        let uri = Uri::from_str(graph_uri).map_err(|e| {
+            ExecutionError::GraphRef(format!("Invalid graph uri {}: {:?}", graph_uri, e))
+        })?;
+        let storage = self.storage_resolver.resolve(&uri).map_err(|e| {
+            ExecutionError::GraphRef(format!("Failed to resolve {}: {:?}", graph_uri, e))
+        })?;
+        let bytes = storage.get_sync(uri.path().as_path()).map_err(|e| {
+            ExecutionError::GraphRef(format!("Failed to read {}: {:?}", graph_uri, e))
+        })?;
+        let content = String::from_utf8(bytes.to_vec()).map_err(|e| {
+            ExecutionError::GraphRef(format!("Failed to read {}: {:?}", graph_uri, e))
+        })?;
+        GraphDefinition::try_from_str(&content).map_err(|e| {
+            ExecutionError::GraphRef(format!("Failed to parse: {}", e.with_path(graph_uri)))
+        })
+    }
+}
+
+fn duplicate_graph_ids(graphs: &[Graph]) -> impl Iterator<Item = GraphRefFailure> + '_ {
+    let mut graph_ids = HashSet::new();
+    graphs
+        .iter()
+        .filter(move |graph| !graph_ids.insert(graph.id))
+        .map(|graph| GraphRefFailure {
+            graph_id: graph.id,
+            node_id: None,
+            error: ExecutionError::GraphRef(format!("Duplicate graph id: {}", graph.id)),
+        })
+}
+
+/// Parameters of the referencing node on top of the defaults of the referenced graph.
+fn with_defaults(
+    entity: &NodeEntity,
+    graph_uri: &str,
+    definition: &GraphDefinition,
+) -> Result<serde_json::Map<String, serde_json::Value>, ExecutionError> {
+    let mut with = serde_json::Map::new();
+    for parameter in definition.parameters.iter() {
+        if let Some(default) = &parameter.default {
+            with.insert(parameter.name.clone(), default.clone());
+        }
+    }
+    if let Some(params) = &entity.with {
+        with.extend(params.clone());
+    }
+    let missing = definition
+        .parameters
+        .iter()
+        .filter(|parameter| parameter.required && !with.contains_key(&parameter.name))
+        .map(|parameter| parameter.name.as_str())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(ExecutionError::GraphRef(format!(
+            "Missing parameters of {} for node {}: {}",
+            graph_uri,
+            entity.name,
+            missing.join(", ")
+        )));
+    }
+    Ok(with)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const LIBRARY_GRAPH_ID: &str = "5b0c0d0e-0000-4000-8000-0000000000b0";
+
+    fn put(storage_resolver: &StorageResolver, uri: &str, content: Value) {
+        let uri = Uri::for_test(uri);
+        storage_resolver
+            .resolve(&uri)
+            .unwrap()
+            .put_sync(uri.path().as_path(), Bytes::from(content.to_string()))
+            .unwrap();
+    }
+
+    /// A graph definition whose entry graph holds `nodes`.
+    fn definition(version: &str, parameters: Value, nodes: Value) -> Value {
+        json!({
+            "name": "library",
+            "version": version,
+            "parameters": parameters,
+            "entryGraphId": LIBRARY_GRAPH_ID,
+            "graphs": [{"id": LIBRARY_GRAPH_ID, "name": "library", "nodes": nodes, "edges": []}]
+        })
+    }
+
+    fn graph_ref(id: &str, graph_uri: &str, version: Option<&str>, with: Value) -> Value {
+        json!({
+            "id": id,
+            "name": "reference",
+            "type": "graphRef",
+            "graphUri": graph_uri,
+            "version": version,
+            "with": with
+        })
+    }
+
+    fn resolve(
+        storage_resolver: &StorageResolver,
+        nodes: Value,
+    ) -> Result<Vec<Graph>, ExecutionError> {
+        let graph = serde_json::from_value(json!({
+            "id": "5b0c0d0e-0000-4000-8000-0000000000a0",
+            "name": "entry",
+            "nodes": nodes,
+            "edges": []
+        }))
+        .unwrap();
+        GraphRefResolver::new(storage_resolver).resolve(vec![graph])
+    }
+
+    fn error_message(result: Result<Vec<Graph>, ExecutionError>) -> String {
+        match result {
+            Err(ExecutionError::GraphRef(message)) => message,
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_resolve_with_parameter_defaults() {
+        let storage_resolver = StorageResolver::new();
+        put(
+            &storage_resolver,
+            "ram:///graphs/library.json",
+            definition(
+                "1.0.0",
+                json!([
+                    {"name": "tolerance", "default": 0.5},
+                    {"name": "attribute", "default": "name"},
+                    {"name": "optional"}
+                ]),
+                json!([]),
+            ),
+        );
+        let graphs = resolve(
+            &storage_resolver,
+            json!([
+                graph_ref(
+                    "5b0c0d0e-0000-4000-8000-000000000001",
+                    "ram:///graphs/library.json",
+                    Some("1.0.0"),
+                    json!({"attribute": "code"})
+                ),
+                graph_ref(
+                    "5b0c0d0e-0000-4000-8000-000000000002",
+                    "ram:///graphs/library.json",
+                    None,
+                    Value::Null
+                )
+            ]),
+        )
+        .unwrap();
+        // The file referenced twice is added once.
+        assert_eq!(graphs.len(), 2);
+        assert_eq!(graphs[0].id.to_string(), LIBRARY_GRAPH_ID);
+        let withs = graphs[1]
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::SubGraph {
+                    entity,
+                    sub_graph_id,
+                } => {
+                    assert_eq!(sub_graph_id.to_string(), LIBRARY_GRAPH_ID);
+                    Value::Object(entity.with.clone().unwrap().into_iter().collect())
+                }
+                node => panic!("Unexpected node: {:?}", node),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            withs,
+            vec![
+                json!({"tolerance": 0.5, "attribute": "code"}),
+                json!({"tolerance": 0.5, "attribute": "name"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_required_parameter() {
+        let storage_resolver = StorageResolver::new();
+        put(
+            &storage_resolver,
+            "ram:///graphs/library.json",
+            definition(
+                "1.0.0",
+                json!([
+                    {"name": "tolerance", "required": true, "default": 0.5},
+                    {"name": "attribute", "required": true}
+                ]),
+                json!([]),
+            ),
+        );
+        let message = error_message(resolve(
+            &storage_resolver,
+            json!([graph_ref(
+                "5b0c0d0e-0000-4000-8000-000000000001",
+                "ram:///graphs/library.json",
+                None,
+                json!({})
+            )]),
+        ));
+        assert_eq!(
+            message,
+            "Missing parameters of ram:///graphs/library.json for node reference: attribute"
+        );
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let storage_resolver = StorageResolver::new();
+        put(
+            &storage_resolver,
+            "ram:///graphs/library.json",
+            definition("1.0.0", json!([]), json!([])),
+        );
+        let message = error_message(resolve(
+            &storage_resolver,
+            json!([graph_ref(
+                "5b0c0d0e-0000-4000-8000-000000000001",
+                "ram:///graphs/library.json",
+                Some("2.0.0"),
+                Value::Null
+            )]),
+        ));
+        assert_eq!(
+            message,
+            "Version mismatch of ram:///graphs/library.json: expected 2.0.0, found 1.0.0"
+        );
+    }
+
+    #[test]
+    fn test_recursive_reference() {
+        let storage_resolver = StorageResolver::new();
+        put(
+            &storage_resolver,
+            "ram:///graphs/a.json",
+            definition(
+                "1.0.0",
+                json!([]),
+                json!([graph_ref(
+                    "5b0c0d0e-0000-4000-8000-000000000011",
+                    "ram:///graphs/b.json",
+                    None,
+                    Value::Null
+                )]),
+            ),
+        );
+        put(
+            &storage_resolver,
+            "ram:///graphs/b.json",
+            definition(
+                "1.0.0",
+                json!([]),
+                json!([graph_ref(
+                    "5b0c0d0e-0000-4000-8000-000000000012",
+                    "ram:///graphs/a.json",
+                    None,
+                    Value::Null
+                )]),
+            ),
+        );
+        let message = error_message(resolve(
+            &storage_resolver,
+            json!([graph_ref(
+                "5b0c0d0e-0000-4000-8000-000000000001",
+                "ram:///graphs/a.json",
+                None,
+                Value::Null
+            )]),
+        ));
+        assert_eq!(
+            message,
+            "Cyclic graph reference: ram:///graphs/a.json -> ram:///graphs/b.json -> ram:///graphs/a.json"
+        );
+    }
+}
@@ -1,6 +1,11 @@
-use std::{collections::HashMap, fmt::Debug, sync::Arc};
+use std::{collections::HashMap, fmt::Debug, path::PathBuf, sync::Arc};
 
+use bytes::Bytes;
+use reearth_flow_common::{str::to_hash, uri::Uri};
+use reearth_flow_storage::{resolve::StorageResolver, storage::Storage};
 use reearth_flow_types::AttributeValue;
+use thiserror::Error;
+use tracing::error;
 
 #[nutype::nutype(
     sanitize(trim),
@@ -61,10 +66,26 @@ impl<T: ?Sized + KvStore> KvStore for Box<T> {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum KvStoreError {
+    #[error("Failed to resolve kv store storage: {0}")]
+    Resolve(String),
+}
+
 pub fn create_kv_store() -> Box<dyn KvStore> {
     Box::new(MemoryKvStore::new())
 }
 
+/// Create a key-value store whose entries are persisted under `root`.
+///
+/// Entries written by one job can be read by later jobs that point at the same `root`.
+pub fn create_persistent_kv_store(
+    root: &Uri,
+    storage_resolver: &StorageResolver,
+) -> Result<Box<dyn KvStore>, KvStoreError> {
+    Ok(Box::new(StorageKvStore::new(root, storage_resolver)?))
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct MemoryKvStore(Arc<parking_lot::RwLock<HashMap<KvStoreKey, AttributeValue>>>);
 
@@ -91,3 +112,100 @@ impl KvStore for MemoryKvStore {
         Box::new(self.clone())
     }
 }
+
+/// Key-value store backed by [`Storage`], one object per key.
+///
+/// Values are not cached in memory, so the store is not limited by the amount of RAM.
+#[derive(Debug, Clone)]
+pub struct StorageKvStore {
+    storage: Arc<Storage>,
+    root: PathBuf,
+}
+
+impl StorageKvStore {
+    pub fn new(root: &Uri, storage_resolver: &StorageResolver) -> Result<Self, KvStoreError> {
+        let storage = storage_resolver
+            .resolve(root)
+            .map_err(|e| KvStoreError::Resolve(format!("{:?}", e)))?;
+        Ok(Self {
+            storage,
+            root: root.path(),
+        })
+    }
+
+    fn key_to_location(&self, key: &KvStoreKey) -> PathBuf {
+        self.root.join(format!("{}.json", to_hash(key.as_ref().as_str())))
+    }
+}
+
+impl KvStore for StorageKvStore {
+    fn get(&self, key: &KvStoreKey) -> Option<AttributeValue> {
+        let location = self.key_to_location(key);
+        if !self.storage.exists_sync(location.as_path()).ok()? {
+            return None;
+        }
+        let bytes = match self.storage.get_sync(location.as_path()) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("Failed to read kv store entry: key = {}, error = {:?}", key, e);
+                return None;
+            }
+        };
+        match serde_json::from_slice(bytes.as_ref()) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                error!("Failed to parse kv store entry: key = {}, error = {:?}", key, e);
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, key: KvStoreKey, value: AttributeValue) {
+        let content = match serde_json::to_vec(&value) {
+            Ok(content) => content,
+            Err(e) => {
+                error!("Failed to serialize kv store entry: key = {}, error = {:?}", key, e);
+                return;
+            }
+        };
+        let location = self.key_to_location(&key);
+        if let Err(e) = self.storage.put_sync(location.as_path(), Bytes::from(content)) {
+            error!("Failed to write kv store entry: key = {}, error = {:?}", key, e);
+        }
+    }
+
+    fn remove(&mut self, key: &KvStoreKey) {
+        let location = self.key_to_location(key);
+        if let Err(e) = self.storage.delete_sync(location.as_path()) {
+            error!("Failed to remove kv store entry: key = {}, error = {:?}", key, e);
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn KvStore> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_kv_store() {
+        let storage_resolver = StorageResolver::new();
+        let root = Uri::for_test("ram:///kv-store");
+        let mut kv_store = create_persistent_kv_store(&root, &storage_resolver).unwrap();
+        let key = KvStoreKey::new("dictionaries/codelists");
+        assert_eq!(kv_store.get(&key), None);
+
+        let value = AttributeValue::String("value".to_string());
+        kv_store.insert(key.clone(), value.clone());
+        assert_eq!(kv_store.get(&key), Some(value.clone()));
+
+        let other = create_persistent_kv_store(&root, &storage_resolver).unwrap();
+        assert_eq!(other.get(&key), Some(value));
+
+        kv_store.remove(&key);
+        assert_eq!(other.get(&key), None);
+    }
+}
@@ -1,5 +1,10 @@
+// The factory derives refer to the runtime by its crate name, also when used here.
+extern crate self as reearth_flow_runtime;
+
 pub mod builder_dag;
+pub mod cache;
 pub mod channels;
+pub mod checkpoint;
 pub mod dag_schemas;
 pub mod epoch;
 pub mod error_manager;
@@ -8,7 +13,17 @@ pub mod event;
 pub mod executor;
 pub mod executor_operation;
 pub mod feature_store;
+pub mod for_each;
 pub mod forwarder;
+mod graph_ref;
 pub mod kvs;
+pub mod looping;
 pub mod node;
+pub mod replay;
 pub mod shutdown;
+pub mod spill;
+pub mod stats;
+mod sub_graph;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod tests;
+pub mod validation;
@@ -0,0 +1,355 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use petgraph::graph::DiGraph;
+use reearth_flow_eval_expr::engine::Engine;
+use reearth_flow_types::{workflow::Loop, Attribute, AttributeValue};
+use serde_json::Number;
+
+use crate::channels::ProcessorChannelForwarder;
+use crate::dag_schemas::{SchemaEdgeType, SchemaNodeType};
+use crate::errors::BoxedError;
+use crate::event::EventHub;
+use crate::executor_operation::{ExecutorContext, InnerNodeContext, NodeContext};
+use crate::node::{Port, Processor, ProcessorFactory, DEFAULT_PORT};
+use crate::sub_graph::{SubGraphInstance, SubGraphTemplate};
+
+pub static LOOP_ACTION: &str = "Loop";
+
+static DEFAULT_ITERATION_ATTRIBUTE: &str = "_iteration";
+
+/// Builds the processor running a sub graph again on its own output.
+#[derive(Debug, Clone, ProcessorFactory)]
+#[factory(
+    name = LOOP_ACTION,
+    description = "Runs a sub graph again on its own output while a condition holds",
+    input_ports = [],
+    output_ports = [],
+)]
+pub struct LoopFactory {
+    graph: Arc<DiGraph<SchemaNodeType, SchemaEdgeType>>,
+    repeat: Loop,
+}
+
+impl LoopFactory {
+    pub(crate) fn new(graph: DiGraph<SchemaNodeType, SchemaEdgeType>, repeat: Loop) -> Self {
+        Self {
+            graph: Arc::new(graph),
+            repeat,
+        }
+    }
+
+    fn build_processor(
+        &self,
+        ctx: NodeContext,
+        event_hub: EventHub,
+        _action: String,
+    ) -> Result<Box<dyn Processor>, BoxedError> {
+        let condition = ctx
+            .expr_engine
+            .compile(self.repeat.condition.as_ref())
+            .map_err(|e| format!("Failed to compile the loop condition: {:?}", e))?;
+        let expr_engine = Arc::clone(&ctx.expr_engine);
+        let template = SubGraphTemplate::build(&self.graph, ctx, event_hub)?;
+        Ok(Box::new(LoopProcessor::new(
+            Arc::new(template),
+            expr_engine,
+            Arc::new(condition),
+            self.repeat.max_iterations,
+            self.repeat
+                .iteration_attribute
+                .clone()
+                .unwrap_or_else(|| Attribute::new(DEFAULT_ITERATION_ATTRIBUTE)),
+        )))
+    }
+}
+
+/// Runs the first iteration of the sub graph as features arrive. The features still satisfying
+/// the condition are collected and run through a fresh instance of the sub graph once the
+/// previous iteration has finished, until none is left.
+///
+/// The first iteration always runs, even when `maxIterations` is 0.
+#[derive(Debug)]
+pub struct LoopProcessor {
+    template: Arc<SubGraphTemplate>,
+    expr_engine: Arc<Engine>,
+    condition: Arc<rhai::AST>,
+    max_iterations: u32,
+    iteration_attribute: Attribute,
+    /// Port the features entered the loop through, used to feed them back.
+    input_port: Option<Port>,
+    /// Instance running the first iteration, started with the first feature.
+    instance: Mutex<Option<SubGraphInstance>>,
+    /// Features running through the sub graph again in the next iteration.
+    pending: Mutex<Vec<ExecutorContext>>,
+}
+
+impl LoopProcessor {
+    fn new(
+        template: Arc<SubGraphTemplate>,
+        expr_engine: Arc<Engine>,
+        condition: Arc<rhai::AST>,
+        max_iterations: u32,
+        iteration_attribute: Attribute,
+    ) -> Self {
+        Self {
+            template,
+            expr_engine,
+            condition,
+            max_iterations,
+            iteration_attribute,
+            input_port: None,
+            instance: Mutex::new(None),
+            pending: Mutex::new(vec![]),
+        }
+    }
+
+    /// Sends the features leaving an iteration on, or keeps them for the next one.
+    fn dispatch(
+        &self,
+        features: Vec<ExecutorContext>,
+        iteration: u32,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        for ctx in features {
+            let mut feature = ctx.feature.clone();
+            feature.insert(
+                &self.iteration_attribute,
+                AttributeValue::Number(Number::from(iteration)),
+            );
+            let again = iteration < self.max_iterations
+                && feature
+                    .new_scope(Arc::clone(&self.expr_engine))
+                    .eval_ast::<bool>(&self.condition)
+                    .map_err(|e| format!("Failed to evaluate the loop condition: {:?}", e))?;
+            if again {
+                let port = self
+                    .input_port
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_PORT.clone());
+                self.pending
+                    .lock()
+                    .push(ctx.new_with_feature_and_port(feature, port));
+            } else {
+                let port = ctx.port.clone();
+                fw.send(ctx.new_with_feature_and_port(feature, port));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Clone for LoopProcessor {
+    /// Clones are fresh processors without any instance.
+    fn clone(&self) -> Self {
+        Self::new(
+            Arc::clone(&self.template),
+            Arc::clone(&self.expr_engine),
+            Arc::clone(&self.condition),
+            self.max_iterations,
+            self.iteration_attribute.clone(),
+        )
+    }
+}
+
+impl Processor for LoopProcessor {
+    fn initialize(&mut self, _ctx: NodeContext) {}
+
+    fn attach(&mut self, ctx: &InnerNodeContext) {
+        self.template.attach(ctx);
+    }
+
+    fn process(
+        &mut self,
+        ctx: ExecutorContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        self.input_port.get_or_insert_with(|| ctx.port.clone());
+        let features = {
+            let mut instance = self.instance.lock();
+            let instance = instance.get_or_insert_with(|| SubGraphInstance::new(&self.template));
+            instance.process(&self.template, ctx)?
+        };
+        self.dispatch(features, 1, fw)
+    }
+
+    fn finish(
+        &self,
+        ctx: NodeContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        let mut instance = self.instance.lock().take();
+        let mut iteration = 1;
+        loop {
+            if let Some(mut instance) = instance.take() {
+                let features = instance.finish(&self.template, ctx.clone())?;
+                self.dispatch(features, iteration, fw)?;
+            }
+            let pending = std::mem::take(&mut *self.pending.lock());
+            if pending.is_empty() {
+                self.template.flush();
+                return Ok(());
+            }
+            iteration += 1;
+            let mut next = SubGraphInstance::new(&self.template);
+            for ctx in pending {
+                let features = next.process(&self.template, ctx)?;
+                self.dispatch(features, iteration, fw)?;
+            }
+            instance = Some(next);
+        }
+    }
+
+    fn name(&self) -> &str {
+        LOOP_ACTION
+    }
+
+    fn is_stateful(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use reearth_flow_storage::resolve::StorageResolver;
+    use reearth_flow_types::AttributeValue;
+    use serde_json::{json, Value};
+
+    use crate::stats::JobStats;
+    use crate::tests::utils::{
+        create_executor_options, create_factories, create_state, execute, feature_value,
+        node_summary, CollectingSinkFactory,
+    };
+
+    fn id(n: u32) -> String {
+        format!("7b3d8e21-0000-4000-8000-{:012}", n)
+    }
+
+    /// Sends 5 features through a sub graph with a single node, looping while `condition` holds
+    /// for at most 3 iterations.
+    fn workflow(condition: &str, node: Value) -> String {
+        json!({
+            "id": id(0),
+            "name": "loop",
+            "entryGraphId": id(100),
+            "graphs": [
+                {
+                    "id": id(100),
+                    "name": "entry",
+                    "nodes": [
+                        {
+                            "id": id(1),
+                            "name": "source",
+                            "type": "action",
+                            "action": "NumberSource",
+                            "with": {"count": 5}
+                        },
+                        {
+                            "id": id(2),
+                            "name": "loop",
+                            "type": "subGraph",
+                            "subGraphId": id(200),
+                            "loop": {
+                                "condition": condition,
+                                "maxIterations": 3,
+                                "iterationAttribute": "iteration"
+                            }
+                        },
+                        {
+                            "id": id(3),
+                            "name": "sink",
+                            "type": "action",
+                            "action": "Collecting"
+                        }
+                    ],
+                    "edges": [
+                        {
+                            "id": id(101),
+                            "from": id(1),
+                            "to": id(2),
+                            "fromPort": "default",
+                            "toPort": "default"
+                        },
+                        {
+                            "id": id(102),
+                            "from": id(2),
+                            "to": id(3),
+                            "fromPort": "default",
+                            "toPort": "default"
+                        }
+                    ]
+                },
+                {"id": id(200), "name": "sub", "nodes": [node], "edges": []}
+            ]
+        })
+        .to_string()
+    }
+
+    fn run(condition: &str, node: Value) -> (CollectingSinkFactory, Arc<JobStats>) {
+        let sink = CollectingSinkFactory::default();
+        let options = create_executor_options();
+        let stats = Arc::clone(&options.stats);
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///loop/", &storage_resolver);
+        execute(
+            &workflow(condition, node),
+            create_factories(&sink),
+            options,
+            storage_resolver,
+            state,
+        )
+        .unwrap();
+        (sink, stats)
+    }
+
+    #[test]
+    fn test_repeats_while_condition_holds() {
+        let (sink, stats) = run(
+            r#"env.get("__value").iteration <= env.get("__value").value"#,
+            json!({"id": id(201), "name": "pass", "type": "action", "action": "PassThrough"}),
+        );
+        let mut iterations = sink
+            .features
+            .lock()
+            .iter()
+            .map(|feature| {
+                let Some(AttributeValue::Number(iteration)) = feature.get(&"iteration") else {
+                    panic!("Missing iteration: {:?}", feature);
+                };
+                (feature_value(feature), iteration.as_u64().unwrap())
+            })
+            .collect::<Vec<_>>();
+        iterations.sort();
+        // The last two features stop at the maximum number of iterations.
+        assert_eq!(iterations, vec![(0, 1), (1, 2), (2, 3), (3, 3), (4, 3)]);
+        assert_eq!(
+            node_summary(&stats, "pass")
+                .unwrap()
+                .features_in
+                .get("default"),
+            Some(&12)
+        );
+    }
+
+    #[test]
+    fn test_inner_nodes_apply_error_policy() {
+        let (sink, stats) = run(
+            "false",
+            json!({
+                "id": id(201),
+                "name": "failing",
+                "type": "action",
+                "action": "Failing",
+                "with": {"failures": 1},
+                "onError": {"type": "retry", "maxRetries": 1}
+            }),
+        );
+        // Only the copies sent by the successful attempts leave the loop.
+        assert_eq!(sink.values(), vec![0, 1, 2, 3, 4]);
+        let failing = node_summary(&stats, "failing").unwrap();
+        assert_eq!(failing.errors, 0);
+        assert_eq!(failing.features_in.get("default"), Some(&5));
+    }
+}
@@ -6,19 +6,24 @@ use std::sync::Arc;
 use once_cell::sync::Lazy;
 use reearth_flow_types::Feature;
 use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::mpsc::Sender;
 
 use crate::channels::ProcessorChannelForwarder;
-use crate::errors::{BoxedError, DeserializationError};
+use crate::errors::{BoxedError, ExecutionError};
 use crate::event::EventHub;
-use crate::executor_operation::{ExecutorContext, NodeContext};
+use crate::executor_operation::{ExecutorContext, InnerNodeContext, NodeContext};
+
+pub use reearth_flow_macros::{ProcessorFactory, SinkFactory, SourceFactory};
 
 pub static DEFAULT_PORT: Lazy<Port> = Lazy::new(|| Port::new("default"));
 pub static REJECTED_PORT: Lazy<Port> = Lazy::new(|| Port::new("rejected"));
 pub static ROUTING_PARAM_KEY: &str = "routingPort";
 pub static REMAIN_PORT: Lazy<Port> = Lazy::new(|| Port::new("remain"));
+/// Attribute holding the error message of a feature sent to the rejected port by an error policy.
+pub static ERROR_ATTRIBUTE: &str = "_error";
 
 pub(super) type NodeId = uuid::Uuid;
 pub(super) type GraphId = uuid::Uuid;
@@ -77,7 +82,7 @@ pub enum NodeKind {
     Sink(Box<dyn SinkFactory>),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct NodeHandle {
     pub id: uuid::Uuid,
 }
@@ -97,7 +102,14 @@ impl Display for NodeHandle {
 #[derive(Clone, Debug, PartialEq)]
 /// All possible kinds of `IngestionMessage`.
 pub enum IngestionMessage {
-    OperationEvent { feature: Feature },
+    OperationEvent {
+        feature: Feature,
+    },
+    /// The state of the source once the features sent before it are processed, from
+    /// [`Source::serialize_state`]. A checkpoint taken after them restarts the source from it.
+    State {
+        state: Vec<u8>,
+    },
 }
 
 pub trait SourceClone {
@@ -157,6 +169,18 @@ impl Clone for Box<dyn Sink> {
     }
 }
 
+/// Deserializes the `with` parameters of a node into the parameters of its action.
+pub fn parse_params<T: DeserializeOwned>(
+    with: Option<HashMap<String, Value>>,
+) -> Result<T, String> {
+    let Some(with) = with else {
+        return Err("Missing required parameter `with`".to_string());
+    };
+    let value =
+        serde_json::to_value(with).map_err(|e| format!("Failed to serialize with: {}", e))?;
+    serde_json::from_value(value).map_err(|e| format!("Failed to deserialize with: {}", e))
+}
+
 pub trait SourceFactory: Send + Sync + Debug + SourceFactoryClone {
     fn name(&self) -> &str;
     fn description(&self) -> &str {
@@ -201,6 +225,10 @@ impl Clone for Box<dyn SourceFactory> {
 #[async_trait::async_trait]
 pub trait Source: Send + Sync + Debug + SourceClone {
     async fn initialize(&self, ctx: NodeContext);
+
+    /// Serialize the state the source restarts from when a job resumes, which
+    /// [`SourceFactory::build`] receives. Resumable sources send it with
+    /// [`IngestionMessage::State`] before their first feature and after each feature.
     async fn serialize_state(&self) -> Result<Vec<u8>, BoxedError>;
 
     async fn start(
@@ -214,6 +242,8 @@ pub trait Source: Send + Sync + Debug + SourceClone {
 pub enum SourceState {
     /// This source hasn't been ingested.
     NotStarted,
+    /// This source has some data ingested, and it can be restarted from its serialized state.
+    Restartable(Vec<u8>),
     /// This source has some data ingested, and it can't be restarted.
     NonRestartable,
 }
@@ -263,6 +293,12 @@ impl Clone for Box<dyn ProcessorFactory> {
 
 pub trait Processor: Send + Sync + Debug + ProcessorClone {
     fn initialize(&mut self, ctx: NodeContext);
+
+    /// Attaches the nodes the processor runs itself, like the nodes of a sub graph, to the job so
+    /// that they apply their error policy, record statistics and store the features they send.
+    /// Called before [`Processor::initialize`].
+    fn attach(&mut self, _ctx: &InnerNodeContext) {}
+
     fn num_threads(&self) -> usize {
         1
     }
@@ -278,6 +314,22 @@ pub trait Processor: Send + Sync + Debug + ProcessorClone {
     ) -> Result<(), BoxedError>;
 
     fn name(&self) -> &str;
+
+    /// Whether the processor keeps what it receives until it finishes, e.g. buffered features or
+    /// aggregates. A job is not resumed from a checkpoint lacking the state of such a processor.
+    fn is_stateful(&self) -> bool {
+        false
+    }
+
+    /// Serialize the internal state for a checkpoint. Stateful processors must implement this
+    /// together with [`Processor::restore_state`] to be resumable.
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        Ok(None)
+    }
+
+    fn restore_state(&mut self, _state: &[u8]) -> Result<(), BoxedError> {
+        Ok(())
+    }
 }
 
 pub trait SinkFactory: Send + Sync + Debug + SinkFactoryClone {
@@ -348,46 +400,37 @@ pub trait Sink: Send + Debug + SinkClone {
     fn supports_batching(&self) -> bool {
         false
     }
-}
 
-#[derive(Debug, Clone, Default)]
-pub struct RouterFactory;
-
-impl ProcessorFactory for RouterFactory {
-    fn name(&self) -> &str {
-        "Router"
-    }
-
-    fn description(&self) -> &str {
-        "Action for last port forwarding for sub-workflows."
-    }
-
-    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
-        Some(schemars::schema_for!(Router))
+    /// Serialize the buffered data for a checkpoint.
+    fn serialize_state(&self) -> Result<Option<Vec<u8>>, BoxedError> {
+        Ok(None)
     }
 
-    fn get_input_ports(&self) -> Vec<Port> {
-        vec![DEFAULT_PORT.clone()]
+    fn restore_state(&mut self, _state: &[u8]) -> Result<(), BoxedError> {
+        Ok(())
     }
+}
 
-    fn get_output_ports(&self) -> Vec<Port> {
-        vec![]
-    }
+#[derive(Debug, Clone, Default, ProcessorFactory)]
+#[factory(
+    name = "Router",
+    description = "Action for last port forwarding for sub-workflows.",
+    input_ports = [DEFAULT_PORT],
+    output_ports = [],
+    param = Router,
+    error = ExecutionError::BuildFactory,
+)]
+pub struct RouterFactory;
 
-    fn build(
+impl RouterFactory {
+    fn build_processor(
         &self,
         _ctx: NodeContext,
         _event_hub: EventHub,
         _action: String,
-        with: Option<HashMap<String, Value>>,
+        params: Router,
     ) -> Result<Box<dyn Processor>, BoxedError> {
-        let process: Router = if let Some(with) = with {
-            let value: Value = serde_json::to_value(with).map_err(DeserializationError::Json)?;
-            serde_json::from_value(value).map_err(DeserializationError::Json)?
-        } else {
-            return Err(DeserializationError::EmptyInput.into());
-        };
-        Ok(Box::new(process))
+        Ok(Box::new(params))
     }
 }
 
@@ -0,0 +1,325 @@
+use std::sync::Arc;
+
+use reearth_flow_state::State;
+use reearth_flow_types::{
+    workflow::{Node, NodeEntity},
+    Feature,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    dag_schemas::{SchemaEdgeType, SchemaNodeType},
+    errors::{BoxedError, ExecutionError},
+    event::EventHub,
+    executor_operation::NodeContext,
+    feature_store::StreamingFeatureReader,
+    node::{IngestionMessage, NodeKind, Port, Source, SourceFactory},
+};
+
+static FEATURE_STORE_READER_ACTION: &str = "FeatureStoreReader";
+
+/// Runs only the part of the workflow downstream of an edge, feeding it with the features
+/// stored by a previous job.
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    pub edge_id: uuid::Uuid,
+    /// Feature store of the job that recorded the edge.
+    pub state: Arc<State>,
+}
+
+impl ReplayOptions {
+    /// Creates the source node that replaces the upstream of `edge`. `edge_ids` are the edges
+    /// whose stored features may hold the features of `edge`.
+    pub(crate) fn create_source(
+        &self,
+        edge: &SchemaEdgeType,
+        edge_ids: Vec<uuid::Uuid>,
+    ) -> SchemaNodeType {
+        feature_store_source(edge.id, &self.state, edge_ids, edge.from.clone())
+    }
+}
+
+/// Creates a source node sending the features stored in `state` for the first of `edge_ids`
+/// that has any, through `port`.
+pub(crate) fn feature_store_source(
+    id: uuid::Uuid,
+    state: &Arc<State>,
+    edge_ids: Vec<uuid::Uuid>,
+    port: Port,
+) -> SchemaNodeType {
+    let params = FeatureStoreReader { edge_ids, port };
+    let with = match serde_json::to_value(&params) {
+        Ok(Value::Object(with)) => with,
+        _ => unreachable!("FeatureStoreReader params are always an object"),
+    };
+    let name = format!("{}({})", FEATURE_STORE_READER_ACTION, id);
+    SchemaNodeType::new(
+        id,
+        name.clone(),
+        Node::Action {
+            entity: NodeEntity {
+                id,
+                name,
+                with: Some(with.clone()),
+                on_error: None,
+                num_threads: None,
+                preserve_order: None,
+                for_each: None,
+                repeat: None,
+            },
+            action: FEATURE_STORE_READER_ACTION.to_string(),
+        },
+        Some(NodeKind::Source(Box::new(FeatureStoreReaderFactory {
+            state: Arc::clone(state),
+        }))),
+        Some(with.into_iter().collect()),
+    )
+}
+
+#[derive(Debug, Clone, SourceFactory)]
+#[factory(
+    name = FEATURE_STORE_READER_ACTION,
+    description = "Reads the features stored for an edge by a previous job",
+    output_ports = [],
+    param = FeatureStoreReader,
+    error = ExecutionError::BuildFactory,
+)]
+pub(crate) struct FeatureStoreReaderFactory {
+    state: Arc<State>,
+}
+
+impl FeatureStoreReaderFactory {
+    fn build_source(
+        &self,
+        _ctx: NodeContext,
+        _event_hub: EventHub,
+        _action: String,
+        params: FeatureStoreReader,
+        _state: Option<Vec<u8>>,
+    ) -> Result<Box<dyn Source>, BoxedError> {
+        Ok(Box::new(FeatureStoreReaderSource {
+            state: Arc::clone(&self.state),
+            params,
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct FeatureStoreReader {
+    edge_ids: Vec<uuid::Uuid>,
+    port: Port,
+}
+
+#[derive(Debug, Clone)]
+struct FeatureStoreReaderSource {
+    state: Arc<State>,
+    params: FeatureStoreReader,
+}
+
+impl FeatureStoreReaderSource {
+    async fn send(
+        &self,
+        sender: &Sender<(Port, IngestionMessage)>,
+        features: Vec<Feature>,
+    ) -> Result<(), ExecutionError> {
+        for feature in features {
+            sender
+                .send((
+                    self.params.port.clone(),
+                    IngestionMessage::OperationEvent { feature },
+                ))
+                .await
+                .map_err(|e| ExecutionError::CannotSendToChannel(format!("{:?}", e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Source for FeatureStoreReaderSource {
+    async fn initialize(&self, _ctx: NodeContext) {}
+
+    async fn serialize_state(&self) -> Result<Vec<u8>, BoxedError> {
+        Ok(vec![])
+    }
+
+    async fn start(
+        &mut self,
+        _ctx: NodeContext,
+        sender: Sender<(Port, IngestionMessage)>,
+    ) -> Result<(), BoxedError> {
+        // Edges sharing an output port share one stored file, named after one of them.
+        for edge_id in self.params.edge_ids.iter() {
+            let mut reader = StreamingFeatureReader::new(*edge_id, Arc::clone(&self.state));
+            if let Some(features) = reader.next_chunk().await? {
+                self.send(&sender, features).await?;
+                while let Some(features) = reader.next_chunk().await? {
+                    self.send(&sender, features).await?;
+                }
+                return Ok(());
+            }
+            if let Ok(features) = self
+                .state
+                .get::<Vec<Value>>(edge_id.to_string().as_str())
+                .await
+            {
+                let features = features.into_iter().map(Feature::from).collect();
+                return self.send(&sender, features).await.map_err(Into::into);
+            }
+        }
+        Err(ExecutionError::Replay(format!(
+            "No stored features for edges {:?}",
+            self.params.edge_ids
+        ))
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use reearth_flow_storage::resolve::StorageResolver;
+
+    use super::*;
+    use crate::tests::utils::{
+        create_executor_options, create_factories, create_state, execute, node_summary,
+        CollectingSinkFactory,
+    };
+
+    #[test]
+    fn test_factory_rejects_invalid_parameters() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let factory = FeatureStoreReaderFactory {
+            state: create_state("ram:///feature-store/", &storage_resolver),
+        };
+        assert_eq!(factory.name(), FEATURE_STORE_READER_ACTION);
+        assert!(factory.parameter_schema().is_some());
+        for with in [
+            None,
+            Some(HashMap::from([(
+                "port".to_string(),
+                Value::String("default".to_string()),
+            )])),
+        ] {
+            let error = factory
+                .build(
+                    NodeContext::default(),
+                    EventHub::new(1),
+                    FEATURE_STORE_READER_ACTION.to_string(),
+                    with,
+                    None,
+                )
+                .err()
+                .unwrap();
+            assert!(matches!(
+                error.downcast_ref::<ExecutionError>(),
+                Some(ExecutionError::BuildFactory(_))
+            ));
+        }
+    }
+
+    static WORKFLOW: &str = r#"{
+        "id": "5ec2a5d4-4dd3-4d0b-b1b9-5c4b0b4cc1d1",
+        "name": "replay",
+        "entryGraphId": "0c5a1c36-0b4f-4b4a-9b5b-b1b9b8a9c2f1",
+        "graphs": [
+            {
+                "id": "0c5a1c36-0b4f-4b4a-9b5b-b1b9b8a9c2f1",
+                "name": "entry",
+                "nodes": [
+                    {
+                        "id": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a01",
+                        "name": "Source",
+                        "type": "action",
+                        "action": "NumberSource",
+                        "with": { "count": 3 }
+                    },
+                    {
+                        "id": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a02",
+                        "name": "Upstream",
+                        "type": "action",
+                        "action": "PassThrough"
+                    },
+                    {
+                        "id": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a03",
+                        "name": "Downstream",
+                        "type": "action",
+                        "action": "PassThrough"
+                    },
+                    {
+                        "id": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a04",
+                        "name": "Sink",
+                        "type": "action",
+                        "action": "Collecting"
+                    }
+                ],
+                "edges": [
+                    {
+                        "id": "9a0b1c2d-3e4f-4a5b-8c6d-7e8f9a0b1c01",
+                        "from": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a01",
+                        "to": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a02",
+                        "fromPort": "default",
+                        "toPort": "default"
+                    },
+                    {
+                        "id": "9a0b1c2d-3e4f-4a5b-8c6d-7e8f9a0b1c02",
+                        "from": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a02",
+                        "to": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a03",
+                        "fromPort": "default",
+                        "toPort": "default"
+                    },
+                    {
+                        "id": "9a0b1c2d-3e4f-4a5b-8c6d-7e8f9a0b1c03",
+                        "from": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a03",
+                        "to": "6d8f1a2e-3c4b-4e5f-8a9b-0c1d2e3f4a04",
+                        "fromPort": "default",
+                        "toPort": "default"
+                    }
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_replay_runs_only_downstream_nodes() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+        let state = create_state("ram:///feature-store/", &storage_resolver);
+        let sink = CollectingSinkFactory::default();
+        execute(
+            WORKFLOW,
+            create_factories(&sink),
+            create_executor_options(),
+            Arc::clone(&storage_resolver),
+            Arc::clone(&state),
+        )
+        .unwrap();
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+
+        let sink = CollectingSinkFactory::default();
+        let mut options = create_executor_options();
+        let edge_id = uuid::Uuid::parse_str("9a0b1c2d-3e4f-4a5b-8c6d-7e8f9a0b1c02").unwrap();
+        options.replay = Some(ReplayOptions {
+            edge_id,
+            state: Arc::clone(&state),
+        });
+        let stats = Arc::clone(&options.stats);
+        execute(
+            WORKFLOW,
+            create_factories(&sink),
+            options,
+            Arc::clone(&storage_resolver),
+            create_state("ram:///replay/", &storage_resolver),
+        )
+        .unwrap();
+        assert_eq!(sink.values(), vec![0, 1, 2]);
+        assert!(node_summary(&stats, "Source").is_none());
+        assert!(node_summary(&stats, "Upstream").is_none());
+        let downstream = node_summary(&stats, "Downstream").unwrap();
+        assert_eq!(downstream.features_in.get("default"), Some(&3));
+    }
+}
@@ -32,6 +32,11 @@ impl ShutdownReceiver {
     pub fn create_shutdown_future(&self) -> impl Future<Output = ()> {
         wait_shutdown(self.sender.clone())
     }
+
+    /// Whether the shutdown has been requested.
+    pub fn is_shutdown(&self) -> bool {
+        self.sender.is_closed()
+    }
 }
 
 async fn wait_shutdown(sender: Arc<Sender<()>>) {
@@ -0,0 +1,758 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{self, AtomicU64, AtomicUsize};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use reearth_flow_common::uri::Uri;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_storage::storage::Storage;
+use reearth_flow_storage::storage_sync::StorageWriter;
+use reearth_flow_types::Feature;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Memory the buffers of a job may use before spilling, when the workflow does not set it.
+pub const DEFAULT_MEMORY_LIMIT: usize = 1024 * 1024 * 1024;
+
+/// Memory a buffer holds at least before spilling, unless the spill manager sets another minimum.
+pub const DEFAULT_MIN_SPILL_SIZE: usize = 4 * 1024 * 1024;
+
+/// Size of the ranges read from a spill file at once.
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Maximum number of spill files merged at once when iterating over a sorted buffer. More files
+/// are first merged into intermediate files, so that the number of files read at the same time,
+/// and the memory of their read buffers, stays bounded.
+const MAX_MERGE_FAN_IN: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum SpillError {
+    #[error("Failed to initialize spill storage: {0}")]
+    Init(String),
+    #[error("Failed to write spill file: {0}")]
+    Write(String),
+    #[error("Failed to read spill file: {0}")]
+    Read(String),
+}
+
+/// Order of the features of a sorted [`FeatureBuffer`].
+pub type FeatureOrder = Arc<dyn Fn(&Feature, &Feature) -> Ordering + Send + Sync>;
+
+/// Shares the memory budget of a job between its buffers, and stores what they spill.
+#[derive(Debug)]
+pub struct SpillManager {
+    memory_limit: usize,
+    used: AtomicUsize,
+    /// Storage and directory of the spill files. Buffers never spill without it.
+    storage: Option<(Arc<Storage>, PathBuf)>,
+    /// Prefix of the names of the spill files, so that a resumed job does not overwrite the
+    /// files of the run it resumes.
+    prefix: uuid::Uuid,
+    next_file: AtomicU64,
+    /// Whether the files of the buffers outlive them, for checkpoints to refer to.
+    keep_files: bool,
+    /// Memory a buffer holds at least before it spills, so that a buffer does not write a file
+    /// for every few features while other buffers hold the budget.
+    min_spill_size: usize,
+    /// Maximum number of spill files merged at once, [`MAX_MERGE_FAN_IN`] but in tests.
+    max_merge_fan_in: usize,
+}
+
+impl SpillManager {
+    pub fn new(
+        memory_limit: usize,
+        root: &Uri,
+        storage_resolver: &StorageResolver,
+    ) -> Result<Self, SpillError> {
+        let storage = storage_resolver
+            .resolve(root)
+            .map_err(|e| SpillError::Init(format!("{:?}", e)))?;
+        Ok(Self {
+            memory_limit,
+            used: AtomicUsize::new(0),
+            storage: Some((storage, root.path())),
+            prefix: uuid::Uuid::new_v4(),
+            next_file: AtomicU64::new(0),
+            keep_files: false,
+            min_spill_size: DEFAULT_MIN_SPILL_SIZE,
+            max_merge_fan_in: MAX_MERGE_FAN_IN,
+        })
+    }
+
+    /// Keeps every feature in memory.
+    pub fn unlimited() -> Self {
+        Self {
+            memory_limit: usize::MAX,
+            used: AtomicUsize::new(0),
+            storage: None,
+            prefix: uuid::Uuid::new_v4(),
+            next_file: AtomicU64::new(0),
+            keep_files: false,
+            min_spill_size: DEFAULT_MIN_SPILL_SIZE,
+            max_merge_fan_in: MAX_MERGE_FAN_IN,
+        }
+    }
+
+    /// Keeps the spill files of the buffers when they are dropped, so that a job stopping before
+    /// it finishes can resume from the files its checkpoint refers to. They are deleted by
+    /// [`SpillManager::remove_files`] once the job has completed.
+    pub fn keep_files(mut self) -> Self {
+        self.keep_files = true;
+        self
+    }
+
+    /// Sets the memory a buffer holds at least before it spills, [`DEFAULT_MIN_SPILL_SIZE`] by
+    /// default.
+    pub fn min_spill_size(mut self, min_spill_size: usize) -> Self {
+        self.min_spill_size = min_spill_size;
+        self
+    }
+
+    /// Deletes every file in the spill directory, including those of the runs a job resumed.
+    pub fn remove_files(&self) -> Result<(), SpillError> {
+        let Some((storage, root)) = &self.storage else {
+            return Ok(());
+        };
+        let files = storage
+            .list_sync(Some(root.as_path()), false)
+            .map_err(|e| SpillError::Write(format!("{:?}", e)))?;
+        for file in files {
+            let path = file.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("jsonl") {
+                continue;
+            }
+            storage
+                .delete_sync(path.as_path())
+                .map_err(|e| SpillError::Write(format!("{:?}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Memory used by the features held in memory.
+    pub fn used(&self) -> usize {
+        self.used.load(atomic::Ordering::SeqCst)
+    }
+
+    fn try_reserve(&self, size: usize) -> bool {
+        if self.storage.is_none() {
+            self.reserve(size);
+            return true;
+        }
+        self.used
+            .fetch_update(atomic::Ordering::SeqCst, atomic::Ordering::SeqCst, |used| {
+                used.checked_add(size)
+                    .filter(|used| *used <= self.memory_limit)
+            })
+            .is_ok()
+    }
+
+    fn reserve(&self, size: usize) {
+        self.used.fetch_add(size, atomic::Ordering::SeqCst);
+    }
+
+    fn release(&self, size: usize) {
+        self.used.fetch_sub(size, atomic::Ordering::SeqCst);
+    }
+
+    fn next_path(&self, root: &std::path::Path) -> PathBuf {
+        let id = self.next_file.fetch_add(1, atomic::Ordering::SeqCst);
+        root.join(format!("{}-{}.jsonl", self.prefix, id))
+    }
+
+    fn write(&self, content: Vec<u8>) -> Result<SpillFile, SpillError> {
+        let Some((storage, root)) = &self.storage else {
+            return Err(SpillError::Write("No spill storage".to_string()));
+        };
+        let path = self.next_path(root);
+        let len = content.len();
+        storage
+            .put_sync(path.as_path(), Bytes::from(content))
+            .map_err(|e| SpillError::Write(format!("{:?}", e)))?;
+        Ok(SpillFile {
+            storage: Arc::clone(storage),
+            path,
+            len,
+            keep: self.keep_files,
+        })
+    }
+
+    /// Creates an intermediate file of a merge, written a feature at a time. It is always deleted
+    /// once dropped, as no checkpoint refers to it.
+    fn create_intermediate(&self) -> Result<SpillFileWriter, SpillError> {
+        let Some((storage, root)) = &self.storage else {
+            return Err(SpillError::Write("No spill storage".to_string()));
+        };
+        let path = self.next_path(root);
+        let writer = storage
+            .writer_sync(path.as_path())
+            .map_err(|e| SpillError::Write(format!("{:?}", e)))?;
+        Ok(SpillFileWriter {
+            writer: BufWriter::new(writer),
+            storage: Arc::clone(storage),
+            path,
+            len: 0,
+        })
+    }
+
+    /// Takes over a spill file written before, e.g. by the run a job resumes.
+    fn adopt(&self, path: PathBuf, len: usize) -> Result<SpillFile, SpillError> {
+        let Some((storage, _)) = &self.storage else {
+            return Err(SpillError::Read("No spill storage".to_string()));
+        };
+        Ok(SpillFile {
+            storage: Arc::clone(storage),
+            path,
+            len,
+            keep: self.keep_files,
+        })
+    }
+}
+
+/// Features spilled at once, one JSON document per line. Deleted once no buffer refers to it,
+/// unless the manager keeps the files.
+#[derive(Debug)]
+struct SpillFile {
+    storage: Arc<Storage>,
+    path: PathBuf,
+    len: usize,
+    keep: bool,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = self.storage.delete_sync(self.path.as_path());
+        }
+    }
+}
+
+/// Writes an intermediate spill file.
+struct SpillFileWriter {
+    writer: BufWriter<StorageWriter>,
+    storage: Arc<Storage>,
+    path: PathBuf,
+    len: usize,
+}
+
+impl SpillFileWriter {
+    fn write(&mut self, feature: &Feature) -> Result<(), SpillError> {
+        let mut line = serde_json::to_vec(feature).map_err(|e| SpillError::Write(e.to_string()))?;
+        line.push(b'\n');
+        self.writer
+            .write_all(&line)
+            .map_err(|e| SpillError::Write(e.to_string()))?;
+        self.len += line.len();
+        Ok(())
+    }
+
+    fn finish(self) -> Result<SpillFile, SpillError> {
+        let writer = self
+            .writer
+            .into_inner()
+            .map_err(|e| SpillError::Write(e.to_string()))?;
+        writer
+            .close()
+            .map_err(|e| SpillError::Write(format!("{:?}", e)))?;
+        Ok(SpillFile {
+            storage: self.storage,
+            path: self.path,
+            len: self.len,
+            keep: false,
+        })
+    }
+}
+
+/// Reads the features of a spill file one range at a time.
+struct SpillFileReader {
+    file: Arc<SpillFile>,
+    offset: usize,
+    buffer: Vec<u8>,
+}
+
+impl SpillFileReader {
+    fn new(file: Arc<SpillFile>) -> Self {
+        Self {
+            file,
+            offset: 0,
+            buffer: vec![],
+        }
+    }
+}
+
+impl Iterator for SpillFileReader {
+    type Item = Result<Feature, SpillError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+                let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+                return Some(
+                    serde_json::from_slice(&line).map_err(|e| SpillError::Read(e.to_string())),
+                );
+            }
+            if self.offset >= self.file.len {
+                return None;
+            }
+            let end = (self.offset + READ_CHUNK_SIZE).min(self.file.len);
+            match self
+                .file
+                .storage
+                .get_range_sync(self.file.path.as_path(), self.offset..end)
+            {
+                Ok(bytes) => self.buffer.extend_from_slice(&bytes),
+                Err(e) => {
+                    self.offset = self.file.len;
+                    return Some(Err(SpillError::Read(format!("{:?}", e))));
+                }
+            }
+            self.offset = end;
+        }
+    }
+}
+
+/// What a checkpoint keeps of a [`FeatureBuffer`]: the spill files it refers to, and the
+/// features still in memory.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureBufferState {
+    files: Vec<SpillFileState>,
+    memory: Vec<Feature>,
+    len: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SpillFileState {
+    path: PathBuf,
+    len: usize,
+}
+
+type Features<'a> = Box<dyn Iterator<Item = Result<Feature, SpillError>> + Send + 'a>;
+
+/// Holds the features of a blocking processor until it finishes. Once the memory budget of the
+/// job is used up, the features in memory are written to a spill file and read back when
+/// iterating.
+///
+/// A buffer created with [`FeatureBuffer::sorted`] sorts the features of each spill file, and
+/// merges the files when iterating, so that it never holds all features in memory to sort them.
+pub struct FeatureBuffer {
+    manager: Arc<SpillManager>,
+    order: Option<FeatureOrder>,
+    memory: Vec<Feature>,
+    /// Memory reserved for the features in memory.
+    reserved: usize,
+    files: Vec<Arc<SpillFile>>,
+    len: usize,
+}
+
+impl FeatureBuffer {
+    /// A buffer iterating in insertion order.
+    pub fn new(manager: Arc<SpillManager>) -> Self {
+        Self {
+            manager,
+            order: None,
+            memory: vec![],
+            reserved: 0,
+            files: vec![],
+            len: 0,
+        }
+    }
+
+    /// A buffer iterating in the given order. Features in the same position keep their
+    /// insertion order.
+    pub fn sorted(manager: Arc<SpillManager>, order: FeatureOrder) -> Self {
+        Self {
+            manager,
+            order: Some(order),
+            memory: vec![],
+            reserved: 0,
+            files: vec![],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, feature: Feature) -> Result<(), SpillError> {
+        let size = serde_json::to_vec(&feature)
+            .map_err(|e| SpillError::Write(e.to_string()))?
+            .len();
+        if !self.manager.try_reserve(size) {
+            if self.reserved >= self.manager.min_spill_size {
+                self.spill()?;
+            }
+            // Other buffers may hold the budget. The feature is kept anyway and spilled with the
+            // next ones.
+            if !self.manager.try_reserve(size) {
+                self.manager.reserve(size);
+            }
+        }
+        self.reserved += size;
+        self.memory.push(feature);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes every feature, deleting the spill files no clone refers to.
+    pub fn clear(&mut self) {
+        self.memory.clear();
+        self.manager.release(self.reserved);
+        self.reserved = 0;
+        self.files.clear();
+        self.len = 0;
+    }
+
+    /// Writes the features in memory to a spill file.
+    fn spill(&mut self) -> Result<(), SpillError> {
+        if self.memory.is_empty() {
+            return Ok(());
+        }
+        if let Some(order) = &self.order {
+            self.memory.sort_by(|a, b| order(a, b));
+        }
+        let mut content = Vec::with_capacity(self.reserved + self.memory.len());
+        for feature in self.memory.iter() {
+            serde_json::to_writer(&mut content, feature)
+                .map_err(|e| SpillError::Write(e.to_string()))?;
+            content.push(b'\n');
+        }
+        self.files.push(Arc::new(self.manager.write(content)?));
+        self.memory.clear();
+        self.manager.release(self.reserved);
+        self.reserved = 0;
+        Ok(())
+    }
+
+    /// The state to checkpoint. Spill files are referred to rather than copied, and are kept by a
+    /// job that stops before finishing, so that the job can resume from them.
+    pub fn state(&self) -> FeatureBufferState {
+        FeatureBufferState {
+            files: self
+                .files
+                .iter()
+                .map(|file| SpillFileState {
+                    path: file.path.clone(),
+                    len: file.len,
+                })
+                .collect(),
+            memory: self.memory.clone(),
+            len: self.len,
+        }
+    }
+
+    /// Replaces the features with those of a checkpointed state.
+    pub fn restore(&mut self, state: FeatureBufferState) -> Result<(), SpillError> {
+        self.clear();
+        for file in state.files {
+            self.files
+                .push(Arc::new(self.manager.adopt(file.path, file.len)?));
+        }
+        self.len = state.len.saturating_sub(state.memory.len());
+        for feature in state.memory {
+            self.push(feature)?;
+        }
+        Ok(())
+    }
+
+    /// Iterates over the features, reading the spill files back.
+    pub fn iter(&self) -> impl Iterator<Item = Result<Feature, SpillError>> + Send + '_ {
+        let readers = |files: Vec<Arc<SpillFile>>| {
+            files
+                .into_iter()
+                .map(|file| Box::new(SpillFileReader::new(file)) as Features)
+                .collect::<Vec<_>>()
+        };
+        match &self.order {
+            None => {
+                let memory = self.memory.iter().cloned().map(Ok);
+                let files = readers(self.files.clone());
+                Box::new(files.into_iter().flatten().chain(memory)) as Features
+            }
+            Some(order) => {
+                let mut files = match self.merge_files(order) {
+                    Ok(files) => readers(files),
+                    Err(e) => return Box::new(std::iter::once(Err(e))) as Features,
+                };
+                let mut memory = self.memory.iter().collect::<Vec<_>>();
+                memory.sort_by(|a, b| order(a, b));
+                files.push(Box::new(memory.into_iter().cloned().map(Ok)));
+                Box::new(Merge::new(files, Arc::clone(order))) as Features
+            }
+        }
+    }
+
+    /// Merges the sorted spill files into intermediate files until they can be merged at once.
+    /// Adjacent files are merged together, which keeps the sort stable. The intermediate files
+    /// are not part of the buffer and are deleted once the iteration is dropped.
+    fn merge_files(&self, order: &FeatureOrder) -> Result<Vec<Arc<SpillFile>>, SpillError> {
+        let fan_in = self.manager.max_merge_fan_in.max(2);
+        let mut files = self.files.clone();
+        while files.len() > fan_in {
+            let mut merged = Vec::with_capacity(files.len().div_ceil(fan_in));
+            for group in files.chunks(fan_in) {
+                let runs = group
+                    .iter()
+                    .map(|file| Box::new(SpillFileReader::new(Arc::clone(file))) as Features)
+                    .collect();
+                let mut writer = self.manager.create_intermediate()?;
+                for feature in Merge::new(runs, Arc::clone(order)) {
+                    writer.write(&feature?)?;
+                }
+                merged.push(Arc::new(writer.finish()?));
+            }
+            files = merged;
+        }
+        Ok(files)
+    }
+}
+
+impl Debug for FeatureBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeatureBuffer")
+            .field("sorted", &self.order.is_some())
+            .field("in_memory", &self.memory.len())
+            .field("spill_files", &self.files.len())
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Clone for FeatureBuffer {
+    /// Clones share the spill files, and reserve memory for their own copy of the features in
+    /// memory.
+    fn clone(&self) -> Self {
+        self.manager.reserve(self.reserved);
+        Self {
+            manager: Arc::clone(&self.manager),
+            order: self.order.clone(),
+            memory: self.memory.clone(),
+            reserved: self.reserved,
+            files: self.files.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl Drop for FeatureBuffer {
+    fn drop(&mut self) {
+        self.manager.release(self.reserved);
+    }
+}
+
+/// Merges sorted runs of features. Ties go to the earlier run, which keeps the sort stable.
+struct Merge<'a> {
+    runs: Vec<Features<'a>>,
+    heads: Vec<Option<Feature>>,
+    order: FeatureOrder,
+    started: bool,
+}
+
+impl<'a> Merge<'a> {
+    fn new(runs: Vec<Features<'a>>, order: FeatureOrder) -> Self {
+        let heads = runs.iter().map(|_| None).collect();
+        Self {
+            runs,
+            heads,
+            order,
+            started: false,
+        }
+    }
+
+    fn advance(&mut self, run: usize) -> Result<(), SpillError> {
+        self.heads[run] = self.runs[run].next().transpose()?;
+        Ok(())
+    }
+}
+
+impl Iterator for Merge<'_> {
+    type Item = Result<Feature, SpillError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            for run in 0..self.runs.len() {
+                if let Err(e) = self.advance(run) {
+                    return Some(Err(e));
+                }
+            }
+        }
+        let mut next: Option<usize> = None;
+        for (run, head) in self.heads.iter().enumerate() {
+            let Some(head) = head else {
+                continue;
+            };
+            let first = match next.and_then(|current| self.heads[current].as_ref()) {
+                Some(current) => (self.order)(head, current) == Ordering::Less,
+                None => true,
+            };
+            if first {
+                next = Some(run);
+            }
+        }
+        let run = next?;
+        let feature = self.heads[run].take();
+        if let Err(e) = self.advance(run) {
+            return Some(Err(e));
+        }
+        feature.map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reearth_flow_types::AttributeValue;
+
+    use super::*;
+
+    fn feature(value: i64) -> Feature {
+        let mut feature = Feature::new();
+        feature.insert("value", AttributeValue::Number(value.into()));
+        feature
+    }
+
+    fn value(feature: &Feature) -> i64 {
+        match feature.get(&"value") {
+            Some(AttributeValue::Number(value)) => value.as_i64().unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn manager(memory_limit: usize) -> Arc<SpillManager> {
+        Arc::new(
+            SpillManager::new(
+                memory_limit,
+                &Uri::for_test("ram:///spill/"),
+                &StorageResolver::new(),
+            )
+            .unwrap()
+            .min_spill_size(0),
+        )
+    }
+
+    fn spill_files(manager: &SpillManager) -> usize {
+        let (storage, root) = manager.storage.as_ref().unwrap();
+        storage
+            .list_sync(Some(root.as_path()), false)
+            .unwrap()
+            .iter()
+            .filter(|file| file.path().extension().and_then(|e| e.to_str()) == Some("jsonl"))
+            .count()
+    }
+
+    #[test]
+    fn test_spill_keeps_insertion_order() {
+        let manager = manager(200);
+        let mut buffer = FeatureBuffer::new(Arc::clone(&manager));
+        for i in 0..20 {
+            buffer.push(feature(i)).unwrap();
+        }
+        assert!(!buffer.files.is_empty());
+        assert!(manager.used() <= 200 + 100);
+        let values = buffer
+            .iter()
+            .map(|feature| value(&feature.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..20).collect::<Vec<_>>());
+        drop(buffer);
+        assert_eq!(manager.used(), 0);
+    }
+
+    #[test]
+    fn test_external_sort() {
+        let order: FeatureOrder = Arc::new(|a, b| value(a).cmp(&value(b)));
+        let mut buffer = FeatureBuffer::sorted(manager(300), order);
+        for i in 0..50 {
+            buffer.push(feature((i * 37) % 50)).unwrap();
+        }
+        assert!(buffer.files.len() > 1);
+        let values = buffer
+            .iter()
+            .map(|feature| value(&feature.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_external_sort_merges_files_in_passes() {
+        let mut manager = SpillManager::new(
+            100,
+            &Uri::for_test("ram:///spill-passes/"),
+            &StorageResolver::new(),
+        )
+        .unwrap()
+        .min_spill_size(0);
+        manager.max_merge_fan_in = 3;
+        let manager = Arc::new(manager);
+        let order: FeatureOrder = Arc::new(|a, b| value(a).cmp(&value(b)));
+        let mut buffer = FeatureBuffer::sorted(Arc::clone(&manager), order);
+        for i in 0..40 {
+            buffer.push(feature((i * 7) % 40)).unwrap();
+        }
+        let files = buffer.files.len();
+        assert!(files > 9, "{} spill files", files);
+        let mut features = buffer.iter();
+        // The first feature is only read once the intermediate files are written.
+        let first = value(&features.next().unwrap().unwrap());
+        assert!(spill_files(&manager) > files);
+        let values = std::iter::once(first)
+            .chain(features.map(|feature| value(&feature.unwrap())))
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..40).collect::<Vec<_>>());
+        // The intermediate files are deleted with the iteration, and are not part of the buffer.
+        assert_eq!(spill_files(&manager), files);
+        assert_eq!(buffer.files.len(), files);
+        assert_eq!(buffer.state().files.len(), files);
+    }
+
+    #[test]
+    fn test_buffers_contending_for_budget_spill_at_least_min_size() {
+        let size = serde_json::to_vec(&feature(10)).unwrap().len();
+        let manager = Arc::new(
+            SpillManager::new(
+                10 * size,
+                &Uri::for_test("ram:///spill-contention/"),
+                &StorageResolver::new(),
+            )
+            .unwrap()
+            .min_spill_size(3 * size),
+        );
+        let mut first = FeatureBuffer::new(Arc::clone(&manager));
+        for i in 10..19 {
+            first.push(feature(i)).unwrap();
+        }
+        let mut second = FeatureBuffer::new(Arc::clone(&manager));
+        for i in 20..32 {
+            second.push(feature(i)).unwrap();
+        }
+        // The first buffer holds most of the budget. The second one spills a few features at a
+        // time rather than every feature it receives.
+        assert!(first.files.is_empty());
+        assert!(!second.files.is_empty());
+        assert!(
+            second.files.len() <= 4,
+            "{} spill files",
+            second.files.len()
+        );
+        let values = |buffer: &FeatureBuffer| {
+            buffer
+                .iter()
+                .map(|feature| value(&feature.unwrap()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&first), (10..19).collect::<Vec<_>>());
+        assert_eq!(values(&second), (20..32).collect::<Vec<_>>());
+        drop(first);
+        drop(second);
+        assert_eq!(manager.used(), 0);
+        assert_eq!(spill_files(&manager), 0);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use opentelemetry::{
+    metrics::{Counter, Histogram},
+    KeyValue,
+};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::node::{NodeHandle, Port};
+
+/// Instruments of a job. They record into the meter provider installed when they were created.
+struct Instruments {
+    features_in: Counter<u64>,
+    features_out: Counter<u64>,
+    errors: Counter<u64>,
+    processing_time: Histogram<f64>,
+}
+
+impl Instruments {
+    fn new() -> Self {
+        let meter = reearth_flow_telemetry::meter();
+        Self {
+            features_in: meter
+                .u64_counter("flow.node.features.in")
+                .with_description("Number of features received by a node")
+                .init(),
+            features_out: meter
+                .u64_counter("flow.node.features.out")
+                .with_description("Number of features sent by a node")
+                .init(),
+            errors: meter
+                .u64_counter("flow.node.errors")
+                .with_description("Number of features a node failed to process")
+                .init(),
+            processing_time: meter
+                .f64_histogram("flow.node.processing.duration")
+                .with_description("Time spent processing a feature, in seconds")
+                .init(),
+        }
+    }
+}
+
+impl Debug for Instruments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Instruments").finish_non_exhaustive()
+    }
+}
+
+/// Number of features of a port, and the attributes of its metrics.
+#[derive(Debug)]
+struct PortCounter {
+    count: AtomicU64,
+    attributes: Vec<KeyValue>,
+}
+
+impl PortCounter {
+    fn new(port: &Port, attributes: &[KeyValue]) -> Self {
+        let mut attributes = attributes.to_vec();
+        attributes.push(KeyValue::new("port", port.to_string()));
+        Self {
+            count: AtomicU64::new(0),
+            attributes,
+        }
+    }
+}
+
+/// Counters of the ports of a node. The ports of its edges, known when the node is registered,
+/// are counted without locking. The others, e.g. a port without any edge, are added on their
+/// first feature.
+#[derive(Debug)]
+struct PortCounters {
+    registered: HashMap<Port, PortCounter>,
+    others: Mutex<HashMap<Port, PortCounter>>,
+}
+
+impl PortCounters {
+    fn new(ports: &[Port], attributes: &[KeyValue]) -> Self {
+        Self {
+            registered: ports
+                .iter()
+                .map(|port| (port.clone(), PortCounter::new(port, attributes)))
+                .collect(),
+            others: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of features of each port that had any.
+    fn counts(&self) -> HashMap<String, u64> {
+        let count = |(port, counter): (&Port, &PortCounter)| {
+            let count = counter.count.load(Ordering::Relaxed);
+            (count > 0).then(|| (port.to_string(), count))
+        };
+        let mut counts = self
+            .registered
+            .iter()
+            .filter_map(count)
+            .collect::<HashMap<_, _>>();
+        counts.extend(self.others.lock().iter().filter_map(count));
+        counts
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeStatsKind {
+    Source,
+    Processor,
+    Sink,
+}
+
+/// Counters of a running node, shared by all of its threads.
+#[derive(Debug)]
+pub struct NodeStats {
+    id: uuid::Uuid,
+    name: String,
+    action: String,
+    kind: NodeStatsKind,
+    features_in: PortCounters,
+    features_out: PortCounters,
+    errors: AtomicU64,
+    elapsed_nanos: AtomicU64,
+    instruments: Arc<Instruments>,
+    attributes: Vec<KeyValue>,
+}
+
+impl NodeStats {
+    pub fn record_in(&self, port: &Port) {
+        self.record_port(&self.features_in, &self.instruments.features_in, port);
+    }
+
+    pub fn record_out(&self, port: &Port) {
+        self.record_port(&self.features_out, &self.instruments.features_out, port);
+    }
+
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.instruments.errors.add(1, &self.attributes);
+    }
+
+    /// Adds time spent by the node. Processors and sinks record the time spent on each feature,
+    /// sources the time until they are exhausted.
+    pub fn record_elapsed(&self, elapsed: Duration) {
+        self.elapsed_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        self.instruments
+            .processing_time
+            .record(elapsed.as_secs_f64(), &self.attributes);
+    }
+
+    pub fn summary(&self) -> NodeSummary {
+        NodeSummary {
+            id: self.id,
+            name: self.name.clone(),
+            action: self.action.clone(),
+            kind: self.kind,
+            features_in: self.features_in.counts(),
+            features_out: self.features_out.counts(),
+            errors: self.errors.load(Ordering::Relaxed),
+            elapsed_ms: self.elapsed_nanos.load(Ordering::Relaxed) / 1_000_000,
+        }
+    }
+
+    /// Counts a feature of `port`. The attributes of a port are built once, when it is added.
+    fn record_port(&self, counters: &PortCounters, counter: &Counter<u64>, port: &Port) {
+        let record = |port_counter: &PortCounter| {
+            port_counter.count.fetch_add(1, Ordering::Relaxed);
+            counter.add(1, &port_counter.attributes);
+        };
+        if let Some(port_counter) = counters.registered.get(port) {
+            record(port_counter);
+            return;
+        }
+        let mut others = counters.others.lock();
+        record(
+            others
+                .entry(port.clone())
+                .or_insert_with(|| PortCounter::new(port, &self.attributes)),
+        );
+    }
+}
+
+/// Statistics of a node at the time of the summary.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeSummary {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub action: String,
+    pub kind: NodeStatsKind,
+    /// Number of features received on each input port.
+    pub features_in: HashMap<String, u64>,
+    /// Number of features sent to each output port.
+    pub features_out: HashMap<String, u64>,
+    pub errors: u64,
+    pub elapsed_ms: u64,
+}
+
+/// Statistics of all the nodes of a job.
+#[derive(Debug)]
+pub struct JobStats {
+    nodes: Mutex<Vec<Arc<NodeStats>>>,
+    instruments: Arc<Instruments>,
+}
+
+impl Default for JobStats {
+    fn default() -> Self {
+        Self {
+            nodes: Mutex::new(vec![]),
+            instruments: Arc::new(Instruments::new()),
+        }
+    }
+}
+
+impl JobStats {
+    /// Metrics are recorded into the meter provider installed at this point, if any.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a node receiving features on `input_ports` and sending them to `output_ports`,
+    /// the ports of its edges.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn register(
+        &self,
+        workflow_id: uuid::Uuid,
+        node: &NodeHandle,
+        name: &str,
+        action: &str,
+        kind: NodeStatsKind,
+        input_ports: &[Port],
+        output_ports: &[Port],
+    ) -> Arc<NodeStats> {
+        let attributes = vec![
+            KeyValue::new("workflow.id", workflow_id.to_string()),
+            KeyValue::new("node.id", node.id.to_string()),
+            KeyValue::new("node.name", name.to_string()),
+            KeyValue::new("node.action", action.to_string()),
+        ];
+        let stats = Arc::new(NodeStats {
+            id: node.id,
+            name: name.to_string(),
+            action: action.to_string(),
+            kind,
+            features_in: PortCounters::new(input_ports, &attributes),
+            features_out: PortCounters::new(output_ports, &attributes),
+            errors: AtomicU64::new(0),
+            elapsed_nanos: AtomicU64::new(0),
+            instruments: Arc::clone(&self.instruments),
+            attributes,
+        });
+        self.nodes.lock().push(Arc::clone(&stats));
+        stats
+    }
+
+    pub fn summary(&self) -> Vec<NodeSummary> {
+        self.nodes
+            .lock()
+            .iter()
+            .map(|node| node.summary())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_registered_and_other_ports() {
+        let stats = JobStats::new();
+        let node = NodeHandle::new(uuid::Uuid::new_v4());
+        let default = Port::new("default");
+        let rejected = Port::new("rejected");
+        let node_stats = stats.register(
+            uuid::Uuid::new_v4(),
+            &node,
+            "node",
+            "Action",
+            NodeStatsKind::Processor,
+            &[default.clone()],
+            &[default.clone(), Port::new("unused")],
+        );
+        node_stats.record_in(&default);
+        node_stats.record_in(&default);
+        node_stats.record_out(&default);
+        node_stats.record_out(&rejected);
+        let summary = node_stats.summary();
+        assert_eq!(
+            summary.features_in,
+            HashMap::from([("default".to_string(), 2)])
+        );
+        // Ports without features are left out.
+        assert_eq!(
+            summary.features_out,
+            HashMap::from([("default".to_string(), 1), ("rejected".to_string(), 1)])
+        );
+    }
+}
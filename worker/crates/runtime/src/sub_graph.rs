@@ -0,0 +1,397 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Instant;
+
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use petgraph::graph::DiGraph;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use reearth_flow_types::workflow::ErrorPolicy;
+use tokio::runtime::Runtime;
+
+use crate::channels::Collector;
+use crate::dag_schemas::{SchemaEdgeType, SchemaNodeType};
+use crate::error_manager::{ErrorManager, NodeErrorHandler};
+use crate::errors::BoxedError;
+use crate::event::EventHub;
+use crate::executor_operation::{ExecutorContext, InnerNodeContext, NodeContext};
+use crate::feature_store::{create_feature_writer, FeatureWriter};
+use crate::node::{EdgeId, NodeHandle, NodeKind, Port, Processor, ROUTING_PARAM_KEY};
+use crate::stats::{NodeStats, NodeStatsKind};
+
+/// The processors of a sub graph run inside a single node, built once and cloned for each
+/// instance.
+///
+/// The sub graph may only contain processors. Features enter it at every node without incoming
+/// edges, or only at those whose `routingPort` is the port they arrived on when the node has one,
+/// and leave it from the nodes without outgoing edges, like an inline sub graph.
+///
+/// Until the template is attached to a job, the errors of the processors are returned as is.
+#[derive(Debug)]
+pub(crate) struct SubGraphTemplate {
+    processors: Vec<Box<dyn Processor>>,
+    /// Context the processors were built with, used to initialize the instances.
+    ctx: NodeContext,
+    nodes: Vec<InnerNode>,
+    /// Nodes the features enter the sub graph at, with the port they must arrive on if any.
+    entries: Vec<(usize, Option<Port>)>,
+    /// Order in which the processors of an instance are finished.
+    order: Vec<usize>,
+    /// Targets of the features sent by each node, by output port.
+    edges: Vec<HashMap<Port, Vec<(usize, Port)>>>,
+    /// Whether the features sent by a node leave the sub graph.
+    leaves: Vec<bool>,
+    attached: OnceCell<Attached>,
+}
+
+/// A node of the sub graph as declared in the workflow.
+#[derive(Debug)]
+struct InnerNode {
+    handle: NodeHandle,
+    name: String,
+    action: String,
+    error_policy: ErrorPolicy,
+    /// Ports of the edges reaching the node.
+    input_ports: Vec<Port>,
+    /// Edge whose id the features sent to each output port are stored under.
+    stored_edges: HashMap<Port, EdgeId>,
+}
+
+/// The nodes of the sub graph as part of a job.
+#[derive(Debug)]
+struct Attached {
+    nodes: Vec<AttachedNode>,
+    error_manager: Arc<ErrorManager>,
+    runtime: Arc<Runtime>,
+}
+
+#[derive(Debug)]
+struct AttachedNode {
+    error_handler: NodeErrorHandler,
+    stats: Arc<NodeStats>,
+    /// Shared by all instances, like the feature writers of an edge are shared by the threads of
+    /// a node.
+    feature_writers: Mutex<HashMap<Port, Box<dyn FeatureWriter>>>,
+}
+
+impl SubGraphTemplate {
+    pub(crate) fn build(
+        graph: &DiGraph<SchemaNodeType, SchemaEdgeType>,
+        ctx: NodeContext,
+        event_hub: EventHub,
+    ) -> Result<Self, BoxedError> {
+        let mut processors = vec![];
+        let mut nodes = vec![];
+        let mut entries = vec![];
+        let mut edges = vec![];
+        let mut leaves = vec![];
+        for node_index in graph.node_indices() {
+            let node = &graph[node_index];
+            let Some(NodeKind::Processor(factory)) = &node.kind else {
+                return Err(format!(
+                    "Only processors can run inside a single node: {}",
+                    node.name
+                )
+                .into());
+            };
+            processors.push(factory.build(
+                ctx.clone(),
+                event_hub.clone(),
+                node.node.action().to_string(),
+                node.with.clone(),
+            )?);
+            if graph
+                .edges_directed(node_index, Direction::Incoming)
+                .next()
+                .is_none()
+            {
+                let routing_port = node
+                    .with
+                    .as_ref()
+                    .and_then(|with| with.get(ROUTING_PARAM_KEY))
+                    .and_then(|value| value.as_str())
+                    .map(Port::new);
+                entries.push((node_index.index(), routing_port));
+            }
+            let mut targets = HashMap::<Port, Vec<(usize, Port)>>::new();
+            let mut stored_edges = HashMap::new();
+            for edge in graph.edges_directed(node_index, Direction::Outgoing) {
+                targets
+                    .entry(edge.weight().from.clone())
+                    .or_default()
+                    .push((edge.target().index(), edge.weight().to.clone()));
+                stored_edges
+                    .entry(edge.weight().from.clone())
+                    .or_insert(edge.weight().id);
+            }
+            nodes.push(InnerNode {
+                handle: node.handle.clone(),
+                name: node.name.clone(),
+                action: node.node.action().to_string(),
+                error_policy: node.node.on_error(),
+                input_ports: graph
+                    .edges_directed(node_index, Direction::Incoming)
+                    .map(|edge| edge.weight().to.clone())
+                    .collect(),
+                stored_edges,
+            });
+            leaves.push(targets.is_empty());
+            edges.push(targets);
+        }
+        if processors.is_empty() {
+            return Err("The sub graph has no nodes".into());
+        }
+        let order = petgraph::algo::toposort(graph, None)
+            .map_err(|_| "The sub graph contains a cycle")?
+            .into_iter()
+            .map(|node_index| node_index.index())
+            .collect();
+        Ok(Self {
+            processors,
+            ctx,
+            nodes,
+            entries,
+            order,
+            edges,
+            leaves,
+            attached: OnceCell::new(),
+        })
+    }
+
+    /// Registers the nodes in the job. Only the first call has an effect.
+    pub(crate) fn attach(&self, ctx: &InnerNodeContext) {
+        self.attached.get_or_init(|| Attached {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|node| {
+                    let output_ports = node.stored_edges.keys().cloned().collect::<Vec<_>>();
+                    let stats = ctx.stats.register(
+                        ctx.graph_id,
+                        &node.handle,
+                        &node.name,
+                        &node.action,
+                        NodeStatsKind::Processor,
+                        &node.input_ports,
+                        &output_ports,
+                    );
+                    let error_handler = NodeErrorHandler::new(
+                        node.handle.clone(),
+                        node.name.clone(),
+                        node.error_policy.clone(),
+                        Arc::clone(&ctx.error_manager),
+                        Arc::clone(&stats),
+                        ctx.event_sender.clone(),
+                    );
+                    let feature_writers = node
+                        .stored_edges
+                        .iter()
+                        .map(|(port, edge_id)| {
+                            (
+                                port.clone(),
+                                create_feature_writer(
+                                    *edge_id,
+                                    Arc::clone(&ctx.state),
+                                    ctx.feature_store_format,
+                                ),
+                            )
+                        })
+                        .collect();
+                    AttachedNode {
+                        error_handler,
+                        stats,
+                        feature_writers: Mutex::new(feature_writers),
+                    }
+                })
+                .collect(),
+            error_manager: Arc::clone(&ctx.error_manager),
+            runtime: Arc::clone(&ctx.runtime),
+        });
+    }
+
+    /// Flushes the features stored by the nodes. Called once all instances have finished.
+    pub(crate) fn flush(&self) {
+        let Some(attached) = self.attached.get() else {
+            return;
+        };
+        let writers = attached
+            .nodes
+            .iter()
+            .flat_map(|node| {
+                node.feature_writers
+                    .lock()
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        attached.runtime.block_on(async {
+            for writer in writers {
+                if let Err(e) = writer.flush().await {
+                    attached.error_manager.report(e.into());
+                }
+            }
+        });
+    }
+
+    /// Records the features sent by a node, as its channel manager would.
+    fn record_sent(&self, node: usize, sent: &[ExecutorContext]) {
+        let Some(attached) = self.attached.get() else {
+            return;
+        };
+        let attached_node = &attached.nodes[node];
+        let mut feature_writers = attached_node.feature_writers.lock();
+        for ctx in sent {
+            attached_node.stats.record_out(&ctx.port);
+            if let Some(writer) = feature_writers.get_mut(&ctx.port) {
+                if let Err(e) = writer.write(&ctx.feature) {
+                    attached.error_manager.report(e.into());
+                }
+            }
+        }
+    }
+}
+
+/// One copy of a sub graph with its own processor state.
+#[derive(Debug)]
+pub(crate) struct SubGraphInstance {
+    processors: Vec<Box<dyn Processor>>,
+}
+
+impl SubGraphInstance {
+    pub(crate) fn new(template: &SubGraphTemplate) -> Self {
+        let mut processors = template.processors.clone();
+        for processor in processors.iter_mut() {
+            processor.initialize(template.ctx.clone());
+        }
+        Self { processors }
+    }
+
+    /// Runs a feature through the sub graph. Returns the features leaving it.
+    pub(crate) fn process(
+        &mut self,
+        template: &SubGraphTemplate,
+        ctx: ExecutorContext,
+    ) -> Result<Vec<ExecutorContext>, BoxedError> {
+        let queue = template
+            .entries
+            .iter()
+            .filter(|(_, routing_port)| {
+                routing_port.is_none() || routing_port.as_ref() == Some(&ctx.port)
+            })
+            .map(|(node, _)| (*node, ctx.clone()))
+            .collect();
+        let mut output = vec![];
+        self.run(template, queue, &mut output)?;
+        Ok(output)
+    }
+
+    /// Finishes the processors in order, running what they send through the rest of the sub
+    /// graph. Returns the features leaving it.
+    pub(crate) fn finish(
+        &mut self,
+        template: &SubGraphTemplate,
+        ctx: NodeContext,
+    ) -> Result<Vec<ExecutorContext>, BoxedError> {
+        let mut output = vec![];
+        for &node in template.order.iter() {
+            let mut fw = Collector::default();
+            self.processors[node].finish(ctx.clone(), &mut fw)?;
+            template.record_sent(node, &fw.0);
+            let mut queue = VecDeque::new();
+            route(template, node, fw.0, &mut queue, &mut output);
+            self.run(template, queue, &mut output)?;
+        }
+        Ok(output)
+    }
+
+    fn run(
+        &mut self,
+        template: &SubGraphTemplate,
+        mut queue: VecDeque<(usize, ExecutorContext)>,
+        output: &mut Vec<ExecutorContext>,
+    ) -> Result<(), BoxedError> {
+        while let Some((node, ctx)) = queue.pop_front() {
+            let sent = self.process_node(template, node, ctx)?;
+            template.record_sent(node, &sent);
+            route(template, node, sent, &mut queue, output);
+        }
+        Ok(())
+    }
+
+    /// Processes a feature with a node, applying the error policy of the node once the template
+    /// is attached. What a failed attempt sent is discarded when the feature is retried or rejected,
+    /// as the processor nodes do.
+    fn process_node(
+        &mut self,
+        template: &SubGraphTemplate,
+        node: usize,
+        ctx: ExecutorContext,
+    ) -> Result<Vec<ExecutorContext>, BoxedError> {
+        let Some(attached) = template.attached.get() else {
+            let mut fw = Collector::default();
+            self.processors[node].process(ctx, &mut fw)?;
+            return Ok(fw.0);
+        };
+        let attached_node = &attached.nodes[node];
+        let error_handler = &attached_node.error_handler;
+        attached_node.stats.record_in(&ctx.port);
+        let now = Instant::now();
+        let feature_id = ctx.feature.id;
+        let original = error_handler.keeps_feature().then(|| ctx.clone());
+        let processor = &mut self.processors[node];
+        let snapshot = (original.is_some() && processor.is_stateful()).then(|| processor.clone());
+        let mut fw = Collector::default();
+        let mut result = processor.process(ctx, &mut fw);
+        let mut attempts = 1;
+        while let (Err(_), Some(ctx)) = (&result, &original) {
+            if let Some(snapshot) = &snapshot {
+                *processor = snapshot.clone();
+            }
+            if attempts >= error_handler.max_attempts() {
+                break;
+            }
+            attempts += 1;
+            fw = Collector::default();
+            result = processor.process(ctx.clone(), &mut fw);
+        }
+        let sent = match result {
+            Ok(()) => fw.0,
+            // A failing job is stopped by the node running the sub graph.
+            Err(e) => {
+                let discarded = original.is_some();
+                let rejected = error_handler.handle(feature_id, original, e);
+                let mut sent = if discarded { vec![] } else { fw.0 };
+                sent.extend(rejected);
+                sent
+            }
+        };
+        attached_node.stats.record_elapsed(now.elapsed());
+        Ok(sent)
+    }
+}
+
+fn route(
+    template: &SubGraphTemplate,
+    node: usize,
+    sent: Vec<ExecutorContext>,
+    queue: &mut VecDeque<(usize, ExecutorContext)>,
+    output: &mut Vec<ExecutorContext>,
+) {
+    for ctx in sent {
+        if template.leaves[node] {
+            output.push(ctx);
+            continue;
+        }
+        let Some(targets) = template.edges[node].get(&ctx.port) else {
+            continue;
+        };
+        for (target, port) in targets {
+            queue.push_back((
+                *target,
+                ctx.new_with_feature_and_port(ctx.feature.clone(), port.clone()),
+            ));
+        }
+    }
+}
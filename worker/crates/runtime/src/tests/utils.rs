@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use reearth_flow_common::{future::SharedFuture, uri::Uri};
+use reearth_flow_state::State;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::{workflow::Workflow, Attribute, AttributeValue, Feature};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{runtime::Runtime, sync::mpsc::Sender};
+
+use crate::{
+    channels::ProcessorChannelForwarder,
+    errors::{BoxedError, ExecutionError},
+    event::EventHub,
+    executor::dag_executor::DagExecutor,
+    executor_operation::{ExecutorContext, ExecutorOptions, NodeContext},
+    feature_store::FeatureStoreFormat,
+    node::{
+        parse_params, IngestionMessage, NodeKind, Port, Processor, ProcessorFactory, Sink,
+        SinkFactory, Source, SourceFactory, DEFAULT_PORT, REJECTED_PORT,
+    },
+    shutdown,
+    stats::{JobStats, NodeSummary},
+};
+
+/// Attribute set by [`NumberSource`] to the index of each feature.
+pub const VALUE_ATTRIBUTE: &str = "value";
+
+pub fn create_state(root: &str, storage_resolver: &StorageResolver) -> Arc<State> {
+    Arc::new(State::new(&Uri::for_test(root), storage_resolver).unwrap())
+}
+
+pub fn create_executor_options() -> ExecutorOptions {
+    ExecutorOptions {
+        channel_buffer_sz: 20,
+        event_hub: EventHub::new(1024),
+        error_threshold: None,
+        thread_pool_size: 4,
+        preserve_order: false,
+        feature_store_format: FeatureStoreFormat::Json,
+        checkpoint: None,
+        resume_from: None,
+        replay: None,
+        cache: None,
+        stats: Arc::new(JobStats::new()),
+    }
+}
+
+/// Factories of the test actions: `NumberSource`, `PassThrough`, `Failing` and, collecting into
+/// `sink`, `Collecting`.
+pub fn create_factories(sink: &CollectingSinkFactory) -> HashMap<String, NodeKind> {
+    HashMap::from([
+        (
+            "NumberSource".to_string(),
+            NodeKind::Source(Box::new(NumberSourceFactory)),
+        ),
+        (
+            "PassThrough".to_string(),
+            NodeKind::Processor(Box::new(PassThroughFactory)),
+        ),
+        (
+            "Failing".to_string(),
+            NodeKind::Processor(Box::new(FailingFactory)),
+        ),
+        (
+            "Collecting".to_string(),
+            NodeKind::Sink(Box::new(sink.clone())),
+        ),
+    ])
+}
+
+/// Runs `workflow` to completion, storing the features of its edges in `state`.
+pub fn execute(
+    workflow: &str,
+    factories: HashMap<String, NodeKind>,
+    options: ExecutorOptions,
+    storage_resolver: Arc<StorageResolver>,
+    state: Arc<State>,
+) -> Result<(), ExecutionError> {
+    execute_until_shutdown(workflow, factories, options, storage_resolver, state, None)
+}
+
+/// Runs `workflow`, requesting the shutdown of the job after `shutdown_after` if set.
+pub fn execute_until_shutdown(
+    workflow: &str,
+    factories: HashMap<String, NodeKind>,
+    options: ExecutorOptions,
+    storage_resolver: Arc<StorageResolver>,
+    state: Arc<State>,
+    shutdown_after: Option<Duration>,
+) -> Result<(), ExecutionError> {
+    let workflow = Workflow::try_from_str(workflow).unwrap();
+    let runtime = Arc::new(Runtime::new().unwrap());
+    let ctx = NodeContext {
+        storage_resolver,
+        ..Default::default()
+    };
+    let dag_executor = runtime.block_on(DagExecutor::new(
+        ctx.clone(),
+        workflow.entry_graph_id,
+        workflow.graphs,
+        options,
+        factories,
+        workflow.with,
+    ))?;
+    let (shutdown_sender, shutdown_receiver) = shutdown::new(&runtime);
+    let _shutdown = runtime.spawn(async move {
+        match shutdown_after {
+            Some(after) => tokio::time::sleep(after).await,
+            None => std::future::pending::<()>().await,
+        }
+        shutdown_sender.shutdown();
+    });
+    let join_handle = runtime.block_on(dag_executor.start(
+        SharedFuture::new(Box::pin(shutdown_receiver.create_shutdown_future())),
+        Arc::clone(&runtime),
+        ctx.expr_engine,
+        ctx.storage_resolver,
+        ctx.logger,
+        ctx.kv_store,
+        ctx.spill_manager,
+        state,
+    ))?;
+    join_handle.join()
+}
+
+/// Finds the statistics of the node named `name`.
+pub fn node_summary(stats: &JobStats, name: &str) -> Option<NodeSummary> {
+    stats.summary().into_iter().find(|node| node.name == name)
+}
+
+pub fn feature_value(feature: &Feature) -> i64 {
+    match feature.get(&VALUE_ATTRIBUTE) {
+        Some(AttributeValue::Number(value)) => value.as_i64().unwrap(),
+        value => panic!("Unexpected value: {:?}", value),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NumberSourceParam {
+    count: i64,
+    /// Milliseconds to wait before sending each feature.
+    #[serde(default)]
+    interval_ms: u64,
+}
+
+/// Sends `count` features numbered from 0, or from the number of the next feature to send given
+/// as its state.
+#[derive(Debug, Clone)]
+pub struct NumberSourceFactory;
+
+impl SourceFactory for NumberSourceFactory {
+    fn name(&self) -> &str {
+        "NumberSource"
+    }
+
+    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
+        None
+    }
+
+    fn get_output_ports(&self) -> Vec<Port> {
+        vec![DEFAULT_PORT.clone()]
+    }
+
+    fn build(
+        &self,
+        _ctx: NodeContext,
+        _event_hub: EventHub,
+        _action: String,
+        with: Option<HashMap<String, Value>>,
+        state: Option<Vec<u8>>,
+    ) -> Result<Box<dyn Source>, BoxedError> {
+        let params: NumberSourceParam = parse_params(with)?;
+        let next = match state {
+            Some(state) => serde_json::from_slice(&state)?,
+            None => 0,
+        };
+        Ok(Box::new(NumberSource {
+            count: params.count,
+            interval: Duration::from_millis(params.interval_ms),
+            next,
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct NumberSource {
+    count: i64,
+    interval: Duration,
+    next: i64,
+}
+
+#[async_trait::async_trait]
+impl Source for NumberSource {
+    async fn initialize(&self, _ctx: NodeContext) {}
+
+    async fn serialize_state(&self) -> Result<Vec<u8>, BoxedError> {
+        Ok(serde_json::to_vec(&self.next)?)
+    }
+
+    async fn start(
+        &mut self,
+        _ctx: NodeContext,
+        sender: Sender<(Port, IngestionMessage)>,
+    ) -> Result<(), BoxedError> {
+        let state = self.serialize_state().await?;
+        sender
+            .send((DEFAULT_PORT.clone(), IngestionMessage::State { state }))
+            .await
+            .map_err(|e| ExecutionError::CannotSendToChannel(format!("{:?}", e)))?;
+        while self.next < self.count {
+            if !self.interval.is_zero() {
+                tokio::time::sleep(self.interval).await;
+            }
+            let feature = Feature::new_with_attributes(HashMap::from([(
+                Attribute::new(VALUE_ATTRIBUTE),
+                AttributeValue::Number(self.next.into()),
+            )]));
+            self.next += 1;
+            let state = self.serialize_state().await?;
+            for message in [
+                IngestionMessage::OperationEvent { feature },
+                IngestionMessage::State { state },
+            ] {
+                sender
+                    .send((DEFAULT_PORT.clone(), message))
+                    .await
+                    .map_err(|e| ExecutionError::CannotSendToChannel(format!("{:?}", e)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends each feature as it is.
+#[derive(Debug, Clone)]
+pub struct PassThroughFactory;
+
+impl ProcessorFactory for PassThroughFactory {
+    fn name(&self) -> &str {
+        "PassThrough"
+    }
+
+    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
+        None
+    }
+
+    fn get_input_ports(&self) -> Vec<Port> {
+        vec![DEFAULT_PORT.clone()]
+    }
+
+    fn get_output_ports(&self) -> Vec<Port> {
+        vec![DEFAULT_PORT.clone()]
+    }
+
+    fn build(
+        &self,
+        _ctx: NodeContext,
+        _event_hub: EventHub,
+        _action: String,
+        _with: Option<HashMap<String, Value>>,
+    ) -> Result<Box<dyn Processor>, BoxedError> {
+        Ok(Box::new(PassThrough))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PassThrough;
+
+impl Processor for PassThrough {
+    fn initialize(&mut self, _ctx: NodeContext) {}
+
+    fn process(
+        &mut self,
+        ctx: ExecutorContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        fw.send(ctx);
+        Ok(())
+    }
+
+    fn finish(
+        &self,
+        _ctx: NodeContext,
+        _fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "PassThrough"
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FailingParam {
+    /// Number of attempts failing for each feature.
+    failures: usize,
+    /// Whether the features are buffered until the processor finishes instead of being sent.
+    #[serde(default)]
+    buffers: bool,
+}
+
+/// Fails the first `failures` attempts to process each feature, after sending or buffering a
+/// copy of it, and sends or buffers the feature on the next attempt.
+#[derive(Debug, Clone)]
+pub struct FailingFactory;
+
+impl ProcessorFactory for FailingFactory {
+    fn name(&self) -> &str {
+        "Failing"
+    }
+
+    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
+        None
+    }
+
+    fn get_input_ports(&self) -> Vec<Port> {
+        vec![DEFAULT_PORT.clone()]
+    }
+
+    fn get_output_ports(&self) -> Vec<Port> {
+        vec![DEFAULT_PORT.clone(), REJECTED_PORT.clone()]
+    }
+
+    fn build(
+        &self,
+        _ctx: NodeContext,
+        _event_hub: EventHub,
+        _action: String,
+        with: Option<HashMap<String, Value>>,
+    ) -> Result<Box<dyn Processor>, BoxedError> {
+        let params: FailingParam = parse_params(with)?;
+        Ok(Box::new(Failing {
+            failures: params.failures,
+            attempts: Arc::new(Mutex::new(HashMap::new())),
+            buffered: params.buffers.then(Vec::new),
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Failing {
+    failures: usize,
+    attempts: Arc<Mutex<HashMap<uuid::Uuid, usize>>>,
+    buffered: Option<Vec<ExecutorContext>>,
+}
+
+impl Processor for Failing {
+    fn initialize(&mut self, _ctx: NodeContext) {}
+
+    fn process(
+        &mut self,
+        ctx: ExecutorContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        let attempt = {
+            let mut attempts = self.attempts.lock();
+            let attempt = attempts.entry(ctx.feature.id).or_default();
+            *attempt += 1;
+            *attempt
+        };
+        let ctx = ctx.new_with_feature_and_port(ctx.feature.clone(), DEFAULT_PORT.clone());
+        match &mut self.buffered {
+            Some(buffered) => buffered.push(ctx),
+            None => fw.send(ctx),
+        }
+        if attempt <= self.failures {
+            return Err(format!("Attempt {} failed", attempt).into());
+        }
+        Ok(())
+    }
+
+    fn finish(
+        &self,
+        _ctx: NodeContext,
+        fw: &mut dyn ProcessorChannelForwarder,
+    ) -> Result<(), BoxedError> {
+        for ctx in self.buffered.iter().flatten() {
+            fw.send(ctx.clone());
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "Failing"
+    }
+
+    fn is_stateful(&self) -> bool {
+        self.buffered.is_some()
+    }
+}
+
+/// Keeps the features received by all the sinks it builds.
+#[derive(Debug, Clone, Default)]
+pub struct CollectingSinkFactory {
+    pub features: Arc<Mutex<Vec<Feature>>>,
+}
+
+impl CollectingSinkFactory {
+    pub fn values(&self) -> Vec<i64> {
+        let mut values = self
+            .features
+            .lock()
+            .iter()
+            .map(feature_value)
+            .collect::<Vec<_>>();
+        values.sort();
+        values
+    }
+}
+
+impl SinkFactory for CollectingSinkFactory {
+    fn name(&self) -> &str {
+        "Collecting"
+    }
+
+    fn parameter_schema(&self) -> Option<schemars::schema::RootSchema> {
+        None
+    }
+
+    fn get_input_ports(&self) -> Vec<Port> {
+        vec![DEFAULT_PORT.clone()]
+    }
+
+    fn prepare(&self) -> Result<(), BoxedError> {
+        Ok(())
+    }
+
+    fn build(
+        &self,
+        _ctx: NodeContext,
+        _event_hub: EventHub,
+        _action: String,
+        _with: Option<HashMap<String, Value>>,
+    ) -> Result<Box<dyn Sink>, BoxedError> {
+        Ok(Box::new(CollectingSink {
+            features: Arc::clone(&self.features),
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CollectingSink {
+    features: Arc<Mutex<Vec<Feature>>>,
+}
+
+impl Sink for CollectingSink {
+    fn initialize(&self, _ctx: NodeContext) {}
+
+    fn process(&mut self, ctx: ExecutorContext) -> Result<(), BoxedError> {
+        self.features.lock().push(ctx.feature);
+        Ok(())
+    }
+
+    fn finish(&self, _ctx: NodeContext) -> Result<(), BoxedError> {
+        Ok(())
+    }
+}
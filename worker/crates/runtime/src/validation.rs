@@ -0,0 +1,832 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::DiGraph;
+use reearth_flow_storage::resolve::StorageResolver;
+use reearth_flow_types::workflow::{ErrorPolicy, Graph, Node, Workflow};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::errors::ExecutionError;
+use crate::graph_ref::GraphRefResolver;
+use crate::node::{EdgeId, GraphId, NodeId, NodeKind, Port, RouterFactory, REJECTED_PORT};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub graph_id: Option<GraphId>,
+    pub node_id: Option<NodeId>,
+    pub edge_id: Option<EdgeId>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(graph_id: Option<GraphId>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            graph_id,
+            node_id: None,
+            edge_id: None,
+            message: message.into(),
+        }
+    }
+
+    fn warning(graph_id: Option<GraphId>, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(graph_id, message)
+        }
+    }
+
+    fn with_node(mut self, node_id: NodeId) -> Self {
+        self.node_id = Some(node_id);
+        self
+    }
+
+    fn with_edge(mut self, edge_id: EdgeId) -> Self {
+        self.edge_id = Some(edge_id);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}", severity, self.message)?;
+        if let Some(graph_id) = self.graph_id {
+            write!(f, " (graph = {}", graph_id)?;
+            if let Some(node_id) = self.node_id {
+                write!(f, ", node = {}", node_id)?;
+            }
+            if let Some(edge_id) = self.edge_id {
+                write!(f, ", edge = {}", edge_id)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks a workflow against the available actions without building any node.
+///
+/// Graph references are resolved with `storage_resolver` and the graphs they load are checked
+/// along with the workflow's. The workflow can be executed when no diagnostic is an error.
+pub fn validate_workflow(
+    workflow: &Workflow,
+    factories: &HashMap<String, NodeKind>,
+    storage_resolver: &StorageResolver,
+) -> Vec<Diagnostic> {
+    let mut factories = factories.clone();
+    factories
+        .entry("Router".to_string())
+        .or_insert_with(|| NodeKind::Processor(Box::<RouterFactory>::default()));
+    let mut diagnostics = vec![];
+    if !workflow
+        .graphs
+        .iter()
+        .any(|graph| graph.id == workflow.entry_graph_id)
+    {
+        diagnostics.push(Diagnostic::error(
+            None,
+            format!("Entry graph not found: {}", workflow.entry_graph_id),
+        ));
+    }
+    for error in workflow.parameter_errors() {
+        diagnostics.push(Diagnostic::error(None, error));
+    }
+    let (graphs, failures) =
+        GraphRefResolver::new(storage_resolver).resolve_all(workflow.graphs.clone());
+    for failure in failures {
+        let message = match failure.error {
+            ExecutionError::GraphRef(message) => message,
+            error => error.to_string(),
+        };
+        let diagnostic = Diagnostic::error(Some(failure.graph_id), message);
+        diagnostics.push(match failure.node_id {
+            Some(node_id) => diagnostic.with_node(node_id),
+            None => diagnostic,
+        });
+    }
+    let graph_ids = graphs.iter().map(|graph| graph.id).collect::<HashSet<_>>();
+    for graph in graphs.iter() {
+        validate_graph(
+            graph,
+            &graph_ids,
+            &factories,
+            &workflow.with,
+            &mut diagnostics,
+        );
+    }
+    diagnostics
+}
+
+fn validate_graph(
+    graph: &Graph,
+    graph_ids: &HashSet<GraphId>,
+    factories: &HashMap<String, NodeKind>,
+    global_params: &Option<serde_json::Map<String, Value>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let graph_id = Some(graph.id);
+    let mut nodes = HashMap::<NodeId, (&Node, Option<&NodeKind>)>::new();
+    for node in graph.nodes.iter() {
+        if nodes.contains_key(&node.id()) {
+            diagnostics.push(
+                Diagnostic::error(graph_id, format!("Duplicate node id: {}", node.id()))
+                    .with_node(node.id()),
+            );
+            continue;
+        }
+        let kind = match node {
+            Node::Action { action, .. } => {
+                let kind = factories.get(action);
+                if kind.is_none() {
+                    diagnostics.push(
+                        Diagnostic::error(graph_id, format!("Unknown action: {}", action))
+                            .with_node(node.id()),
+                    );
+                }
+                kind
+            }
+            Node::SubGraph { sub_graph_id, .. } => {
+                if !graph_ids.contains(sub_graph_id) {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            graph_id,
+                            format!("Sub graph not found: {}", sub_graph_id),
+                        )
+                        .with_node(node.id()),
+                    );
+                }
+                None
+            }
+            // References that cannot be resolved are reported by `validate_workflow`.
+            Node::GraphRef { .. } => None,
+        };
+        if node.for_each().is_some() && matches!(node, Node::Action { .. }) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    graph_id,
+                    format!("forEach only applies to sub graphs: {}", node.name()),
+                )
+                .with_node(node.id()),
+            );
+        }
+        if node.repeat().is_some() && matches!(node, Node::Action { .. }) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    graph_id,
+                    format!("loop only applies to sub graphs: {}", node.name()),
+                )
+                .with_node(node.id()),
+            );
+        }
+        if node.for_each().is_some() && node.repeat().is_some() {
+            diagnostics.push(
+                Diagnostic::error(
+                    graph_id,
+                    format!("forEach and loop cannot be combined: {}", node.name()),
+                )
+                .with_node(node.id()),
+            );
+        }
+        if let Some(kind) = kind {
+            validate_parameters(graph.id, node, kind, global_params, diagnostics);
+            if matches!(kind, NodeKind::Sink(_)) && node.on_error() == ErrorPolicy::Reject {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        graph_id,
+                        format!(
+                            "Sinks have no rejected port, failed features are skipped: {}",
+                            node.name()
+                        ),
+                    )
+                    .with_node(node.id()),
+                );
+            }
+        }
+        nodes.insert(node.id(), (node, kind));
+    }
+
+    let mut dag = DiGraph::<NodeId, EdgeId>::new();
+    let node_indices = nodes
+        .keys()
+        .map(|node_id| (*node_id, dag.add_node(*node_id)))
+        .collect::<HashMap<_, _>>();
+    for edge in graph.edges.iter() {
+        let from = nodes.get(&edge.from);
+        let to = nodes.get(&edge.to);
+        if from.is_none() {
+            diagnostics.push(
+                Diagnostic::error(
+                    graph_id,
+                    format!("Edge source node not found: {}", edge.from),
+                )
+                .with_edge(edge.id),
+            );
+        }
+        if to.is_none() {
+            diagnostics.push(
+                Diagnostic::error(graph_id, format!("Edge target node not found: {}", edge.to))
+                    .with_edge(edge.id),
+            );
+        }
+        if let Some((node, Some(kind))) = from {
+            let ports = output_ports(kind);
+            let port = Port::new(edge.from_port.clone());
+            // The rejected port is added to any node rejecting failed features.
+            let rejects = node.on_error() == ErrorPolicy::Reject && port == *REJECTED_PORT;
+            if let Some(message) = check_port(ports, &port, "output").filter(|_| !rejects) {
+                diagnostics.push(
+                    Diagnostic::error(graph_id, message)
+                        .with_node(edge.from)
+                        .with_edge(edge.id),
+                );
+            }
+        }
+        if let Some((_, Some(kind))) = to {
+            let ports = input_ports(kind);
+            let port = Port::new(edge.to_port.clone());
+            if let Some(message) = check_port(ports, &port, "input") {
+                diagnostics.push(
+                    Diagnostic::error(graph_id, message)
+                        .with_node(edge.to)
+                        .with_edge(edge.id),
+                );
+            }
+        }
+        if let (Some(from), Some(to)) = (node_indices.get(&edge.from), node_indices.get(&edge.to)) {
+            dag.add_edge(*from, *to, edge.id);
+        }
+    }
+
+    for scc in tarjan_scc(&dag) {
+        let is_cycle = scc.len() > 1 || dag.find_edge(scc[0], scc[0]).is_some();
+        if !is_cycle {
+            continue;
+        }
+        for node_index in scc {
+            diagnostics.push(
+                Diagnostic::error(graph_id, "Node is part of a cycle").with_node(dag[node_index]),
+            );
+        }
+    }
+
+    for (node_id, (node, kind)) in nodes.iter() {
+        let node_index = node_indices[node_id];
+        let has_incoming = dag
+            .neighbors_directed(node_index, petgraph::Direction::Incoming)
+            .next()
+            .is_some();
+        let has_outgoing = dag
+            .neighbors_directed(node_index, petgraph::Direction::Outgoing)
+            .next()
+            .is_some();
+        let message = match kind {
+            _ if nodes.len() > 1 && !has_incoming && !has_outgoing => {
+                Some(format!("Node is not connected: {}", node.name()))
+            }
+            Some(NodeKind::Processor(_)) | Some(NodeKind::Sink(_)) if !has_incoming => {
+                Some(format!("Node never receives features: {}", node.name()))
+            }
+            _ => None,
+        };
+        if let Some(message) = message {
+            diagnostics.push(Diagnostic::warning(graph_id, message).with_node(*node_id));
+        }
+    }
+}
+
+fn validate_parameters(
+    graph_id: GraphId,
+    node: &Node,
+    kind: &NodeKind,
+    global_params: &Option<serde_json::Map<String, Value>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let schema = match kind {
+        NodeKind::Source(factory) => factory.parameter_schema(),
+        NodeKind::Processor(factory) => factory.parameter_schema(),
+        NodeKind::Sink(factory) => factory.parameter_schema(),
+    };
+    let Some(schema) = schema else {
+        return;
+    };
+    let Ok(schema) = serde_json::to_value(schema) else {
+        return;
+    };
+    let compiled = match jsonschema::JSONSchema::compile(&schema) {
+        Ok(compiled) => compiled,
+        Err(e) => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    Some(graph_id),
+                    format!("Invalid parameter schema of {}: {}", node.action(), e),
+                )
+                .with_node(node.id()),
+            );
+            return;
+        }
+    };
+    // Parameters are merged the same way as when the DAG is built.
+    let mut with = global_params.clone().unwrap_or_default();
+    if let Some(params) = node.with() {
+        with.extend(params.clone());
+    }
+    let with = Value::Object(with);
+    if let Err(errors) = compiled.validate(&with) {
+        for error in errors {
+            diagnostics.push(
+                Diagnostic::error(
+                    Some(graph_id),
+                    format!(
+                        "Invalid parameter at `{}` of {}: {}",
+                        error.instance_path,
+                        node.name(),
+                        error
+                    ),
+                )
+                .with_node(node.id()),
+            );
+        }
+    }
+}
+
+fn input_ports(kind: &NodeKind) -> Option<Vec<Port>> {
+    match kind {
+        NodeKind::Source(_) => Some(vec![]),
+        NodeKind::Processor(factory) => non_empty(factory.get_input_ports()),
+        NodeKind::Sink(factory) => non_empty(factory.get_input_ports()),
+    }
+}
+
+fn output_ports(kind: &NodeKind) -> Option<Vec<Port>> {
+    match kind {
+        NodeKind::Source(factory) => non_empty(factory.get_output_ports()),
+        NodeKind::Processor(factory) => non_empty(factory.get_output_ports()),
+        NodeKind::Sink(_) => Some(vec![]),
+    }
+}
+
+/// Actions that declare no ports decide them dynamically, so their ports are not checked.
+fn non_empty(ports: Vec<Port>) -> Option<Vec<Port>> {
+    (!ports.is_empty()).then_some(ports)
+}
+
+fn check_port(ports: Option<Vec<Port>>, port: &Port, direction: &str) -> Option<String> {
+    let ports = ports?;
+    if ports.contains(port) {
+        return None;
+    }
+    if ports.is_empty() {
+        return Some(format!("Node has no {} ports, but got {}", direction, port));
+    }
+    Some(format!(
+        "Unknown {} port: {}, expected one of {:?}",
+        direction,
+        port,
+        ports
+            .iter()
+            .map(|port| port.to_string())
+            .collect::<Vec<_>>()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use reearth_flow_common::uri::Uri;
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests::utils::{create_factories, CollectingSinkFactory};
+
+    const WORKFLOW_ID: &str = "00000000-0000-0000-0000-000000000000";
+    const ENTRY_GRAPH_ID: &str = "00000000-0000-0000-0000-0000000000a0";
+    const SUB_GRAPH_ID: &str = "00000000-0000-0000-0000-0000000000a1";
+    const SOURCE_ID: &str = "00000000-0000-0000-0000-000000000001";
+    const PROCESSOR_ID: &str = "00000000-0000-0000-0000-000000000002";
+    const SINK_ID: &str = "00000000-0000-0000-0000-000000000003";
+    const OTHER_ID: &str = "00000000-0000-0000-0000-000000000004";
+    const LIBRARY_GRAPH_ID: &str = "00000000-0000-0000-0000-0000000000b0";
+    const LIBRARY_URI: &str = "ram:///graphs/library.json";
+
+    fn action(id: &str, name: &str, action: &str) -> Value {
+        json!({"id": id, "name": name, "type": "action", "action": action})
+    }
+
+    fn edge(id: u8, from: &str, from_port: &str, to: &str, to_port: &str) -> Value {
+        json!({
+            "id": format!("00000000-0000-0000-0000-0000000000e{}", id),
+            "from": from,
+            "to": to,
+            "fromPort": from_port,
+            "toPort": to_port,
+        })
+    }
+
+    fn workflow(graphs: Value, extra: Value) -> Workflow {
+        let mut workflow = json!({
+            "id": WORKFLOW_ID,
+            "name": "validation",
+            "entryGraphId": ENTRY_GRAPH_ID,
+            "graphs": graphs,
+        });
+        if let (Value::Object(workflow), Value::Object(extra)) = (&mut workflow, extra) {
+            workflow.extend(extra);
+        }
+        Workflow::try_from_str(&workflow.to_string()).unwrap()
+    }
+
+    /// Validates a workflow made of one graph with `nodes` and `edges`.
+    fn validate(nodes: Value, edges: Value) -> Vec<(Severity, String)> {
+        validate_graphs(json!([{
+            "id": ENTRY_GRAPH_ID,
+            "name": "entry",
+            "nodes": nodes,
+            "edges": edges,
+        }]))
+    }
+
+    fn validate_graphs(graphs: Value) -> Vec<(Severity, String)> {
+        validate_workflow(&workflow(graphs, json!({})))
+    }
+
+    fn validate_workflow(workflow: &Workflow) -> Vec<(Severity, String)> {
+        validate_with_storage(workflow, &StorageResolver::new())
+    }
+
+    fn validate_with_storage(
+        workflow: &Workflow,
+        storage_resolver: &StorageResolver,
+    ) -> Vec<(Severity, String)> {
+        let factories = create_factories(&CollectingSinkFactory::default());
+        super::validate_workflow(workflow, &factories, storage_resolver)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message))
+            .collect()
+    }
+
+    /// Stores a graph definition at `LIBRARY_URI` whose entry graph holds `nodes`.
+    fn put_library(parameters: Value, nodes: Value) -> StorageResolver {
+        let storage_resolver = StorageResolver::new();
+        let definition = json!({
+            "name": "library",
+            "version": "1.0.0",
+            "parameters": parameters,
+            "entryGraphId": LIBRARY_GRAPH_ID,
+            "graphs": [{"id": LIBRARY_GRAPH_ID, "name": "library", "nodes": nodes, "edges": []}]
+        });
+        let uri = Uri::for_test(LIBRARY_URI);
+        storage_resolver
+            .resolve(&uri)
+            .unwrap()
+            .put_sync(uri.path().as_path(), Bytes::from(definition.to_string()))
+            .unwrap();
+        storage_resolver
+    }
+
+    /// Validates the pipeline with a reference to `LIBRARY_URI` after its processor.
+    fn validate_graph_ref(
+        storage_resolver: &StorageResolver,
+        version: &str,
+        with: Value,
+    ) -> Vec<(Severity, String)> {
+        let (mut nodes, mut edges) = pipeline();
+        nodes.push(json!({
+            "id": OTHER_ID,
+            "name": "library",
+            "type": "graphRef",
+            "graphUri": LIBRARY_URI,
+            "version": version,
+            "with": with
+        }));
+        edges.push(edge(3, PROCESSOR_ID, "default", OTHER_ID, "any"));
+        let workflow = workflow(
+            json!([{"id": ENTRY_GRAPH_ID, "name": "entry", "nodes": nodes, "edges": edges}]),
+            json!({}),
+        );
+        validate_with_storage(&workflow, storage_resolver)
+    }
+
+    fn pipeline() -> (Vec<Value>, Vec<Value>) {
+        (
+            vec![
+                json!({
+                    "id": SOURCE_ID,
+                    "name": "source",
+                    "type": "action",
+                    "action": "NumberSource",
+                    "with": {"count": 1}
+                }),
+                action(PROCESSOR_ID, "processor", "PassThrough"),
+                action(SINK_ID, "sink", "Collecting"),
+            ],
+            vec![
+                edge(1, SOURCE_ID, "default", PROCESSOR_ID, "default"),
+                edge(2, PROCESSOR_ID, "default", SINK_ID, "default"),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_valid_workflow() {
+        let (nodes, edges) = pipeline();
+        assert_eq!(validate(json!(nodes), json!(edges)), vec![]);
+    }
+
+    #[test]
+    fn test_entry_graph_not_found() {
+        let diagnostics = validate_graphs(json!([]));
+        assert_eq!(
+            diagnostics,
+            vec![(
+                Severity::Error,
+                format!("Entry graph not found: {}", ENTRY_GRAPH_ID)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_missing_required_parameter() {
+        let (nodes, edges) = pipeline();
+        let workflow = workflow(
+            json!([{"id": ENTRY_GRAPH_ID, "name": "entry", "nodes": nodes, "edges": edges}]),
+            json!({"parameters": [{"name": "city", "type": "string", "required": true}]}),
+        );
+        assert_eq!(
+            validate_workflow(&workflow),
+            vec![(
+                Severity::Error,
+                "Missing required parameter: city".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_node_and_unknown_action() {
+        let (mut nodes, edges) = pipeline();
+        nodes.push(action(SINK_ID, "duplicate", "Collecting"));
+        nodes.push(action(OTHER_ID, "unknown", "Unknown"));
+        let diagnostics = validate(json!(nodes), json!(edges));
+        assert!(diagnostics.contains(&(Severity::Error, format!("Duplicate node id: {}", SINK_ID))));
+        assert!(diagnostics.contains(&(Severity::Error, "Unknown action: Unknown".to_string())));
+    }
+
+    #[test]
+    fn test_edge_nodes_not_found() {
+        let (nodes, mut edges) = pipeline();
+        edges.push(edge(3, OTHER_ID, "default", SINK_ID, "default"));
+        edges.push(edge(4, PROCESSOR_ID, "default", OTHER_ID, "default"));
+        let diagnostics = validate(json!(nodes), json!(edges));
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    Severity::Error,
+                    format!("Edge source node not found: {}", OTHER_ID)
+                ),
+                (
+                    Severity::Error,
+                    format!("Edge target node not found: {}", OTHER_ID)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_ports() {
+        let (nodes, mut edges) = pipeline();
+        edges[0] = edge(1, SOURCE_ID, "missing", PROCESSOR_ID, "default");
+        edges[1] = edge(2, PROCESSOR_ID, "default", SINK_ID, "missing");
+        edges.push(edge(3, PROCESSOR_ID, "default", SOURCE_ID, "default"));
+        let diagnostics = validate(json!(nodes), json!(edges));
+        assert!(diagnostics.contains(&(
+            Severity::Error,
+            "Unknown output port: missing, expected one of [\"default\"]".to_string()
+        )));
+        assert!(diagnostics.contains(&(
+            Severity::Error,
+            "Unknown input port: missing, expected one of [\"default\"]".to_string()
+        )));
+        assert!(diagnostics.contains(&(
+            Severity::Error,
+            "Node has no input ports, but got default".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_rejected_port_of_rejecting_node() {
+        let (mut nodes, mut edges) = pipeline();
+        nodes.push(action(OTHER_ID, "rejected", "Collecting"));
+        edges.push(edge(3, PROCESSOR_ID, "rejected", OTHER_ID, "default"));
+        let diagnostics = validate(json!(nodes.clone()), json!(edges));
+        assert_eq!(
+            diagnostics,
+            vec![(
+                Severity::Error,
+                "Unknown output port: rejected, expected one of [\"default\"]".to_string()
+            )]
+        );
+
+        nodes[1]["onError"] = json!({"type": "reject"});
+        assert_eq!(validate(json!(nodes), json!(edges)), vec![]);
+    }
+
+    #[test]
+    fn test_rejecting_sink() {
+        let (mut nodes, edges) = pipeline();
+        nodes[2]["onError"] = json!({"type": "reject"});
+        assert_eq!(
+            validate(json!(nodes), json!(edges)),
+            vec![(
+                Severity::Warning,
+                "Sinks have no rejected port, failed features are skipped: sink".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_invalid_parameter() {
+        let (mut nodes, mut edges) = pipeline();
+        nodes.push(json!({
+            "id": OTHER_ID,
+            "name": "router",
+            "type": "action",
+            "action": "Router",
+            "with": {"routingPort": 1}
+        }));
+        edges.push(edge(3, PROCESSOR_ID, "default", OTHER_ID, "default"));
+        let diagnostics = validate(json!(nodes), json!(edges));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, Severity::Error);
+        assert!(diagnostics[0]
+            .1
+            .starts_with("Invalid parameter at `/routingPort` of router:"));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let (nodes, mut edges) = pipeline();
+        edges.push(edge(3, PROCESSOR_ID, "default", PROCESSOR_ID, "default"));
+        assert_eq!(
+            validate(json!(nodes), json!(edges)),
+            vec![(Severity::Error, "Node is part of a cycle".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_unconnected_nodes() {
+        let (mut nodes, edges) = pipeline();
+        nodes.push(action(OTHER_ID, "unconnected", "PassThrough"));
+        assert_eq!(
+            validate(json!(nodes), json!(edges)),
+            vec![(
+                Severity::Warning,
+                "Node is not connected: unconnected".to_string()
+            )]
+        );
+
+        let (nodes, edges) = pipeline();
+        let diagnostics = validate(json!(nodes), Value::from(edges[1..].to_vec()));
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.contains(&(
+            Severity::Warning,
+            "Node is not connected: source".to_string()
+        )));
+        assert!(diagnostics.contains(&(
+            Severity::Warning,
+            "Node never receives features: processor".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_sub_graph_not_found() {
+        let (mut nodes, mut edges) = pipeline();
+        nodes.push(json!({
+            "id": OTHER_ID,
+            "name": "sub graph",
+            "type": "subGraph",
+            "subGraphId": SUB_GRAPH_ID
+        }));
+        edges.push(edge(3, PROCESSOR_ID, "default", OTHER_ID, "default"));
+        assert_eq!(
+            validate(json!(nodes), json!(edges)),
+            vec![(
+                Severity::Error,
+                format!("Sub graph not found: {}", SUB_GRAPH_ID)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_graph_ref_ports_are_not_checked() {
+        let storage_resolver = put_library(json!([]), json!([]));
+        assert_eq!(
+            validate_graph_ref(&storage_resolver, "1.0.0", json!({})),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_graph_ref_not_found() {
+        let diagnostics = validate_graph_ref(&StorageResolver::new(), "1.0.0", json!({}));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, Severity::Error);
+        assert!(diagnostics[0]
+            .1
+            .starts_with(&format!("Failed to read {}", LIBRARY_URI)));
+    }
+
+    #[test]
+    fn test_graph_ref_version_mismatch() {
+        let storage_resolver = put_library(json!([]), json!([]));
+        assert_eq!(
+            validate_graph_ref(&storage_resolver, "2.0.0", json!({})),
+            vec![(
+                Severity::Error,
+                format!(
+                    "Version mismatch of {}: expected 2.0.0, found 1.0.0",
+                    LIBRARY_URI
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn test_graph_ref_missing_parameter() {
+        let storage_resolver =
+            put_library(json!([{"name": "attribute", "required": true}]), json!([]));
+        assert_eq!(
+            validate_graph_ref(&storage_resolver, "1.0.0", json!({})),
+            vec![(
+                Severity::Error,
+                format!(
+                    "Missing parameters of {} for node library: attribute",
+                    LIBRARY_URI
+                )
+            )]
+        );
+        assert_eq!(
+            validate_graph_ref(&storage_resolver, "1.0.0", json!({"attribute": "code"})),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_referenced_graph_is_validated() {
+        let storage_resolver =
+            put_library(json!([]), json!([action(OTHER_ID, "unknown", "Unknown")]));
+        assert_eq!(
+            validate_graph_ref(&storage_resolver, "1.0.0", json!({})),
+            vec![(Severity::Error, "Unknown action: Unknown".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_for_each_and_loop() {
+        let (mut nodes, edges) = pipeline();
+        nodes[1]["forEach"] = json!({"groupBy": ["value"]});
+        nodes[2]["loop"] = json!({"condition": "true", "maxIterations": 2});
+        nodes.push(json!({
+            "id": OTHER_ID,
+            "name": "sub graph",
+            "type": "subGraph",
+            "subGraphId": SUB_GRAPH_ID,
+            "forEach": {"groupBy": ["value"]},
+            "loop": {"condition": "true", "maxIterations": 2}
+        }));
+        let diagnostics = validate_graphs(json!([
+            {"id": ENTRY_GRAPH_ID, "name": "entry", "nodes": nodes, "edges": edges},
+            {"id": SUB_GRAPH_ID, "name": "sub", "nodes": [], "edges": []},
+        ]));
+        assert!(diagnostics.contains(&(
+            Severity::Warning,
+            "forEach only applies to sub graphs: processor".to_string()
+        )));
+        assert!(diagnostics.contains(&(
+            Severity::Warning,
+            "loop only applies to sub graphs: sink".to_string()
+        )));
+        assert!(diagnostics.contains(&(
+            Severity::Error,
+            "forEach and loop cannot be combined: sub graph".to_string()
+        )));
+    }
+}
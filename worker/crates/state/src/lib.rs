@@ -66,6 +66,49 @@ impl State {
         self.string_to_object(content.as_str())
     }
 
+    /// Writes `content` as the `part`-th chunk of the newline-delimited JSON stream `id`.
+    pub fn save_lines_part_sync(&self, content: bytes::Bytes, id: &str, part: usize) -> Result<()> {
+        let p = self.id_to_part_location(id, part);
+        self.storage
+            .put_sync(p.as_path(), content)
+            .map_err(|e| Error::new(ErrorKind::Other, e))
+    }
+
+    pub async fn save_lines_part(
+        &self,
+        content: bytes::Bytes,
+        id: &str,
+        part: usize,
+    ) -> Result<()> {
+        let p = self.id_to_part_location(id, part);
+        self.storage
+            .put(p.as_path(), content)
+            .await
+            .map_err(|e| Error::new(ErrorKind::Other, e))
+    }
+
+    /// Reads the `part`-th chunk of the newline-delimited JSON stream `id`, or `None` when the
+    /// stream has no such chunk.
+    pub async fn get_lines_part<T>(&self, id: &str, part: usize) -> Result<Option<Vec<T>>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let p = self.id_to_part_location(id, part);
+        if !self.storage.exists(p.as_path()).await? {
+            return Ok(None);
+        }
+        let result = self.storage.get(p.as_path()).await?;
+        let byte = result.bytes().await?;
+        let content =
+            String::from_utf8(byte.to_vec()).map_err(|e| Error::new(ErrorKind::Other, e))?;
+        content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| self.string_to_object(line))
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
     pub async fn delete(&self, id: &str) -> Result<()> {
         self.storage
             .delete(self.id_to_location(id).as_path())
@@ -73,6 +116,38 @@ impl State {
             .map_err(|e| Error::new(ErrorKind::Other, e))
     }
 
+    /// Deletes every chunk of the newline-delimited JSON stream `id`.
+    pub async fn delete_lines(&self, id: &str) -> Result<()> {
+        let mut part = 0;
+        loop {
+            let p = self.id_to_part_location(id, part);
+            if !self.storage.exists(p.as_path()).await? {
+                return Ok(());
+            }
+            self.storage
+                .delete(p.as_path())
+                .await
+                .map_err(|e| Error::new(ErrorKind::Other, e))?;
+            part += 1;
+        }
+    }
+
+    /// Ids of the objects saved with [`State::save`].
+    pub async fn list_ids(&self) -> Result<Vec<String>> {
+        let uris = self
+            .storage
+            .list_with_result(Some(self.root.as_path()), false)
+            .await
+            .map_err(|e| Error::new(ErrorKind::Other, e))?;
+        Ok(uris
+            .iter()
+            .filter_map(|uri| {
+                let name = uri.path().file_name()?.to_str()?.to_string();
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect())
+    }
+
     fn string_to_object<T>(&self, s: &str) -> Result<T>
     where
         for<'de> T: Deserialize<'de>,
@@ -85,6 +160,19 @@ impl State {
             .to_path_buf()
     }
 
+    fn id_to_part_location(&self, id: &str, part: usize) -> PathBuf {
+        Path::new(
+            format!(
+                "{}/{}/{:08}.jsonl",
+                self.root.to_str().unwrap_or_default(),
+                id,
+                part
+            )
+            .as_str(),
+        )
+        .to_path_buf()
+    }
+
     fn object_to_string<T: Serialize>(&self, obj: &T) -> Result<String> {
         serde_json::to_string(obj).map_err(|err| Error::new(ErrorKind::Other, err))
     }
@@ -115,4 +203,22 @@ mod tests {
         let result: Data = state.get("test").await.unwrap();
         assert_eq!(result.x, 42);
     }
+
+    #[tokio::test]
+    async fn test_write_and_read_lines_parts() {
+        let storage_resolver = Arc::new(StorageResolver::new());
+
+        let state = State::new(&Uri::for_test("ram:///lines"), &storage_resolver).unwrap();
+        state
+            .save_lines_part(bytes::Bytes::from("{\"x\":1}\n{\"x\":2}\n"), "test", 0)
+            .await
+            .unwrap();
+        let result: Vec<Data> = state.get_lines_part("test", 0).await.unwrap().unwrap();
+        assert_eq!(result.iter().map(|d| d.x).collect::<Vec<_>>(), vec![1, 2]);
+        let result: Option<Vec<Data>> = state.get_lines_part("test", 1).await.unwrap();
+        assert!(result.is_none());
+        state.delete_lines("test").await.unwrap();
+        let result: Option<Vec<Data>> = state.get_lines_part("test", 0).await.unwrap();
+        assert!(result.is_none());
+    }
 }
@@ -161,7 +161,7 @@ impl Storage {
             location: object_store::path::Path::parse(p)?,
             last_modified: meta.last_modified().unwrap_or_default(),
             size: meta.content_length() as usize,
-            e_tag: None,
+            e_tag: meta.etag().map(|e_tag| e_tag.to_string()),
             version: None,
         })
     }
@@ -1,3 +1,4 @@
+use std::io;
 use std::ops::Range;
 use std::path::Path;
 use std::time::Duration;
@@ -25,6 +26,25 @@ impl Storage {
             .map_err(|err| format_object_store_error(err, p))
     }
 
+    /// Opens a file for writing in chunks, so that large contents need not be held in memory.
+    /// The file is complete once [`StorageWriter::close`] returns.
+    pub fn writer_sync(&self, location: &Path) -> Result<StorageWriter> {
+        let p = location.to_str().ok_or(object_store::Error::InvalidPath {
+            source: object_store::path::Error::InvalidPath {
+                path: format!("{:?}", location).into(),
+            },
+        })?;
+        let inner = self
+            .inner
+            .blocking()
+            .writer(p)
+            .map_err(|err| format_object_store_error(err, p))?;
+        Ok(StorageWriter {
+            inner,
+            path: p.to_string(),
+        })
+    }
+
     pub fn create_dir_sync(&self, location: &Path) -> Result<()> {
         let p = location.to_str().ok_or(object_store::Error::InvalidPath {
             source: object_store::path::Error::InvalidPath {
@@ -217,3 +237,40 @@ impl Storage {
         Ok(())
     }
 }
+
+/// Writes a file of a [`Storage`] chunk by chunk. Wrap it in a [`std::io::BufWriter`] when
+/// writing small pieces.
+pub struct StorageWriter {
+    inner: opendal::BlockingWriter,
+    path: String,
+}
+
+impl StorageWriter {
+    pub fn close(mut self) -> Result<()> {
+        self.inner
+            .close()
+            .map_err(|err| format_object_store_error(err, &self.path))?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for StorageWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StorageWriter")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl io::Write for StorageWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner
+            .write(Bytes::copy_from_slice(buf))
+            .map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -59,6 +59,18 @@ pub fn init_metrics(service_name: String) -> Result<SdkMeterProvider> {
     Ok(metrics)
 }
 
+/// Installs the meter provider of [`init_metrics`] as the global one when a collector endpoint is
+/// configured with `OTEL_COLLECTOR_ENDPOINT`. Must be called within a Tokio runtime, which exports
+/// the metrics until the returned provider is shut down.
+pub fn install_metrics(service_name: String) -> Result<Option<SdkMeterProvider>> {
+    if OTEL_COLLECTOR_ENDPOINT.lock().unwrap().is_none() {
+        return Ok(None);
+    }
+    let provider = init_metrics(service_name)?;
+    opentelemetry::global::set_meter_provider(provider.clone());
+    Ok(Some(provider))
+}
+
 pub fn init_tracing(service_name: String) -> Result<Tracer> {
     let tracer = match OTEL_COLLECTOR_ENDPOINT.lock().unwrap().clone() {
         Some(endpoint) => opentelemetry_otlp::new_pipeline()
@@ -98,3 +110,12 @@ pub fn init_tracing(service_name: String) -> Result<Tracer> {
     };
     Ok(tracer.tracer(service_name.clone()))
 }
+
+static METER_NAME: &str = "reearth-flow";
+
+/// Returns the meter of the flow engine. Instruments record into the global meter provider, and
+/// are no-ops until [`install_metrics`] installs one, which it only does when
+/// `OTEL_COLLECTOR_ENDPOINT` is set.
+pub fn meter() -> opentelemetry::metrics::Meter {
+    opentelemetry::global::meter(METER_NAME)
+}
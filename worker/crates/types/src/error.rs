@@ -1,5 +1,10 @@
+use std::fmt::Display;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("{0}")]
+    Parse(ParseError),
+
     #[error("Error while running action: {0}")]
     InternalRuntime(String),
 
@@ -22,6 +27,17 @@ pub enum Error {
 }
 
 impl Error {
+    /// Sets the path of the file a parse error happened in.
+    pub fn with_path<T: ToString>(self, path: T) -> Self {
+        match self {
+            Self::Parse(e) => Self::Parse(ParseError {
+                path: Some(path.to_string()),
+                ..e
+            }),
+            e => e,
+        }
+    }
+
     pub fn internal_runtime<T: ToString>(message: T) -> Self {
         Self::InternalRuntime(message.to_string())
     }
@@ -54,6 +70,7 @@ impl Error {
 impl PartialEq for Error {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (Self::Parse(a), Self::Parse(b)) => a == b,
             (Self::InternalRuntime(a), Self::InternalRuntime(b)) => a == b,
             (Self::Input(a), Self::Input(b)) => a == b,
             (Self::Output(a), Self::Output(b)) => a == b,
@@ -65,4 +82,34 @@ impl PartialEq for Error {
     }
 }
 
+/// Location and cause of an invalid workflow document.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseError {
+    pub path: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// JSON pointer to the offending node, e.g. `/graphs/0/nodes/2`.
+    pub pointer: String,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path)?;
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}:", line, column)?;
+        }
+        if self.path.is_some() || self.line.is_some() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.message)?;
+        if !self.pointer.is_empty() {
+            write!(f, " (at {})", self.pointer)?;
+        }
+        Ok(())
+    }
+}
+
 pub type Result<T, E = Error> = std::result::Result<T, E>;
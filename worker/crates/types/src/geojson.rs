@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use geojson::{feature::Id, GeoJson, JsonObject, Position, Value};
+use nusamai_projection::crs::{EpsgCode, EPSG_WGS84_GEOGRAPHIC_2D};
+use reearth_flow_geometry::types::{
+    coordinate::Coordinate, coordnum::CoordNum, geometry::Geometry as FlowGeometry,
+    line_string::LineString, multi_line_string::MultiLineString, multi_point::MultiPoint,
+    multi_polygon::MultiPolygon, point::Point, polygon::Polygon,
+};
+
+use crate::error::Error;
+use crate::{Attribute, AttributeValue, Feature, Geometry, GeometryValue};
+
+/// Coordinate system of GeoJSON without a `crs` member (RFC 7946).
+pub const GEOJSON_DEFAULT_EPSG: EpsgCode = EPSG_WGS84_GEOGRAPHIC_2D;
+
+/// Converts a GeoJSON document to features.
+///
+/// The coordinate system is read from the legacy `crs` member of the collection or of a feature,
+/// and defaults to WGS 84. Geometries with a third coordinate become `FlowGeometry3D`, the
+/// others `FlowGeometry2D`. The properties become attributes, and the id of a feature is kept as
+/// the id of the feature when it is a UUID, or as the `id` attribute otherwise.
+pub fn features_from_geojson(geojson: GeoJson) -> Result<Vec<Feature>, Error> {
+    match geojson {
+        GeoJson::FeatureCollection(collection) => {
+            let epsg = epsg_from_crs(collection.foreign_members.as_ref())?;
+            collection
+                .features
+                .into_iter()
+                .map(|feature| feature_from_geojson(feature, epsg))
+                .collect()
+        }
+        GeoJson::Feature(feature) => Ok(vec![feature_from_geojson(feature, None)?]),
+        GeoJson::Geometry(geometry) => {
+            let epsg = epsg_from_crs(geometry.foreign_members.as_ref())?;
+            let value = geometry_value_from_geojson(&geometry.value)?;
+            Ok(vec![Feature::from(Geometry {
+                epsg: Some(epsg.unwrap_or(GEOJSON_DEFAULT_EPSG)),
+                value,
+            })])
+        }
+    }
+}
+
+/// Converts a GeoJSON feature, whose coordinates are in `epsg` unless it has a `crs` member.
+pub fn feature_from_geojson(
+    feature: geojson::Feature,
+    epsg: Option<EpsgCode>,
+) -> Result<Feature, Error> {
+    let epsg = epsg_from_crs(feature.foreign_members.as_ref())?
+        .or(epsg)
+        .unwrap_or(GEOJSON_DEFAULT_EPSG);
+    let mut attributes = feature
+        .properties
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(k, v)| (Attribute::new(k), AttributeValue::from(v)))
+        .collect::<HashMap<_, _>>();
+    let id = match feature.id {
+        Some(Id::String(id)) => match uuid::Uuid::from_str(id.as_str()) {
+            Ok(id) => Some(id),
+            Err(_) => {
+                attributes
+                    .entry(Attribute::new("id".to_string()))
+                    .or_insert(AttributeValue::String(id));
+                None
+            }
+        },
+        Some(Id::Number(id)) => {
+            attributes
+                .entry(Attribute::new("id".to_string()))
+                .or_insert(AttributeValue::Number(id));
+            None
+        }
+        None => None,
+    };
+    let geometry = feature
+        .geometry
+        .map(|geometry| {
+            geometry_value_from_geojson(&geometry.value).map(|value| Geometry {
+                epsg: Some(epsg),
+                value,
+            })
+        })
+        .transpose()?;
+    Ok(Feature {
+        id: id.unwrap_or_else(uuid::Uuid::new_v4),
+        attributes,
+        geometry,
+    })
+}
+
+/// Converts a feature to a GeoJSON feature. The coordinates are written as they are, see
+/// [`crs_member`] for features not in WGS 84.
+pub fn feature_to_geojson(feature: &Feature) -> geojson::Feature {
+    let properties = feature
+        .attributes
+        .iter()
+        .map(|(k, v)| (k.inner(), serde_json::Value::from(v.clone())))
+        .collect::<JsonObject>();
+    geojson::Feature {
+        bbox: None,
+        geometry: feature
+            .geometry
+            .as_ref()
+            .and_then(|geometry| geometry_value_to_geojson(&geometry.value)),
+        id: Some(Id::String(feature.id.to_string())),
+        properties: Some(properties),
+        foreign_members: None,
+    }
+}
+
+/// Converts a geometry. The polygons of a `CityGmlGeometry` are written as a 3D MultiPolygon.
+pub fn geometry_value_to_geojson(value: &GeometryValue) -> Option<geojson::Geometry> {
+    let value = match value {
+        GeometryValue::None => return None,
+        GeometryValue::CityGmlGeometry(geometry) => Value::MultiPolygon(
+            geometry
+                .features
+                .iter()
+                .flat_map(|feature| feature.polygons.iter())
+                .map(|polygon| polygon_to_geojson(polygon, &position_3d))
+                .collect(),
+        ),
+        GeometryValue::FlowGeometry2D(geometry) => geometry_to_geojson(geometry, &position_2d),
+        GeometryValue::FlowGeometry3D(geometry) => geometry_to_geojson(geometry, &position_3d),
+    };
+    Some(geojson::Geometry::new(value))
+}
+
+/// Converts a GeoJSON geometry, which becomes `FlowGeometry3D` when a position has a third
+/// coordinate.
+pub fn geometry_value_from_geojson(value: &Value) -> Result<GeometryValue, Error> {
+    let geometry = geometry_from_geojson(value)?;
+    if has_z(value) {
+        Ok(GeometryValue::FlowGeometry3D(geometry))
+    } else {
+        Ok(GeometryValue::FlowGeometry2D(geometry.into()))
+    }
+}
+
+/// The legacy `crs` member naming `epsg`, for consumers of coordinates not in WGS 84.
+pub fn crs_member(epsg: EpsgCode) -> serde_json::Value {
+    serde_json::json!({
+        "type": "name",
+        "properties": {
+            "name": format!("urn:ogc:def:crs:EPSG::{}", epsg),
+        },
+    })
+}
+
+/// Reads the EPSG code of a legacy `crs` member.
+pub fn epsg_from_crs(foreign_members: Option<&JsonObject>) -> Result<Option<EpsgCode>, Error> {
+    let Some(crs) = foreign_members.and_then(|members| members.get("crs")) else {
+        return Ok(None);
+    };
+    let Some(name) = crs
+        .get("properties")
+        .and_then(|properties| properties.get("name"))
+        .and_then(serde_json::Value::as_str)
+    else {
+        return Err(Error::input(format!("Unsupported crs: {}", crs)));
+    };
+    if name.ends_with("CRS84") {
+        return Ok(Some(EPSG_WGS84_GEOGRAPHIC_2D));
+    }
+    let code = name
+        .rsplit([':', '/'])
+        .next()
+        .and_then(|code| code.parse::<EpsgCode>().ok());
+    match code {
+        Some(code) if name.contains("EPSG") => Ok(Some(code)),
+        _ => Err(Error::input(format!("Unsupported crs: {}", name))),
+    }
+}
+
+fn position_2d<Z: CoordNum>(coordinate: &Coordinate<f64, Z>) -> Position {
+    vec![coordinate.x, coordinate.y]
+}
+
+fn position_3d(coordinate: &Coordinate<f64, f64>) -> Position {
+    vec![coordinate.x, coordinate.y, coordinate.z]
+}
+
+fn ring_to_geojson<Z: CoordNum>(
+    coordinates: &[Coordinate<f64, Z>],
+    position: &impl Fn(&Coordinate<f64, Z>) -> Position,
+) -> Vec<Position> {
+    let mut ring = coordinates.iter().map(position).collect::<Vec<_>>();
+    if ring.len() > 1 && ring.first() != ring.last() {
+        ring.push(ring[0].clone());
+    }
+    ring
+}
+
+fn polygon_to_geojson<Z: CoordNum>(
+    polygon: &Polygon<f64, Z>,
+    position: &impl Fn(&Coordinate<f64, Z>) -> Position,
+) -> Vec<Vec<Position>> {
+    std::iter::once(polygon.exterior())
+        .chain(polygon.interiors().iter())
+        .map(|ring| ring_to_geojson(&ring.0, position))
+        .collect()
+}
+
+fn geometry_to_geojson<Z: CoordNum>(
+    geometry: &FlowGeometry<f64, Z>,
+    position: &impl Fn(&Coordinate<f64, Z>) -> Position,
+) -> Value {
+    match geometry {
+        FlowGeometry::Point(point) => Value::Point(position(&point.0)),
+        FlowGeometry::Line(line) => {
+            Value::LineString(vec![position(&line.start), position(&line.end)])
+        }
+        FlowGeometry::LineString(line_string) => {
+            Value::LineString(line_string.coords().map(position).collect())
+        }
+        FlowGeometry::Polygon(polygon) => Value::Polygon(polygon_to_geojson(polygon, position)),
+        FlowGeometry::MultiPoint(multi_point) => {
+            Value::MultiPoint(multi_point.iter().map(|point| position(&point.0)).collect())
+        }
+        FlowGeometry::MultiLineString(multi_line_string) => Value::MultiLineString(
+            multi_line_string
+                .iter()
+                .map(|line_string| line_string.coords().map(position).collect())
+                .collect(),
+        ),
+        FlowGeometry::MultiPolygon(multi_polygon) => Value::MultiPolygon(
+            multi_polygon
+                .iter()
+                .map(|polygon| polygon_to_geojson(polygon, position))
+                .collect(),
+        ),
+        FlowGeometry::Rect(rect) => {
+            let (min, max) = (rect.min(), rect.max());
+            let corner = |x, y| Coordinate::new__(x, y, min.z);
+            Value::Polygon(vec![ring_to_geojson(
+                &[
+                    corner(min.x, min.y),
+                    corner(max.x, min.y),
+                    corner(max.x, max.y),
+                    corner(min.x, max.y),
+                ],
+                position,
+            )])
+        }
+        FlowGeometry::Triangle(triangle) => {
+            Value::Polygon(vec![ring_to_geojson(&triangle.to_array(), position)])
+        }
+        FlowGeometry::Solid(solid) => Value::MultiPolygon(
+            solid
+                .all_faces()
+                .into_iter()
+                .map(|face| vec![ring_to_geojson(&face.0, position)])
+                .collect(),
+        ),
+        FlowGeometry::GeometryCollection(geometries) => Value::GeometryCollection(
+            geometries
+                .iter()
+                .map(|geometry| geojson::Geometry::new(geometry_to_geojson(geometry, position)))
+                .collect(),
+        ),
+    }
+}
+
+fn has_z(value: &Value) -> bool {
+    let is_3d = |position: &Position| position.len() > 2;
+    match value {
+        Value::Point(position) => is_3d(position),
+        Value::MultiPoint(positions) | Value::LineString(positions) => positions.iter().any(is_3d),
+        Value::MultiLineString(rings) | Value::Polygon(rings) => rings.iter().flatten().any(is_3d),
+        Value::MultiPolygon(polygons) => polygons.iter().flatten().flatten().any(is_3d),
+        Value::GeometryCollection(geometries) => {
+            geometries.iter().any(|geometry| has_z(&geometry.value))
+        }
+    }
+}
+
+fn coordinate_from_geojson(position: &Position) -> Result<Coordinate<f64, f64>, Error> {
+    match position.as_slice() {
+        [x, y] => Ok(Coordinate::new__(*x, *y, 0.0)),
+        [x, y, z, ..] => Ok(Coordinate::new__(*x, *y, *z)),
+        _ => Err(Error::input("A position needs at least two coordinates")),
+    }
+}
+
+fn line_string_from_geojson(positions: &[Position]) -> Result<LineString<f64, f64>, Error> {
+    positions
+        .iter()
+        .map(coordinate_from_geojson)
+        .collect::<Result<Vec<_>, _>>()
+        .map(LineString::new)
+}
+
+fn polygon_from_geojson(rings: &[Vec<Position>]) -> Result<Polygon<f64, f64>, Error> {
+    let mut rings = rings
+        .iter()
+        .map(|ring| line_string_from_geojson(ring))
+        .collect::<Result<Vec<_>, _>>()?;
+    if rings.is_empty() {
+        return Ok(Polygon::new(LineString::new(vec![]), vec![]));
+    }
+    let exterior = rings.remove(0);
+    Ok(Polygon::new(exterior, rings))
+}
+
+fn geometry_from_geojson(value: &Value) -> Result<FlowGeometry<f64, f64>, Error> {
+    let geometry = match value {
+        Value::Point(position) => FlowGeometry::Point(Point(coordinate_from_geojson(position)?)),
+        Value::MultiPoint(positions) => FlowGeometry::MultiPoint(MultiPoint::new(
+            positions
+                .iter()
+                .map(|position| coordinate_from_geojson(position).map(Point))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        Value::LineString(positions) => {
+            FlowGeometry::LineString(line_string_from_geojson(positions)?)
+        }
+        Value::MultiLineString(line_strings) => {
+            FlowGeometry::MultiLineString(MultiLineString::new(
+                line_strings
+                    .iter()
+                    .map(|positions| line_string_from_geojson(positions))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ))
+        }
+        Value::Polygon(rings) => FlowGeometry::Polygon(polygon_from_geojson(rings)?),
+        Value::MultiPolygon(polygons) => FlowGeometry::MultiPolygon(MultiPolygon::new(
+            polygons
+                .iter()
+                .map(|rings| polygon_from_geojson(rings))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        Value::GeometryCollection(geometries) => FlowGeometry::GeometryCollection(
+            geometries
+                .iter()
+                .map(|geometry| geometry_from_geojson(&geometry.value))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+    Ok(geometry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features_from_geojson() {
+        let geojson = GeoJson::from_str(
+            r#"{
+                "type": "FeatureCollection",
+                "crs": {"type": "name", "properties": {"name": "urn:ogc:def:crs:EPSG::6697"}},
+                "features": [
+                    {
+                        "type": "Feature",
+                        "id": 1,
+                        "properties": {"name": "a"},
+                        "geometry": {"type": "Point", "coordinates": [139.0, 35.0, 10.0]}
+                    },
+                    {
+                        "type": "Feature",
+                        "properties": null,
+                        "geometry": {
+                            "type": "Polygon",
+                            "coordinates": [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]]
+                        }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let features = features_from_geojson(geojson).unwrap();
+        assert_eq!(features.len(), 2);
+        assert_eq!(
+            features[0].get(&"name"),
+            Some(&AttributeValue::String("a".to_string()))
+        );
+        assert!(features[0].get(&"id").is_some());
+        let geometry = features[0].geometry.as_ref().unwrap();
+        assert_eq!(geometry.epsg, Some(6697));
+        assert!(matches!(geometry.value, GeometryValue::FlowGeometry3D(_)));
+        let geometry = features[1].geometry.as_ref().unwrap();
+        assert!(matches!(geometry.value, GeometryValue::FlowGeometry2D(_)));
+    }
+
+    #[test]
+    fn test_feature_to_geojson() {
+        let geojson = GeoJson::from_str(
+            r#"{
+                "type": "Feature",
+                "properties": {"height": 3},
+                "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]]}
+            }"#,
+        )
+        .unwrap();
+        let feature = features_from_geojson(geojson).unwrap().remove(0);
+        let geojson = feature_to_geojson(&feature);
+        assert_eq!(
+            geojson.geometry.unwrap().value,
+            Value::LineString(vec![vec![0.0, 0.0], vec![1.0, 1.0]])
+        );
+        assert_eq!(
+            geojson.properties.unwrap().get("height"),
+            Some(&serde_json::json!(3))
+        );
+        assert_eq!(geojson.id, Some(Id::String(feature.id.to_string())));
+    }
+
+    #[test]
+    fn test_epsg_from_crs() {
+        let crs = |name: &str| {
+            let mut members = JsonObject::new();
+            members.insert(
+                "crs".to_string(),
+                serde_json::json!({"type": "name", "properties": {"name": name}}),
+            );
+            epsg_from_crs(Some(&members))
+        };
+        assert_eq!(crs("EPSG:6697"), Ok(Some(6697)));
+        assert_eq!(crs("urn:ogc:def:crs:OGC:1.3:CRS84"), Ok(Some(4326)));
+        assert_eq!(
+            crs("http://www.opengis.net/def/crs/EPSG/0/6668"),
+            Ok(Some(6668))
+        );
+        assert!(crs("urn:ogc:def:crs:OGC:1.3:unknown").is_err());
+        assert_eq!(epsg_from_crs(None), Ok(None));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::error::Error;
+use crate::geometry::GeometryValue;
+use crate::wkb::{geometry_value_from_wkb, geometry_value_to_wkb, Wkb};
+
+const MAGIC: &[u8; 2] = b"GP";
+const VERSION: u8 = 0;
+const FLAG_LITTLE_ENDIAN: u8 = 0b0000_0001;
+const FLAG_EMPTY: u8 = 0b0001_0000;
+const ENVELOPE_XY: u8 = 1;
+const ENVELOPE_XYZ: u8 = 2;
+
+/// Encodes a geometry as a GeoPackage geometry blob, with an envelope of its extent. Returns
+/// the blob with the WKB in it, whose type and extent describe the geometry column.
+pub fn geometry_value_to_gpkg(value: &GeometryValue, srs_id: i32) -> Option<(Vec<u8>, Wkb)> {
+    let wkb = geometry_value_to_wkb(value)?;
+    let mut blob = MAGIC.to_vec();
+    blob.push(VERSION);
+    let mut envelope = vec![];
+    let flags = match (wkb.bbox, wkb.z_range) {
+        (None, _) => FLAG_EMPTY,
+        (Some([min_x, min_y, max_x, max_y]), z_range) => {
+            envelope.extend([min_x, max_x, min_y, max_y]);
+            match z_range {
+                Some([min_z, max_z]) => {
+                    envelope.extend([min_z, max_z]);
+                    ENVELOPE_XYZ << 1
+                }
+                None => ENVELOPE_XY << 1,
+            }
+        }
+    };
+    blob.push(flags | FLAG_LITTLE_ENDIAN);
+    blob.extend(srs_id.to_le_bytes());
+    for value in envelope {
+        blob.extend(value.to_le_bytes());
+    }
+    blob.extend_from_slice(&wkb.data);
+    Some((blob, wkb))
+}
+
+/// Decodes a GeoPackage geometry blob, skipping its header.
+pub fn geometry_value_from_gpkg(blob: &[u8]) -> Result<GeometryValue, Error> {
+    if blob.len() < 8 || &blob[..2] != MAGIC {
+        return Err(Error::input("Not a GeoPackage geometry"));
+    }
+    let flags = blob[3];
+    let envelope_len = match (flags >> 1) & 0b111 {
+        0 => 0,
+        1 => 32,
+        2 | 3 => 48,
+        4 => 64,
+        indicator => {
+            return Err(Error::input(format!(
+                "Invalid GeoPackage envelope: {}",
+                indicator
+            )))
+        }
+    };
+    let wkb = blob
+        .get(8 + envelope_len..)
+        .ok_or_else(|| Error::input("Truncated GeoPackage geometry"))?;
+    geometry_value_from_wkb(wkb)
+}
@@ -4,7 +4,11 @@ pub mod error;
 pub mod expr;
 pub mod feature;
 pub mod file;
+pub mod geojson;
 pub mod geometry;
+pub mod gpkg;
+pub mod shapefile;
+pub mod wkb;
 pub mod workflow;
 
 pub use attribute::*;
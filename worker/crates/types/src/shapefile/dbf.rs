@@ -0,0 +1,500 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+
+use chrono::Datelike;
+use encoding_rs::{Encoding, SHIFT_JIS};
+use serde_json::Number;
+
+use crate::attribute::{Attribute, AttributeValue};
+use crate::error::Error;
+
+const VERSION: u8 = 0x03;
+const HEADER_LEN: usize = 32;
+const DESCRIPTOR_LEN: usize = 32;
+const HEADER_END: u8 = 0x0d;
+const FILE_END: u8 = 0x1a;
+const DELETED: u8 = b'*';
+/// Language driver of Japanese (code page 932).
+const LANGUAGE_DRIVER_SHIFT_JIS: u8 = 0x13;
+const MAX_FIELD_LEN: usize = 254;
+const MAX_NAME_LEN: usize = 10;
+const MAX_DECIMALS: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    Character,
+    Integer,
+    Real,
+    Logical,
+}
+
+impl FieldType {
+    fn of(value: &AttributeValue) -> Option<Self> {
+        match value {
+            AttributeValue::Null => None,
+            AttributeValue::Bool(_) => Some(Self::Logical),
+            AttributeValue::Number(number) if number.is_i64() || number.is_u64() => {
+                Some(Self::Integer)
+            }
+            AttributeValue::Number(_) => Some(Self::Real),
+            _ => Some(Self::Character),
+        }
+    }
+
+    fn merge(current: Option<Self>, value: Self) -> Self {
+        match (current, value) {
+            (None, value) => value,
+            (Some(current), value) if current == value => value,
+            (Some(Self::Integer), Self::Real) | (Some(Self::Real), Self::Integer) => Self::Real,
+            _ => Self::Character,
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            Self::Character => b'C',
+            Self::Integer | Self::Real => b'N',
+            Self::Logical => b'L',
+        }
+    }
+}
+
+/// What is known of the values of an attribute once the features are scanned.
+#[derive(Debug, Default)]
+struct FieldStats {
+    field_type: Option<FieldType>,
+    /// Digits after the decimal point of the longest fraction.
+    decimals: usize,
+    /// Largest number that is not negative.
+    max_positive: Option<f64>,
+    /// Largest magnitude of the negative numbers.
+    max_negative: Option<f64>,
+    /// Longest integer in characters.
+    integer_len: usize,
+    /// Longest value as text in bytes.
+    text_len: usize,
+}
+
+impl FieldStats {
+    fn add(&mut self, value: &AttributeValue, encoding: &'static Encoding) {
+        let Some(field_type) = FieldType::of(value) else {
+            return;
+        };
+        self.field_type = Some(FieldType::merge(self.field_type, field_type));
+        if let AttributeValue::Number(number) = value {
+            if number.is_i64() || number.is_u64() {
+                self.integer_len = self.integer_len.max(number.to_string().len());
+            }
+            if let Some(number) = number.as_f64() {
+                let decimals = number
+                    .to_string()
+                    .split_once('.')
+                    .map_or(0, |(_, fraction)| fraction.len());
+                self.decimals = self.decimals.max(decimals.min(MAX_DECIMALS));
+                let max = if number < 0.0 {
+                    &mut self.max_negative
+                } else {
+                    &mut self.max_positive
+                };
+                *max = Some(max.map_or(number.abs(), |max| max.max(number.abs())));
+            }
+        }
+        self.text_len = self.text_len.max(text(value, encoding).len());
+    }
+}
+
+/// Field of a table to write.
+#[derive(Debug)]
+struct Column {
+    attribute: Attribute,
+    name: Vec<u8>,
+    field_type: FieldType,
+    len: usize,
+    decimals: usize,
+}
+
+impl Column {
+    fn new(attribute: Attribute, name: Vec<u8>, stats: &FieldStats) -> Self {
+        let field_type = stats.field_type.unwrap_or(FieldType::Character);
+        let decimals = match field_type {
+            FieldType::Real => stats.decimals,
+            _ => 0,
+        };
+        let real_len = |number: f64| format!("{:.*}", decimals, number).len();
+        let len = match field_type {
+            FieldType::Logical => 1,
+            FieldType::Integer => stats.integer_len,
+            FieldType::Real => stats
+                .max_positive
+                .map_or(0, real_len)
+                .max(stats.max_negative.map_or(0, |number| real_len(number) + 1)),
+            FieldType::Character => stats.text_len,
+        }
+        .max(1);
+        if len > MAX_FIELD_LEN {
+            // Numbers too long for a numeric field are kept as text.
+            return Self {
+                attribute,
+                name,
+                field_type: FieldType::Character,
+                len: stats.text_len.max(1),
+                decimals: 0,
+            };
+        }
+        Self {
+            attribute,
+            name,
+            field_type,
+            len,
+            decimals,
+        }
+    }
+
+    fn put_value(
+        &self,
+        record: &mut Vec<u8>,
+        value: Option<&AttributeValue>,
+        encoding: &'static Encoding,
+    ) {
+        let mut value = match (self.field_type, value) {
+            (FieldType::Logical, Some(AttributeValue::Bool(true))) => b"T".to_vec(),
+            (FieldType::Logical, Some(AttributeValue::Bool(false))) => b"F".to_vec(),
+            (FieldType::Logical, _) => b"?".to_vec(),
+            (_, None | Some(AttributeValue::Null)) => vec![],
+            (FieldType::Integer, Some(AttributeValue::Number(number))) => {
+                number.to_string().into_bytes()
+            }
+            (FieldType::Real, Some(AttributeValue::Number(number))) => {
+                format!("{:.*}", self.decimals, number.as_f64().unwrap_or_default()).into_bytes()
+            }
+            (_, Some(value)) => text(value, encoding),
+        };
+        value.truncate(self.len);
+        let padding = self.len - value.len();
+        if self.field_type == FieldType::Character {
+            record.extend(value);
+            record.resize(record.len() + padding, b' ');
+        } else {
+            record.resize(record.len() + padding, b' ');
+            record.extend(value);
+        }
+    }
+}
+
+/// Gathers the fields of a `.dbf` file from the attributes of the features, since its header
+/// holds their sizes.
+///
+/// Booleans become logical fields, and numbers numeric fields. Other values and attributes with
+/// values of several types become character fields, holding the JSON of arrays and maps.
+/// Field names longer than 10 bytes are truncated and made unique.
+#[derive(Debug)]
+pub struct TableBuilder {
+    encoding: &'static Encoding,
+    fields: BTreeMap<Attribute, FieldStats>,
+    count: usize,
+}
+
+impl TableBuilder {
+    pub fn new(encoding: &'static Encoding) -> Self {
+        Self {
+            encoding,
+            fields: BTreeMap::new(),
+            count: 0,
+        }
+    }
+
+    pub fn add(&mut self, attributes: &HashMap<Attribute, AttributeValue>) {
+        self.count += 1;
+        for (attribute, value) in attributes {
+            self.fields
+                .entry(attribute.clone())
+                .or_default()
+                .add(value, self.encoding);
+        }
+    }
+
+    pub fn build(&self) -> Result<Table, Error> {
+        let mut used_names = HashSet::new();
+        let columns = self
+            .fields
+            .iter()
+            .map(|(attribute, stats)| {
+                let name = field_name(attribute.as_ref(), self.encoding, &mut used_names);
+                Column::new(attribute.clone(), name, stats)
+            })
+            .collect::<Vec<_>>();
+        let header_len = HEADER_LEN + DESCRIPTOR_LEN * columns.len() + 1;
+        let record_len = 1 + columns.iter().map(|column| column.len).sum::<usize>();
+        let (Ok(header_len), Ok(record_len)) =
+            (u16::try_from(header_len), u16::try_from(record_len))
+        else {
+            return Err(Error::output("Too many attributes for a dbf file"));
+        };
+        let count = u32::try_from(self.count)
+            .map_err(|_| Error::output("Too many features for a dbf file"))?;
+        Ok(Table {
+            encoding: self.encoding,
+            columns,
+            count,
+            header_len,
+            record_len,
+        })
+    }
+}
+
+/// The fields of a `.dbf` file, written record by record.
+#[derive(Debug)]
+pub struct Table {
+    encoding: &'static Encoding,
+    columns: Vec<Column>,
+    count: u32,
+    header_len: u16,
+    record_len: u16,
+}
+
+impl Table {
+    pub fn write_header(&self, writer: &mut impl Write) -> io::Result<()> {
+        let today = chrono::Utc::now();
+        let mut header = vec![
+            VERSION,
+            (today.year() - 1900) as u8,
+            today.month() as u8,
+            today.day() as u8,
+        ];
+        header.extend(self.count.to_le_bytes());
+        header.extend(self.header_len.to_le_bytes());
+        header.extend(self.record_len.to_le_bytes());
+        header.extend([0; 17]);
+        header.push(if self.encoding == SHIFT_JIS {
+            LANGUAGE_DRIVER_SHIFT_JIS
+        } else {
+            0
+        });
+        header.extend([0; 2]);
+        for column in self.columns.iter() {
+            let mut name = column.name.clone();
+            name.resize(11, 0);
+            header.extend(name);
+            header.push(column.field_type.code());
+            header.extend([0; 4]);
+            header.push(column.len as u8);
+            header.push(column.decimals as u8);
+            header.extend([0; 14]);
+        }
+        header.push(HEADER_END);
+        writer.write_all(&header)
+    }
+
+    pub fn write_record(
+        &self,
+        writer: &mut impl Write,
+        attributes: &HashMap<Attribute, AttributeValue>,
+    ) -> io::Result<()> {
+        let mut record = Vec::with_capacity(self.record_len as usize);
+        record.push(b' ');
+        for column in self.columns.iter() {
+            column.put_value(
+                &mut record,
+                attributes.get(&column.attribute),
+                self.encoding,
+            );
+        }
+        writer.write_all(&record)
+    }
+
+    pub fn write_end(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&[FILE_END])
+    }
+}
+
+/// Reads the records of a `.dbf` file. Deleted records are `None`.
+pub fn read_records(
+    dbf: &[u8],
+    encoding: &'static Encoding,
+) -> Result<Vec<Option<HashMap<Attribute, AttributeValue>>>, Error> {
+    let header = dbf
+        .get(..HEADER_LEN)
+        .ok_or_else(|| Error::input("Truncated dbf file"))?;
+    let num_records = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let header_len = u16::from_le_bytes(header[8..10].try_into().unwrap()) as usize;
+    let record_len = u16::from_le_bytes(header[10..12].try_into().unwrap()) as usize;
+
+    let mut fields = vec![];
+    // Values start after the deletion flag of the record.
+    let mut offset = 1;
+    for descriptor in dbf
+        .get(HEADER_LEN..header_len)
+        .unwrap_or_default()
+        .chunks_exact(DESCRIPTOR_LEN)
+    {
+        if descriptor[0] == HEADER_END {
+            break;
+        }
+        let name_len = descriptor[..11].iter().position(|b| *b == 0).unwrap_or(11);
+        let len = descriptor[16] as usize;
+        fields.push(Field {
+            name: Attribute::new(decode(&descriptor[..name_len], encoding)),
+            field_type: descriptor[11],
+            offset,
+            len,
+            decimals: descriptor[17],
+        });
+        offset += len;
+    }
+
+    (0..num_records)
+        .map(|index| {
+            let start = header_len + index * record_len;
+            let record = dbf
+                .get(start..start + record_len)
+                .ok_or_else(|| Error::input("Truncated dbf file"))?;
+            if record.first() == Some(&DELETED) {
+                return Ok(None);
+            }
+            fields
+                .iter()
+                .map(|field| {
+                    let value = record
+                        .get(field.offset..field.offset + field.len)
+                        .ok_or_else(|| Error::input("Truncated dbf record"))?;
+                    Ok((field.name.clone(), field.value(value, encoding)))
+                })
+                .collect::<Result<HashMap<_, _>, Error>>()
+                .map(Some)
+        })
+        .collect()
+}
+
+/// Encoding given by the language driver of a `.dbf` file without a `.cpg` file.
+pub fn language_driver_encoding(dbf: &[u8]) -> Option<&'static Encoding> {
+    match dbf.get(29) {
+        Some(&LANGUAGE_DRIVER_SHIFT_JIS) => Some(SHIFT_JIS),
+        _ => None,
+    }
+}
+
+struct Field {
+    name: Attribute,
+    field_type: u8,
+    offset: usize,
+    len: usize,
+    decimals: u8,
+}
+
+impl Field {
+    fn value(&self, bytes: &[u8], encoding: &'static Encoding) -> AttributeValue {
+        let text = decode(bytes, encoding);
+        let text = text.trim();
+        match self.field_type {
+            b'N' | b'F' if text.is_empty() || text.starts_with('*') => AttributeValue::Null,
+            b'N' | b'F' => {
+                let integer = if self.decimals == 0 {
+                    text.parse::<i64>().ok().map(Number::from)
+                } else {
+                    None
+                };
+                integer
+                    .or_else(|| text.parse::<f64>().ok().and_then(Number::from_f64))
+                    .map_or(AttributeValue::Null, AttributeValue::Number)
+            }
+            b'L' => match text {
+                "T" | "t" | "Y" | "y" => AttributeValue::Bool(true),
+                "F" | "f" | "N" | "n" => AttributeValue::Bool(false),
+                _ => AttributeValue::Null,
+            },
+            b'D' if text.is_empty() => AttributeValue::Null,
+            b'D' if text.len() == 8 && text.bytes().all(|b| b.is_ascii_digit()) => {
+                AttributeValue::String(format!("{}-{}-{}", &text[..4], &text[4..6], &text[6..]))
+            }
+            _ => AttributeValue::String(text.to_string()),
+        }
+    }
+}
+
+fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+    encoding
+        .decode_without_bom_handling(bytes)
+        .0
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// The value as text, as held by a character field.
+fn text(value: &AttributeValue, encoding: &'static Encoding) -> Vec<u8> {
+    match value {
+        AttributeValue::String(text) => encode(text, encoding, MAX_FIELD_LEN),
+        AttributeValue::DateTime(datetime) => {
+            encode(&datetime.to_string(), encoding, MAX_FIELD_LEN)
+        }
+        value => encode(
+            &serde_json::Value::from(value.clone()).to_string(),
+            encoding,
+            MAX_FIELD_LEN,
+        ),
+    }
+}
+
+/// Encodes `text`, truncated to whole characters of at most `max` bytes.
+fn encode(text: &str, encoding: &'static Encoding, max: usize) -> Vec<u8> {
+    let mut text = match text.char_indices().nth(max) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    };
+    loop {
+        let bytes = encoding.encode(text).0;
+        if bytes.len() <= max {
+            return bytes.into_owned();
+        }
+        let end = text.char_indices().last().map_or(0, |(end, _)| end);
+        text = &text[..end];
+    }
+}
+
+fn field_name(name: &str, encoding: &'static Encoding, used: &mut HashSet<Vec<u8>>) -> Vec<u8> {
+    let name = if name.is_empty() { "FIELD" } else { name };
+    let mut field_name = encode(name, encoding, MAX_NAME_LEN);
+    let mut suffix = 0;
+    while !used.insert(field_name.to_ascii_uppercase()) {
+        suffix += 1;
+        let suffix = format!("_{}", suffix);
+        field_name = encode(name, encoding, MAX_NAME_LEN - suffix.len());
+        field_name.extend(suffix.as_bytes());
+    }
+    field_name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let attributes = HashMap::from([
+            (
+                Attribute::new("name"),
+                AttributeValue::String("東京".to_string()),
+            ),
+            (
+                Attribute::new("population"),
+                AttributeValue::Number(14_000_000.into()),
+            ),
+            (
+                Attribute::new("area"),
+                AttributeValue::Number(Number::from_f64(2194.07).unwrap()),
+            ),
+            (Attribute::new("capital"), AttributeValue::Bool(true)),
+        ]);
+        let mut builder = TableBuilder::new(SHIFT_JIS);
+        builder.add(&attributes);
+        let table = builder.build().unwrap();
+        let mut dbf = vec![];
+        table.write_header(&mut dbf).unwrap();
+        table.write_record(&mut dbf, &attributes).unwrap();
+        table.write_end(&mut dbf).unwrap();
+        assert_eq!(language_driver_encoding(&dbf), Some(SHIFT_JIS));
+        assert_eq!(
+            read_records(&dbf, SHIFT_JIS).unwrap(),
+            vec![Some(attributes)]
+        );
+    }
+}
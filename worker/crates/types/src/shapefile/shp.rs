@@ -0,0 +1,719 @@
+use std::io::{self, Write};
+
+use reearth_flow_geometry::types::{
+    coordinate::Coordinate, coordnum::CoordNum, geometry::Geometry as FlowGeometry,
+    line_string::LineString, multi_line_string::MultiLineString, multi_point::MultiPoint,
+    multi_polygon::MultiPolygon, point::Point, polygon::Polygon,
+};
+
+use crate::error::Error;
+use crate::geometry::GeometryValue;
+
+const HEADER_LEN: usize = 100;
+const FILE_CODE: i32 = 9994;
+const VERSION: i32 = 1000;
+/// Measures below -10^38 mean "no data".
+const NO_DATA: f64 = -1e39;
+
+const NULL_SHAPE: i32 = 0;
+const POINT: i32 = 1;
+const POLYLINE: i32 = 3;
+const POLYGON: i32 = 5;
+const MULTIPOINT: i32 = 8;
+const POINT_Z: i32 = 11;
+const POLYLINE_Z: i32 = 13;
+const POLYGON_Z: i32 = 15;
+const MULTIPOINT_Z: i32 = 18;
+const POINT_M: i32 = 21;
+const POLYLINE_M: i32 = 23;
+const POLYGON_M: i32 = 25;
+const MULTIPOINT_M: i32 = 28;
+const MULTIPATCH: i32 = 31;
+
+type Xyz = [f64; 3];
+
+/// Type of the shapes of a shapefile, which holds a single type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShapeType {
+    Null,
+    Point,
+    MultiPoint,
+    PolyLine,
+    Polygon,
+}
+
+impl ShapeType {
+    fn code(self, z: bool) -> i32 {
+        match (self, z) {
+            (Self::Null, _) => NULL_SHAPE,
+            (Self::Point, false) => POINT,
+            (Self::Point, true) => POINT_Z,
+            (Self::MultiPoint, false) => MULTIPOINT,
+            (Self::MultiPoint, true) => MULTIPOINT_Z,
+            (Self::PolyLine, false) => POLYLINE,
+            (Self::PolyLine, true) => POLYLINE_Z,
+            (Self::Polygon, false) => POLYGON,
+            (Self::Polygon, true) => POLYGON_Z,
+        }
+    }
+
+    pub fn name(self, z: bool) -> String {
+        let name = match self {
+            Self::Null => "null",
+            Self::Point => "point",
+            Self::MultiPoint => "multipoint",
+            Self::PolyLine => "polyline",
+            Self::Polygon => "polygon",
+        };
+        if z && self != Self::Null {
+            format!("{}z", name)
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// A shape to write, as parts of coordinates. Points have a single part, and the rings of
+/// polygons are closed and oriented.
+#[derive(Debug, Clone)]
+pub struct Shape {
+    pub shape_type: ShapeType,
+    pub z: bool,
+    parts: Vec<Vec<Xyz>>,
+}
+
+impl Shape {
+    pub fn from_geometry(value: Option<&GeometryValue>) -> Result<Self, Error> {
+        match value {
+            None | Some(GeometryValue::None) => Ok(Self::new(ShapeType::Null, false, vec![])),
+            Some(GeometryValue::CityGmlGeometry(geometry)) => Ok(Self::new(
+                ShapeType::Polygon,
+                true,
+                geometry
+                    .features
+                    .iter()
+                    .flat_map(|feature| feature.polygons.iter())
+                    .flat_map(|polygon| polygon_rings(polygon, &xyz_3d))
+                    .collect(),
+            )),
+            Some(GeometryValue::FlowGeometry2D(geometry)) => {
+                Self::from_flow(geometry, false, &|c| [c.x, c.y, 0.0])
+            }
+            Some(GeometryValue::FlowGeometry3D(geometry)) => {
+                Self::from_flow(geometry, true, &xyz_3d)
+            }
+        }
+    }
+
+    fn new(shape_type: ShapeType, z: bool, parts: Vec<Vec<Xyz>>) -> Self {
+        Self {
+            shape_type,
+            z: z && shape_type != ShapeType::Null,
+            parts,
+        }
+    }
+
+    fn from_flow<Z: CoordNum>(
+        geometry: &FlowGeometry<f64, Z>,
+        z: bool,
+        xyz: &impl Fn(&Coordinate<f64, Z>) -> Xyz,
+    ) -> Result<Self, Error> {
+        let (shape_type, parts) = match geometry {
+            FlowGeometry::Point(point) => (ShapeType::Point, vec![vec![xyz(&point.0)]]),
+            FlowGeometry::MultiPoint(multi_point) => (
+                ShapeType::MultiPoint,
+                vec![multi_point.iter().map(|point| xyz(&point.0)).collect()],
+            ),
+            FlowGeometry::Line(line) => (
+                ShapeType::PolyLine,
+                vec![vec![xyz(&line.start), xyz(&line.end)]],
+            ),
+            FlowGeometry::LineString(line_string) => (
+                ShapeType::PolyLine,
+                vec![line_string.coords().map(xyz).collect()],
+            ),
+            FlowGeometry::MultiLineString(multi_line_string) => (
+                ShapeType::PolyLine,
+                multi_line_string
+                    .iter()
+                    .map(|line_string| line_string.coords().map(xyz).collect())
+                    .collect(),
+            ),
+            FlowGeometry::Polygon(polygon) => (ShapeType::Polygon, polygon_rings(polygon, xyz)),
+            FlowGeometry::MultiPolygon(multi_polygon) => (
+                ShapeType::Polygon,
+                multi_polygon
+                    .iter()
+                    .flat_map(|polygon| polygon_rings(polygon, xyz))
+                    .collect(),
+            ),
+            FlowGeometry::Rect(rect) => {
+                let (min, max) = (xyz(&rect.min()), xyz(&rect.max()));
+                let corners = vec![
+                    [min[0], min[1], min[2]],
+                    [min[0], max[1], min[2]],
+                    [max[0], max[1], min[2]],
+                    [max[0], min[1], min[2]],
+                ];
+                (ShapeType::Polygon, vec![ring(corners, true)])
+            }
+            FlowGeometry::Triangle(triangle) => (
+                ShapeType::Polygon,
+                vec![ring(triangle.to_array().iter().map(xyz).collect(), true)],
+            ),
+            FlowGeometry::Solid(solid) => (
+                ShapeType::Polygon,
+                solid
+                    .all_faces()
+                    .into_iter()
+                    .map(|face| ring(face.0.iter().map(xyz).collect(), true))
+                    .collect(),
+            ),
+            FlowGeometry::GeometryCollection(geometries) => {
+                let mut merged = Self::new(ShapeType::Null, z, vec![]);
+                for geometry in geometries {
+                    let shape = Self::from_flow(geometry, z, xyz)?;
+                    merged = match (merged.shape_type, shape.shape_type) {
+                        (_, ShapeType::Null) => merged,
+                        (ShapeType::Null, _) => shape,
+                        (
+                            ShapeType::Point | ShapeType::MultiPoint,
+                            ShapeType::Point | ShapeType::MultiPoint,
+                        ) => {
+                            let points = merged.parts.into_iter().chain(shape.parts).flatten();
+                            Self::new(ShapeType::MultiPoint, z, vec![points.collect()])
+                        }
+                        (a, b) if a == b => {
+                            Self::new(a, z, merged.parts.into_iter().chain(shape.parts).collect())
+                        }
+                        (a, b) => {
+                            return Err(Error::output(format!(
+                                "A collection of {} and {} cannot be written to a shapefile",
+                                a.name(z),
+                                b.name(z)
+                            )))
+                        }
+                    };
+                }
+                return Ok(merged);
+            }
+        };
+        Ok(Self::new(shape_type, z, parts))
+    }
+
+    fn points(&self) -> impl Iterator<Item = &Xyz> {
+        self.parts.iter().flatten()
+    }
+
+    fn content(&self) -> Vec<u8> {
+        let mut content = vec![];
+        let code = self.shape_type.code(self.z);
+        put_i32_le(&mut content, code);
+        match self.shape_type {
+            ShapeType::Null => {}
+            ShapeType::Point => {
+                let [x, y, z] = self.points().next().copied().unwrap_or_default();
+                put_f64s(&mut content, &[x, y]);
+                if self.z {
+                    put_f64s(&mut content, &[z, NO_DATA]);
+                }
+            }
+            ShapeType::MultiPoint => {
+                put_f64s(&mut content, &bounding_box(self.points()));
+                put_i32_le(&mut content, self.points().count() as i32);
+                self.put_points(&mut content);
+            }
+            ShapeType::PolyLine | ShapeType::Polygon => {
+                put_f64s(&mut content, &bounding_box(self.points()));
+                put_i32_le(&mut content, self.parts.len() as i32);
+                put_i32_le(&mut content, self.points().count() as i32);
+                let mut start = 0;
+                for part in self.parts.iter() {
+                    put_i32_le(&mut content, start);
+                    start += part.len() as i32;
+                }
+                self.put_points(&mut content);
+            }
+        }
+        content
+    }
+
+    fn put_points(&self, content: &mut Vec<u8>) {
+        for [x, y, _] in self.points() {
+            put_f64s(content, &[*x, *y]);
+        }
+        if self.z {
+            put_f64s(content, &z_range(self.points()));
+            for [_, _, z] in self.points() {
+                put_f64s(content, &[*z]);
+            }
+        }
+    }
+}
+
+/// Extent and size of the shapes of a shapefile, gathered before it is written since the
+/// headers of its files hold them.
+#[derive(Debug, Clone)]
+pub struct ShapeLayer {
+    shape_type: ShapeType,
+    z: bool,
+    count: usize,
+    bbox: Option<[f64; 4]>,
+    z_range: Option<[f64; 2]>,
+    /// Length of the records of the `.shp` file in bytes.
+    len: usize,
+}
+
+impl ShapeLayer {
+    pub fn new(shape_type: ShapeType, z: bool) -> Self {
+        Self {
+            shape_type,
+            z,
+            count: 0,
+            bbox: None,
+            z_range: None,
+            len: 0,
+        }
+    }
+
+    pub fn add(&mut self, shape: &Shape) {
+        self.count += 1;
+        self.len += 8 + shape.content().len();
+        if shape.points().next().is_none() {
+            return;
+        }
+        let [min_x, min_y, max_x, max_y] = bounding_box(shape.points());
+        self.bbox = Some(match self.bbox {
+            None => [min_x, min_y, max_x, max_y],
+            Some(bbox) => [
+                bbox[0].min(min_x),
+                bbox[1].min(min_y),
+                bbox[2].max(max_x),
+                bbox[3].max(max_y),
+            ],
+        });
+        let [min_z, max_z] = z_range(shape.points());
+        self.z_range = Some(match self.z_range {
+            None => [min_z, max_z],
+            Some([min, max]) => [min.min(min_z), max.max(max_z)],
+        });
+    }
+
+    /// Header of the `.shp` file, or of the `.shx` file when `index` is set.
+    pub fn header(&self, index: bool) -> Vec<u8> {
+        let len = if index { self.count * 8 } else { self.len };
+        let [min_x, min_y, max_x, max_y] = self.bbox.unwrap_or_default();
+        let [min_z, max_z] = match self.z_range {
+            Some(range) if self.z => range,
+            _ => [0.0, 0.0],
+        };
+        let mut header = vec![];
+        put_i32_be(&mut header, FILE_CODE);
+        header.extend([0; 20]);
+        put_i32_be(&mut header, ((HEADER_LEN + len) / 2) as i32);
+        put_i32_le(&mut header, VERSION);
+        put_i32_le(&mut header, self.shape_type.code(self.z));
+        put_f64s(
+            &mut header,
+            &[min_x, min_y, max_x, max_y, min_z, max_z, 0.0, 0.0],
+        );
+        header
+    }
+}
+
+/// Writes the records of a `.shp` file, or the entries of the `.shx` file indexing them, one
+/// shape at a time.
+#[derive(Debug)]
+pub struct RecordWriter {
+    index: bool,
+    number: i32,
+    offset: usize,
+}
+
+impl RecordWriter {
+    pub fn new(index: bool) -> Self {
+        Self {
+            index,
+            number: 0,
+            offset: HEADER_LEN,
+        }
+    }
+
+    pub fn write(&mut self, writer: &mut impl Write, shape: &Shape) -> io::Result<()> {
+        let content = shape.content();
+        let len = (content.len() / 2) as i32;
+        self.number += 1;
+        let mut record = vec![];
+        if self.index {
+            put_i32_be(&mut record, (self.offset / 2) as i32);
+            put_i32_be(&mut record, len);
+        } else {
+            put_i32_be(&mut record, self.number);
+            put_i32_be(&mut record, len);
+            record.extend(content.iter());
+        }
+        self.offset += 8 + content.len();
+        writer.write_all(&record)
+    }
+}
+
+/// Reads the shapes of a `.shp` file, locating the records with the `.shx` file if given.
+/// Shapes with Z become `FlowGeometry3D`, the others `FlowGeometry2D`.
+pub fn read_shapes(shp: &[u8], shx: Option<&[u8]>) -> Result<Vec<Option<GeometryValue>>, Error> {
+    let mut header = Input::new(shp);
+    if header.i32_be()? != FILE_CODE {
+        return Err(error("Not a shapefile"));
+    }
+    header.skip(28)?;
+    let z = matches!(
+        header.i32_le()?,
+        POINT_Z | POLYLINE_Z | POLYGON_Z | MULTIPOINT_Z | MULTIPATCH
+    );
+    let mut contents = vec![];
+    match shx {
+        Some(shx) => {
+            let mut index = Input::new(shx);
+            index.skip(HEADER_LEN)?;
+            while index.remaining() >= 8 {
+                let offset = index.i32_be()? as usize * 2 + 8;
+                let len = index.i32_be()? as usize * 2;
+                contents.push(Input::new(shp).slice(offset, len)?);
+            }
+        }
+        None => {
+            let mut records = Input::new(shp);
+            records.skip(HEADER_LEN)?;
+            while records.remaining() >= 8 {
+                records.skip(4)?;
+                let len = records.i32_be()? as usize * 2;
+                contents.push(records.take(len)?);
+            }
+        }
+    }
+    contents
+        .into_iter()
+        .map(|content| {
+            let geometry = read_shape(content)?;
+            Ok(geometry.map(|geometry| {
+                if z {
+                    GeometryValue::FlowGeometry3D(geometry)
+                } else {
+                    GeometryValue::FlowGeometry2D(geometry.into())
+                }
+            }))
+        })
+        .collect()
+}
+
+fn read_shape(content: &[u8]) -> Result<Option<FlowGeometry<f64, f64>>, Error> {
+    let mut input = Input::new(content);
+    let code = input.i32_le()?;
+    let z = matches!(
+        code,
+        POINT_Z | POLYLINE_Z | POLYGON_Z | MULTIPOINT_Z | MULTIPATCH
+    );
+    let geometry = match code {
+        NULL_SHAPE => return Ok(None),
+        POINT | POINT_Z | POINT_M => {
+            let (x, y) = (input.f64_le()?, input.f64_le()?);
+            let z = if z { input.f64_le()? } else { 0.0 };
+            FlowGeometry::Point(Point(Coordinate::new__(x, y, z)))
+        }
+        MULTIPOINT | MULTIPOINT_Z | MULTIPOINT_M => {
+            input.skip(32)?;
+            let len = input.len(16)?;
+            let points = input.points(len, z)?;
+            FlowGeometry::MultiPoint(MultiPoint::new(
+                points.into_iter().map(|p| Point(coordinate(p))).collect(),
+            ))
+        }
+        POLYLINE | POLYLINE_Z | POLYLINE_M | POLYGON | POLYGON_Z | POLYGON_M | MULTIPATCH => {
+            input.skip(32)?;
+            let num_parts = input.len(4)?;
+            let num_points = input.len(16)?;
+            let starts = (0..num_parts)
+                .map(|_| input.len(0))
+                .collect::<Result<Vec<_>, _>>()?;
+            let part_types = if code == MULTIPATCH {
+                (0..num_parts)
+                    .map(|_| input.i32_le())
+                    .collect::<Result<Vec<_>, _>>()?
+            } else {
+                vec![]
+            };
+            let points = input.points(num_points, z)?;
+            let parts = split_parts(&starts, points)?;
+            match code {
+                POLYLINE | POLYLINE_Z | POLYLINE_M => {
+                    let mut line_strings = parts.into_iter().map(line_string).collect::<Vec<_>>();
+                    if line_strings.len() == 1 {
+                        FlowGeometry::LineString(line_strings.remove(0))
+                    } else {
+                        FlowGeometry::MultiLineString(MultiLineString::new(line_strings))
+                    }
+                }
+                MULTIPATCH => {
+                    FlowGeometry::MultiPolygon(MultiPolygon::new(patches(&part_types, parts)))
+                }
+                _ => {
+                    let mut polygons = assemble_polygons(parts);
+                    if polygons.len() == 1 {
+                        FlowGeometry::Polygon(polygons.remove(0))
+                    } else {
+                        FlowGeometry::MultiPolygon(MultiPolygon::new(polygons))
+                    }
+                }
+            }
+        }
+        code => return Err(Error::input(format!("Unsupported shape type: {}", code))),
+    };
+    Ok(Some(geometry))
+}
+
+fn split_parts(starts: &[usize], points: Vec<Xyz>) -> Result<Vec<Vec<Xyz>>, Error> {
+    let mut parts = vec![];
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(points.len());
+        let part = points
+            .get(*start..end)
+            .ok_or_else(|| error("Invalid parts of a shape"))?;
+        parts.push(part.to_vec());
+    }
+    Ok(parts)
+}
+
+/// Assembles polygons from rings, where clockwise rings are exteriors and the others holes of
+/// the exterior before them.
+fn assemble_polygons(rings: Vec<Vec<Xyz>>) -> Vec<Polygon<f64, f64>> {
+    let mut polygons: Vec<(LineString<f64, f64>, Vec<LineString<f64, f64>>)> = vec![];
+    for ring in rings {
+        let is_hole = signed_area(&ring) > 0.0;
+        match polygons.last_mut() {
+            Some((_, interiors)) if is_hole => interiors.push(line_string(ring)),
+            _ => polygons.push((line_string(ring), vec![])),
+        }
+    }
+    polygons
+        .into_iter()
+        .map(|(exterior, interiors)| Polygon::new(exterior, interiors))
+        .collect()
+}
+
+/// Assembles the parts of a multipatch, made of triangle strips, triangle fans and rings.
+fn patches(part_types: &[i32], parts: Vec<Vec<Xyz>>) -> Vec<Polygon<f64, f64>> {
+    const TRIANGLE_STRIP: i32 = 0;
+    const TRIANGLE_FAN: i32 = 1;
+    const INNER_RING: i32 = 3;
+    const RING: i32 = 5;
+    let triangle = |a: Xyz, b: Xyz, c: Xyz| Polygon::new(line_string(vec![a, b, c, a]), vec![]);
+    let mut polygons = vec![];
+    for (part_type, part) in part_types.iter().zip(parts) {
+        match *part_type {
+            TRIANGLE_STRIP => polygons.extend(part.windows(3).map(|p| triangle(p[0], p[1], p[2]))),
+            TRIANGLE_FAN => {
+                if let Some(first) = part.first() {
+                    polygons.extend(part[1..].windows(2).map(|p| triangle(*first, p[0], p[1])));
+                }
+            }
+            INNER_RING | RING if !polygons.is_empty() => {
+                polygons
+                    .last_mut()
+                    .unwrap()
+                    .interiors_push(line_string(part));
+            }
+            _ => polygons.push(Polygon::new(line_string(part), vec![])),
+        }
+    }
+    polygons
+}
+
+fn coordinate([x, y, z]: Xyz) -> Coordinate<f64, f64> {
+    Coordinate::new__(x, y, z)
+}
+
+fn line_string(points: Vec<Xyz>) -> LineString<f64, f64> {
+    LineString::new(points.into_iter().map(coordinate).collect())
+}
+
+fn xyz_3d(coordinate: &Coordinate<f64, f64>) -> Xyz {
+    [coordinate.x, coordinate.y, coordinate.z]
+}
+
+fn polygon_rings<Z: CoordNum>(
+    polygon: &Polygon<f64, Z>,
+    xyz: &impl Fn(&Coordinate<f64, Z>) -> Xyz,
+) -> Vec<Vec<Xyz>> {
+    std::iter::once(ring(polygon.exterior().coords().map(xyz).collect(), true))
+        .chain(
+            polygon
+                .interiors()
+                .iter()
+                .map(|interior| ring(interior.coords().map(xyz).collect(), false)),
+        )
+        .collect()
+}
+
+/// Closes a ring, and orients it clockwise for an exterior or counterclockwise for a hole.
+fn ring(mut points: Vec<Xyz>, exterior: bool) -> Vec<Xyz> {
+    if points.len() > 1 && points.first() != points.last() {
+        points.push(points[0]);
+    }
+    let area = signed_area(&points);
+    if (exterior && area > 0.0) || (!exterior && area < 0.0) {
+        points.reverse();
+    }
+    points
+}
+
+/// Twice the area of a ring in the XY plane, positive when counterclockwise.
+fn signed_area(ring: &[Xyz]) -> f64 {
+    ring.windows(2)
+        .map(|p| p[0][0] * p[1][1] - p[1][0] * p[0][1])
+        .sum()
+}
+
+fn bounding_box<'a>(points: impl Iterator<Item = &'a Xyz>) -> [f64; 4] {
+    points
+        .fold(None, |bbox: Option<[f64; 4]>, [x, y, _]| {
+            Some(match bbox {
+                None => [*x, *y, *x, *y],
+                Some([min_x, min_y, max_x, max_y]) => {
+                    [min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y)]
+                }
+            })
+        })
+        .unwrap_or_default()
+}
+
+fn z_range<'a>(points: impl Iterator<Item = &'a Xyz>) -> [f64; 2] {
+    points
+        .fold(None, |range: Option<[f64; 2]>, [_, _, z]| {
+            Some(match range {
+                None => [*z, *z],
+                Some([min, max]) => [min.min(*z), max.max(*z)],
+            })
+        })
+        .unwrap_or_default()
+}
+
+fn put_i32_be(buf: &mut Vec<u8>, value: i32) {
+    buf.extend(value.to_be_bytes());
+}
+
+fn put_i32_le(buf: &mut Vec<u8>, value: i32) {
+    buf.extend(value.to_le_bytes());
+}
+
+fn put_f64s(buf: &mut Vec<u8>, values: &[f64]) {
+    for value in values {
+        buf.extend(value.to_le_bytes());
+    }
+}
+
+fn error(message: &str) -> Error {
+    Error::input(message)
+}
+
+/// Reads the binary values of a shapefile, failing on truncated data.
+struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    fn slice(&self, offset: usize, len: usize) -> Result<&'a [u8], Error> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| error("Truncated shapefile"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.slice(self.pos, len)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.take(len).map(|_| ())
+    }
+
+    fn i32_be(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32_le(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64_le(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a count of items of `size` bytes each, checking that they fit in the data.
+    fn len(&mut self, size: usize) -> Result<usize, Error> {
+        let len = usize::try_from(self.i32_le()?).map_err(|_| error("Negative count"))?;
+        if len.saturating_mul(size) > self.remaining() {
+            return Err(error("Truncated shapefile"));
+        }
+        Ok(len)
+    }
+
+    fn points(&mut self, len: usize, z: bool) -> Result<Vec<Xyz>, Error> {
+        let mut points = (0..len)
+            .map(|_| Ok([self.f64_le()?, self.f64_le()?, 0.0]))
+            .collect::<Result<Vec<_>, Error>>()?;
+        if z {
+            self.skip(16)?;
+            for point in points.iter_mut() {
+                point[2] = self.f64_le()?;
+            }
+        }
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let exterior = LineString::new(
+            [
+                (0.0, 0.0, 1.0),
+                (0.0, 3.0, 2.0),
+                (4.0, 3.0, 3.0),
+                (0.0, 0.0, 1.0),
+            ]
+            .iter()
+            .map(|(x, y, z)| Coordinate::new__(*x, *y, *z))
+            .collect(),
+        );
+        let geometry = FlowGeometry::Polygon(Polygon::new(exterior, vec![]));
+        let shape =
+            Shape::from_geometry(Some(&GeometryValue::FlowGeometry3D(geometry.clone()))).unwrap();
+        assert_eq!((shape.shape_type, shape.z), (ShapeType::Polygon, true));
+        let mut layer = ShapeLayer::new(shape.shape_type, shape.z);
+        layer.add(&shape);
+        let (mut shp, mut shx) = (layer.header(false), layer.header(true));
+        RecordWriter::new(false).write(&mut shp, &shape).unwrap();
+        RecordWriter::new(true).write(&mut shx, &shape).unwrap();
+        for shx in [Some(shx.as_slice()), None] {
+            let shapes = read_shapes(&shp, shx).unwrap();
+            let [Some(GeometryValue::FlowGeometry3D(decoded))] = shapes.as_slice() else {
+                panic!("Expected a 3D geometry");
+            };
+            assert_eq!(decoded, &geometry);
+        }
+        assert!(read_shapes(&shp[..shp.len() - 8], None).is_err());
+    }
+}
@@ -0,0 +1,570 @@
+use reearth_flow_geometry::types::{
+    coordinate::Coordinate, coordnum::CoordNum, geometry::Geometry as FlowGeometry,
+    line_string::LineString, multi_line_string::MultiLineString, multi_point::MultiPoint,
+    multi_polygon::MultiPolygon, point::Point, polygon::Polygon,
+};
+
+use crate::error::Error;
+use crate::geometry::GeometryValue;
+
+type Xyz = [f64; 3];
+
+/// Geometry types of Well-Known Binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WkbGeometryType {
+    Point,
+    LineString,
+    Polygon,
+    MultiPoint,
+    MultiLineString,
+    MultiPolygon,
+    GeometryCollection,
+}
+
+impl WkbGeometryType {
+    /// The name of the type in Well-Known Text, e.g. `MultiPolygon`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Point => "Point",
+            Self::LineString => "LineString",
+            Self::Polygon => "Polygon",
+            Self::MultiPoint => "MultiPoint",
+            Self::MultiLineString => "MultiLineString",
+            Self::MultiPolygon => "MultiPolygon",
+            Self::GeometryCollection => "GeometryCollection",
+        }
+    }
+
+    fn code(self) -> u32 {
+        match self {
+            Self::Point => 1,
+            Self::LineString => 2,
+            Self::Polygon => 3,
+            Self::MultiPoint => 4,
+            Self::MultiLineString => 5,
+            Self::MultiPolygon => 6,
+            Self::GeometryCollection => 7,
+        }
+    }
+}
+
+/// A geometry encoded as ISO Well-Known Binary in little endian, with its type and extent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wkb {
+    pub data: Vec<u8>,
+    pub geometry_type: WkbGeometryType,
+    pub z: bool,
+    /// `[min_x, min_y, max_x, max_y]`, or `None` for an empty geometry.
+    pub bbox: Option<[f64; 4]>,
+    /// `[min_z, max_z]` of a geometry with Z.
+    pub z_range: Option<[f64; 2]>,
+}
+
+/// Encodes a geometry. Lines become LineStrings, rectangles and triangles Polygons, and
+/// solids and the polygons of a `CityGmlGeometry` MultiPolygons.
+pub fn geometry_value_to_wkb(value: &GeometryValue) -> Option<Wkb> {
+    let (shape, z) = match value {
+        GeometryValue::None => return None,
+        GeometryValue::CityGmlGeometry(geometry) => (
+            Shape::MultiPolygon(
+                geometry
+                    .features
+                    .iter()
+                    .flat_map(|feature| feature.polygons.iter())
+                    .map(|polygon| polygon_rings(polygon, &xyz_3d))
+                    .collect(),
+            ),
+            true,
+        ),
+        GeometryValue::FlowGeometry2D(geometry) => {
+            (Shape::from_flow(geometry, &|c| [c.x, c.y, 0.0]), false)
+        }
+        GeometryValue::FlowGeometry3D(geometry) => (Shape::from_flow(geometry, &xyz_3d), true),
+    };
+    let mut writer = Writer {
+        data: vec![],
+        z,
+        bbox: None,
+        z_range: None,
+    };
+    writer.shape(&shape);
+    Some(Wkb {
+        data: writer.data,
+        geometry_type: shape.geometry_type(),
+        z,
+        bbox: writer.bbox,
+        z_range: writer.z_range.filter(|_| z),
+    })
+}
+
+/// Decodes Well-Known Binary, in its ISO or extended (PostGIS) flavor. Geometries with Z
+/// become `FlowGeometry3D`, and measures are dropped.
+pub fn geometry_value_from_wkb(wkb: &[u8]) -> Result<GeometryValue, Error> {
+    let mut reader = Reader { data: wkb, pos: 0 };
+    let (shape, z) = reader.shape()?;
+    let Some(shape) = shape else {
+        return Ok(GeometryValue::None);
+    };
+    let geometry = shape.into_flow();
+    if z {
+        Ok(GeometryValue::FlowGeometry3D(geometry))
+    } else {
+        Ok(GeometryValue::FlowGeometry2D(geometry.into()))
+    }
+}
+
+/// A geometry in the model of Well-Known Binary.
+#[derive(Debug, Clone)]
+enum Shape {
+    Point(Xyz),
+    LineString(Vec<Xyz>),
+    Polygon(Vec<Vec<Xyz>>),
+    MultiPoint(Vec<Xyz>),
+    MultiLineString(Vec<Vec<Xyz>>),
+    MultiPolygon(Vec<Vec<Vec<Xyz>>>),
+    GeometryCollection(Vec<Shape>),
+}
+
+impl Shape {
+    fn from_flow<Z: CoordNum>(
+        geometry: &FlowGeometry<f64, Z>,
+        xyz: &impl Fn(&Coordinate<f64, Z>) -> Xyz,
+    ) -> Self {
+        match geometry {
+            FlowGeometry::Point(point) => Self::Point(xyz(&point.0)),
+            FlowGeometry::Line(line) => Self::LineString(vec![xyz(&line.start), xyz(&line.end)]),
+            FlowGeometry::LineString(line_string) => {
+                Self::LineString(line_string.coords().map(xyz).collect())
+            }
+            FlowGeometry::Polygon(polygon) => Self::Polygon(polygon_rings(polygon, xyz)),
+            FlowGeometry::MultiPoint(multi_point) => {
+                Self::MultiPoint(multi_point.iter().map(|point| xyz(&point.0)).collect())
+            }
+            FlowGeometry::MultiLineString(multi_line_string) => Self::MultiLineString(
+                multi_line_string
+                    .iter()
+                    .map(|line_string| line_string.coords().map(xyz).collect())
+                    .collect(),
+            ),
+            FlowGeometry::MultiPolygon(multi_polygon) => Self::MultiPolygon(
+                multi_polygon
+                    .iter()
+                    .map(|polygon| polygon_rings(polygon, xyz))
+                    .collect(),
+            ),
+            FlowGeometry::Rect(rect) => {
+                let (min, max) = (xyz(&rect.min()), xyz(&rect.max()));
+                Self::Polygon(vec![closed(vec![
+                    [min[0], min[1], min[2]],
+                    [max[0], min[1], min[2]],
+                    [max[0], max[1], min[2]],
+                    [min[0], max[1], min[2]],
+                ])])
+            }
+            FlowGeometry::Triangle(triangle) => {
+                Self::Polygon(vec![closed(triangle.to_array().iter().map(xyz).collect())])
+            }
+            FlowGeometry::Solid(solid) => Self::MultiPolygon(
+                solid
+                    .all_faces()
+                    .into_iter()
+                    .map(|face| vec![closed(face.0.iter().map(xyz).collect())])
+                    .collect(),
+            ),
+            FlowGeometry::GeometryCollection(geometries) => Self::GeometryCollection(
+                geometries
+                    .iter()
+                    .map(|geometry| Self::from_flow(geometry, xyz))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn into_flow(self) -> FlowGeometry<f64, f64> {
+        match self {
+            Self::Point(point) => FlowGeometry::Point(Point(coordinate(point))),
+            Self::LineString(points) => FlowGeometry::LineString(line_string(points)),
+            Self::Polygon(rings) => FlowGeometry::Polygon(polygon(rings)),
+            Self::MultiPoint(points) => FlowGeometry::MultiPoint(MultiPoint::new(
+                points.into_iter().map(|p| Point(coordinate(p))).collect(),
+            )),
+            Self::MultiLineString(lines) => FlowGeometry::MultiLineString(MultiLineString::new(
+                lines.into_iter().map(line_string).collect(),
+            )),
+            Self::MultiPolygon(polygons) => FlowGeometry::MultiPolygon(MultiPolygon::new(
+                polygons.into_iter().map(polygon).collect(),
+            )),
+            Self::GeometryCollection(shapes) => {
+                FlowGeometry::GeometryCollection(shapes.into_iter().map(Self::into_flow).collect())
+            }
+        }
+    }
+
+    fn geometry_type(&self) -> WkbGeometryType {
+        match self {
+            Self::Point(_) => WkbGeometryType::Point,
+            Self::LineString(_) => WkbGeometryType::LineString,
+            Self::Polygon(_) => WkbGeometryType::Polygon,
+            Self::MultiPoint(_) => WkbGeometryType::MultiPoint,
+            Self::MultiLineString(_) => WkbGeometryType::MultiLineString,
+            Self::MultiPolygon(_) => WkbGeometryType::MultiPolygon,
+            Self::GeometryCollection(_) => WkbGeometryType::GeometryCollection,
+        }
+    }
+}
+
+struct Writer {
+    data: Vec<u8>,
+    z: bool,
+    bbox: Option<[f64; 4]>,
+    z_range: Option<[f64; 2]>,
+}
+
+impl Writer {
+    fn shape(&mut self, shape: &Shape) {
+        self.data.push(1);
+        let code = shape.geometry_type().code() + if self.z { 1000 } else { 0 };
+        self.u32(code);
+        match shape {
+            Shape::Point(point) => self.point(point),
+            Shape::LineString(points) => self.points(points),
+            Shape::Polygon(rings) => self.rings(rings),
+            Shape::MultiPoint(points) => {
+                self.u32(points.len() as u32);
+                for point in points {
+                    self.shape(&Shape::Point(*point));
+                }
+            }
+            Shape::MultiLineString(lines) => {
+                self.u32(lines.len() as u32);
+                for line in lines {
+                    self.data.push(1);
+                    self.u32(WkbGeometryType::LineString.code() + if self.z { 1000 } else { 0 });
+                    self.points(line);
+                }
+            }
+            Shape::MultiPolygon(polygons) => {
+                self.u32(polygons.len() as u32);
+                for rings in polygons {
+                    self.data.push(1);
+                    self.u32(WkbGeometryType::Polygon.code() + if self.z { 1000 } else { 0 });
+                    self.rings(rings);
+                }
+            }
+            Shape::GeometryCollection(shapes) => {
+                self.u32(shapes.len() as u32);
+                for shape in shapes {
+                    self.shape(shape);
+                }
+            }
+        }
+    }
+
+    fn rings(&mut self, rings: &[Vec<Xyz>]) {
+        self.u32(rings.len() as u32);
+        for ring in rings {
+            self.points(ring);
+        }
+    }
+
+    fn points(&mut self, points: &[Xyz]) {
+        self.u32(points.len() as u32);
+        for point in points {
+            self.point(point);
+        }
+    }
+
+    fn point(&mut self, [x, y, z]: &Xyz) {
+        self.f64(*x);
+        self.f64(*y);
+        if self.z {
+            self.f64(*z);
+        }
+        self.bbox = Some(match self.bbox {
+            None => [*x, *y, *x, *y],
+            Some([min_x, min_y, max_x, max_y]) => {
+                [min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y)]
+            }
+        });
+        self.z_range = Some(match self.z_range {
+            None => [*z, *z],
+            Some([min, max]) => [min.min(*z), max.max(*z)],
+        });
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.data.extend(value.to_le_bytes());
+    }
+}
+
+const EWKB_Z: u32 = 0x8000_0000;
+const EWKB_M: u32 = 0x4000_0000;
+const EWKB_SRID: u32 = 0x2000_0000;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    /// Reads a geometry and whether it has Z. Empty points are read as `None`.
+    fn shape(&mut self) -> Result<(Option<Shape>, bool), Error> {
+        let little_endian = match self.take(1)?[0] {
+            0 => false,
+            1 => true,
+            order => return Err(Error::input(format!("Invalid WKB byte order: {}", order))),
+        };
+        let code = self.u32(little_endian)?;
+        if code & EWKB_SRID != 0 {
+            self.skip(4)?;
+        }
+        let ewkb_z = code & EWKB_Z != 0;
+        let ewkb_m = code & EWKB_M != 0;
+        let code = code & !(EWKB_Z | EWKB_M | EWKB_SRID);
+        let (z, m) = match code / 1000 {
+            0 => (ewkb_z, ewkb_m),
+            1 => (true, false),
+            2 => (false, true),
+            3 => (true, true),
+            _ => return Err(Error::input(format!("Invalid WKB type: {}", code))),
+        };
+        let dims = Dims {
+            little_endian,
+            z,
+            m,
+        };
+        let shape = match code % 1000 {
+            1 => {
+                let point = self.point(&dims)?;
+                if point[0].is_nan() && point[1].is_nan() {
+                    return Ok((None, z));
+                }
+                Shape::Point(point)
+            }
+            2 => Shape::LineString(self.points(&dims)?),
+            3 => Shape::Polygon(self.rings(&dims)?),
+            4 => Shape::MultiPoint(
+                self.members(&dims)?
+                    .into_iter()
+                    .map(|shape| match shape {
+                        Shape::Point(point) => Ok(point),
+                        _ => Err(Error::input("Invalid member of a WKB MultiPoint")),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            5 => Shape::MultiLineString(
+                self.members(&dims)?
+                    .into_iter()
+                    .map(|shape| match shape {
+                        Shape::LineString(points) => Ok(points),
+                        _ => Err(Error::input("Invalid member of a WKB MultiLineString")),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            6 => Shape::MultiPolygon(
+                self.members(&dims)?
+                    .into_iter()
+                    .map(|shape| match shape {
+                        Shape::Polygon(rings) => Ok(rings),
+                        _ => Err(Error::input("Invalid member of a WKB MultiPolygon")),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            7 => Shape::GeometryCollection(self.members(&dims)?),
+            code => {
+                return Err(Error::unsupported_feature(format!(
+                    "WKB geometry type {}",
+                    code
+                )))
+            }
+        };
+        Ok((Some(shape), z))
+    }
+
+    /// Reads the members of a multi geometry or collection, skipping empty points.
+    fn members(&mut self, dims: &Dims) -> Result<Vec<Shape>, Error> {
+        // Each member is at least a byte order and a type.
+        let len = self.len(dims.little_endian, 5)?;
+        let mut members = vec![];
+        for _ in 0..len {
+            let (shape, _) = self.shape()?;
+            members.extend(shape);
+        }
+        Ok(members)
+    }
+
+    fn rings(&mut self, dims: &Dims) -> Result<Vec<Vec<Xyz>>, Error> {
+        let len = self.len(dims.little_endian, 4)?;
+        (0..len).map(|_| self.points(dims)).collect()
+    }
+
+    fn points(&mut self, dims: &Dims) -> Result<Vec<Xyz>, Error> {
+        let len = self.len(dims.little_endian, dims.point_size())?;
+        (0..len).map(|_| self.point(dims)).collect()
+    }
+
+    fn point(&mut self, dims: &Dims) -> Result<Xyz, Error> {
+        let x = self.f64(dims.little_endian)?;
+        let y = self.f64(dims.little_endian)?;
+        let z = if dims.z {
+            self.f64(dims.little_endian)?
+        } else {
+            0.0
+        };
+        if dims.m {
+            self.skip(8)?;
+        }
+        Ok([x, y, z])
+    }
+
+    /// Reads a count of items of at least `size` bytes each, checking that they fit in the data.
+    fn len(&mut self, little_endian: bool, size: usize) -> Result<usize, Error> {
+        let len = self.u32(little_endian)? as usize;
+        if len.saturating_mul(size) > self.data.len().saturating_sub(self.pos) {
+            return Err(Error::input("Truncated WKB"));
+        }
+        Ok(len)
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32, Error> {
+        let bytes = self.take(4)?.try_into().unwrap();
+        Ok(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn f64(&mut self, little_endian: bool) -> Result<f64, Error> {
+        let bytes = self.take(8)?.try_into().unwrap();
+        Ok(if little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], Error> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| Error::input("Truncated WKB"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.take(len).map(|_| ())
+    }
+}
+
+struct Dims {
+    little_endian: bool,
+    z: bool,
+    m: bool,
+}
+
+impl Dims {
+    fn point_size(&self) -> usize {
+        8 * (2 + self.z as usize + self.m as usize)
+    }
+}
+
+fn xyz_3d(coordinate: &Coordinate<f64, f64>) -> Xyz {
+    [coordinate.x, coordinate.y, coordinate.z]
+}
+
+fn coordinate([x, y, z]: Xyz) -> Coordinate<f64, f64> {
+    Coordinate::new__(x, y, z)
+}
+
+fn line_string(points: Vec<Xyz>) -> LineString<f64, f64> {
+    LineString::new(points.into_iter().map(coordinate).collect())
+}
+
+fn polygon(mut rings: Vec<Vec<Xyz>>) -> Polygon<f64, f64> {
+    if rings.is_empty() {
+        return Polygon::new(LineString::new(vec![]), vec![]);
+    }
+    let exterior = line_string(rings.remove(0));
+    Polygon::new(exterior, rings.into_iter().map(line_string).collect())
+}
+
+fn polygon_rings<Z: CoordNum>(
+    polygon: &Polygon<f64, Z>,
+    xyz: &impl Fn(&Coordinate<f64, Z>) -> Xyz,
+) -> Vec<Vec<Xyz>> {
+    std::iter::once(polygon.exterior())
+        .chain(polygon.interiors())
+        .filter(|ring| !ring.0.is_empty())
+        .map(|ring| closed(ring.coords().map(xyz).collect()))
+        .collect()
+}
+
+fn closed(mut points: Vec<Xyz>) -> Vec<Xyz> {
+    if points.len() > 1 && points.first() != points.last() {
+        points.push(points[0]);
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let ring = |coords: &[(f64, f64, f64)]| {
+            LineString::new(
+                coords
+                    .iter()
+                    .map(|(x, y, z)| Coordinate::new__(*x, *y, *z))
+                    .collect(),
+            )
+        };
+        let geometry = FlowGeometry::MultiPolygon(MultiPolygon::new(vec![Polygon::new(
+            ring(&[
+                (0.0, 0.0, 1.0),
+                (4.0, 0.0, 2.0),
+                (4.0, 3.0, 3.0),
+                (0.0, 0.0, 1.0),
+            ]),
+            vec![],
+        )]));
+        let wkb = geometry_value_to_wkb(&GeometryValue::FlowGeometry3D(geometry.clone())).unwrap();
+        assert_eq!(wkb.geometry_type, WkbGeometryType::MultiPolygon);
+        assert!(wkb.z);
+        assert_eq!(wkb.bbox, Some([0.0, 0.0, 4.0, 3.0]));
+        assert_eq!(wkb.z_range, Some([1.0, 3.0]));
+        assert_eq!(&wkb.data[..5], &[1, 0xee, 0x0b, 0, 0]);
+        let GeometryValue::FlowGeometry3D(decoded) = geometry_value_from_wkb(&wkb.data).unwrap()
+        else {
+            panic!("Expected a 3D geometry");
+        };
+        assert_eq!(decoded, geometry);
+    }
+
+    #[test]
+    fn test_extended_wkb() {
+        // A big endian point with Z and an SRID, as written by PostGIS.
+        let mut data = vec![0];
+        data.extend((1 | EWKB_Z | EWKB_SRID).to_be_bytes());
+        data.extend(6677u32.to_be_bytes());
+        for value in [1.0f64, 2.0, 3.0] {
+            data.extend(value.to_be_bytes());
+        }
+        let GeometryValue::FlowGeometry3D(decoded) = geometry_value_from_wkb(&data).unwrap() else {
+            panic!("Expected a 3D geometry");
+        };
+        assert_eq!(
+            decoded,
+            FlowGeometry::Point(Point(Coordinate::new__(1.0, 2.0, 3.0)))
+        );
+        assert!(geometry_value_from_wkb(&data[..20]).is_err());
+    }
+}
@@ -2,13 +2,16 @@ use std::{collections::HashMap, env};
 
 use reearth_flow_common::serde::SerdeFormat;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{Map, Value};
+use strum_macros::Display;
 use uuid::Uuid;
 
 use reearth_flow_common::serde::determine_format;
 use reearth_flow_common::serde::from_str;
 
+use crate::{Attribute, Expr};
+
 pub type Id = Uuid;
 pub type NodeProperty = Map<String, Value>;
 pub type NodeAction = String;
@@ -23,9 +26,42 @@ pub struct Workflow {
     pub name: String,
     pub entry_graph_id: Id,
     pub with: Option<Parameter>,
+    /// Declarations of the parameters set in `with`, on the command line or in the environment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<ParameterDeclaration>,
+    pub config: Option<WorkflowConfig>,
     pub graphs: Vec<Graph>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowConfig {
+    /// Location of a persistent key-value store shared across jobs (e.g. `file:///var/flow/kvs`).
+    /// An in-memory store is used when omitted.
+    pub kv_store: Option<String>,
+    /// Number of skipped or rejected features after which the job fails. Unlimited when omitted.
+    pub error_threshold: Option<u32>,
+    /// Capacity of the channel between two nodes. Defaults to 20.
+    pub channel_buffer_size: Option<usize>,
+    /// Number of events the event hub holds for handlers that lag behind. Defaults to 4096.
+    pub event_hub_capacity: Option<usize>,
+    /// Upper bound of the threads of each processor node. The `numThreads` of a node, or the
+    /// default of its action, is clamped to it, so it never raises a node's thread count.
+    /// Defaults to 30.
+    pub thread_pool_size: Option<usize>,
+    /// Number of worker threads of the async runtime. Defaults to 30.
+    pub worker_threads: Option<usize>,
+    /// Whether processors send features in the order they were received, even when they process
+    /// them on several threads. Defaults to false.
+    pub preserve_order: Option<bool>,
+    /// Memory in bytes the blocking processors of a job may use to buffer features before
+    /// spilling them to storage. Defaults to 1 GiB.
+    pub buffer_memory_limit: Option<usize>,
+    /// Location of the spill files (e.g. `file:///var/flow/spill`). Defaults to the temporary
+    /// directory.
+    pub spill_storage: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct WorkflowParameter {
@@ -34,68 +70,329 @@ pub struct WorkflowParameter {
 }
 
 impl Workflow {
-    pub fn try_from_str(s: &str) -> Self {
-        let mut workflow: Self = from_str(s).unwrap();
-        workflow.load_variables_from_environment();
-        workflow
+    pub fn try_from_str(s: &str) -> crate::error::Result<Self> {
+        let mut workflow: Self = parse_document(s)?;
+        workflow.load_variables_from_environment()?;
+        Ok(workflow)
     }
 
-    fn load_variables_from_environment(&mut self) {
+    pub fn config_mut(&mut self) -> &mut WorkflowConfig {
+        self.config.get_or_insert_with(Default::default)
+    }
+
+    /// Sets the parameters given as `FLOW_VAR_*` environment variables. Variables of unknown
+    /// parameters are ignored, as the environment is shared with other workflows.
+    fn load_variables_from_environment(&mut self) -> crate::error::Result<()> {
         let environment_vars: Vec<(String, String)> = env::vars()
             .filter(|(key, _)| key.starts_with(ENVIRONMENT_PREFIX))
             .map(|(key, value)| (key[ENVIRONMENT_PREFIX.len()..].to_string(), value))
-            .filter(|(key, _)| {
-                self.with
-                    .as_ref()
-                    .unwrap_or(&serde_json::Map::new())
-                    .contains_key(key)
-            })
+            .filter(|(key, _)| self.is_parameter(key))
             .collect();
-        if environment_vars.is_empty() {
-            return;
+        self.set_variables(environment_vars)
+    }
+
+    /// Sets the parameters given on the command line. Unknown parameters are an error.
+    pub fn merge_with(&mut self, params: HashMap<String, String>) -> crate::error::Result<()> {
+        let mut unknown = params
+            .keys()
+            .filter(|key| !self.is_parameter(key))
+            .map(|key| key.as_str())
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(crate::error::Error::input(format!(
+                "Unknown parameters: {}",
+                unknown.join(", ")
+            )));
         }
-        let mut with = if let Some(with) = self.with.clone() {
-            with
+        self.set_variables(params)
+    }
+
+    /// Sets the defaults of the declared parameters without a value, then checks the values
+    /// against their declarations.
+    pub fn resolve_parameters(&mut self) -> crate::error::Result<()> {
+        let mut with = self.with.clone().unwrap_or_default();
+        for parameter in self.parameters.iter() {
+            if let Some(default) = &parameter.default {
+                let unset = with.get(&parameter.name).map_or(true, Value::is_null);
+                if unset {
+                    with.insert(parameter.name.clone(), default.clone());
+                }
+            }
+        }
+        self.with = Some(with);
+        let errors = self.parameter_errors();
+        if errors.is_empty() {
+            Ok(())
         } else {
-            serde_json::Map::<String, Value>::new()
-        };
-        with.extend(environment_vars.into_iter().map(|(key, value)| {
-            let value = match determine_format(value.as_str()) {
-                SerdeFormat::Json | SerdeFormat::Yaml => from_str(value.as_str()).unwrap(),
-                SerdeFormat::Unknown => serde_json::to_value(value).unwrap(),
+            Err(crate::error::Error::validate(errors.join(", ")))
+        }
+    }
+
+    /// Problems of the parameter values, taking the defaults into account. Values of secret
+    /// parameters are never part of the messages.
+    pub fn parameter_errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        for parameter in self.parameters.iter() {
+            let value = self
+                .with
+                .as_ref()
+                .and_then(|with| with.get(&parameter.name))
+                .filter(|value| !value.is_null())
+                .or(parameter.default.as_ref());
+            match value {
+                Some(value) => {
+                    if let Err(e) = parameter.check(value) {
+                        errors.push(e);
+                    }
+                }
+                None if parameter.required => {
+                    errors.push(format!("Missing required parameter: {}", parameter.name));
+                }
+                None => {}
+            }
+        }
+        errors
+    }
+
+    /// Parameters are declared in `parameters`, or implicitly by a key in `with`.
+    fn is_parameter(&self, key: &str) -> bool {
+        self.parameter(key).is_some()
+            || self
+                .with
+                .as_ref()
+                .is_some_and(|with| with.contains_key(key))
+    }
+
+    fn parameter(&self, name: &str) -> Option<&ParameterDeclaration> {
+        self.parameters
+            .iter()
+            .find(|parameter| parameter.name == name)
+    }
+
+    fn set_variables(
+        &mut self,
+        variables: impl IntoIterator<Item = (String, String)>,
+    ) -> crate::error::Result<()> {
+        let mut with = self.with.clone().unwrap_or_default();
+        for (key, value) in variables {
+            let value = match self.parameter(&key) {
+                Some(parameter) => parameter.parse(&value)?,
+                None => parse_untyped(&key, &value)?,
             };
-            (key, value)
-        }));
+            with.insert(key, value);
+        }
         self.with = Some(with);
+        Ok(())
     }
+}
 
-    pub fn merge_with(&mut self, params: HashMap<String, String>) {
-        if params.is_empty() {
-            return;
+/// Parses a JSON or YAML document, locating the error when it is invalid.
+fn parse_document<T: DeserializeOwned>(s: &str) -> crate::error::Result<T> {
+    let error = match determine_format(s) {
+        SerdeFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(s);
+            match serde_path_to_error::deserialize(&mut deserializer) {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    let pointer = json_pointer(e.path());
+                    let e = e.into_inner();
+                    parse_error(e.to_string(), Some((e.line(), e.column())), pointer)
+                }
+            }
         }
-        let mut with = if let Some(with) = self.with.clone() {
-            with
-        } else {
-            serde_json::Map::<String, Value>::new()
+        SerdeFormat::Yaml => {
+            let deserializer = serde_yaml::Deserializer::from_str(s);
+            match serde_path_to_error::deserialize(deserializer) {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    let pointer = json_pointer(e.path());
+                    let e = e.into_inner();
+                    let location = e.location().map(|l| (l.line(), l.column()));
+                    parse_error(e.to_string(), location, pointer)
+                }
+            }
+        }
+        // Neither valid JSON nor YAML. YAML being a superset of JSON, its syntax error is reported.
+        SerdeFormat::Unknown => match serde_yaml::from_str::<serde_yaml::Value>(s) {
+            Ok(_) => parse_error("Unknown format".to_string(), None, String::new()),
+            Err(e) => {
+                let location = e.location().map(|l| (l.line(), l.column()));
+                parse_error(e.to_string(), location, String::new())
+            }
+        },
+    };
+    Err(crate::error::Error::Parse(error))
+}
+
+fn parse_error(
+    message: String,
+    location: Option<(usize, usize)>,
+    pointer: String,
+) -> crate::error::ParseError {
+    // The location is kept apart, so it is removed from the messages of serde.
+    let message = match location {
+        Some((line, column)) => message
+            .strip_suffix(&format!(" at line {} column {}", line, column))
+            .map(str::to_string)
+            .unwrap_or(message),
+        None => message,
+    };
+    crate::error::ParseError {
+        path: None,
+        line: location.map(|(line, _)| line),
+        column: location.map(|(_, column)| column),
+        pointer,
+        message,
+    }
+}
+
+fn json_pointer(path: &serde_path_to_error::Path) -> String {
+    path.iter()
+        .filter_map(|segment| match segment {
+            serde_path_to_error::Segment::Seq { index } => Some(index.to_string()),
+            serde_path_to_error::Segment::Map { key } => {
+                Some(key.replace('~', "~0").replace('/', "~1"))
+            }
+            serde_path_to_error::Segment::Enum { .. } | serde_path_to_error::Segment::Unknown => {
+                None
+            }
+        })
+        .map(|segment| format!("/{}", segment))
+        .collect()
+}
+
+/// Values of parameters without a declaration are JSON or YAML when they look like it, strings
+/// otherwise.
+fn parse_untyped(key: &str, value: &str) -> crate::error::Result<Value> {
+    match determine_format(value) {
+        SerdeFormat::Json | SerdeFormat::Yaml => from_str(value).map_err(|e| {
+            crate::error::Error::input(format!("Invalid value of parameter {}: {}", key, e))
+        }),
+        SerdeFormat::Unknown => Ok(Value::String(value.to_string())),
+    }
+}
+
+/// Declares a parameter of a workflow, so that its value is checked before the workflow runs and
+/// can be prompted for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterDeclaration {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub parameter_type: ParameterType,
+    pub description: Option<String>,
+    /// Whether the workflow fails without a value when there is no default.
+    #[serde(default)]
+    pub required: bool,
+    pub default: Option<Value>,
+    /// Values the parameter is restricted to.
+    #[serde(rename = "enum")]
+    pub allowed_values: Option<Vec<Value>>,
+    /// Regular expression the whole value of a string parameter must match.
+    pub pattern: Option<String>,
+    /// Whether the value is sensitive, e.g. a credential. It is not shown in messages.
+    #[serde(default)]
+    pub secret: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Display, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum ParameterType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl ParameterDeclaration {
+    /// Parses a value given as text, e.g. on the command line, according to the type of the
+    /// parameter.
+    pub fn parse(&self, value: &str) -> crate::error::Result<Value> {
+        let parsed = match self.parameter_type {
+            ParameterType::String => Some(Value::String(value.to_string())),
+            ParameterType::Number => value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number),
+            ParameterType::Integer => value
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .map(|value| Value::Number(value.into())),
+            ParameterType::Boolean => value.trim().parse::<bool>().ok().map(Value::Bool),
+            ParameterType::Array | ParameterType::Object => from_str::<Value>(value)
+                .ok()
+                .filter(|value| self.has_type(value)),
         };
-        let params = params
-            .into_iter()
-            .filter(|(key, _)| {
-                self.with
-                    .as_ref()
-                    .unwrap_or(&serde_json::Map::new())
-                    .contains_key(key)
-            })
-            .map(|(key, value)| {
-                let value = match determine_format(value.as_str()) {
-                    SerdeFormat::Json | SerdeFormat::Yaml => from_str(value.as_str()).unwrap(),
-                    SerdeFormat::Unknown => serde_json::to_value(value).unwrap(),
-                };
-                (key, value)
-            })
-            .collect::<serde_json::Map<String, Value>>();
-        with.extend(params);
-        self.with = Some(with);
+        parsed.ok_or_else(|| {
+            crate::error::Error::input(format!(
+                "Invalid value of parameter {}: {}expected {}",
+                self.name,
+                self.shown(value),
+                self.parameter_type
+            ))
+        })
+    }
+
+    /// Checks a value against the type and the constraints of the parameter.
+    pub fn check(&self, value: &Value) -> Result<(), String> {
+        if !self.has_type(value) {
+            return Err(format!(
+                "Invalid value of parameter {}: {}expected {}",
+                self.name,
+                self.shown(value),
+                self.parameter_type
+            ));
+        }
+        if let Some(allowed_values) = &self.allowed_values {
+            if !allowed_values.contains(value) {
+                return Err(format!(
+                    "Invalid value of parameter {}: {}expected one of {}",
+                    self.name,
+                    self.shown(value),
+                    Value::Array(allowed_values.clone())
+                ));
+            }
+        }
+        if let (Some(pattern), Value::String(value)) = (&self.pattern, value) {
+            let regex = regex::Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(|e| format!("Invalid pattern of parameter {}: {}", self.name, e))?;
+            if !regex.is_match(value) {
+                return Err(format!(
+                    "Invalid value of parameter {}: {}expected to match {}",
+                    self.name,
+                    self.shown(value),
+                    pattern
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn has_type(&self, value: &Value) -> bool {
+        match self.parameter_type {
+            ParameterType::String => value.is_string(),
+            ParameterType::Number => value.is_number(),
+            ParameterType::Integer => value.is_i64() || value.is_u64(),
+            ParameterType::Boolean => value.is_boolean(),
+            ParameterType::Array => value.is_array(),
+            ParameterType::Object => value.is_object(),
+        }
+    }
+
+    /// The value as part of a message, hidden for secret parameters.
+    fn shown(&self, value: impl std::fmt::Display) -> String {
+        if self.secret {
+            String::new()
+        } else {
+            format!("{}, ", value)
+        }
     }
 }
 
@@ -104,6 +401,76 @@ pub struct NodeEntity {
     pub id: Id,
     pub name: String,
     pub with: Option<NodeProperty>,
+    #[serde(rename = "onError", default, skip_serializing_if = "Option::is_none")]
+    pub on_error: Option<ErrorPolicy>,
+    /// Number of threads processing features, overriding the default of the action. Clamped to
+    /// the `threadPoolSize` of the workflow config.
+    #[serde(
+        rename = "numThreads",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub num_threads: Option<usize>,
+    /// Sends the features in the order they were received, overriding the workflow config.
+    #[serde(
+        rename = "preserveOrder",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub preserve_order: Option<bool>,
+    /// Runs a separate instance of the sub graph for each group of features.
+    #[serde(rename = "forEach", default, skip_serializing_if = "Option::is_none")]
+    pub for_each: Option<ForEach>,
+    /// Runs the sub graph again on its own output while a condition holds.
+    #[serde(rename = "loop", default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<Loop>,
+}
+
+/// Instantiates a sub graph per group of features, e.g. per input file, so that buffering
+/// actions do not mix the features of different groups.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ForEach {
+    /// Attributes whose values identify the group of a feature.
+    pub group_by: Vec<Attribute>,
+    /// Maximum number of instances running at the same time. Defaults to the number of CPUs.
+    pub concurrency: Option<usize>,
+}
+
+/// Feeds the features leaving a sub graph back into it, e.g. to simplify geometries until they
+/// are small enough.
+///
+/// A feature leaves the loop once the condition evaluates to false for it or after
+/// `maxIterations` runs of the sub graph.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Loop {
+    /// Expression evaluated with the attributes of each feature leaving the sub graph. The
+    /// feature runs through the sub graph again while it is true.
+    pub condition: Expr,
+    pub max_iterations: u32,
+    /// Attribute set to the number of runs of the sub graph on the feature, starting at 1.
+    /// Defaults to `_iteration`.
+    pub iteration_attribute: Option<Attribute>,
+}
+
+/// What a node does with a feature it failed to process.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ErrorPolicy {
+    /// Fails the whole job.
+    Fail,
+    /// Drops the feature and carries on. What the failed attempt sent before the error is already
+    /// forwarded. The error still counts towards the error threshold of the workflow.
+    #[default]
+    Skip,
+    /// Sends the feature to the `rejected` port with the error message as an attribute, instead
+    /// of what the failed attempt sent.
+    Reject,
+    /// Processes the feature again up to `maxRetries` times, then fails the job. Only what the
+    /// successful attempt sent is forwarded.
+    #[serde(rename_all = "camelCase")]
+    Retry { max_retries: u32 },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
@@ -122,6 +489,18 @@ pub enum Node {
         #[serde(rename = "subGraphId")]
         sub_graph_id: Id,
     },
+    /// A graph defined in another file, e.g. a library shared between workflows.
+    #[serde(rename = "graphRef")]
+    GraphRef {
+        #[serde(flatten)]
+        entity: NodeEntity,
+        /// Location of the [`GraphDefinition`], e.g. `file:///var/flow/graphs/flatten.yml`.
+        #[serde(rename = "graphUri")]
+        graph_uri: String,
+        /// Version the referenced graph must have. Any version is accepted when omitted.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+    },
 }
 
 impl Node {
@@ -132,6 +511,7 @@ impl Node {
                 entity,
                 sub_graph_id: _,
             } => entity.id,
+            Node::GraphRef { entity, .. } => entity.id,
         }
     }
 
@@ -142,6 +522,7 @@ impl Node {
                 entity,
                 sub_graph_id: _,
             } => &entity.name,
+            Node::GraphRef { entity, .. } => &entity.name,
         }
     }
 
@@ -152,6 +533,7 @@ impl Node {
                 entity: _,
                 sub_graph_id: _,
             } => "subGraph",
+            Node::GraphRef { .. } => "graphRef",
         }
     }
 
@@ -162,6 +544,62 @@ impl Node {
                 entity,
                 sub_graph_id: _,
             } => &entity.with,
+            Node::GraphRef { entity, .. } => &entity.with,
+        }
+    }
+
+    pub fn num_threads(&self) -> Option<usize> {
+        match self {
+            Node::Action { entity, action: _ } => entity.num_threads,
+            Node::SubGraph {
+                entity,
+                sub_graph_id: _,
+            } => entity.num_threads,
+            Node::GraphRef { entity, .. } => entity.num_threads,
+        }
+    }
+
+    pub fn preserve_order(&self) -> Option<bool> {
+        match self {
+            Node::Action { entity, action: _ } => entity.preserve_order,
+            Node::SubGraph {
+                entity,
+                sub_graph_id: _,
+            } => entity.preserve_order,
+            Node::GraphRef { entity, .. } => entity.preserve_order,
+        }
+    }
+
+    pub fn for_each(&self) -> Option<&ForEach> {
+        match self {
+            Node::Action { entity, action: _ } => entity.for_each.as_ref(),
+            Node::SubGraph {
+                entity,
+                sub_graph_id: _,
+            } => entity.for_each.as_ref(),
+            Node::GraphRef { entity, .. } => entity.for_each.as_ref(),
+        }
+    }
+
+    pub fn repeat(&self) -> Option<&Loop> {
+        match self {
+            Node::Action { entity, action: _ } => entity.repeat.as_ref(),
+            Node::SubGraph {
+                entity,
+                sub_graph_id: _,
+            } => entity.repeat.as_ref(),
+            Node::GraphRef { entity, .. } => entity.repeat.as_ref(),
+        }
+    }
+
+    pub fn on_error(&self) -> ErrorPolicy {
+        match self {
+            Node::Action { entity, action: _ } => entity.on_error.clone().unwrap_or_default(),
+            Node::SubGraph {
+                entity,
+                sub_graph_id: _,
+            } => entity.on_error.clone().unwrap_or_default(),
+            Node::GraphRef { entity, .. } => entity.on_error.clone().unwrap_or_default(),
         }
     }
 }
@@ -183,3 +621,166 @@ pub struct Graph {
     pub nodes: Vec<Node>,
     pub edges: Vec<Edge>,
 }
+
+/// A graph stored in its own file, so that several workflows can reference it with a
+/// [`Node::GraphRef`] node.
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphDefinition {
+    pub name: String,
+    pub version: String,
+    /// Parameters the graph accepts. The `with` of the referencing node overrides the defaults.
+    #[serde(default)]
+    pub parameters: Vec<GraphParameter>,
+    pub entry_graph_id: Id,
+    /// The entry graph and the sub graphs it uses. Sub graphs may reference further files.
+    pub graphs: Vec<Graph>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphParameter {
+    pub name: String,
+    pub description: Option<String>,
+    pub default: Option<Value>,
+    /// Whether the referencing node must set the parameter when there is no default.
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl GraphDefinition {
+    pub fn try_from_str(s: &str) -> crate::error::Result<Self> {
+        parse_document(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workflow(parameters: &str) -> Workflow {
+        Workflow::try_from_str(&format!(
+            r#"{{
+                "id": "a7fc8f35-b84f-496b-a2cb-65be3bfec285",
+                "name": "test",
+                "entryGraphId": "3e3450c8-2344-4728-afa9-5fdb81eec33a",
+                "with": {{"untyped": null}},
+                "parameters": {},
+                "graphs": []
+            }}"#,
+            parameters
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_merge_with_typed_parameters() {
+        let mut workflow = workflow(
+            r#"[
+                {"name": "count", "type": "integer"},
+                {"name": "code", "type": "string"}
+            ]"#,
+        );
+        workflow
+            .merge_with(HashMap::from([
+                ("count".to_string(), "3".to_string()),
+                ("code".to_string(), "123".to_string()),
+                ("untyped".to_string(), "[1, 2]".to_string()),
+            ]))
+            .unwrap();
+        let with = workflow.with.unwrap();
+        assert_eq!(with["count"], serde_json::json!(3));
+        assert_eq!(with["code"], serde_json::json!("123"));
+        assert_eq!(with["untyped"], serde_json::json!([1, 2]));
+    }
+
+    #[test]
+    fn test_merge_with_invalid_values() {
+        let mut workflow = workflow(r#"[{"name": "count", "type": "integer"}]"#);
+        let unknown = workflow.merge_with(HashMap::from([("other".to_string(), "1".to_string())]));
+        assert_eq!(
+            unknown,
+            Err(crate::error::Error::input("Unknown parameters: other"))
+        );
+        let invalid = workflow.merge_with(HashMap::from([("count".to_string(), "a".to_string())]));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_resolve_parameters() {
+        let mut workflow = workflow(
+            r#"[
+                {"name": "mode", "type": "string", "default": "fast", "enum": ["fast", "slow"]},
+                {"name": "token", "type": "string", "required": true, "secret": true, "pattern": "[a-z]+"}
+            ]"#,
+        );
+        assert_eq!(
+            workflow.parameter_errors(),
+            vec!["Missing required parameter: token".to_string()]
+        );
+        workflow
+            .merge_with(HashMap::from([("token".to_string(), "S3CRET".to_string())]))
+            .unwrap();
+        let error = workflow.resolve_parameters().unwrap_err().to_string();
+        assert!(!error.contains("S3CRET"));
+        workflow
+            .merge_with(HashMap::from([("token".to_string(), "secret".to_string())]))
+            .unwrap();
+        workflow.resolve_parameters().unwrap();
+        assert_eq!(workflow.with.unwrap()["mode"], serde_json::json!("fast"));
+    }
+
+    #[test]
+    fn test_parse_executor_config() {
+        let workflow = Workflow::try_from_str(
+            r#"{
+                "id": "a7fc8f35-b84f-496b-a2cb-65be3bfec285",
+                "name": "test",
+                "entryGraphId": "3e3450c8-2344-4728-afa9-5fdb81eec33a",
+                "config": {
+                    "channelBufferSize": 5,
+                    "eventHubCapacity": 64,
+                    "threadPoolSize": 4,
+                    "workerThreads": 2
+                },
+                "graphs": [{
+                    "id": "3e3450c8-2344-4728-afa9-5fdb81eec33a",
+                    "name": "main",
+                    "nodes": [{
+                        "id": "5f2d1c3b-0000-4000-8000-000000000001",
+                        "name": "processor",
+                        "type": "action",
+                        "action": "PassThrough",
+                        "numThreads": 8
+                    }],
+                    "edges": []
+                }]
+            }"#,
+        )
+        .unwrap();
+        let config = workflow.config.unwrap();
+        assert_eq!(config.channel_buffer_size, Some(5));
+        assert_eq!(config.event_hub_capacity, Some(64));
+        assert_eq!(config.thread_pool_size, Some(4));
+        assert_eq!(config.worker_threads, Some(2));
+        assert_eq!(workflow.graphs[0].nodes[0].num_threads(), Some(8));
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let yaml = "id: a7fc8f35-b84f-496b-a2cb-65be3bfec285
+name: test
+entryGraphId: 3e3450c8-2344-4728-afa9-5fdb81eec33a
+graphs:
+  - id: 3e3450c8-2344-4728-afa9-5fdb81eec33a
+    name: main
+    nodes: []
+    edges: 1
+";
+        let Err(crate::error::Error::Parse(error)) = Workflow::try_from_str(yaml) else {
+            panic!("expected a parse error");
+        };
+        assert_eq!(error.pointer, "/graphs/0/edges");
+        assert_eq!(error.line, Some(8));
+    }
+}
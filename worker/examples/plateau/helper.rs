@@ -94,7 +94,7 @@ pub fn create_workflow(workflow: &str) -> Workflow {
     let path = absolute_path.unwrap();
     let yaml = Transformer::new(path, false).unwrap();
     let yaml = yaml.to_string();
-    Workflow::try_from_str(yaml.as_str())
+    Workflow::try_from_str(yaml.as_str()).unwrap()
 }
 
 pub fn setup_logging_and_tracing() {
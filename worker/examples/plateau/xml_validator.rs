@@ -5,6 +5,7 @@ use reearth_flow_runtime::{
     dag_schemas::DagSchemas,
     node::{NodeKind, RouterFactory},
 };
+use reearth_flow_storage::resolve::StorageResolver;
 
 mod helper;
 
@@ -21,7 +22,9 @@ fn main() {
         workflow.graphs,
         factories,
         workflow.with,
-    );
+        &StorageResolver::new(),
+    )
+    .unwrap();
     let dot = dag.to_dot();
     println!("{}", dot);
 }
@@ -62,7 +62,7 @@ pub(crate) fn execute(test_id: &str, fixture_files: Vec<&str>) {
         ),
         Uri::for_test("ram:///log/").path(),
     ));
-    let workflow = Workflow::try_from_str(workflow);
+    let workflow = Workflow::try_from_str(workflow).unwrap();
     Runner::run(
         job_id.to_string(),
         workflow,